@@ -0,0 +1,25 @@
+//! Stamps the NEP-330 contract version with the git commit the contract is built from,
+//! e.g. "1.0.0+8a1df69c2b41". near-sdk reads NEP330_VERSION when it expands the
+//! contract state; an explicitly set NEP330_VERSION or a build outside git keeps it as is
+use std::env;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=NEP330_VERSION");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/logs/HEAD");
+    if env::var("NEP330_VERSION").is_ok() {
+        return;
+    }
+
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+    if let Some(commit) = commit {
+        let version = env::var("CARGO_PKG_VERSION").expect("set by cargo");
+        println!("cargo:rustc-env=NEP330_VERSION={}+{}", version, commit.trim());
+    }
+}
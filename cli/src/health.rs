@@ -0,0 +1,154 @@
+use std::fmt::Write;
+
+use price_oracle::{AssetInfo, HeartbeatStatus, Pair, PriceResponse, SourceInfo};
+use serde_json::json;
+
+use crate::rpc::Oracle;
+
+/// What the oracle knows about one pair, as the views report it
+pub struct PairHealth {
+    pub pair: Pair,
+    pub info: AssetInfo,
+    pub valid: bool,
+    pub answer: Result<PriceResponse, String>, // The error get_price fails with, if it does
+    pub sources: Vec<SourceInfo>,              // Sources of the pair itself, not of other quotes
+    pub heartbeats: Vec<HeartbeatStatus>,
+}
+
+pub async fn pair_health(oracle: &Oracle, pair: Pair) -> Result<PairHealth, String> {
+    let info: AssetInfo = oracle.view("get_asset_info", json!({ "asset_id": pair.base })).await?;
+    let valid = oracle.view("is_valid", json!({ "pair": pair })).await?;
+    let answer = oracle.view("get_price", json!({ "pair": pair })).await;
+    let sources: Vec<SourceInfo> = oracle.view("get_sources", json!({ "asset_id": pair.base })).await?;
+    let heartbeats = oracle.view("get_heartbeat_status", json!({ "pair": pair })).await?;
+    let sources = sources.into_iter().filter(|source| source.quote == pair.quote).collect();
+    Ok(PairHealth { pair, info, valid, answer, sources, heartbeats })
+}
+
+/// A human readable report: the oracle's state, then every pair with each of its sources
+pub fn render(oracle_id: &str, paused: bool, pairs: &[PairHealth]) -> String {
+    let mut out = String::new();
+    writeln!(out, "{}: {}", oracle_id, if paused { "PAUSED" } else { "running" }).unwrap();
+    for health in pairs {
+        let state = if health.valid { "valid" } else { "INVALID" };
+        match &health.answer {
+            Ok(answer) if answer.is_override => writeln!(
+                out,
+                "{}: {}, {} OVERRIDDEN by the owner at {} ({} live sources)",
+                health.pair,
+                state,
+                format_price(answer.price, answer.decimals),
+                answer.timestamp,
+                answer.n_sources,
+            ),
+            Ok(answer) => writeln!(
+                out,
+                "{}: {}, {} +- {} from {} of {} live sources (min {}), finalized at {}",
+                health.pair,
+                state,
+                format_price(answer.price, answer.decimals),
+                format_price(answer.confidence, answer.decimals),
+                answer.sources_used,
+                answer.n_sources,
+                health.info.min_sources,
+                answer.timestamp,
+            ),
+            Err(err) => writeln!(out, "{}: {}, no answer: {}", health.pair, state, err),
+        }
+        .unwrap();
+        for source in &health.sources {
+            let state = if !source.enabled {
+                "disabled"
+            } else if source.quarantined {
+                "quarantined"
+            } else if source.active {
+                "active"
+            } else {
+                "inactive"
+            };
+            write!(
+                out,
+                "  {:<20} {:<11} {} at {} by {}",
+                source.source,
+                state,
+                format_price(source.last_price, source.decimals),
+                source.last_timestamp,
+                source.last_reporter,
+            )
+            .unwrap();
+            if let Some(heartbeat) = health.heartbeats.iter().find(|heartbeat| heartbeat.source == source.source) {
+                write!(out, ", missed {} heartbeats of {}s", heartbeat.missed, heartbeat.interval).unwrap();
+                if heartbeat.excluded {
+                    write!(out, " (excluded)").unwrap();
+                }
+            }
+            writeln!(out).unwrap();
+        }
+    }
+    out
+}
+
+/// `price` scaled down by 10^decimals, with every fractional digit kept
+pub fn format_price(price: u128, decimals: u8) -> String {
+    if decimals == 0 {
+        return price.to_string();
+    }
+    let digits = format!("{:0>width$}", price, width = decimals as usize + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals as usize);
+    format!("{}.{}", whole, fraction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use price_oracle::AggregationMethod;
+
+    #[test]
+    fn test_format_price() {
+        assert_eq!(format_price(5280000, 6), "5.280000");
+        assert_eq!(format_price(25000, 6), "0.025000");
+        assert_eq!(format_price(7, 2), "0.07");
+        assert_eq!(format_price(3000, 0), "3000");
+    }
+
+    #[test]
+    fn test_render_pair_with_sources() {
+        let source = |name: &str, active, quarantined| SourceInfo {
+            source: name.to_string(),
+            quote: "USD".to_string(),
+            bound_reporter: None,
+            last_reporter: "feed.testnet".to_string(),
+            last_timestamp: 1760000000000,
+            last_price: 5280000,
+            decimals: 6,
+            active,
+            enabled: true,
+            weight: 1,
+            quarantined,
+        };
+        let health = PairHealth {
+            pair: Pair::usd("NEAR"),
+            info: AssetInfo {
+                asset_id: "NEAR".to_string(),
+                symbol: "NEAR".to_string(),
+                name: "NEAR Protocol".to_string(),
+                decimals: 6,
+                token_id: None,
+                aggregation: AggregationMethod::Median,
+                sources: vec!["binance".to_string(), "stale".to_string()],
+                min_sources: 1,
+                last_update: 1760000000000,
+            },
+            valid: false,
+            answer: Err("Oracle paused".to_string()),
+            sources: vec![source("binance", true, false), source("stale", false, true)],
+            heartbeats: vec![HeartbeatStatus { source: "stale".to_string(), interval: 60, last_report: 1760000000000, missed: 4, excluded: true }],
+        };
+        let report = render("oracle.testnet", true, &[health]);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "oracle.testnet: PAUSED");
+        assert_eq!(lines[1], "NEAR/USD: INVALID, no answer: Oracle paused");
+        assert!(lines[2].starts_with("  binance              active      5.280000 at 1760000000000 by feed.testnet"));
+        assert!(lines[3].contains("quarantined") && lines[3].ends_with("missed 4 heartbeats of 60s (excluded)"));
+    }
+}
@@ -0,0 +1,217 @@
+//! Admin CLI for the NEAR price oracle: common owner and role-holder calls with typed
+//! arguments, built from the contract's own types so their JSON always matches its ABI
+//!
+//! Usage: oracle-admin --oracle <account> [--credentials <key.json>] <command>
+
+mod health;
+mod rpc;
+
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use near_crypto::InMemorySigner;
+use price_oracle::{AssetConfig, AssetId, Pair, PriceBounds, Role, DEFAULT_DECIMALS, DEFAULT_QUOTE};
+use serde_json::{json, Value};
+
+use rpc::Oracle;
+
+#[derive(Parser, Debug)]
+#[command(name = "oracle-admin", about = "Admin operations on the NEAR price oracle")]
+struct Cli {
+    /// JSON-RPC endpoint of the network the oracle is on
+    #[arg(long, default_value = "https://rpc.testnet.near.org")]
+    rpc_url: String,
+    /// Account the oracle is deployed to
+    #[arg(long)]
+    oracle: String,
+    /// near-cli credentials file of the account calls are signed by; views need none
+    #[arg(long)]
+    credentials: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+enum Command {
+    /// Register an asset so pairs with it as base accept reports (add_asset, Admin)
+    RegisterAsset {
+        asset_id: AssetId,
+        /// Ticker shown to users, the asset id by default
+        #[arg(long)]
+        symbol: Option<String>,
+        /// Human readable name, the asset id by default
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long, default_value_t = DEFAULT_DECIMALS)]
+        decimals: u8,
+        /// NEP-141 contract of the asset, e.g. wrap.near
+        #[arg(long)]
+        token_id: Option<String>,
+        /// Decimals of that token's balances, e.g. 24 for wrap.near
+        #[arg(long, requires = "token_id")]
+        token_decimals: Option<u8>,
+    },
+    /// Whitelist an account to submit prices (add_reporter, Admin)
+    AddReporter { account_id: String },
+    /// Revoke a reporter and delete its reports (remove_reporter, Admin)
+    RemoveReporter { account_id: String },
+    /// Grant a role to an account (grant_role, Admin)
+    GrantRole { role: RoleArg, account_id: String },
+    /// Revoke a role from an account (revoke_role, Admin)
+    RevokeRole { role: RoleArg, account_id: String },
+    /// Reject USD prices of an asset outside min..max, scaled by its decimals (set_price_bounds, ConfigManager)
+    SetBounds {
+        asset_id: AssetId,
+        #[arg(long, required_unless_present = "clear", requires = "max")]
+        min: Option<u128>,
+        #[arg(long, required_unless_present = "clear", requires = "min")]
+        max: Option<u128>,
+        /// Drop the asset's bounds instead
+        #[arg(long, conflicts_with_all = ["min", "max"])]
+        clear: bool,
+    },
+    /// Halt reports and price views (pause, PauseGuardian)
+    Pause,
+    /// Resume reports and price views (unpause, PauseGuardian)
+    Unpause,
+    /// Show whether the oracle is paused and the answer and sources of each pair
+    Health {
+        /// Base assets to inspect, every registered asset by default
+        asset_ids: Vec<AssetId>,
+        #[arg(long, default_value = DEFAULT_QUOTE)]
+        quote: AssetId,
+    },
+}
+
+/// Roles of the oracle's access control, as command line values
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum RoleArg {
+    Admin,
+    ConfigManager,
+    Reporter,
+    PauseGuardian,
+}
+
+impl From<RoleArg> for Role {
+    fn from(role: RoleArg) -> Self {
+        match role {
+            RoleArg::Admin => Role::Admin,
+            RoleArg::ConfigManager => Role::ConfigManager,
+            RoleArg::Reporter => Role::Reporter,
+            RoleArg::PauseGuardian => Role::PauseGuardian,
+        }
+    }
+}
+
+impl Command {
+    /// The contract method a command calls with its JSON arguments; None for views
+    fn call(&self) -> Option<(&'static str, Value)> {
+        let call = match self {
+            Command::RegisterAsset { asset_id, symbol, name, decimals, token_id, token_decimals } => {
+                let mut config = AssetConfig::new(
+                    symbol.clone().unwrap_or_else(|| asset_id.clone()),
+                    name.clone().unwrap_or_else(|| asset_id.clone()),
+                    *decimals,
+                );
+                config.token_id = token_id.clone();
+                config.token_decimals = *token_decimals;
+                ("add_asset", json!({ "asset_id": asset_id, "config": config }))
+            }
+            Command::AddReporter { account_id } => ("add_reporter", json!({ "account_id": account_id })),
+            Command::RemoveReporter { account_id } => ("remove_reporter", json!({ "account_id": account_id })),
+            Command::GrantRole { role, account_id } => {
+                ("grant_role", json!({ "role": Role::from(*role), "account_id": account_id }))
+            }
+            Command::RevokeRole { role, account_id } => {
+                ("revoke_role", json!({ "role": Role::from(*role), "account_id": account_id }))
+            }
+            Command::SetBounds { asset_id, min, max, .. } => {
+                let bounds = min.zip(*max).map(|(min, max)| PriceBounds { min, max });
+                ("set_price_bounds", json!({ "asset_id": asset_id, "bounds": bounds }))
+            }
+            Command::Pause => ("pause", json!({})),
+            Command::Unpause => ("unpause", json!({})),
+            Command::Health { .. } => return None,
+        };
+        Some(call)
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(err) = run(Cli::parse()).await {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), String> {
+    let signer = match &cli.credentials {
+        Some(path) => Some(InMemorySigner::from_file(path).map_err(|err| format!("Cannot read {}: {}", path.display(), err))?),
+        None => None,
+    };
+    let oracle = Oracle::new(&cli.rpc_url, &cli.oracle, signer)?;
+
+    if let Some((method_name, args)) = cli.command.call() {
+        let outcome = oracle.call(method_name, args).await?;
+        for log in outcome.logs {
+            println!("{}", log);
+        }
+        if !outcome.value.is_empty() {
+            println!("{}", String::from_utf8_lossy(&outcome.value));
+        }
+        return Ok(());
+    }
+
+    let Command::Health { asset_ids, quote } = cli.command else {
+        unreachable!("every other command is a call");
+    };
+    let asset_ids = if asset_ids.is_empty() {
+        oracle.view("get_assets", json!({ "from_index": null, "limit": null })).await?
+    } else {
+        asset_ids
+    };
+    let paused = oracle.view("is_paused", json!({})).await?;
+    let mut pairs = Vec::new();
+    for asset_id in asset_ids {
+        pairs.push(health::pair_health(&oracle, Pair::new(asset_id, quote.clone())).await?);
+    }
+    print!("{}", health::render(oracle.oracle_id().as_str(), paused, &pairs));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, clap::Error> {
+        Cli::try_parse_from(["oracle-admin", "--oracle", "oracle.testnet"].iter().chain(args)).map(|cli| cli.command)
+    }
+
+    #[test]
+    fn test_register_asset_args_match_contract_types() {
+        let command = parse(&["register-asset", "wNEAR", "--name", "Wrapped NEAR", "--token-id", "wrap.near", "--token-decimals", "24"]).unwrap();
+        let (method_name, args) = command.call().unwrap();
+        assert_eq!(method_name, "add_asset");
+        assert_eq!(args["asset_id"], "wNEAR");
+        let config: AssetConfig = serde_json::from_value(args["config"].clone()).unwrap();
+        assert_eq!(config, AssetConfig::new("wNEAR", "Wrapped NEAR", 6).with_token("wrap.near").with_token_decimals(24));
+
+        assert!(parse(&["register-asset", "NEAR", "--token-decimals", "24"]).is_err());
+    }
+
+    #[test]
+    fn test_bounds_and_roles() {
+        let (_, args) = parse(&["set-bounds", "NEAR", "--min", "100000", "--max", "1000000000"]).unwrap().call().unwrap();
+        assert_eq!(args, json!({ "asset_id": "NEAR", "bounds": { "min": "100000", "max": "1000000000" } }));
+        let (_, args) = parse(&["set-bounds", "NEAR", "--clear"]).unwrap().call().unwrap();
+        assert_eq!(args, json!({ "asset_id": "NEAR", "bounds": null }));
+        assert!(parse(&["set-bounds", "NEAR", "--min", "100000"]).is_err());
+        assert!(parse(&["set-bounds", "NEAR"]).is_err());
+
+        let (method_name, args) = parse(&["grant-role", "pause-guardian", "ops.testnet"]).unwrap().call().unwrap();
+        assert_eq!(method_name, "grant_role");
+        assert_eq!(args, json!({ "role": "PauseGuardian", "account_id": "ops.testnet" }));
+        assert_eq!(parse(&["health"]).unwrap().call(), None);
+    }
+}
@@ -0,0 +1,104 @@
+use near_crypto::Signer;
+use near_jsonrpc_client::{methods, JsonRpcClient};
+use near_jsonrpc_primitives::types::query::QueryResponseKind;
+use near_primitives::transaction::{Action, FunctionCallAction, SignedTransaction, Transaction, TransactionV0};
+use near_primitives::types::{AccountId, BlockReference};
+use near_primitives::views::{FinalExecutionStatus, QueryRequest, TxExecutionStatus};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// Gas of an admin call; removing a reporter walks every pair it reported
+const CALL_GAS: u64 = 300_000_000_000_000;
+
+/// Result of a call: its return value and every log line of its receipts
+pub struct Outcome {
+    pub value: Vec<u8>,
+    pub logs: Vec<String>,
+}
+
+/// The oracle contract over JSON-RPC, signing calls with `signer` if there is one
+pub struct Oracle {
+    client: JsonRpcClient,
+    signer: Option<Signer>,
+    oracle_id: AccountId,
+}
+
+impl Oracle {
+    pub fn new(rpc_url: &str, oracle_id: &str, signer: Option<Signer>) -> Result<Self, String> {
+        let oracle_id = oracle_id.parse().map_err(|_| format!("Invalid oracle id {}", oracle_id))?;
+        Ok(Self { client: JsonRpcClient::connect(rpc_url), signer, oracle_id })
+    }
+
+    pub fn oracle_id(&self) -> &AccountId {
+        &self.oracle_id
+    }
+
+    pub async fn view<T: DeserializeOwned>(&self, method_name: &str, args: Value) -> Result<T, String> {
+        let response = self
+            .client
+            .call(methods::query::RpcQueryRequest {
+                block_reference: BlockReference::latest(),
+                request: QueryRequest::CallFunction {
+                    account_id: self.oracle_id.clone(),
+                    method_name: method_name.to_string(),
+                    args: args.to_string().into_bytes().into(),
+                },
+            })
+            .await
+            .map_err(|err| format!("{} failed: {}", method_name, err))?;
+        match response.kind {
+            QueryResponseKind::CallResult(result) => {
+                serde_json::from_slice(&result.result).map_err(|err| format!("{} returned bad JSON: {}", method_name, err))
+            }
+            _ => Err(format!("{} returned no call result", method_name)),
+        }
+    }
+
+    /// Sends `method_name` with JSON `args` from the signer's account and waits for it to finish
+    pub async fn call(&self, method_name: &str, args: Value) -> Result<Outcome, String> {
+        let signer = self.signer.as_ref().ok_or("Calls need --credentials")?;
+        let key = self
+            .client
+            .call(methods::query::RpcQueryRequest {
+                block_reference: BlockReference::latest(),
+                request: QueryRequest::ViewAccessKey { account_id: signer.get_account_id(), public_key: signer.public_key() },
+            })
+            .await
+            .map_err(|err| format!("Cannot read the access key: {}", err))?;
+        let QueryResponseKind::AccessKey(access_key) = key.kind else {
+            return Err("Cannot read the access key".to_string());
+        };
+        let transaction = Transaction::V0(TransactionV0 {
+            signer_id: signer.get_account_id(),
+            public_key: signer.public_key(),
+            nonce: access_key.nonce + 1,
+            receiver_id: self.oracle_id.clone(),
+            block_hash: key.block_hash,
+            actions: vec![Action::FunctionCall(Box::new(FunctionCallAction {
+                method_name: method_name.to_string(),
+                args: args.to_string().into_bytes(),
+                gas: CALL_GAS,
+                deposit: 0,
+            }))],
+        });
+        let signature = signer.sign(transaction.get_hash_and_size().0.as_ref());
+        let response = self
+            .client
+            .call(methods::send_tx::RpcSendTransactionRequest {
+                signed_transaction: SignedTransaction::new(signature, transaction),
+                wait_until: TxExecutionStatus::Final,
+            })
+            .await
+            .map_err(|err| format!("{} failed: {}", method_name, err))?;
+        let outcome = response
+            .final_execution_outcome
+            .ok_or_else(|| format!("{} has no outcome", method_name))?
+            .into_outcome();
+        let logs = outcome.receipts_outcome.iter().flat_map(|receipt| receipt.outcome.logs.clone()).collect();
+        match outcome.status {
+            FinalExecutionStatus::SuccessValue(value) => Ok(Outcome { value, logs }),
+            FinalExecutionStatus::Failure(err) => Err(format!("{} failed: {}", method_name, err)),
+            status => Err(format!("{} didn't finish: {:?}", method_name, status)),
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::retry::RetryConfig;
+
+/// What the feeder does with each cycle's prices
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// Send them in report_prices transactions signed by the reporter
+    #[default]
+    Submit,
+    /// Print them as signed packets, one submit_signed_prices argument per line, for a relayer
+    Sign,
+}
+
+/// Price APIs the feeder reads
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceKind {
+    Coingecko,
+    Binance,
+    Coinmarketcap,
+}
+
+/// One price API and how to reach it
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct SourceConfig {
+    pub kind: SourceKind,
+    #[serde(default)]
+    pub name: Option<String>,        // Source name reported to the oracle, the kind by default
+    #[serde(default)]
+    pub base_url: Option<String>,    // API root, the public endpoint by default
+    #[serde(default)]
+    pub api_key_env: Option<String>, // Environment variable holding the API key
+    #[serde(default = "default_timeout_seconds")]
+    pub timeout_seconds: u64,
+}
+
+impl SourceConfig {
+    pub fn name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| format!("{:?}", self.kind).to_lowercase())
+    }
+}
+
+/// An asset reported to the oracle, as each source names it
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct AssetFeed {
+    pub asset_id: String,
+    pub decimals: u8,                     // The asset's decimals on the oracle
+    pub symbols: HashMap<String, String>, // source name -> id there, e.g. "binance" -> "NEARUSDT"
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct Config {
+    pub rpc_url: String,
+    pub oracle_id: String,
+    pub credentials: PathBuf,        // near-cli key file of the reporter
+    #[serde(default = "default_interval_seconds")]
+    pub interval_seconds: u64,
+    #[serde(default)]
+    pub mode: Mode,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    pub sources: Vec<SourceConfig>,
+    pub assets: Vec<AssetFeed>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| format!("Cannot read {}: {}", path.display(), err))?;
+        let config: Config = serde_json::from_str(&text).map_err(|err| format!("Invalid config {}: {}", path.display(), err))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.interval_seconds == 0 {
+            return Err("interval_seconds must be positive".to_string());
+        }
+        let names: Vec<String> = self.sources.iter().map(SourceConfig::name).collect();
+        for asset in &self.assets {
+            if let Some(unknown) = asset.symbols.keys().find(|name| !names.contains(name)) {
+                return Err(format!("Asset {} names unknown source {}", asset.asset_id, unknown));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn default_interval_seconds() -> u64 {
+    60
+}
+
+fn default_timeout_seconds() -> u64 {
+    10
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_example_config_loads() {
+        let config = Config::load(Path::new("config.example.json")).unwrap();
+        assert_eq!((config.mode, config.interval_seconds), (Mode::Submit, 60));
+        assert_eq!(config.sources[0].name(), "coingecko");
+        assert_eq!(config.assets[0].symbols["binance"], "NEARUSDT");
+    }
+
+    #[test]
+    fn test_unknown_source_refused() {
+        let config: Config = serde_json::from_str(
+            r#"{"rpc_url":"http://localhost:3030","oracle_id":"oracle.near","credentials":"key.json",
+                "sources":[{"kind":"binance","name":"binance-us"}],
+                "assets":[{"asset_id":"NEAR","decimals":6,"symbols":{"binance":"NEARUSDT"}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.validate(), Err("Asset NEAR names unknown source binance".to_string()));
+    }
+}
@@ -0,0 +1,102 @@
+//! Reference feeder for the NEAR price oracle: every interval it fetches the configured
+//! assets from each configured price API and reports them, either in report_prices
+//! transactions of the reporter or as signed packets for a relayer
+//!
+//! Usage: feeder <config.json> [--once]
+
+mod config;
+mod oracle;
+mod retry;
+mod sources;
+
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use near_crypto::InMemorySigner;
+use price_oracle::{BatchReport, Pair, ReportStatus, MAX_BATCH_REPORTS};
+use serde_json::json;
+
+use config::{Config, Mode};
+use oracle::{sign_packets, Oracle};
+use retry::retry;
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(path) = args.iter().find(|arg| !arg.starts_with("--")) else {
+        eprintln!("Usage: feeder <config.json> [--once]");
+        std::process::exit(2);
+    };
+    if let Err(err) = run(Path::new(path), args.iter().any(|arg| arg == "--once")).await {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+async fn run(path: &Path, once: bool) -> Result<(), String> {
+    let config = Config::load(path)?;
+    let signer = InMemorySigner::from_file(&config.credentials)
+        .map_err(|err| format!("Cannot read {}: {}", config.credentials.display(), err))?;
+    let oracle = Oracle::new(&config.rpc_url, &config.oracle_id, signer.clone())?;
+    let client = reqwest::Client::new();
+    eprintln!("Feeding {} as {} every {}s", config.oracle_id, oracle.reporter_id(), config.interval_seconds);
+
+    loop {
+        let reports = fetch_all(&config, &client).await;
+        for batch in reports.chunks(MAX_BATCH_REPORTS) {
+            if let Err(err) = report(&config, &oracle, &signer, batch).await {
+                eprintln!("{}", err);
+            }
+        }
+        if once {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_secs(config.interval_seconds)).await;
+    }
+}
+
+/// The price of every asset at every source naming it; failed fetches are logged and left out
+async fn fetch_all(config: &Config, client: &reqwest::Client) -> Vec<BatchReport> {
+    let mut reports = Vec::new();
+    for asset in &config.assets {
+        for source in &config.sources {
+            let name = source.name();
+            let Some(symbol) = asset.symbols.get(&name) else {
+                continue;
+            };
+            let what = format!("{} from {}", asset.asset_id, name);
+            match retry(&config.retry, &what, || sources::fetch_price(client, source, symbol, asset.decimals)).await {
+                Ok(price) => reports.push(BatchReport {
+                    pair: Pair::usd(asset.asset_id.clone()),
+                    source: name,
+                    price,
+                    decimals: asset.decimals,
+                    volume: None,
+                }),
+                Err(err) => eprintln!("{}", err),
+            }
+        }
+    }
+    reports
+}
+
+async fn report(config: &Config, oracle: &Oracle, signer: &near_crypto::Signer, batch: &[BatchReport]) -> Result<(), String> {
+    match config.mode {
+        Mode::Submit => {
+            let statuses = retry(&config.retry, "report_prices", || oracle.report_prices(batch)).await?;
+            for (entry, status) in batch.iter().zip(statuses) {
+                if let ReportStatus::Rejected(reason) = status {
+                    eprintln!("{} from {} rejected: {}", entry.pair, entry.source, reason);
+                }
+            }
+            eprintln!("Reported {} prices", batch.len());
+        }
+        Mode::Sign => {
+            // Millisecond timestamps keep nonces increasing across cycles and restarts
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("clock after 1970").as_millis() as u64;
+            let packets = sign_packets(signer, &config.oracle_id, batch, now, now * 1000)?;
+            println!("{}", json!({ "packets": packets }));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use near_crypto::{Signature, Signer};
+use near_jsonrpc_client::{methods, JsonRpcClient};
+use near_jsonrpc_primitives::types::query::QueryResponseKind;
+use near_primitives::transaction::{Action, FunctionCallAction, SignedTransaction, Transaction, TransactionV0};
+use near_primitives::types::{AccountId, BlockReference};
+use near_primitives::views::{FinalExecutionStatus, QueryRequest, TxExecutionStatus};
+use price_oracle::{packet_message, BatchReport, ReportStatus, SignedPrice};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+/// Gas of a report_prices transaction; enough for a full batch
+const REPORT_GAS: u64 = 300_000_000_000_000;
+
+/// The oracle contract, reached as the reporter the signer belongs to
+pub struct Oracle {
+    client: JsonRpcClient,
+    signer: Signer,
+    oracle_id: AccountId,
+}
+
+impl Oracle {
+    pub fn new(rpc_url: &str, oracle_id: &str, signer: Signer) -> Result<Self, String> {
+        let oracle_id = oracle_id.parse().map_err(|_| format!("Invalid oracle id {}", oracle_id))?;
+        Ok(Self { client: JsonRpcClient::connect(rpc_url), signer, oracle_id })
+    }
+
+    pub fn reporter_id(&self) -> AccountId {
+        self.signer.get_account_id()
+    }
+
+    pub async fn view<T: DeserializeOwned>(&self, method_name: &str, args: Value) -> Result<T, String> {
+        let response = self
+            .client
+            .call(methods::query::RpcQueryRequest {
+                block_reference: BlockReference::latest(),
+                request: QueryRequest::CallFunction {
+                    account_id: self.oracle_id.clone(),
+                    method_name: method_name.to_string(),
+                    args: args.to_string().into_bytes().into(),
+                },
+            })
+            .await
+            .map_err(|err| format!("{} failed: {}", method_name, err))?;
+        match response.kind {
+            QueryResponseKind::CallResult(result) => {
+                serde_json::from_slice(&result.result).map_err(|err| format!("{} returned bad JSON: {}", method_name, err))
+            }
+            _ => Err(format!("{} returned no call result", method_name)),
+        }
+    }
+
+    /// Sends a report_prices transaction with the report deposit of every entry
+    pub async fn report_prices(&self, reports: &[BatchReport]) -> Result<Vec<ReportStatus>, String> {
+        let deposit: String = self.view::<String>("get_report_deposit", json!({})).await?;
+        let deposit: u128 = deposit.parse().map_err(|_| format!("Bad report deposit {}", deposit))?;
+        let args = json!({ "reports": reports }).to_string().into_bytes();
+        let value = self.call("report_prices", args, deposit * reports.len() as u128).await?;
+        serde_json::from_slice(&value).map_err(|err| format!("report_prices returned bad JSON: {}", err))
+    }
+
+    async fn call(&self, method_name: &str, args: Vec<u8>, deposit: u128) -> Result<Vec<u8>, String> {
+        let key = self
+            .client
+            .call(methods::query::RpcQueryRequest {
+                block_reference: BlockReference::latest(),
+                request: QueryRequest::ViewAccessKey {
+                    account_id: self.signer.get_account_id(),
+                    public_key: self.signer.public_key(),
+                },
+            })
+            .await
+            .map_err(|err| format!("Cannot read the access key: {}", err))?;
+        let QueryResponseKind::AccessKey(access_key) = key.kind else {
+            return Err("Cannot read the access key".to_string());
+        };
+        let transaction = Transaction::V0(TransactionV0 {
+            signer_id: self.signer.get_account_id(),
+            public_key: self.signer.public_key(),
+            nonce: access_key.nonce + 1,
+            receiver_id: self.oracle_id.clone(),
+            block_hash: key.block_hash,
+            actions: vec![Action::FunctionCall(Box::new(FunctionCallAction {
+                method_name: method_name.to_string(),
+                args,
+                gas: REPORT_GAS,
+                deposit,
+            }))],
+        });
+        let signature = self.signer.sign(transaction.get_hash_and_size().0.as_ref());
+        let response = self
+            .client
+            .call(methods::send_tx::RpcSendTransactionRequest {
+                signed_transaction: SignedTransaction::new(signature, transaction),
+                wait_until: TxExecutionStatus::Final,
+            })
+            .await
+            .map_err(|err| format!("{} failed: {}", method_name, err))?;
+        let outcome = response
+            .final_execution_outcome
+            .ok_or_else(|| format!("{} has no outcome", method_name))?
+            .into_outcome();
+        match outcome.status {
+            FinalExecutionStatus::SuccessValue(value) => Ok(value),
+            FinalExecutionStatus::Failure(err) => Err(format!("{} failed: {}", method_name, err)),
+            status => Err(format!("{} didn't finish: {:?}", method_name, status)),
+        }
+    }
+}
+
+/// Signs reports as packets any account can relay with submit_signed_prices, with
+/// nonces counted up from `first_nonce`
+/// The signer's key must be the reporter's packet key, see set_reporter_key
+pub fn sign_packets(signer: &Signer, oracle_id: &str, reports: &[BatchReport], timestamp: u64, first_nonce: u64) -> Result<Vec<SignedPrice>, String> {
+    let oracle_id = oracle_id.parse().map_err(|_| format!("Invalid oracle id {}", oracle_id))?;
+    reports
+        .iter()
+        .zip(first_nonce..)
+        .map(|(report, nonce)| {
+            let mut packet = SignedPrice {
+                reporter: signer.get_account_id().as_str().parse().expect("account ids parse"),
+                pair: report.pair.clone(),
+                source: report.source.clone(),
+                price: report.price,
+                decimals: report.decimals,
+                timestamp,
+                nonce,
+                signature: Vec::new().into(),
+            };
+            let Signature::ED25519(signature) = signer.sign(&packet_message(&oracle_id, &packet)) else {
+                return Err("Packet keys must be ed25519".to_string());
+            };
+            packet.signature = signature.to_bytes().to_vec().into();
+            Ok(packet)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_crypto::{InMemorySigner, KeyType, PublicKey, SecretKey};
+    use price_oracle::Pair;
+
+    #[test]
+    fn test_packets_verify_under_reporter_key() {
+        let secret_key = SecretKey::from_seed(KeyType::ED25519, "feeder");
+        let signer = InMemorySigner::from_secret_key("reporter.testnet".parse().unwrap(), secret_key.clone());
+        let reports = [
+            BatchReport { pair: Pair::usd("NEAR"), source: "binance".to_string(), price: 5250000, decimals: 6, volume: None },
+            BatchReport { pair: Pair::usd("ETH"), source: "binance".to_string(), price: 3000000000, decimals: 6, volume: None },
+        ];
+        let packets = sign_packets(&signer, "oracle.testnet", &reports, 1760400000000, 41).unwrap();
+        assert_eq!(packets.iter().map(|packet| packet.nonce).collect::<Vec<_>>(), [41, 42]);
+
+        let public_key: PublicKey = secret_key.public_key();
+        for packet in &packets {
+            let signature = Signature::from_parts(KeyType::ED25519, &packet.signature.0).unwrap();
+            let message = packet_message(&"oracle.testnet".parse().unwrap(), packet);
+            assert!(signature.verify(&message, &public_key));
+        }
+    }
+}
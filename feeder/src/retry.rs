@@ -0,0 +1,79 @@
+use std::future::Future;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+/// How often and how patiently a failing request is retried
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct RetryConfig {
+    pub attempts: u32,             // Tries in all, the first included
+    pub initial_backoff_ms: u64,   // Wait after the first failure, doubled after each next one
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self { attempts: 3, initial_backoff_ms: 500, max_backoff_ms: 10_000 }
+    }
+}
+
+impl RetryConfig {
+    /// Wait after the `failures`th failure in a row
+    pub fn backoff(&self, failures: u32) -> Duration {
+        let factor = 1u64.checked_shl(failures.saturating_sub(1)).unwrap_or(u64::MAX);
+        Duration::from_millis(self.initial_backoff_ms.saturating_mul(factor).min(self.max_backoff_ms))
+    }
+}
+
+/// Runs `attempt` until it succeeds or `config.attempts` tries failed, backing off in
+/// between; returns the last error
+pub async fn retry<T, F, Fut>(config: &RetryConfig, what: &str, mut attempt: F) -> Result<T, String>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, String>>,
+{
+    let mut failures = 0;
+    loop {
+        match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(err) => {
+                failures += 1;
+                if failures >= config.attempts.max(1) {
+                    return Err(format!("{} failed {} times: {}", what, failures, err));
+                }
+                let wait = config.backoff(failures);
+                eprintln!("{} failed ({}), retrying in {:?}", what, err, wait);
+                tokio::time::sleep(wait).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let config = RetryConfig::default();
+        let waits: Vec<u64> = (1..=7).map(|failures| config.backoff(failures).as_millis() as u64).collect();
+        assert_eq!(waits, [500, 1000, 2000, 4000, 8000, 10_000, 10_000]);
+        assert_eq!(config.backoff(200), Duration::from_millis(10_000));
+    }
+
+    #[tokio::test]
+    async fn test_retry_until_success_or_attempts() {
+        let config = RetryConfig { attempts: 3, initial_backoff_ms: 0, max_backoff_ms: 0 };
+        let mut calls = 0;
+        let result = retry(&config, "fetch", || {
+            calls += 1;
+            let call = calls;
+            async move { if call < 3 { Err(format!("timeout {}", call)) } else { Ok(call) } }
+        })
+        .await;
+        assert_eq!(result, Ok(3));
+
+        let result: Result<(), String> = retry(&config, "fetch", || async { Err("503".to_string()) }).await;
+        assert_eq!(result, Err("fetch failed 3 times: 503".to_string()));
+    }
+}
@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::config::{SourceConfig, SourceKind};
+
+/// USD price of `symbol` at a source, scaled by 10^decimals
+pub async fn fetch_price(client: &reqwest::Client, source: &SourceConfig, symbol: &str, decimals: u8) -> Result<u128, String> {
+    let base_url = source.base_url.clone().unwrap_or_else(|| default_base_url(source.kind).to_string());
+    let api_key = match &source.api_key_env {
+        Some(var) => Some(std::env::var(var).map_err(|_| format!("{} is not set", var))?),
+        None => None,
+    };
+    let mut request = match source.kind {
+        SourceKind::Coingecko => client
+            .get(format!("{}/api/v3/simple/price", base_url))
+            .query(&[("ids", symbol), ("vs_currencies", "usd")]),
+        SourceKind::Binance => client.get(format!("{}/api/v3/ticker/price", base_url)).query(&[("symbol", symbol)]),
+        SourceKind::Coinmarketcap => client
+            .get(format!("{}/v1/cryptocurrency/quotes/latest", base_url))
+            .query(&[("symbol", symbol), ("convert", "USD")]),
+    };
+    if let Some(key) = api_key {
+        let header = match source.kind {
+            SourceKind::Coingecko => "x-cg-demo-api-key",
+            SourceKind::Binance => "X-MBX-APIKEY",
+            SourceKind::Coinmarketcap => "X-CMC_PRO_API_KEY",
+        };
+        request = request.header(header, key);
+    }
+    let response = request
+        .timeout(Duration::from_secs(source.timeout_seconds))
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|err| err.to_string())?;
+    let body: Value = response.json().await.map_err(|err| err.to_string())?;
+    let text = price_text(source.kind, symbol, &body)?;
+    scale_decimal(&text, decimals).ok_or_else(|| format!("Unusable price {} for {}", text, symbol))
+}
+
+fn default_base_url(kind: SourceKind) -> &'static str {
+    match kind {
+        SourceKind::Coingecko => "https://api.coingecko.com",
+        SourceKind::Binance => "https://api.binance.com",
+        SourceKind::Coinmarketcap => "https://pro-api.coinmarketcap.com",
+    }
+}
+
+/// The price in a source's response, as the decimal text it was sent as
+fn price_text(kind: SourceKind, symbol: &str, body: &Value) -> Result<String, String> {
+    let price = match kind {
+        SourceKind::Coingecko => &body[symbol]["usd"],
+        SourceKind::Binance => &body["price"],
+        SourceKind::Coinmarketcap => &body["data"][symbol]["quote"]["USD"]["price"],
+    };
+    match price {
+        Value::Number(number) => Ok(number.to_string()),
+        Value::String(text) => Ok(text.clone()),
+        _ => Err(format!("No price for {} in {}", symbol, body)),
+    }
+}
+
+/// A non-negative decimal like "5.25" or "1.2e-5" scaled by 10^decimals, rounded down;
+/// None if it isn't one or doesn't fit in a u128
+pub fn scale_decimal(text: &str, decimals: u8) -> Option<u128> {
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i32>().ok()?),
+        None => (text, 0),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if whole.is_empty() && fraction.is_empty() || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    // digits * 10^(shift), with shift counted from the last fraction digit
+    let digits = format!("{}{}", whole, fraction);
+    let shift = exponent + decimals as i32 - fraction.len() as i32;
+    let digits = if shift >= 0 {
+        format!("{}{}", digits, "0".repeat(shift as usize))
+    } else {
+        let keep = digits.len().saturating_sub(shift.unsigned_abs() as usize);
+        digits[..keep].to_string()
+    };
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Some(0);
+    }
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_scale_decimal() {
+        assert_eq!(scale_decimal("5.25", 6), Some(5250000));
+        assert_eq!(scale_decimal("5.2512349", 6), Some(5251234));
+        assert_eq!(scale_decimal("3000", 8), Some(300000000000));
+        assert_eq!(scale_decimal("1.2e-5", 8), Some(1200));
+        assert_eq!(scale_decimal("12E2", 0), Some(1200));
+        assert_eq!(scale_decimal(".5", 1), Some(5));
+        assert_eq!(scale_decimal("0.0000001", 6), Some(0));
+        assert_eq!(scale_decimal("-1", 6), None);
+        assert_eq!(scale_decimal("NaN", 6), None);
+        assert_eq!(scale_decimal("1e40", 6), None);
+    }
+
+    #[test]
+    fn test_price_of_each_source() {
+        let coingecko = json!({ "near": { "usd": 5.25 } });
+        let binance = json!({ "symbol": "NEARUSDT", "price": "5.25100000" });
+        let coinmarketcap = json!({ "data": { "NEAR": { "quote": { "USD": { "price": 5.2498 } } } } });
+        assert_eq!(price_text(SourceKind::Coingecko, "near", &coingecko), Ok("5.25".to_string()));
+        assert_eq!(price_text(SourceKind::Binance, "NEARUSDT", &binance), Ok("5.25100000".to_string()));
+        assert_eq!(price_text(SourceKind::Coinmarketcap, "NEAR", &coinmarketcap), Ok("5.2498".to_string()));
+        assert!(price_text(SourceKind::Coingecko, "aurora", &coingecko).is_err());
+    }
+}
@@ -0,0 +1,230 @@
+use near_sdk::{env, near, AccountId};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::{paginate, OracleEvent, PriceOracle, PriceOracleExt};
+
+/// Operational duty that can be granted to an account
+/// The owner and Admins pass every role check
+#[derive(
+    BorshSerialize, BorshDeserialize, Serialize, Deserialize,
+    Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord,
+)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub enum Role {
+    Admin,         // Assets, reporters, source bindings, quarantine, roles
+    ConfigManager, // Aggregation, staleness, guard and feed parameters
+    Reporter,      // Submitting prices
+    PauseGuardian, // Halting the oracle during an incident
+}
+
+#[near]
+impl PriceOracle {
+    /// Get the account that owns the contract
+    pub fn get_owner(&self) -> AccountId {
+        self.owner.clone()
+    }
+
+    /// Get the account proposed as the next owner, if any
+    pub fn get_pending_owner(&self) -> Option<AccountId> {
+        self.pending_owner.clone()
+    }
+
+    /// Propose a new owner, who takes over once it calls `accept_ownership` (owner only)
+    /// Proposing again replaces the pending proposal
+    pub fn propose_owner(&mut self, new_owner: AccountId) {
+        self.assert_owner();
+        near_sdk::log!("Ownership proposed to {}", new_owner);
+        self.pending_owner = Some(new_owner);
+    }
+
+    /// Withdraw the pending ownership proposal (owner only)
+    pub fn cancel_ownership_proposal(&mut self) {
+        self.assert_owner();
+        let proposed = self.pending_owner.take().expect("No ownership proposal pending");
+        near_sdk::log!("Ownership proposal to {} cancelled", proposed);
+    }
+
+    /// Become the owner as the proposed account
+    pub fn accept_ownership(&mut self) {
+        let caller = env::predecessor_account_id();
+        let proposed = self.pending_owner.take().expect("No ownership proposal pending");
+        assert!(caller == proposed, "Only the proposed owner {} can accept ownership", proposed);
+        near_sdk::log!("Ownership transferred from {} to {}", self.owner, proposed);
+        OracleEvent::OwnershipTransferred { previous_owner: self.owner.to_string(), new_owner: proposed.to_string() }.emit();
+        self.owner = proposed;
+    }
+
+    /// Grant a role to an account (Admin)
+    pub fn grant_role(&mut self, role: Role, account_id: AccountId) {
+        self.assert_role(Role::Admin);
+        assert!(self.insert_role(role, account_id.clone()), "{} already has role {:?}", account_id, role);
+        near_sdk::log!("Role {:?} granted to {}", role, account_id);
+    }
+
+    /// Revoke a role from an account (Admin); revoking Reporter retires it as
+    /// remove_reporter does: its reports are deleted, its stake starts unbonding and a
+    /// self-registration deposit is refunded
+    pub fn revoke_role(&mut self, role: Role, account_id: AccountId) {
+        self.assert_role(Role::Admin);
+        assert!(self.remove_role(role, &account_id), "{} does not have role {:?}", account_id, role);
+        near_sdk::log!("Role {:?} revoked from {}", role, account_id);
+        if role == Role::Reporter {
+            self.retire_reporter(&account_id);
+        }
+    }
+
+    /// Check if an account passes the checks of a role, directly or as owner or Admin
+    pub fn has_role(&self, role: Role, account_id: AccountId) -> bool {
+        let granted = |role| self.roles.get(&role).is_some_and(|members| members.contains(&account_id));
+        account_id == self.owner || granted(Role::Admin) || granted(role)
+    }
+
+    /// Get the roles explicitly granted to an account
+    pub fn get_roles(&self, account_id: AccountId) -> Vec<Role> {
+        let mut roles: Vec<Role> = self
+            .roles
+            .iter()
+            .filter(|(_, members)| members.contains(&account_id))
+            .map(|(role, _)| *role)
+            .collect();
+        roles.sort();
+        roles
+    }
+
+    /// Get the accounts explicitly granted a role in lexicographic order
+    pub fn get_role_members(&self, role: Role, from_index: Option<u64>, limit: Option<u64>) -> Vec<AccountId> {
+        let mut members: Vec<AccountId> = self
+            .roles
+            .get(&role)
+            .map(|members| members.iter().cloned().collect())
+            .unwrap_or_default();
+        members.sort();
+        paginate(members, from_index, limit)
+    }
+}
+
+impl PriceOracle {
+    pub(crate) fn assert_owner(&self) {
+        assert_eq!(env::predecessor_account_id(), self.owner, "Only owner");
+    }
+
+    /// Panics unless the caller passes the checks of `role`
+    pub(crate) fn assert_role(&self, role: Role) {
+        let caller = env::predecessor_account_id();
+        assert!(self.has_role(role, caller.clone()), "{} lacks role {:?}", caller, role);
+    }
+
+    /// Adds an account to a role, false if it already had it
+    pub(crate) fn insert_role(&mut self, role: Role, account_id: AccountId) -> bool {
+        self.roles.entry(role).or_default().insert(account_id)
+    }
+
+    /// Removes an account from a role, false if it didn't have it
+    pub(crate) fn remove_role(&mut self, role: Role, account_id: &AccountId) -> bool {
+        self.roles.get_mut(&role).is_some_and(|members| members.remove(account_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn as_account(account_id: AccountId) {
+        testing_env!(VMContextBuilder::new().predecessor_account_id(account_id).build());
+    }
+
+    fn setup() -> PriceOracle {
+        as_account(accounts(0));
+        PriceOracle::for_tests()
+    }
+
+    #[test]
+    fn test_config_manager_can_configure() {
+        let mut contract = setup();
+        contract.grant_role(Role::ConfigManager, accounts(1));
+        assert_eq!(contract.get_roles(accounts(1)), vec![Role::ConfigManager]);
+        assert!(!contract.has_role(Role::Admin, accounts(1)));
+
+        as_account(accounts(1));
+        contract.set_min_sources(2);
+        assert_eq!(contract.get_min_sources(), 2);
+    }
+
+    #[test]
+    fn test_admin_passes_every_role() {
+        let mut contract = setup();
+        contract.grant_role(Role::Admin, accounts(1));
+        as_account(accounts(1));
+        contract.grant_role(Role::PauseGuardian, accounts(2));
+        contract.set_min_sources(2);
+        assert!(contract.has_role(Role::Reporter, accounts(1)));
+        assert_eq!(contract.get_role_members(Role::PauseGuardian, None, None), vec![accounts(2)]);
+
+        contract.revoke_role(Role::PauseGuardian, accounts(2));
+        assert!(contract.get_roles(accounts(2)).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "bob lacks role ConfigManager")]
+    fn test_role_required() {
+        let mut contract = setup();
+        contract.grant_role(Role::Reporter, accounts(1));
+        as_account(accounts(1));
+        contract.set_min_sources(2);
+    }
+
+    #[test]
+    fn test_two_step_ownership_transfer() {
+        let mut contract = setup();
+        contract.propose_owner(accounts(1));
+        assert_eq!(contract.get_owner(), accounts(0));      // Nothing changes until accepted
+        assert_eq!(contract.get_pending_owner(), Some(accounts(1)));
+
+        as_account(accounts(1));
+        contract.accept_ownership();
+        assert_eq!(
+            get_logs(),
+            vec![
+                "Ownership transferred from alice to bob".to_string(),
+                r#"EVENT_JSON:{"standard":"price-oracle","version":"2.0.0","event":"ownership_transferred","data":{"previous_owner":"alice","new_owner":"bob"}}"#.to_string(),
+            ]
+        );
+        assert_eq!(contract.get_owner(), accounts(1));
+        assert_eq!(contract.get_pending_owner(), None);
+        assert!(!contract.has_role(Role::Admin, accounts(0)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the proposed owner bob can accept ownership")]
+    fn test_accept_ownership_requires_proposed_owner() {
+        let mut contract = setup();
+        contract.propose_owner(accounts(1));
+        as_account(accounts(2));
+        contract.accept_ownership();
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner")]
+    fn test_admin_cannot_propose_owner() {
+        let mut contract = setup();
+        contract.grant_role(Role::Admin, accounts(1));
+        as_account(accounts(1));
+        contract.propose_owner(accounts(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "bob lacks role Admin")]
+    fn test_config_manager_cannot_grant() {
+        let mut contract = setup();
+        contract.grant_role(Role::ConfigManager, accounts(1));
+        as_account(accounts(1));
+        contract.grant_role(Role::Admin, accounts(1));
+    }
+}
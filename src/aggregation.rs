@@ -0,0 +1,377 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::math::{U256, U512};
+use crate::stablecoin::BPS_DENOMINATOR;
+
+/// How the reports of an asset's pairs are combined into one price
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub enum AggregationMethod {
+    /// Average of all sources
+    Mean,
+    /// Middle source, so a single outlier can't move the price
+    #[default]
+    Median,
+    /// Median weighted by each reporter's bonded stake
+    Weighted,
+    /// Mean after discarding the lowest and highest `trim_bps` of reports, a middle ground
+    /// between mean and median for assets with 5+ sources
+    TrimmedMean { trim_bps: u16 },
+    /// Median weighted by the weight assigned each source, so tier-1 venues
+    /// count for more than aggregators
+    SourceWeighted,
+}
+
+impl AggregationMethod {
+    pub(crate) fn aggregator(&self) -> Box<dyn Aggregator> {
+        match self {
+            AggregationMethod::Mean => Box::new(Mean),
+            AggregationMethod::Median => Box::new(Median),
+            AggregationMethod::Weighted => Box::new(StakeWeighted),
+            AggregationMethod::TrimmedMean { trim_bps } => Box::new(TrimmedMean { trim_bps: *trim_bps }),
+            AggregationMethod::SourceWeighted => Box::new(SourceWeightedMedian),
+        }
+    }
+
+    pub(crate) fn validate(&self) {
+        if let AggregationMethod::TrimmedMean { trim_bps } = self {
+            assert!(
+                (*trim_bps as u128) * 2 < BPS_DENOMINATOR,
+                "Trimmed mean must keep some reports, got trim_bps {}",
+                trim_bps
+            );
+        }
+    }
+}
+
+/// A report as seen by an aggregator
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Sample {
+    pub price: u128,
+    pub weight: u128,         // Stake of the reporter; 1 for every sample when nobody is staked
+    pub source_weight: u128,  // Weight assigned the source, 1 by default
+}
+
+/// Strategy combining the accepted samples of a pair into one price
+pub(crate) trait Aggregator {
+    /// Called with at least one sample
+    fn aggregate(&self, samples: &[Sample]) -> u128;
+}
+
+pub(crate) struct Mean;
+pub(crate) struct Median;
+pub(crate) struct StakeWeighted;
+pub(crate) struct TrimmedMean {
+    pub trim_bps: u16,
+}
+pub(crate) struct SourceWeightedMedian;
+
+impl Aggregator for Mean {
+    fn aggregate(&self, samples: &[Sample]) -> u128 {
+        mean(&prices(samples))
+    }
+}
+
+impl Aggregator for Median {
+    fn aggregate(&self, samples: &[Sample]) -> u128 {
+        median(&prices(samples))
+    }
+}
+
+impl Aggregator for StakeWeighted {
+    fn aggregate(&self, samples: &[Sample]) -> u128 {
+        let points: Vec<(u128, u128)> = samples.iter().map(|s| (s.price, s.weight)).collect();
+        weighted_median(&points)
+    }
+}
+
+impl Aggregator for TrimmedMean {
+    fn aggregate(&self, samples: &[Sample]) -> u128 {
+        trimmed_mean(&prices(samples), self.trim_bps)
+    }
+}
+
+impl Aggregator for SourceWeightedMedian {
+    fn aggregate(&self, samples: &[Sample]) -> u128 {
+        let points: Vec<(u128, u128)> = samples.iter().map(|s| (s.price, s.source_weight)).collect();
+        weighted_median(&points)
+    }
+}
+
+fn prices(samples: &[Sample]) -> Vec<u128> {
+    samples.iter().map(|s| s.price).collect()
+}
+
+/// Arithmetic mean of the prices (0 for an empty set)
+/// Summed in 256 bits, so no count or size of u128 prices can overflow
+pub(crate) fn mean(prices: &[u128]) -> u128 {
+    if prices.is_empty() {
+        return 0;
+    }
+    let sum = prices.iter().fold(U256::zero(), |sum, price| sum + U256::from(*price));
+    (sum / U256::from(prices.len())).as_u128()
+}
+
+/// Middle price; the average of the two middle prices for an even count (0 for an empty set)
+pub(crate) fn median(prices: &[u128]) -> u128 {
+    if prices.is_empty() {
+        return 0;
+    }
+    let mut sorted = prices.to_vec();
+    sorted.sort_unstable();
+
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 1 {
+        sorted[mid]
+    } else {
+        let (low, high) = (sorted[mid - 1], sorted[mid]);
+        low + (high - low) / 2
+    }
+}
+
+/// Mean of the prices without the lowest and highest `trim_bps` share of them,
+/// rounding the number trimmed from each end down (0 for an empty set)
+pub(crate) fn trimmed_mean(prices: &[u128], trim_bps: u16) -> u128 {
+    let mut sorted = prices.to_vec();
+    sorted.sort_unstable();
+    let trim = sorted.len() * trim_bps as usize / BPS_DENOMINATOR as usize;
+    if trim * 2 >= sorted.len() {
+        return median(&sorted);
+    }
+    mean(&sorted[trim..sorted.len() - trim])
+}
+
+/// Half the distance between the lowest and highest price (0 for an empty set)
+pub(crate) fn half_spread(prices: &[u128]) -> u128 {
+    let min = prices.iter().min().copied().unwrap_or(0);
+    let max = prices.iter().max().copied().unwrap_or(0);
+    (max - min) / 2
+}
+
+/// Average of the prices weighted by their weights (0 if no weight)
+/// Products are summed in 512 bits, so any number of u128 prices and weights fit
+pub(crate) fn weighted_mean(points: &[(u128, u128)]) -> u128 {
+    let (sum, total) = points.iter().fold((U512::zero(), U512::zero()), |(sum, total), (price, weight)| {
+        (sum + U512::from(*price) * U512::from(*weight), total + U512::from(*weight))
+    });
+    if total.is_zero() {
+        return 0;
+    }
+    (sum / total).as_u128()
+}
+
+/// Median of the prices and the median absolute deviation from it
+pub(crate) fn median_absolute_deviation(prices: &[u128]) -> (u128, u128) {
+    let center = median(prices);
+    let deviations: Vec<u128> = prices.iter().map(|p| p.abs_diff(center)).collect();
+    (center, median(&deviations))
+}
+
+/// Price at which half of the total weight lies on either side; the average of the two
+/// straddling prices on an exact split (0 if no weight)
+pub(crate) fn weighted_median(points: &[(u128, u128)]) -> u128 {
+    let mut sorted: Vec<(u128, u128)> = points.iter().copied().filter(|(_, w)| *w > 0).collect();
+    sorted.sort_unstable();
+    let total = sorted.iter().fold(U256::zero(), |sum, (_, w)| sum + U256::from(*w));
+
+    let mut below = U256::zero();
+    for (i, (price, weight)) in sorted.iter().enumerate() {
+        below += U256::from(*weight);
+        let above = total - below;
+        if below == above {
+            let next = sorted[i + 1].0;
+            return price + (next - price) / 2;
+        }
+        if below > above {
+            return *price;
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn samples(points: &[(u128, u128)]) -> Vec<Sample> {
+        points.iter().map(|(price, weight)| Sample { price: *price, weight: *weight, source_weight: *weight }).collect()
+    }
+
+    #[test]
+    fn test_aggregation_methods() {
+        let samples = samples(&[(100, 1), (200, 1), (900, 10)]);
+        assert_eq!(AggregationMethod::Mean.aggregator().aggregate(&samples), 400);
+        assert_eq!(AggregationMethod::Median.aggregator().aggregate(&samples), 200);
+        assert_eq!(AggregationMethod::Weighted.aggregator().aggregate(&samples), 900);
+        assert_eq!(AggregationMethod::SourceWeighted.aggregator().aggregate(&samples), 900);
+        assert_eq!(AggregationMethod::default(), AggregationMethod::Median);
+    }
+
+    #[test]
+    fn test_source_and_stake_weights_are_separate() {
+        let samples = [
+            Sample { price: 100, weight: 1, source_weight: 3 },
+            Sample { price: 200, weight: 1, source_weight: 1 },
+            Sample { price: 900, weight: 10, source_weight: 1 },
+        ];
+        assert_eq!(AggregationMethod::SourceWeighted.aggregator().aggregate(&samples), 100);
+        assert_eq!(AggregationMethod::Weighted.aggregator().aggregate(&samples), 900);
+    }
+
+    #[test]
+    fn test_trimmed_mean() {
+        let prices = [100, 5000000, 5100000, 5200000, 5300000, 5400000, 5500000, 5600000, 5700000, 90000000];
+        assert_eq!(trimmed_mean(&prices, 0), mean(&prices));
+        assert_eq!(trimmed_mean(&prices, 1000), 5350000);       // Drops one from each end
+        assert_eq!(trimmed_mean(&prices, 1999), 5350000);       // Still only one
+        assert_eq!(trimmed_mean(&prices, 2000), 5350000);
+        assert_eq!(trimmed_mean(&[3, 1, 2], 2000), 2);          // Too few to trim: plain mean
+        assert_eq!(trimmed_mean(&[], 2000), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Trimmed mean must keep some reports, got trim_bps 5000")]
+    fn test_trimmed_mean_keeps_reports() {
+        AggregationMethod::TrimmedMean { trim_bps: 5000 }.validate();
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&[]), 0);
+        assert_eq!(median(&[7]), 7);
+        assert_eq!(median(&[3, 1, 2]), 2);
+        assert_eq!(median(&[4, 1, 3, 2]), 2);
+        assert_eq!(median(&[u128::MAX, u128::MAX - 2]), u128::MAX - 1);
+    }
+
+    #[test]
+    fn test_median_ignores_single_outlier() {
+        let honest = [5000000, 5100000, 5200000];
+        let attacked = [5000000, 5100000, 5200000, 500000000];
+        assert_eq!(median(&honest), 5100000);
+        assert_eq!(median(&attacked), 5150000);
+        assert!(mean(&attacked) > 100000000);
+    }
+
+    #[test]
+    fn test_median_absolute_deviation() {
+        assert_eq!(median_absolute_deviation(&[]), (0, 0));
+        assert_eq!(median_absolute_deviation(&[5000000, 5100000, 500000000]), (5100000, 100000));
+        assert_eq!(median_absolute_deviation(&[7, 7, 7, 9]), (7, 0));
+    }
+
+    #[test]
+    fn test_half_spread() {
+        assert_eq!(half_spread(&[]), 0);
+        assert_eq!(half_spread(&[5]), 0);
+        assert_eq!(half_spread(&[5200000, 5000000, 5100000]), 100000);
+    }
+
+    #[test]
+    fn test_weighted_mean() {
+        assert_eq!(weighted_mean(&[]), 0);
+        assert_eq!(weighted_mean(&[(1, 0)]), 0);
+        assert_eq!(weighted_mean(&[(100, 3), (200, 1)]), 125);
+        assert_eq!(weighted_mean(&[(u128::MAX, u128::MAX), (u128::MAX, 1)]), u128::MAX);
+    }
+
+    #[test]
+    fn test_weighted_median() {
+        assert_eq!(weighted_median(&[]), 0);
+        assert_eq!(weighted_median(&[(4, 1), (1, 1), (3, 1), (2, 1)]), median(&[4, 1, 3, 2]));
+        assert_eq!(weighted_median(&[(1, 5), (2, 1), (3, 1)]), 1);
+        assert_eq!(weighted_median(&[(1, 0), (2, 1), (3, 1)]), 2);
+    }
+
+    /// Any u128, biased toward the edges of the domain, where overflows happen
+    fn amount() -> impl Strategy<Value = u128> {
+        prop_oneof![u128::MAX - 15..=u128::MAX, 0..16u128, any::<u128>()]
+    }
+
+    /// Prices of honest sources within 1% of each other, and up to fewer outliers than
+    /// there are honest sources, anywhere in the u128 range
+    fn attacked_prices() -> impl Strategy<Value = (Vec<u128>, Vec<u128>)> {
+        (1u128..=u128::MAX / 2, 1usize..20)
+            .prop_flat_map(|(center, honest)| {
+                let spread = center / 100;
+                (
+                    prop::collection::vec(center - spread..=center + spread, honest),
+                    prop::collection::vec(any::<u128>(), 0..=(honest - 1) / 2),
+                )
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_aggregates_within_inputs(points in prop::collection::vec((amount(), amount()), 1..60)) {
+            let prices: Vec<u128> = points.iter().map(|(price, _)| *price).collect();
+            let (min, max) = (*prices.iter().min().unwrap(), *prices.iter().max().unwrap());
+            let unweighted = points.iter().all(|(_, weight)| *weight == 0);
+            for aggregate in [
+                mean(&prices),
+                median(&prices),
+                trimmed_mean(&prices, 2000),
+                weighted_median(&points),
+                weighted_mean(&points),
+            ] {
+                prop_assert!((min <= aggregate || unweighted) && aggregate <= max);
+            }
+            prop_assert!(half_spread(&prices) <= (max - min) / 2 + 1);
+        }
+
+        #[test]
+        fn prop_median_holds_against_outliers((honest, outliers) in attacked_prices()) {
+            let (low, high) = (*honest.iter().min().unwrap(), *honest.iter().max().unwrap());
+            let all: Vec<u128> = honest.iter().chain(&outliers).copied().collect();
+            let (center, _) = median_absolute_deviation(&all);
+            prop_assert!(low <= center && center <= high);
+            let stakes: Vec<(u128, u128)> = all.iter().map(|price| (*price, 1)).collect();
+            prop_assert_eq!(weighted_median(&stakes), center);
+        }
+
+        #[test]
+        fn prop_trimmed_mean_drops_trimmed_outliers((honest, outliers) in attacked_prices()) {
+            let all: Vec<u128> = honest.iter().chain(&outliers).copied().collect();
+            // Trim just enough from each end to drop every outlier whichever side it's on
+            let trim_bps = (outliers.len() * BPS_DENOMINATOR as usize).div_ceil(all.len()) as u16;
+            let trimmed = trimmed_mean(&all, trim_bps);
+            prop_assert!(*honest.iter().min().unwrap() <= trimmed && trimmed <= *honest.iter().max().unwrap());
+        }
+
+        #[test]
+        fn prop_aggregates_ignore_report_order(
+            (points, shuffled) in prop::collection::vec((any::<u128>(), any::<u128>()), 1..40)
+                .prop_flat_map(|points| (Just(points.clone()), Just(points).prop_shuffle())),
+            trim_bps in 0u16..5000,
+        ) {
+            let (original, reordered) = (samples(&points), samples(&shuffled));
+            for method in [
+                AggregationMethod::Mean,
+                AggregationMethod::Median,
+                AggregationMethod::Weighted,
+                AggregationMethod::TrimmedMean { trim_bps },
+                AggregationMethod::SourceWeighted,
+            ] {
+                let aggregator = method.aggregator();
+                prop_assert_eq!(aggregator.aggregate(&original), aggregator.aggregate(&reordered));
+            }
+        }
+    }
+
+    #[test]
+    fn test_aggregates_of_max_values() {
+        let prices = vec![u128::MAX; 1000];
+        let points = vec![(u128::MAX, u128::MAX); 1000];
+        assert_eq!(mean(&prices), u128::MAX);
+        assert_eq!(trimmed_mean(&prices, 1000), u128::MAX);
+        assert_eq!(weighted_mean(&points), u128::MAX);
+        assert_eq!(weighted_median(&points), u128::MAX);
+        assert_eq!(weighted_median(&[(1, u128::MAX), (3, u128::MAX)]), 2);
+    }
+}
@@ -0,0 +1,130 @@
+use near_sdk::near;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::guard::deviation_bps;
+use crate::{AssetId, OracleEvent, Pair, PriceOracle, PriceOracleExt, Role, DEFAULT_QUOTE};
+
+/// Condition on an asset's finalized USD answers that off-chain alerting watches for
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub enum AlertThreshold {
+    Level {
+        #[serde(with = "crate::json::u128_string")]
+        #[cfg_attr(feature = "abi", schemars(with = "String"))]
+        price: u128,           // Answers moving across this price, either way
+    },
+    Change { bps: u16 },       // Answers at least this far from the previous answer
+}
+
+impl AlertThreshold {
+    fn validate(&self) {
+        match self {
+            AlertThreshold::Level { price } => assert!(*price > 0, "Alert level must be positive"),
+            AlertThreshold::Change { bps } => assert!(*bps > 0, "Alert change must be positive"),
+        }
+    }
+
+    /// Whether moving from `previous` to `price` crosses the threshold
+    fn crossed(&self, previous: u128, price: u128) -> bool {
+        match self {
+            AlertThreshold::Level { price: level } => (previous < *level) != (price < *level),
+            AlertThreshold::Change { bps } => deviation_bps(price, previous) >= *bps as u128,
+        }
+    }
+}
+
+#[near]
+impl PriceOracle {
+    /// Replace the alert thresholds of an asset (ConfigManager); a finalized USD answer
+    /// crossing one emits a threshold_crossed event. Prices use the asset's decimals
+    pub fn set_alert_thresholds(&mut self, asset_id: AssetId, thresholds: Vec<AlertThreshold>) {
+        self.assert_role(Role::ConfigManager);
+        self.asset_config(&asset_id);
+        thresholds.iter().for_each(AlertThreshold::validate);
+        if thresholds.is_empty() {
+            self.alerts.remove(&asset_id);
+        } else {
+            self.alerts.insert(asset_id, thresholds);
+        }
+    }
+
+    /// Get the alert thresholds of an asset
+    pub fn get_alert_thresholds(&self, asset_id: AssetId) -> Vec<AlertThreshold> {
+        self.alerts.get(&asset_id).cloned().unwrap_or_default()
+    }
+}
+
+impl PriceOracle {
+    /// Emits threshold_crossed for every threshold a USD pair's new answer crossed
+    pub(crate) fn check_alerts(&self, pair: &Pair, previous: Option<u128>, round_id: u64, price: u128) {
+        if pair.quote != DEFAULT_QUOTE {
+            return;
+        }
+        let (Some(previous), Some(thresholds)) = (previous, self.alerts.get(&pair.base)) else {
+            return;
+        };
+        for threshold in thresholds.iter().filter(|threshold| threshold.crossed(previous, price)) {
+            OracleEvent::ThresholdCrossed {
+                pair: pair.clone(),
+                threshold: threshold.clone(),
+                round_id,
+                previous_price: previous,
+                price,
+            }
+            .emit();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn setup() -> PriceOracle {
+        testing_env!(VMContextBuilder::new().build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.set_alert_thresholds(
+            "NEAR".to_string(),
+            vec![AlertThreshold::Level { price: 5000000 }, AlertThreshold::Change { bps: 1000 }],
+        );
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(4900000), 6);
+        contract
+    }
+
+    fn crossings() -> Vec<String> {
+        get_logs().into_iter().filter(|log| log.contains("threshold_crossed")).collect()
+    }
+
+    #[test]
+    fn test_level_crossing_emits_event() {
+        let mut contract = setup();
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5100000), 6);
+        assert_eq!(crossings(), [
+            r#"EVENT_JSON:{"standard":"price-oracle","version":"2.0.0","event":"threshold_crossed","data":{"pair":{"base":"NEAR","quote":"USD"},"threshold":{"Level":{"price":"5000000"}},"round_id":2,"previous_price":"4900000","price":"5100000"}}"#
+        ]);
+
+        testing_env!(VMContextBuilder::new().build());
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5200000), 6);
+        assert!(crossings().is_empty());
+    }
+
+    #[test]
+    fn test_large_change_emits_event() {
+        let mut contract = setup();
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(4400000), 6);
+        let logs = crossings();
+        assert_eq!(logs.len(), 1);
+        assert!(logs[0].contains(r#""threshold":{"Change":{"bps":1000}}"#));
+    }
+}
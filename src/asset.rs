@@ -0,0 +1,549 @@
+use near_sdk::{near, AccountId};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::{paginate, AggregationMethod, AssetId, ConfigChange, Pair, PriceBounds, PriceOracle, PriceOracleExt, PriceResponse, Role, DEFAULT_QUOTE};
+
+/// Conventional price decimals (micro-units, 1e6)
+pub const DEFAULT_DECIMALS: u8 = 6;
+
+/// Largest supported decimals; 10^24 still leaves u128 headroom for aggregation
+pub const MAX_DECIMALS: u8 = 24;
+
+/// Per-asset configuration
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct AssetConfig {
+    pub symbol: String,       // Ticker shown to users, e.g. "NEAR"
+    pub name: String,         // Human readable name, e.g. "NEAR Protocol"
+    pub decimals: u8,         // Fractional digits of prices quoted for this asset
+    #[serde(default)]
+    pub token_id: Option<String>, // NEP-141 contract of the asset, e.g. "wrap.near"
+    #[serde(default)]
+    pub token_decimals: Option<u8>, // Decimals of that token's balances, e.g. 24 for wrap.near
+    #[serde(default)]
+    pub aggregation: AggregationMethod, // How reports of the asset's pairs are combined
+    #[serde(default)]
+    pub bounds: Option<PriceBounds>, // USD prices outside these are rejected
+    #[serde(default)]
+    pub max_report_age: Option<u64>, // Seconds after which a report stops counting
+    #[serde(default)]
+    pub heartbeat_interval: Option<u64>, // Seconds every source is expected to report within
+}
+
+impl AssetConfig {
+    pub fn new(symbol: impl Into<String>, name: impl Into<String>, decimals: u8) -> Self {
+        Self {
+            symbol: symbol.into(),
+            name: name.into(),
+            decimals,
+            token_id: None,
+            token_decimals: None,
+            aggregation: AggregationMethod::default(),
+            bounds: None,
+            max_report_age: None,
+            heartbeat_interval: None,
+        }
+    }
+
+    /// Aggregate the asset's reports with `method` instead of the median
+    pub fn with_aggregation(mut self, method: AggregationMethod) -> Self {
+        self.aggregation = method;
+        self
+    }
+
+    /// Bind the asset to the NEP-141 contract that implements it
+    pub fn with_token(mut self, token_id: impl Into<String>) -> Self {
+        self.token_id = Some(token_id.into());
+        self
+    }
+
+    /// Record the decimals of the bound token's balances, for per-unit prices
+    pub fn with_token_decimals(mut self, token_decimals: u8) -> Self {
+        self.token_decimals = Some(token_decimals);
+        self
+    }
+
+    fn validate(&self) {
+        assert!(!self.symbol.is_empty(), "Symbol must not be empty");
+        assert!(self.decimals <= MAX_DECIMALS, "Decimals must be at most {}", MAX_DECIMALS);
+        self.aggregation.validate();
+        if let Some(bounds) = &self.bounds {
+            bounds.validate();
+        }
+        if let Some(token_id) = &self.token_id {
+            assert!(token_id.parse::<AccountId>().is_ok(), "Invalid token id {}", token_id);
+        }
+        if let Some(token_decimals) = self.token_decimals {
+            assert!(token_decimals <= MAX_DECIMALS, "Token decimals must be at most {}", MAX_DECIMALS);
+        }
+    }
+
+    fn token_account(&self) -> Option<AccountId> {
+        self.token_id.as_ref().map(|id| id.parse().expect("validated on registration"))
+    }
+}
+
+/// Everything a frontend needs to display an asset
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct AssetInfo {
+    pub asset_id: AssetId,
+    pub symbol: String,
+    pub name: String,
+    pub decimals: u8,
+    pub token_id: Option<String>,
+    pub aggregation: AggregationMethod,
+    pub sources: Vec<String>, // Sources that reported any pair with this base
+    pub min_sources: u8,      // Sources required for a valid price, the asset's own or the global one
+    pub last_update: u64,     // Latest report timestamp across the asset's pairs (0 if none)
+}
+
+#[near]
+impl PriceOracle {
+    /// Register an asset so pairs with it as base accept reports (Admin)
+    /// It starts with the median and no price bounds; both are timelocked, so they're
+    /// set afterwards with set_aggregation_method and set_price_bounds
+    pub fn add_asset(&mut self, asset_id: AssetId, config: AssetConfig) {
+        self.assert_role(Role::Admin);
+        config.validate();
+        assert!(!self.assets.contains_key(&asset_id), "Asset {} already registered", asset_id);
+        assert!(
+            config.aggregation == AggregationMethod::default(),
+            "Aggregation method of {} is timelocked, use set_aggregation_method",
+            asset_id
+        );
+        assert!(config.bounds.is_none(), "Price bounds of {} are timelocked, use set_price_bounds", asset_id);
+        self.bind_token(&asset_id, config.token_account());
+
+        near_sdk::log!("Asset added: {} ({} decimals)", asset_id, config.decimals);
+        self.assets.insert(asset_id, config);
+    }
+
+    /// Unregister an asset and drop all prices of pairs based on it (Admin), along with
+    /// their rounds, aggregates, averages, history, override, alerts and breaker trips, so
+    /// an asset registered again under the id starts without a price
+    pub fn remove_asset(&mut self, asset_id: AssetId) {
+        self.assert_role(Role::Admin);
+        let config = self
+            .assets
+            .remove(&asset_id)
+            .unwrap_or_else(|| panic!("Unknown asset {}", asset_id));
+
+        if let Some(token_id) = config.token_account() {
+            self.token_assets.remove(&token_id);
+        }
+        self.drop_asset_feeds(&asset_id);
+        self.stablecoins.remove(&asset_id);
+        self.peg_status.remove(&asset_id);
+        self.disabled_sources.remove(&asset_id);
+        self.asset_min_sources.remove(&asset_id);
+        self.price_overrides.remove(&asset_id);
+        self.stale_flags.remove(&asset_id);
+        self.alerts.remove(&asset_id);
+        self.tripped_breakers.retain(|pair, _| pair.base != asset_id);
+        self.commit_phases.retain(|pair, _| pair.base != asset_id);
+        near_sdk::log!("Asset removed: {}", asset_id);
+    }
+
+    /// Replace the configuration of a registered asset (Admin)
+    /// Changing decimals drops existing reports, since they were scaled for the old value
+    /// The aggregation method and price bounds are timelocked, so they must match the
+    /// current ones; change them with set_aggregation_method and set_price_bounds
+    pub fn update_asset_config(&mut self, asset_id: AssetId, config: AssetConfig) {
+        self.assert_role(Role::Admin);
+        config.validate();
+        let current = self.asset_config(&asset_id).clone();
+        assert!(
+            config.aggregation == current.aggregation,
+            "Aggregation method of {} is timelocked, use set_aggregation_method",
+            asset_id
+        );
+        assert!(config.bounds == current.bounds, "Price bounds of {} are timelocked, use set_price_bounds", asset_id);
+
+        if current.decimals != config.decimals {
+            self.drop_asset_feeds(&asset_id);
+        }
+        if current.token_id != config.token_id {
+            if let Some(token_id) = current.token_account() {
+                self.token_assets.remove(&token_id);
+            }
+            self.bind_token(&asset_id, config.token_account());
+        }
+        near_sdk::log!("Asset updated: {} ({} decimals)", asset_id, config.decimals);
+        self.assets.insert(asset_id, config);
+        self.invalidate_aggregates();
+    }
+
+    /// Set or clear the age in seconds after which reports of an asset's pairs are
+    /// excluded from aggregation and from the min_sources count (ConfigManager)
+    pub fn set_max_report_age(&mut self, asset_id: AssetId, max_age_seconds: Option<u64>) {
+        self.assert_role(Role::ConfigManager);
+        self.asset_config(&asset_id);
+        assert!(max_age_seconds != Some(0), "Max report age must be positive");
+        self.assets.get_mut(&asset_id).expect("checked above").max_report_age = max_age_seconds;
+        self.invalidate_aggregates();
+    }
+
+    /// Choose how the reports of an asset's pairs are aggregated (ConfigManager)
+    /// Subject to the timelock delay
+    pub fn set_aggregation_method(&mut self, asset_id: AssetId, method: AggregationMethod) {
+        self.assert_role(Role::ConfigManager);
+        self.asset_config(&asset_id);
+        method.validate();
+        self.request_config_change(ConfigChange::AggregationMethod { asset_id, method });
+    }
+
+    /// Require `min_sources` sources for a valid price of an asset's pairs, or follow the
+    /// global min_sources again with None (ConfigManager, or a council proposal once
+    /// installed)
+    /// Subject to the timelock delay
+    pub fn set_asset_min_sources(&mut self, asset_id: AssetId, min_sources: Option<u8>) {
+        self.assert_role(Role::ConfigManager);
+        self.assert_without_council("set_asset_min_sources");
+        self.asset_config(&asset_id);
+        assert!(min_sources != Some(0), "Min sources must be positive");
+        self.request_config_change(ConfigChange::AssetMinSources { asset_id, min_sources });
+    }
+
+    /// Get registered asset ids in lexicographic order
+    pub fn get_assets(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<AssetId> {
+        let mut assets: Vec<AssetId> = self.assets.keys().cloned().collect();
+        assets.sort();
+        paginate(assets, from_index, limit)
+    }
+
+    /// Get the configuration of an asset, if registered
+    pub fn get_asset_config(&self, asset_id: AssetId) -> Option<AssetConfig> {
+        self.assets.get(&asset_id).cloned()
+    }
+
+    /// Get the decimals prices of an asset are expressed in
+    pub fn get_decimals(&self, asset_id: AssetId) -> u8 {
+        self.asset_config(&asset_id).decimals
+    }
+
+    /// Get the asset implemented by a NEP-141 token contract
+    pub fn get_asset_by_token(&self, token_id: AccountId) -> Option<AssetId> {
+        self.token_assets.get(&token_id).cloned()
+    }
+
+    /// Get the price of the asset behind a NEP-141 token, in `quote` (USD by default)
+    pub fn get_token_price(&self, token_id: AccountId, quote: Option<AssetId>) -> PriceResponse {
+        let asset_id = self
+            .get_asset_by_token(token_id.clone())
+            .unwrap_or_else(|| panic!("No asset registered for token {}", token_id));
+        self.get_price(Pair::new(asset_id, quote.unwrap_or_else(|| DEFAULT_QUOTE.to_string())))
+    }
+
+    /// Get metadata and feed status of a registered asset
+    pub fn get_asset_info(&self, asset_id: AssetId) -> AssetInfo {
+        self.asset_info(&asset_id)
+    }
+
+    /// Get metadata and feed status of all registered assets, ordered by asset id
+    pub fn get_all_assets_info(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<AssetInfo> {
+        self.get_assets(from_index, limit)
+            .iter()
+            .map(|asset_id| self.asset_info(asset_id))
+            .collect()
+    }
+}
+
+impl PriceOracle {
+    /// Configuration of a registered asset
+    pub(crate) fn asset_config(&self, asset_id: &AssetId) -> &AssetConfig {
+        self.assets
+            .get(asset_id)
+            .unwrap_or_else(|| panic!("Unknown asset {}", asset_id))
+    }
+
+    /// Point a token contract at an asset, refusing tokens already bound elsewhere
+    fn bind_token(&mut self, asset_id: &AssetId, token_id: Option<AccountId>) {
+        let Some(token_id) = token_id else {
+            return;
+        };
+        if let Some(existing) = self.token_assets.get(&token_id) {
+            assert!(existing == asset_id, "Token {} already bound to {}", token_id, existing);
+        }
+        self.token_assets.insert(token_id, asset_id.clone());
+    }
+
+    fn asset_info(&self, asset_id: &AssetId) -> AssetInfo {
+        let config = self.asset_config(asset_id);
+        let reports = self.prices.reports_of(asset_id);
+
+        let mut sources: Vec<String> = reports.iter().map(|r| r.source.clone()).collect();
+        sources.sort();
+        sources.dedup();
+
+        AssetInfo {
+            asset_id: asset_id.clone(),
+            symbol: config.symbol.clone(),
+            name: config.name.clone(),
+            decimals: config.decimals,
+            token_id: config.token_id.clone(),
+            aggregation: config.aggregation,
+            sources,
+            min_sources: self.min_sources_of(asset_id),
+            last_update: reports.iter().map(|r| r.timestamp).max().unwrap_or(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pair;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn setup() -> PriceOracle {
+        testing_env!(VMContextBuilder::new().build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract
+    }
+
+    #[test]
+    fn test_registry_pagination() {
+        let mut contract = setup();
+        contract.add_asset("BTC".to_string(), AssetConfig::new("BTC", "Bitcoin", 2));
+        contract.add_asset("ETH".to_string(), AssetConfig::new("ETH", "Ether", 4));
+
+        assert_eq!(contract.get_assets(None, None), vec!["BTC", "ETH", "NEAR"]);
+        assert_eq!(contract.get_assets(Some(1), Some(1)), vec!["ETH"]);
+        assert!(contract.get_assets(Some(5), None).is_empty());
+        assert_eq!(contract.get_asset_config("ETH".to_string()), Some(AssetConfig::new("ETH", "Ether", 4)));
+    }
+
+    #[test]
+    fn test_remove_asset_drops_prices() {
+        let mut contract = setup();
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        contract.remove_asset("NEAR".to_string());
+
+        assert!(contract.get_pairs().is_empty());
+        assert_eq!(contract.get_asset_config("NEAR".to_string()), None);
+    }
+
+    #[test]
+    fn test_readded_asset_serves_no_old_price() {
+        let mut contract = setup();
+        let pair = Pair::usd("NEAR");
+        contract.report_price(pair.clone(), "binance".to_string(), U128(5000000), 6);
+        contract.set_override_price("NEAR".to_string(), U128(4000000), 60_000);
+        contract.trip_breaker_for_tests(&pair);
+        contract.remove_asset("NEAR".to_string());
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+
+        assert_eq!(contract.price_result(&pair), Err("Need at least 1 price sources for NEAR/USD, have 0".to_string()));
+        assert_eq!(contract.get_override_price("NEAR".to_string()), None);
+        assert_eq!(contract.get_latest_round(pair.clone()), None);
+        assert!(contract.get_recent_answers("NEAR".to_string(), None).is_empty());
+        assert_eq!(contract.get_twap_accumulator(pair.clone()), None);
+        assert!(contract.ema.get(&pair).is_none());
+    }
+
+    #[test]
+    fn test_update_decimals_drops_prices() {
+        let mut contract = setup();
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        contract.update_asset_config("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 8));
+
+        assert_eq!(contract.get_source_count(Pair::usd("NEAR")), 0);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(500000000), 8);
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 500000000);
+    }
+
+    #[test]
+    fn test_set_aggregation_method() {
+        let mut contract = setup();
+        contract.set_min_sources(3);
+        let report_round = |contract: &mut PriceOracle| {
+            for (source, price) in [("binance", 5000000), ("coingecko", 5100000), ("rogue", 9000000)] {
+                contract.report_price(Pair::usd("NEAR"), source.to_string(), U128(price), 6);
+            }
+        };
+        report_round(&mut contract);
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5100000);
+
+        // Finalized answers are immutable; the method applies from the next round
+        contract.set_aggregation_method("NEAR".to_string(), AggregationMethod::Mean);
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5100000);
+        report_round(&mut contract);
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 6366666);
+        assert_eq!(contract.get_asset_info("NEAR".to_string()).aggregation, AggregationMethod::Mean);
+
+        contract.set_aggregation_method("NEAR".to_string(), AggregationMethod::TrimmedMean { trim_bps: 3400 });
+        report_round(&mut contract);
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5100000);
+
+        // The explicit views keep their own method
+        assert_eq!(contract.get_mean_price(Pair::usd("NEAR")).price, 6366666);
+        assert_eq!(contract.get_weighted_price(Pair::usd("NEAR")).price, 5100000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Aggregation method of NEAR is timelocked, use set_aggregation_method")]
+    fn test_update_cannot_bypass_timelock() {
+        let mut contract = setup();
+        contract.set_timelock_delay(3600);
+        contract.update_asset_config(
+            "NEAR".to_string(),
+            AssetConfig::new("NEAR", "NEAR Protocol", 6).with_aggregation(AggregationMethod::Mean),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Price bounds of ETH are timelocked, use set_price_bounds")]
+    fn test_add_cannot_bypass_timelock() {
+        let mut contract = setup();
+        contract.set_timelock_delay(3600);
+        let mut config = AssetConfig::new("ETH", "Ether", 6);
+        config.bounds = Some(PriceBounds { min: 1, max: 2 });
+        contract.add_asset("ETH".to_string(), config);
+    }
+
+    #[test]
+    fn test_asset_min_sources_override_global() {
+        let mut contract = setup();
+        contract.set_min_sources(3);
+        contract.add_asset("LONG".to_string(), AssetConfig::new("LONG", "Long Tail", 6));
+        contract.set_asset_min_sources("LONG".to_string(), Some(2));
+        for (source, price) in [("binance", 5000000), ("kraken", 5200000)] {
+            contract.report_price(Pair::usd("NEAR"), source.to_string(), U128(price), 6);
+            contract.report_price(Pair::usd("LONG"), source.to_string(), U128(price), 6);
+        }
+
+        assert!(!contract.is_valid(Pair::usd("NEAR")));
+        assert!(contract.is_valid(Pair::usd("LONG")));
+        assert_eq!(contract.get_price(Pair::usd("LONG")).price, 5100000);
+        assert_eq!(contract.get_asset_info("NEAR".to_string()).min_sources, 3);
+        assert_eq!(contract.get_asset_info("LONG".to_string()).min_sources, 2);
+
+        // A blue chip can ask for more sources than the default
+        contract.set_asset_min_sources("LONG".to_string(), Some(7));
+        assert!(!contract.is_valid(Pair::usd("LONG")));
+        contract.set_asset_min_sources("LONG".to_string(), None);
+        assert_eq!(contract.get_asset_info("LONG".to_string()).min_sources, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Min sources must be positive")]
+    fn test_asset_min_sources_positive() {
+        let mut contract = setup();
+        contract.set_asset_min_sources("NEAR".to_string(), Some(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "set_asset_min_sources requires council approval")]
+    fn test_asset_min_sources_defer_to_council() {
+        let mut contract = setup();
+        contract.set_council(vec![accounts(1), accounts(2)], 2, 3600);
+        contract.set_asset_min_sources("NEAR".to_string(), Some(2));
+    }
+
+    #[test]
+    fn test_expired_reports_do_not_count() {
+        let mut contract = setup();
+        contract.set_min_sources(2);
+        contract.set_max_report_age("NEAR".to_string(), Some(600));
+        contract.set_round_config(None, 3600);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+
+        testing_env!(VMContextBuilder::new().block_timestamp(601 * 1_000_000_000).build());
+        contract.report_price(Pair::usd("NEAR"), "coingecko".to_string(), U128(5200000), 6);
+
+        // The binance report expired: it neither counts nor finalizes the round
+        assert_eq!(contract.get_source_count(Pair::usd("NEAR")), 2);
+        assert!(!contract.is_valid(Pair::usd("NEAR")));
+        assert_eq!(contract.get_latest_round(Pair::usd("NEAR")), None);
+        assert_eq!(contract.get_outliers(Pair::usd("NEAR")).median, 5200000);
+
+        contract.report_price(Pair::usd("NEAR"), "kraken".to_string(), U128(5400000), 6);
+        assert_eq!(contract.get_mean_price(Pair::usd("NEAR")).price, 5300000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown asset DOGE")]
+    fn test_report_unknown_asset() {
+        let mut contract = setup();
+        contract.report_price(Pair::usd("DOGE"), "binance".to_string(), U128(100000), 6);
+    }
+
+    #[test]
+    #[should_panic(expected = "charlie lacks role Admin")]
+    fn test_add_asset_requires_admin() {
+        let mut contract = setup();
+        testing_env!(VMContextBuilder::new().predecessor_account_id(accounts(2)).build());
+        contract.add_asset("BTC".to_string(), AssetConfig::new("BTC", "Bitcoin", 2));
+    }
+
+    #[test]
+    fn test_token_mapping() {
+        let mut contract = setup();
+        contract.add_asset(
+            "USDT".to_string(),
+            AssetConfig::new("USDT", "Tether USD", 6).with_token("usdt.tether-token.near"),
+        );
+        contract.report_price(Pair::usd("USDT"), "binance".to_string(), U128(999000), 6);
+
+        let token: AccountId = "usdt.tether-token.near".parse().unwrap();
+        assert_eq!(contract.get_asset_by_token(token.clone()), Some("USDT".to_string()));
+        assert_eq!(contract.get_token_price(token.clone(), None).price, 999000);
+        assert_eq!(contract.get_asset_info("USDT".to_string()).token_id.as_deref(), Some("usdt.tether-token.near"));
+
+        // Rebinding moves the index
+        contract.update_asset_config(
+            "USDT".to_string(),
+            AssetConfig::new("USDT", "Tether USD", 6).with_token("dac17f958d2ee523a2206206994597c13d831ec7.factory.bridge.near"),
+        );
+        assert_eq!(contract.get_asset_by_token(token), None);
+
+        contract.remove_asset("USDT".to_string());
+        let bridged = "dac17f958d2ee523a2206206994597c13d831ec7.factory.bridge.near".parse().unwrap();
+        assert_eq!(contract.get_asset_by_token(bridged), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token wrap.near already bound to NEAR")]
+    fn test_token_bound_once() {
+        let mut contract = setup();
+        contract.update_asset_config(
+            "NEAR".to_string(),
+            AssetConfig::new("NEAR", "NEAR Protocol", 6).with_token("wrap.near"),
+        );
+        contract.add_asset("WNEAR".to_string(), AssetConfig::new("wNEAR", "Wrapped NEAR", 6).with_token("wrap.near"));
+    }
+
+    #[test]
+    fn test_asset_info() {
+        let mut contract = setup();
+        testing_env!(VMContextBuilder::new().block_timestamp(2_000_000_000).build());
+        contract.report_price(Pair::usd("NEAR"), "coingecko".to_string(), U128(5000000), 6);
+        contract.report_price(Pair::new("NEAR", "USDT"), "binance".to_string(), U128(5000000), 6);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+
+        let info = contract.get_asset_info("NEAR".to_string());
+        assert_eq!(info.symbol, "NEAR");
+        assert_eq!(info.name, "NEAR Protocol");
+        assert_eq!(info.decimals, 6);
+        assert_eq!(info.sources, vec!["binance", "coingecko"]);
+        assert_eq!(info.min_sources, 1);
+        assert_eq!(info.last_update, 2000);
+
+        contract.add_asset("BTC".to_string(), AssetConfig::new("BTC", "Bitcoin", 2));
+        let all = contract.get_all_assets_info(None, None);
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].asset_id, "BTC");
+        assert!(all[0].sources.is_empty());
+        assert_eq!(all[0].last_update, 0);
+    }
+}
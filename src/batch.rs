@@ -0,0 +1,222 @@
+use near_sdk::{near, AccountId};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::reporter::{reporter_account, try_normalize_source};
+use crate::{now_ms, OracleEvent, Pair, PriceOracle, PriceOracleExt, PriceReport, RejectReason};
+
+/// Most reports one report_prices call takes, so a batch fits in a transaction's gas
+pub const MAX_BATCH_REPORTS: usize = 50;
+
+/// One price of a report_prices batch, as report_price_with_volume takes it
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct BatchReport {
+    pub pair: Pair,
+    pub source: String,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub price: u128,
+    pub decimals: u8,
+    #[serde(default)]
+    #[serde(with = "crate::json::option_u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "Option<String>"))]
+    pub volume: Option<u128>,
+}
+
+/// What became of a submitted report
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub enum ReportStatus {
+    Accepted,
+    Quarantined(String),  // Held for review, with the reason
+    Rejected(String),     // Dropped, with the reason
+}
+
+impl ReportStatus {
+    pub fn is_rejected(&self) -> bool {
+        matches!(self, ReportStatus::Rejected(_))
+    }
+}
+
+#[near]
+impl PriceOracle {
+    /// Submit up to MAX_BATCH_REPORTS prices in one transaction
+    /// Each entry is checked on its own: one for an unknown asset, with the wrong
+    /// decimals or under a source bound to someone else is rejected without failing the
+    /// others. The report deposit is due per entry and kept for each rejected one
+    /// Returns the status of every entry, in order
+    #[payable]
+    pub fn report_prices(&mut self, reports: Vec<BatchReport>) -> Vec<ReportStatus> {
+        self.assert_not_paused();
+        self.assert_direct_reports();
+        let caller = reporter_account();
+        assert!(self.is_reporter(caller.clone()), "Reporter {} is not whitelisted", caller);
+        assert!(!reports.is_empty(), "No reports in batch");
+        assert!(
+            reports.len() <= MAX_BATCH_REPORTS,
+            "At most {} reports per batch, got {}",
+            MAX_BATCH_REPORTS,
+            reports.len()
+        );
+        self.assert_report_deposit(reports.len() as u128);
+
+        let statuses: Vec<ReportStatus> = reports
+            .into_iter()
+            .map(|entry| self.ingest_batch_report(entry, &caller, now_ms()))
+            .collect();
+        let accepted = statuses.iter().filter(|status| **status == ReportStatus::Accepted).count();
+        self.record_report_gas(accepted);
+        let rejected = statuses.iter().filter(|status| status.is_rejected()).count();
+        self.settle_report_deposit(rejected as u128);
+        statuses
+    }
+}
+
+impl PriceOracle {
+    /// Checks an entry reported by `reporter` and takes it in as observed at `timestamp`
+    pub(crate) fn ingest_batch_report(&mut self, mut entry: BatchReport, reporter: &AccountId, timestamp: u64) -> ReportStatus {
+        if let Err((code, reason)) = self.check_batch_report(&mut entry, reporter) {
+            near_sdk::log!("Price rejected for {}: {} from {} ({})", entry.pair, entry.price, entry.source, reason);
+            self.record_rejection(reporter, code);
+            OracleEvent::rejected(&entry.pair, &entry.source, reporter.as_str(), entry.price, code, &reason).emit();
+            return ReportStatus::Rejected(reason);
+        }
+        let report = PriceReport {
+            source: entry.source,
+            price: entry.price,
+            decimals: entry.decimals,
+            timestamp,
+            reporter: reporter.to_string(),
+            volume: entry.volume,
+            attestation: None,
+        };
+        self.ingest_report(entry.pair, report)
+    }
+
+    /// The checks report_price_with_volume panics on, as a rejection code and reason
+    /// Normalizes the entry's source on the way
+    fn check_batch_report(&self, entry: &mut BatchReport, reporter: &AccountId) -> Result<(), (RejectReason, String)> {
+        let invalid = |reason| (RejectReason::Invalid, reason);
+        entry.source = try_normalize_source(&entry.source).map_err(invalid)?;
+        let config = self
+            .assets
+            .get(&entry.pair.base)
+            .ok_or_else(|| invalid(format!("Unknown asset {}", entry.pair.base)))?;
+        if entry.decimals != config.decimals {
+            return Err(invalid(format!("{} prices use {} decimals, got {}", entry.pair.base, config.decimals, entry.decimals)));
+        }
+        match self.source_reporters.get(&entry.source) {
+            Some(bound) if bound != reporter => {
+                Err((RejectReason::Unauthorized, format!("Source {} is bound to {}", entry.source, bound)))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AssetConfig, PriceBounds};
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, NearToken};
+
+    fn entry(pair: Pair, source: &str, price: u128, decimals: u8) -> BatchReport {
+        BatchReport { pair, source: source.to_string(), price, decimals, volume: None }
+    }
+
+    fn setup() -> PriceOracle {
+        testing_env!(VMContextBuilder::new().build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.add_asset("BTC".to_string(), AssetConfig::new("BTC", "Bitcoin", 2));
+        contract.set_price_bounds("BTC".to_string(), Some(PriceBounds { min: 100, max: 100000000 }));
+        contract
+    }
+
+    #[test]
+    fn test_repeated_round_submission_rejected_alone() {
+        let mut contract = setup();
+        contract.set_min_sources(2);
+        let statuses = contract.report_prices(vec![
+            entry(Pair::usd("NEAR"), "binance", 5000000, 6),
+            entry(Pair::usd("NEAR"), "binance", 5000000, 6),
+            entry(Pair::usd("NEAR"), "kraken", 5200000, 6),
+        ]);
+        assert_eq!(
+            statuses,
+            [
+                ReportStatus::Accepted,
+                ReportStatus::Rejected("already reported in round 1".to_string()),
+                ReportStatus::Accepted,
+            ]
+        );
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5100000);
+    }
+
+    #[test]
+    fn test_batch_entries_checked_independently() {
+        let mut contract = setup();
+        contract.bind_source("kraken".to_string(), accounts(0));
+        let statuses = contract.report_prices(vec![
+            entry(Pair::usd("NEAR"), "binance", 5250000, 6),
+            entry(Pair::usd("BTC"), "binance", 1, 2),
+            entry(Pair::usd("ETH"), "binance", 300000, 2),
+            entry(Pair::usd("NEAR"), "coinbase", 5250000, 8),
+            entry(Pair::usd("NEAR"), "kraken", 5250000, 6),
+            entry(Pair::usd("NEAR"), "", 5250000, 6),
+        ]);
+        assert_eq!(
+            statuses,
+            [
+                ReportStatus::Accepted,
+                ReportStatus::Rejected("outside bounds [100, 100000000]".to_string()),
+                ReportStatus::Rejected("Unknown asset ETH".to_string()),
+                ReportStatus::Rejected("NEAR prices use 6 decimals, got 8".to_string()),
+                ReportStatus::Rejected("Source kraken is bound to alice".to_string()),
+                ReportStatus::Rejected("Source name must not be empty".to_string()),
+            ]
+        );
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5250000);
+        let stats = contract.get_reporter_stats("bob.near".parse().unwrap()).unwrap();
+        assert_eq!(
+            stats.rejected,
+            std::collections::BTreeMap::from([
+                (RejectReason::Invalid, 3),
+                (RejectReason::OutOfBounds, 1),
+                (RejectReason::Unauthorized, 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_batch_keeps_deposit_of_rejected_entries() {
+        let mut contract = setup();
+        contract.set_report_deposit(U128(100));
+        testing_env!(VMContextBuilder::new().attached_deposit(NearToken::from_yoctonear(200)).build());
+        contract.report_prices(vec![
+            entry(Pair::usd("NEAR"), "binance", 5250000, 6),
+            entry(Pair::usd("ETH"), "binance", 300000, 2),
+        ]);
+        assert_eq!(contract.get_reward_pool().0, 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "Attach 200 yoctoNEAR to report, got 100")]
+    fn test_batch_requires_deposit_per_entry() {
+        let mut contract = setup();
+        contract.set_report_deposit(U128(100));
+        testing_env!(VMContextBuilder::new().attached_deposit(NearToken::from_yoctonear(100)).build());
+        contract.report_prices(vec![
+            entry(Pair::usd("NEAR"), "binance", 5250000, 6),
+            entry(Pair::usd("NEAR"), "kraken", 5250000, 6),
+        ]);
+    }
+}
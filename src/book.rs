@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use near_sdk::near;
+use near_sdk::store::IterableMap;
+
+use crate::{AssetId, Pair, PriceReport, StorageKey};
+
+/// Latest report of every source, base -> quote -> source -> report
+/// Each base asset's pairs live under their own storage prefix, so a call only loads
+/// the assets and pairs it touches however many are listed
+#[near(serializers = [borsh])]
+pub(crate) struct PriceBook {
+    assets: IterableMap<AssetId, IterableMap<AssetId, HashMap<String, PriceReport>>>,
+}
+
+impl PriceBook {
+    pub(crate) fn new() -> Self {
+        Self { assets: IterableMap::new(StorageKey::Prices) }
+    }
+
+    /// Reports of a pair by source
+    pub(crate) fn get(&self, pair: &Pair) -> Option<&HashMap<String, PriceReport>> {
+        self.assets.get(&pair.base)?.get(&pair.quote)
+    }
+
+    pub(crate) fn contains_key(&self, pair: &Pair) -> bool {
+        self.get(pair).is_some()
+    }
+
+    /// Reports of a pair by source, created empty if the pair has none
+    pub(crate) fn entry(&mut self, pair: &Pair) -> &mut HashMap<String, PriceReport> {
+        self.assets
+            .entry(pair.base.clone())
+            .or_insert_with(|| IterableMap::new(StorageKey::AssetPrices { asset_id: pair.base.clone() }))
+            .entry(pair.quote.clone())
+            .or_default()
+    }
+
+    /// Every pair with reports, by base asset
+    pub(crate) fn pairs(&self) -> Vec<Pair> {
+        self.assets.keys().flat_map(|base| self.pairs_of(base)).collect()
+    }
+
+    /// Pairs of a base asset with reports
+    pub(crate) fn pairs_of(&self, base: &AssetId) -> Vec<Pair> {
+        self.assets
+            .get(base)
+            .map(|quotes| quotes.keys().map(|quote| Pair::new(base.clone(), quote.clone())).collect())
+            .unwrap_or_default()
+    }
+
+    /// Reports of every pair of a base asset
+    pub(crate) fn reports_of(&self, base: &AssetId) -> Vec<&PriceReport> {
+        self.assets
+            .get(base)
+            .map(|quotes| quotes.values().flat_map(|reports| reports.values()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Deletes the reports that match across every pair; returns them with their pairs
+    pub(crate) fn remove_reports(&mut self, matches: impl Fn(&PriceReport) -> bool) -> Vec<(Pair, PriceReport)> {
+        let mut removed = Vec::new();
+        let bases: Vec<AssetId> = self.assets.keys().cloned().collect();
+        for base in bases {
+            let quotes = self.assets.get_mut(&base).expect("listed above");
+            let keys: Vec<AssetId> = quotes.keys().cloned().collect();
+            for quote in keys {
+                let reports = quotes.get_mut(&quote).expect("listed above");
+                let sources: Vec<String> =
+                    reports.iter().filter(|(_, report)| matches(report)).map(|(source, _)| source.clone()).collect();
+                for source in sources {
+                    let report = reports.remove(&source).expect("listed above");
+                    removed.push((Pair::new(base.clone(), quote.clone()), report));
+                }
+                if reports.is_empty() {
+                    quotes.remove(&quote);
+                }
+            }
+            if quotes.is_empty() {
+                self.assets.remove(&base);
+            }
+        }
+        removed
+    }
+
+    /// Drops every pair of a base asset along with its storage; returns the reports dropped
+    pub(crate) fn remove_asset(&mut self, base: &AssetId) -> Vec<(Pair, PriceReport)> {
+        let removed = self.reports_by_pair(base);
+        if let Some(mut quotes) = self.assets.remove(base) {
+            quotes.clear();
+        }
+        removed
+    }
+
+    /// Drops every pair; returns the reports dropped
+    pub(crate) fn clear(&mut self) -> Vec<(Pair, PriceReport)> {
+        let bases: Vec<AssetId> = self.assets.keys().cloned().collect();
+        bases.iter().flat_map(|base| self.remove_asset(base)).collect()
+    }
+
+    fn reports_by_pair(&self, base: &AssetId) -> Vec<(Pair, PriceReport)> {
+        let mut reports = Vec::new();
+        for pair in self.pairs_of(base) {
+            for report in self.get(&pair).expect("listed above").values() {
+                reports.push((pair.clone(), report.clone()));
+            }
+        }
+        reports
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn report(source: &str) -> PriceReport {
+        PriceReport {
+            source: source.to_string(),
+            price: 5000000,
+            decimals: 6,
+            timestamp: 0,
+            reporter: "alice".to_string(),
+            volume: None,
+            attestation: None,
+        }
+    }
+
+    fn book() -> PriceBook {
+        testing_env!(VMContextBuilder::new().build());
+        let mut book = PriceBook::new();
+        for (pair, source) in [(Pair::usd("NEAR"), "binance"), (Pair::new("NEAR", "USDT"), "binance"), (Pair::usd("BTC"), "kraken")] {
+            book.entry(&pair).insert(source.to_string(), report(source));
+        }
+        book
+    }
+
+    #[test]
+    fn test_pairs_grouped_by_asset() {
+        let book = book();
+        assert_eq!(book.pairs(), [Pair::usd("NEAR"), Pair::new("NEAR", "USDT"), Pair::usd("BTC")]);
+        assert_eq!(book.reports_of(&"NEAR".to_string()).len(), 2);
+        assert!(book.contains_key(&Pair::usd("BTC")));
+        assert!(!book.contains_key(&Pair::usd("ETH")));
+    }
+
+    #[test]
+    fn test_removing_reports_drops_empty_pairs() {
+        let mut book = book();
+        assert_eq!(book.remove_reports(|report| report.source == "binance").len(), 2);
+        assert_eq!(book.pairs(), [Pair::usd("BTC")]);
+
+        assert_eq!(book.remove_asset(&"BTC".to_string()), [(Pair::usd("BTC"), report("kraken"))]);
+        assert!(book.pairs().is_empty());
+    }
+}
@@ -0,0 +1,190 @@
+use near_sdk::near;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::guard::deviation_bps;
+use crate::stablecoin::BPS_DENOMINATOR;
+use crate::{now_ms, OracleEvent, Pair, PriceOracle, PriceOracleExt, Role};
+
+/// Limits on a newly finalized answer past which its pair's reads fail closed
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct CircuitBreaker {
+    pub max_spread_bps: Option<u16>, // Widest spread between the round's sources, relative to the answer
+    pub max_move_bps: Option<u16>,   // Largest move from the previous answer
+}
+
+/// Why a pair's breaker tripped
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct BreakerTrip {
+    pub round_id: u64,         // Round whose answer tripped it
+    pub reason: String,
+    pub tripped_at: u64,
+}
+
+#[near]
+impl PriceOracle {
+    /// Set or clear the circuit breaker (ConfigManager): once a finalized answer's sources
+    /// are more than `max_spread_bps` apart, or it moved more than `max_move_bps` from the
+    /// previous answer, reads of its pair fail until a PauseGuardian resets it
+    pub fn set_circuit_breaker(&mut self, breaker: Option<CircuitBreaker>) {
+        self.assert_role(Role::ConfigManager);
+        if let Some(breaker) = &breaker {
+            assert!(
+                breaker.max_spread_bps.is_some() || breaker.max_move_bps.is_some(),
+                "Circuit breaker needs a spread or move limit"
+            );
+        }
+        self.circuit_breaker = breaker;
+    }
+
+    /// Get the circuit breaker, if configured
+    pub fn get_circuit_breaker(&self) -> Option<CircuitBreaker> {
+        self.circuit_breaker.clone()
+    }
+
+    /// Get why a pair's breaker tripped, if it's tripped
+    pub fn get_breaker_trip(&self, pair: Pair) -> Option<BreakerTrip> {
+        self.tripped_breakers.get(&pair).cloned()
+    }
+
+    /// Get every tripped breaker, by pair
+    pub fn get_tripped_breakers(&self) -> Vec<(Pair, BreakerTrip)> {
+        let mut trips: Vec<(Pair, BreakerTrip)> =
+            self.tripped_breakers.iter().map(|(pair, trip)| (pair.clone(), trip.clone())).collect();
+        trips.sort_by(|a, b| a.0.cmp(&b.0));
+        trips
+    }
+
+    /// Reset a pair's tripped breaker so its reads serve the latest answer again (PauseGuardian)
+    pub fn reset_circuit_breaker(&mut self, pair: Pair) {
+        self.assert_role(Role::PauseGuardian);
+        assert!(self.tripped_breakers.remove(&pair).is_some(), "Circuit breaker of {} not tripped", pair);
+        near_sdk::log!("Circuit breaker of {} reset", pair);
+    }
+}
+
+impl PriceOracle {
+    /// Panics while the pair's breaker is tripped
+    pub(crate) fn assert_breaker_closed(&self, pair: &Pair) {
+        if let Some(err) = self.breaker_error(pair) {
+            panic!("{}", err);
+        }
+    }
+
+    /// What reads of the pair fail with while its breaker is tripped
+    pub(crate) fn breaker_error(&self, pair: &Pair) -> Option<String> {
+        let trip = self.tripped_breakers.get(pair)?;
+        Some(format!("Circuit breaker tripped for {}: {}", pair, trip.reason))
+    }
+
+    pub(crate) fn breaker_tripped(&self, pair: &Pair) -> bool {
+        self.tripped_breakers.contains_key(pair)
+    }
+
+    /// Trips the pair's breaker if its new answer breaks a limit; a tripped breaker keeps
+    /// its first trip until reset
+    pub(crate) fn check_breaker(&mut self, pair: &Pair, previous: Option<u128>, round_id: u64, price: u128, confidence: u128) {
+        let Some(breaker) = &self.circuit_breaker else {
+            return;
+        };
+        if self.tripped_breakers.contains_key(pair) {
+            return;
+        }
+        let spread_bps = confidence
+            .saturating_mul(2)
+            .saturating_mul(BPS_DENOMINATOR)
+            .checked_div(price)
+            .unwrap_or(u128::MAX);
+        let reason = match (breaker.max_spread_bps, breaker.max_move_bps, previous) {
+            (Some(max), _, _) if spread_bps > max as u128 => {
+                format!("sources {} bps apart, {} bps allowed", spread_bps, max)
+            }
+            (_, Some(max), Some(previous)) if deviation_bps(price, previous) > max as u128 => {
+                format!("{} bps move from {}, {} bps allowed", deviation_bps(price, previous), previous, max)
+            }
+            _ => return,
+        };
+        near_sdk::log!("Circuit breaker of {} tripped by round {}: {}", pair, round_id, reason);
+        OracleEvent::BreakerTripped { pair: pair.clone(), round_id, reason: reason.clone() }.emit();
+        self.tripped_breakers.insert(pair.clone(), BreakerTrip { round_id, reason, tripped_at: now_ms() });
+    }
+}
+
+/// Trips a pair's breaker outright, for tests of the reads it closes
+#[cfg(test)]
+impl PriceOracle {
+    pub(crate) fn trip_breaker_for_tests(&mut self, pair: &Pair) {
+        let trip = BreakerTrip { round_id: 0, reason: "tripped by the test".to_string(), tripped_at: now_ms() };
+        self.tripped_breakers.insert(pair.clone(), trip);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AssetConfig, PriceResult};
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{accounts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn setup() -> PriceOracle {
+        testing_env!(VMContextBuilder::new().predecessor_account_id(accounts(0)).build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(2);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        for source in ["binance", "kraken"] {
+            contract.report_price(Pair::usd("NEAR"), source.to_string(), U128(5000000), 6);
+        }
+        contract.set_circuit_breaker(Some(CircuitBreaker { max_spread_bps: Some(500), max_move_bps: Some(1000) }));
+        contract.grant_role(Role::PauseGuardian, accounts(1));
+        contract
+    }
+
+    fn report_both(contract: &mut PriceOracle, binance: u128, kraken: u128) {
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(binance), 6);
+        contract.report_price(Pair::usd("NEAR"), "kraken".to_string(), U128(kraken), 6);
+    }
+
+    #[test]
+    fn test_wide_spread_trips_until_reset() {
+        let mut contract = setup();
+        report_both(&mut contract, 5000000, 5200000); // 4% apart
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5100000);
+        report_both(&mut contract, 4800000, 5200000); // 8% apart
+        assert!(get_logs().contains(&"Circuit breaker of NEAR/USD tripped by round 3: sources 800 bps apart, 500 bps allowed".to_string()));
+        assert!(!contract.is_valid(Pair::usd("NEAR")));
+        let prices = contract.get_prices(vec!["NEAR".to_string()]);
+        assert_eq!(prices["NEAR"], PriceResult::Error("Circuit breaker tripped for NEAR/USD: sources 800 bps apart, 500 bps allowed".to_string()));
+
+        // Later rounds keep finalizing, but the first trip stands until reset
+        report_both(&mut contract, 5000000, 5000000);
+        assert_eq!(contract.get_breaker_trip(Pair::usd("NEAR")).unwrap().round_id, 3);
+        testing_env!(VMContextBuilder::new().predecessor_account_id(accounts(1)).build());
+        contract.reset_circuit_breaker(Pair::usd("NEAR"));
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5000000);
+        assert!(contract.get_tripped_breakers().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Circuit breaker tripped for NEAR/USD: 1500 bps move from 5000000, 1000 bps allowed")]
+    fn test_large_move_fails_reads_closed() {
+        let mut contract = setup();
+        report_both(&mut contract, 5750000, 5750000);
+        contract.get_price(Pair::usd("NEAR"));
+    }
+
+    #[test]
+    #[should_panic(expected = "charlie lacks role PauseGuardian")]
+    fn test_reset_requires_guardian() {
+        let mut contract = setup();
+        report_both(&mut contract, 5750000, 5750000);
+        testing_env!(VMContextBuilder::new().predecessor_account_id(accounts(2)).build());
+        contract.reset_circuit_breaker(Pair::usd("NEAR"));
+    }
+}
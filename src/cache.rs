@@ -0,0 +1,132 @@
+use near_sdk::near;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::{now_ms, Pair, Price, PriceOracle, PriceOracleExt, PriceReport};
+
+/// Aggregate of a pair's latest reports, computed when they were last written
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct CachedAggregate {
+    pub price: Option<Price>,      // None while fewer than min_sources are live
+    pub computed_at: u64,
+    pub valid_until: Option<u64>,  // When the first report it counts stops being live (None = never)
+}
+
+#[near]
+impl PriceOracle {
+    /// Get the live aggregate of a pair's latest reports with the base asset's aggregation
+    /// method, as computed when a report was last written. Unlike get_price it doesn't
+    /// wait for a round to finalize, and it's read in O(1) until one of the reports it
+    /// counts expires or misses its heartbeat, after which it's recomputed on read
+    pub fn get_aggregate(&self, pair: Pair) -> Price {
+        self.assert_not_paused();
+        self.cached_aggregate(&pair).unwrap_or_else(|| self.panic_insufficient_sources(&pair))
+    }
+
+    /// Get the cached aggregate of a pair, if it hasn't been invalidated since
+    pub fn get_cached_aggregate(&self, pair: Pair) -> Option<CachedAggregate> {
+        self.aggregates.get(&pair).cloned()
+    }
+}
+
+impl PriceOracle {
+    /// Aggregate of a pair's latest reports, from the cache while it's still valid
+    pub(crate) fn cached_aggregate(&self, pair: &Pair) -> Option<Price> {
+        match self.aggregates.get(pair) {
+            Some(cached) if cached.valid_until.is_none_or(|until| now_ms() <= until) => cached.price.clone(),
+            _ => self.live_aggregate(pair),
+        }
+    }
+
+    /// Recomputes a pair's cached aggregate after its reports changed
+    pub(crate) fn refresh_aggregate(&mut self, pair: &Pair) {
+        let valid_until = self.live_reports(pair).into_iter().filter_map(|r| self.live_until(pair, r)).min();
+        let cached = CachedAggregate { price: self.live_aggregate(pair), computed_at: now_ms(), valid_until };
+        self.aggregates.insert(pair.clone(), cached);
+    }
+
+    /// Drops every cached aggregate after a change to how reports are aggregated;
+    /// they're recomputed on read until the next report
+    pub(crate) fn invalidate_aggregates(&mut self) {
+        self.aggregates.clear();
+    }
+
+    fn live_aggregate(&self, pair: &Pair) -> Option<Price> {
+        let method = self.asset_config(&pair.base).aggregation;
+        self.try_aggregate(pair, method.aggregator().as_ref())
+    }
+
+    /// Last moment a live report still counts, None if it never expires
+    fn live_until(&self, pair: &Pair, report: &PriceReport) -> Option<u64> {
+        let expiry = self
+            .asset_config(&pair.base)
+            .max_report_age
+            .map(|max_age| report.timestamp + max_age * 1000);
+        let flatline = self
+            .heartbeat_interval(pair, &report.source)
+            .map(|interval| report.timestamp + interval * 1000 * self.max_missed_heartbeats as u64 - 1);
+        expiry.into_iter().chain(flatline).min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn at(seconds: u64) {
+        testing_env!(VMContextBuilder::new().block_timestamp(seconds * 1_000_000_000).build());
+    }
+
+    fn setup() -> PriceOracle {
+        at(0);
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(2);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.set_max_report_age("NEAR".to_string(), Some(60));
+        contract
+    }
+
+    #[test]
+    fn test_aggregate_cached_on_write() {
+        let mut contract = setup();
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        assert_eq!(contract.get_cached_aggregate(Pair::usd("NEAR")).unwrap().price, None);
+
+        at(10);
+        contract.report_price(Pair::usd("NEAR"), "kraken".to_string(), U128(5200000), 6);
+        let cached = contract.get_cached_aggregate(Pair::usd("NEAR")).unwrap();
+        assert_eq!(cached.computed_at, 10000);
+        assert_eq!(cached.valid_until, Some(60000));
+        assert_eq!(contract.get_aggregate(Pair::usd("NEAR")).price, 5100000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Need at least 2 price sources for NEAR/USD, have 1")]
+    fn test_expired_cache_recomputed() {
+        let mut contract = setup();
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        at(30);
+        contract.report_price(Pair::usd("NEAR"), "kraken".to_string(), U128(5200000), 6);
+        // binance expires at 60s, so the cached aggregate no longer holds
+        at(61);
+        contract.get_aggregate(Pair::usd("NEAR"));
+    }
+
+    #[test]
+    fn test_config_change_invalidates_cache() {
+        let mut contract = setup();
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        contract.set_min_sources(1);
+        assert_eq!(contract.get_cached_aggregate(Pair::usd("NEAR")), None);
+        assert_eq!(contract.get_aggregate(Pair::usd("NEAR")).price, 5000000);
+    }
+}
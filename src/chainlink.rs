@@ -0,0 +1,120 @@
+use near_sdk::near;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::{AssetId, Pair, PriceOracle, PriceOracleExt, RoundAnswer};
+
+/// Round in the shape of Chainlink's AggregatorV3Interface `latestRoundData`
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct AggregatorRoundData {
+    pub round_id: u64,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub answer: u128,          // Scaled by 10^decimals()
+    pub started_at: u64,       // Unix seconds
+    pub updated_at: u64,       // Unix seconds
+    pub answered_in_round: u64, // Always round_id: every answer comes from its own round
+}
+
+impl From<&RoundAnswer> for AggregatorRoundData {
+    fn from(answer: &RoundAnswer) -> Self {
+        Self {
+            round_id: answer.round_id,
+            answer: answer.price,
+            started_at: answer.started_at / 1000,
+            updated_at: answer.finalized_at / 1000,
+            answered_in_round: answer.round_id,
+        }
+    }
+}
+
+#[near]
+impl PriceOracle {
+    /// Get the latest finalized round of an asset's USD pair like a Chainlink aggregator
+    /// would, so integrations ported from Solidity keep their checks on updated_at and
+    /// answered_in_round
+    pub fn latest_round_data(&self, asset_id: AssetId) -> AggregatorRoundData {
+        self.assert_not_paused();
+        let pair = Pair::usd(asset_id);
+        self.assert_breaker_closed(&pair);
+        self.latest_answer(&pair)
+            .map(AggregatorRoundData::from)
+            .unwrap_or_else(|| panic!("No answer for {}", pair))
+    }
+
+    /// Get a kept round of an asset's USD pair like Chainlink's `getRoundData`
+    pub fn aggregator_round_data(&self, asset_id: AssetId, round_id: u64) -> AggregatorRoundData {
+        AggregatorRoundData::from(&self.get_round_data(asset_id, round_id))
+    }
+
+    /// Get the decimals of an asset's USD answers (Chainlink `decimals`)
+    pub fn decimals(&self, asset_id: AssetId) -> u8 {
+        self.asset_config(&asset_id).decimals
+    }
+
+    /// Get the description of an asset's USD feed, e.g. "NEAR / USD" (Chainlink `description`)
+    pub fn description(&self, asset_id: AssetId) -> String {
+        self.asset_config(&asset_id);
+        let pair = Pair::usd(asset_id);
+        format!("{} / {}", pair.base, pair.quote)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn setup() -> PriceOracle {
+        testing_env!(VMContextBuilder::new().block_timestamp(10 * 1_000_000_000).build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5250000), 6);
+        contract
+    }
+
+    #[test]
+    fn test_latest_round_data() {
+        let contract = setup();
+        assert_eq!(
+            contract.latest_round_data("NEAR".to_string()),
+            AggregatorRoundData { round_id: 1, answer: 5250000, started_at: 10, updated_at: 10, answered_in_round: 1 }
+        );
+        assert_eq!(contract.aggregator_round_data("NEAR".to_string(), 1).answer, 5250000);
+        assert_eq!(contract.decimals("NEAR".to_string()), 6);
+        assert_eq!(contract.description("NEAR".to_string()), "NEAR / USD");
+    }
+
+    #[test]
+    #[should_panic(expected = "No answer for ETH/USD")]
+    fn test_latest_round_data_without_answer() {
+        let mut contract = setup();
+        contract.add_asset("ETH".to_string(), AssetConfig::new("ETH", "Ether", 8));
+        contract.latest_round_data("ETH".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Oracle paused")]
+    fn test_aggregator_round_data_closed_while_paused() {
+        let mut contract = setup();
+        contract.set_paused(true);
+        contract.aggregator_round_data("NEAR".to_string(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Circuit breaker tripped for NEAR/USD")]
+    fn test_aggregator_round_data_closed_while_tripped() {
+        let mut contract = setup();
+        contract.trip_breaker_for_tests(&Pair::usd("NEAR"));
+        contract.aggregator_round_data("NEAR".to_string(), 1);
+    }
+}
@@ -0,0 +1,247 @@
+use near_sdk::json_types::{Base64VecU8, U128};
+use near_sdk::{env, near, AccountId};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::reporter::reporter_account;
+use crate::{now_ms, Pair, PriceOracle, PriceOracleExt, Role};
+
+/// Two-phase reporting: reporters commit a hash of their price while the commit phase
+/// of a pair is open and reveal it after, so nobody can copy a price they saw first
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct CommitReveal {
+    pub commit_seconds: u64,   // How long commitments are taken, from a cycle's first one
+    pub reveal_seconds: u64,   // How long they can be revealed after that
+}
+
+/// A reporter's sealed price in a commit phase
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct Commitment {
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub reporter: AccountId,
+    pub hash: Base64VecU8,     // commitment_hash of the price it will reveal
+    pub committed_at: u64,
+}
+
+/// Commit-reveal cycle of a pair
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct CommitPhase {
+    pub started_at: u64,
+    pub reveals_open_at: u64,  // Commit phase closes, reveal phase opens
+    pub ends_at: u64,          // Unrevealed commitments lapse
+    pub commitments: Vec<Commitment>, // Not yet revealed, in commit order
+}
+
+#[near]
+impl PriceOracle {
+    /// Turn commit-reveal reporting on or off (ConfigManager)
+    /// While it's on, prices can only be reported through commit_price and reveal_price
+    pub fn set_commit_reveal(&mut self, config: Option<CommitReveal>) {
+        self.assert_role(Role::ConfigManager);
+        if let Some(config) = &config {
+            assert!(
+                config.commit_seconds > 0 && config.reveal_seconds > 0,
+                "Commit and reveal phases must be positive"
+            );
+        }
+        self.commit_reveal = config;
+        self.commit_phases.clear();
+    }
+
+    /// Get the commit-reveal phases, if commit-reveal reporting is on
+    pub fn get_commit_reveal(&self) -> Option<CommitReveal> {
+        self.commit_reveal.clone()
+    }
+
+    /// Get the commit-reveal cycle of a pair, unless it has none running
+    pub fn get_commit_phase(&self, pair: Pair) -> Option<CommitPhase> {
+        self.commit_phases.get(&pair).filter(|phase| now_ms() < phase.ends_at).cloned()
+    }
+
+    /// Commit the hash of a price the caller will reveal for a pair (Reporter); see
+    /// commitment_hash. The first commitment after a cycle ends starts the next one
+    /// Committing again in the same phase replaces the caller's commitment
+    pub fn commit_price(&mut self, pair: Pair, hash: Base64VecU8) {
+        self.assert_not_paused();
+        let caller = reporter_account();
+        assert!(self.is_reporter(caller.clone()), "Reporter {} is not whitelisted", caller);
+        self.asset_config(&pair.base);
+        assert!(hash.0.len() == 32, "Commitments are 32-byte sha256 hashes, got {} bytes", hash.0.len());
+        let config = self.commit_reveal.clone().expect("Commit-reveal reporting is off");
+
+        let now = now_ms();
+        let phase = self.commit_phases.get(&pair).filter(|phase| now < phase.ends_at);
+        if let Some(phase) = phase {
+            assert!(
+                now < phase.reveals_open_at,
+                "Commit phase of {} is closed, reveals are open until {}",
+                pair,
+                phase.ends_at
+            );
+        } else {
+            if let Some(lapsed) = self.commit_phases.get(&pair).filter(|phase| !phase.commitments.is_empty()) {
+                near_sdk::log!("Commitments of {} lapsed unrevealed: {}", pair, lapsed.commitments.len());
+            }
+            let reveals_open_at = now + config.commit_seconds * 1000;
+            self.commit_phases.insert(
+                pair.clone(),
+                CommitPhase {
+                    started_at: now,
+                    reveals_open_at,
+                    ends_at: reveals_open_at + config.reveal_seconds * 1000,
+                    commitments: Vec::new(),
+                },
+            );
+        }
+        let commitments = &mut self.commit_phases.get_mut(&pair).expect("started above").commitments;
+        commitments.retain(|commitment| commitment.reporter != caller);
+        commitments.push(Commitment { reporter: caller, hash, committed_at: now });
+    }
+
+    /// Reveal the price the caller committed for a pair once its reveal phase is open
+    /// (Reporter). It must hash to the commitment, and is then reported like
+    /// report_price_with_volume, with the report deposit attached
+    #[payable]
+    pub fn reveal_price(
+        &mut self,
+        pair: Pair,
+        source: String,
+        price: U128,
+        decimals: u8,
+        volume: Option<U128>,
+        salt: Base64VecU8,
+    ) {
+        self.assert_not_paused();
+        let (price, volume) = (price.0, volume.map(|volume| volume.0));
+        let caller = reporter_account();
+        let now = now_ms();
+        let phase = self
+            .commit_phases
+            .get_mut(&pair)
+            .filter(|phase| now < phase.ends_at)
+            .unwrap_or_else(|| panic!("No commit phase of {} running", pair));
+        assert!(now >= phase.reveals_open_at, "Reveals of {} open at {}", pair, phase.reveals_open_at);
+        let index = phase
+            .commitments
+            .iter()
+            .position(|commitment| commitment.reporter == caller)
+            .unwrap_or_else(|| panic!("No commitment of {} for {}", caller, pair));
+        let expected = commitment_hash(&caller, &pair, &source, price, decimals, volume, &salt.0);
+        assert!(phase.commitments[index].hash.0 == expected, "Reveal of {} doesn't match its commitment", caller);
+        phase.commitments.remove(index);
+
+        self.report_now(pair, source, price, decimals, volume, None);
+    }
+}
+
+impl PriceOracle {
+    /// Panics if prices must go through commit-reveal
+    pub(crate) fn assert_direct_reports(&self) {
+        assert!(
+            self.commit_reveal.is_none(),
+            "Commit-reveal reporting is on: commit_price then reveal_price"
+        );
+    }
+}
+
+/// Hash a reporter commits to: sha256 of the borsh encoding of
+/// (reporter, pair, source, price, decimals, volume, salt)
+/// The salt keeps the price from being guessed out of the hash
+pub fn commitment_hash(
+    reporter: &AccountId,
+    pair: &Pair,
+    source: &str,
+    price: u128,
+    decimals: u8,
+    volume: Option<u128>,
+    salt: &[u8],
+) -> Vec<u8> {
+    let encoded = borsh::to_vec(&(reporter, pair, source, price, decimals, volume, salt)).expect("commitments serialize");
+    env::sha256(&encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn call(account_id: AccountId, seconds: u64) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id)
+            .block_timestamp(seconds * 1_000_000_000)
+            .build());
+    }
+
+    fn commit(contract: &mut PriceOracle, reporter: usize, seconds: u64, source: &str, price: u128) {
+        call(accounts(reporter), seconds);
+        let hash = commitment_hash(&accounts(reporter), &Pair::usd("NEAR"), source, price, 6, None, b"salt");
+        contract.commit_price(Pair::usd("NEAR"), Base64VecU8(hash));
+    }
+
+    fn reveal(contract: &mut PriceOracle, reporter: usize, seconds: u64, source: &str, price: u128) {
+        call(accounts(reporter), seconds);
+        contract.reveal_price(Pair::usd("NEAR"), source.to_string(), U128(price), 6, None, Base64VecU8(b"salt".to_vec()));
+    }
+
+    fn setup() -> PriceOracle {
+        call(accounts(0), 0);
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(2);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.add_reporter(accounts(1));
+        contract.add_reporter(accounts(2));
+        contract.set_commit_reveal(Some(CommitReveal { commit_seconds: 30, reveal_seconds: 30 }));
+        contract
+    }
+
+    #[test]
+    fn test_commit_then_reveal() {
+        let mut contract = setup();
+        commit(&mut contract, 1, 100, "binance", 5000000);
+        commit(&mut contract, 2, 110, "kraken", 5200000);
+        let phase = contract.get_commit_phase(Pair::usd("NEAR")).unwrap();
+        assert_eq!((phase.reveals_open_at, phase.ends_at, phase.commitments.len()), (130000, 160000, 2));
+
+        reveal(&mut contract, 1, 130, "binance", 5000000);
+        reveal(&mut contract, 2, 140, "kraken", 5200000);
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5100000);
+        assert!(contract.get_commit_phase(Pair::usd("NEAR")).unwrap().commitments.is_empty());
+        call(accounts(1), 160);
+        assert_eq!(contract.get_commit_phase(Pair::usd("NEAR")), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Commit phase of NEAR/USD is closed, reveals are open until 160000")]
+    fn test_no_commitments_after_reveals_open() {
+        let mut contract = setup();
+        commit(&mut contract, 1, 100, "binance", 5000000);
+        reveal(&mut contract, 1, 130, "binance", 5000000);
+        // A late reporter that saw the reveal can't commit to a copy of it
+        commit(&mut contract, 2, 131, "kraken", 5000000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Reveal of bob doesn't match its commitment")]
+    fn test_reveal_must_match_commitment() {
+        let mut contract = setup();
+        commit(&mut contract, 1, 100, "binance", 5000000);
+        reveal(&mut contract, 1, 130, "binance", 5100000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Commit-reveal reporting is on: commit_price then reveal_price")]
+    fn test_direct_reports_refused() {
+        let mut contract = setup();
+        call(accounts(2), 50);
+        contract.report_price(Pair::usd("NEAR"), "kraken".to_string(), U128(5000000), 6);
+    }
+}
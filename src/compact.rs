@@ -0,0 +1,258 @@
+#[cfg(feature = "abi")]
+use std::collections::BTreeMap;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::AccountId;
+use borsh::io::{Error, ErrorKind, Read, Result, Write};
+#[cfg(feature = "abi")]
+use borsh::schema::{Declaration, Definition, Fields};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+
+use crate::{Attestation, PriceReport};
+
+/// Leads a compact report; a legacy one starts with its source's length, which can't be this
+const COMPACT_TAG: u32 = u32::MAX;
+
+/// Leads a compact report followed by its attestation
+const ATTESTED_TAG: u32 = u32::MAX - 1;
+
+/// Storage shape of a PriceReport, about a third smaller than its plain fields
+/// JSON views keep the plain shape; reports stored before this encoding still decode
+/// An attested report is tagged ATTESTED_TAG and followed by its Attestation
+#[derive(BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "abi", derive(BorshSchema))]
+struct CompactReport {
+    tag: u32,
+    source: String,
+    price: CompactAmount,
+    decimals: u8,
+    seconds: u32,              // Unix seconds of the timestamp
+    millis: u16,               // Milliseconds past `seconds`
+    reporter: String,          // Checked to be an account id when written
+    volume: Option<CompactAmount>,
+}
+
+/// `mantissa * 10^exponent` with trailing zeros stripped into the exponent and the
+/// mantissa written as LEB128, so typical prices and volumes take 2-5 bytes instead of 16
+#[derive(Debug, PartialEq)]
+struct CompactAmount {
+    mantissa: u128,
+    exponent: u8,
+}
+
+impl From<u128> for CompactAmount {
+    fn from(amount: u128) -> Self {
+        let (mut mantissa, mut exponent) = (amount, 0);
+        while mantissa != 0 && mantissa % 10 == 0 {
+            mantissa /= 10;
+            exponent += 1;
+        }
+        CompactAmount { mantissa, exponent }
+    }
+}
+
+impl CompactAmount {
+    fn amount(&self) -> Result<u128> {
+        10u128
+            .checked_pow(self.exponent as u32)
+            .and_then(|scale| self.mantissa.checked_mul(scale))
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "compact amount overflows"))
+    }
+}
+
+impl BorshSerialize for CompactAmount {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        self.exponent.serialize(writer)?;
+        let mut rest = self.mantissa;
+        loop {
+            let byte = (rest & 0x7f) as u8;
+            rest >>= 7;
+            if rest == 0 {
+                return writer.write_all(&[byte]);
+            }
+            writer.write_all(&[byte | 0x80])?;
+        }
+    }
+}
+
+impl BorshDeserialize for CompactAmount {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<Self> {
+        let exponent = u8::deserialize_reader(reader)?;
+        let mut mantissa: u128 = 0;
+        for shift in (0..128).step_by(7) {
+            let byte = u8::deserialize_reader(reader)?;
+            let bits = ((byte & 0x7f) as u128)
+                .checked_shl(shift)
+                .filter(|bits| bits >> shift == (byte & 0x7f) as u128)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, "compact amount overflows"))?;
+            mantissa |= bits;
+            if byte & 0x80 == 0 {
+                return Ok(CompactAmount { mantissa, exponent });
+            }
+        }
+        Err(Error::new(ErrorKind::InvalidData, "compact amount too long"))
+    }
+}
+
+/// Described as its decoded fields; the mantissa is written as LEB128
+#[cfg(feature = "abi")]
+impl BorshSchema for CompactAmount {
+    fn add_definitions_recursively(definitions: &mut BTreeMap<Declaration, Definition>) {
+        u8::add_definitions_recursively(definitions);
+        u128::add_definitions_recursively(definitions);
+        let fields = Fields::NamedFields(vec![
+            ("exponent".to_string(), u8::declaration()),
+            ("mantissa".to_string(), u128::declaration()),
+        ]);
+        definitions.insert(Self::declaration(), Definition::Struct { fields });
+    }
+
+    fn declaration() -> Declaration {
+        "CompactAmount".to_string()
+    }
+}
+
+impl BorshSerialize for PriceReport {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let seconds = u32::try_from(self.timestamp / 1000)
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "report timestamp out of range"))?;
+        if self.reporter.parse::<AccountId>().is_err() {
+            return Err(Error::new(ErrorKind::InvalidInput, "reporter is not an account id"));
+        }
+        CompactReport {
+            tag: if self.attestation.is_some() { ATTESTED_TAG } else { COMPACT_TAG },
+            source: self.source.clone(),
+            price: self.price.into(),
+            decimals: self.decimals,
+            seconds,
+            millis: (self.timestamp % 1000) as u16,
+            reporter: self.reporter.clone(),
+            volume: self.volume.map(CompactAmount::from),
+        }
+        .serialize(writer)?;
+        match &self.attestation {
+            Some(attestation) => attestation.serialize(writer),
+            None => Ok(()),
+        }
+    }
+}
+
+impl BorshDeserialize for PriceReport {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<Self> {
+        let tag = u32::deserialize_reader(reader)?;
+        if tag != COMPACT_TAG && tag != ATTESTED_TAG {
+            return legacy_report(tag, reader);
+        }
+        let source = String::deserialize_reader(reader)?;
+        let price = CompactAmount::deserialize_reader(reader)?.amount()?;
+        let decimals = u8::deserialize_reader(reader)?;
+        let seconds = u32::deserialize_reader(reader)?;
+        let millis = u16::deserialize_reader(reader)?;
+        let reporter = String::deserialize_reader(reader)?;
+        let volume = Option::<CompactAmount>::deserialize_reader(reader)?
+            .map(|volume| volume.amount())
+            .transpose()?;
+        let attestation = match tag {
+            ATTESTED_TAG => Some(Attestation::deserialize_reader(reader)?),
+            _ => None,
+        };
+        Ok(PriceReport {
+            source,
+            price,
+            decimals,
+            timestamp: seconds as u64 * 1000 + millis as u64,
+            reporter,
+            volume,
+            attestation,
+        })
+    }
+}
+
+#[cfg(feature = "abi")]
+impl BorshSchema for PriceReport {
+    fn add_definitions_recursively(definitions: &mut BTreeMap<Declaration, Definition>) {
+        CompactReport::add_definitions_recursively(definitions);
+    }
+
+    fn declaration() -> Declaration {
+        CompactReport::declaration()
+    }
+}
+
+/// Rest of a report stored with its plain fields, whose source is `source_len` long
+fn legacy_report<R: Read>(source_len: u32, reader: &mut R) -> Result<PriceReport> {
+    let mut source = vec![0; source_len as usize];
+    reader.read_exact(&mut source)?;
+    Ok(PriceReport {
+        source: String::from_utf8(source).map_err(|_| Error::new(ErrorKind::InvalidData, "invalid source"))?,
+        price: u128::deserialize_reader(reader)?,
+        decimals: u8::deserialize_reader(reader)?,
+        timestamp: u64::deserialize_reader(reader)?,
+        reporter: String::deserialize_reader(reader)?,
+        volume: Option::<u128>::deserialize_reader(reader)?,
+        attestation: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(price: u128, volume: Option<u128>) -> PriceReport {
+        PriceReport {
+            source: "binance".to_string(),
+            price,
+            decimals: 6,
+            timestamp: 1_760_000_000_123,
+            reporter: "reporter.near".to_string(),
+            volume,
+            attestation: None,
+        }
+    }
+
+    /// Encoding of the plain fields, as reports were stored before
+    fn legacy_bytes(report: &PriceReport) -> Vec<u8> {
+        borsh::to_vec(&(
+            &report.source,
+            report.price,
+            report.decimals,
+            report.timestamp,
+            &report.reporter,
+            report.volume,
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_compact_round_trip() {
+        for report in [report(5250000, None), report(u128::MAX, Some(48 * 10u128.pow(30))), report(0, Some(1))] {
+            let bytes = borsh::to_vec(&report).unwrap();
+            assert_eq!(borsh::from_slice::<PriceReport>(&bytes).unwrap(), report);
+        }
+        assert_eq!(CompactAmount::from(5250000), CompactAmount { mantissa: 525, exponent: 4 });
+        assert_eq!(borsh::to_vec(&CompactAmount::from(5250000)).unwrap(), [4, 0x8d, 0x04]);
+    }
+
+    #[test]
+    fn test_attested_round_trip() {
+        let attestation = Attestation { measurement: "ab".repeat(32), quote_hash: "cd".repeat(32) };
+        let report = PriceReport { attestation: Some(attestation), ..report(5250000, None) };
+        let bytes = borsh::to_vec(&report).unwrap();
+        assert_eq!(bytes[..4], ATTESTED_TAG.to_le_bytes());
+        assert_eq!(borsh::from_slice::<PriceReport>(&bytes).unwrap(), report);
+    }
+
+    #[test]
+    fn test_compact_smaller_than_legacy() {
+        let report = report(5250000, Some(48000000));
+        let (compact, legacy) = (borsh::to_vec(&report).unwrap(), legacy_bytes(&report));
+        assert!(compact.len() * 4 <= legacy.len() * 3, "{} vs {} bytes", compact.len(), legacy.len());
+    }
+
+    #[test]
+    fn test_legacy_report_decodes() {
+        let report = report(5250000, Some(48000000));
+        assert_eq!(borsh::from_slice::<PriceReport>(&legacy_bytes(&report)).unwrap(), report);
+    }
+}
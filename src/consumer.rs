@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+
+use near_sdk::ext_contract;
+use near_sdk::json_types::U128;
+
+use crate::{AssetId, Pair, Price, PriceResponse, PriceResult, RoundAnswer};
+
+/// Methods of the oracle a consumer contract can call; panicking calls fail the promise
+/// Available with the `consumer` feature, which builds without the oracle's own state
+#[ext_contract(ext_price_oracle)]
+pub trait PriceOracle {
+    /// Answer of the pair's latest finalized round, with its age and source counts
+    fn get_price(&self, pair: Pair) -> PriceResponse;
+
+    /// Latest USD answers of several assets, with an error for each asset that has none
+    fn get_prices(&self, asset_ids: Vec<AssetId>) -> BTreeMap<AssetId, PriceResult>;
+
+    /// Deprecated: price, decimals and confidence of the latest answer
+    fn get_price_legacy(&self, pair: Pair) -> Price;
+
+    /// Live aggregate of the pair's latest reports, cached when they were written
+    fn get_aggregate(&self, pair: Pair) -> Price;
+
+    /// Latest answer, failing unless it finalized within `max_age_seconds`
+    fn get_price_with_max_age(&self, pair: Pair, max_age_seconds: u64) -> Price;
+
+    /// Whether the pair's latest answer can be trusted right now
+    fn is_valid(&self, pair: Pair) -> bool;
+
+    /// Latest finalized round of a pair
+    fn get_latest_round(&self, pair: Pair) -> Option<RoundAnswer>;
+
+    /// Id of the latest finalized round of an asset's USD pair
+    fn latest_round(&self, asset_id: AssetId) -> u64;
+
+    /// A kept round of an asset's USD pair
+    fn get_round_data(&self, asset_id: AssetId, round_id: u64) -> RoundAnswer;
+
+    /// Latest USD answer of an asset that finalized at or before `block_height`
+    fn get_price_at_block(&self, asset_id: AssetId, block_height: u64) -> RoundAnswer;
+
+    /// Time-weighted average over the trailing `window_seconds`
+    fn get_twap(&self, pair: Pair, window_seconds: u64) -> Price;
+
+    /// Time-weighted average of an asset's USD pair between two ms timestamps
+    fn get_twap_between(&self, asset_id: AssetId, from_ts: u64, to_ts: u64) -> Price;
+
+    /// USD value, scaled by 10^6 and rounded down, of an amount of an asset in its token units
+    fn convert_to_usd(&self, asset_id: AssetId, amount: U128) -> U128;
+
+    /// Amount of an asset in its token units, rounded down, worth a USD amount scaled by 10^6
+    fn convert_from_usd(&self, asset_id: AssetId, usd_amount: U128) -> U128;
+
+    /// Exponentially smoothed aggregate of a pair
+    fn get_ema_price(&self, pair: Pair) -> Price;
+
+    /// Latest answer, paid for with the deposit attached to the call
+    fn get_price_paid(&mut self, pair: Pair) -> PriceResponse;
+
+    /// Latest answer, paid for from the caller's prepaid subscription
+    fn get_price_subscribed(&mut self, pair: Pair) -> PriceResponse;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::mock::MockAction;
+    use near_sdk::test_utils::{accounts, get_created_receipts, VMContextBuilder};
+    use near_sdk::{testing_env, Gas, NearToken};
+
+    #[test]
+    fn test_ext_call_targets_oracle() {
+        testing_env!(VMContextBuilder::new().build());
+        ext_price_oracle::ext(accounts(0))
+            .with_static_gas(Gas::from_tgas(5))
+            .with_attached_deposit(NearToken::from_yoctonear(1000))
+            .get_price_paid(Pair::usd("NEAR"))
+            .detach();
+
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].receiver_id, accounts(0));
+        let call = receipts[0].actions.iter().find_map(|action| match action {
+            MockAction::FunctionCallWeight { method_name, args, attached_deposit, .. } => {
+                Some((String::from_utf8(method_name.clone()).unwrap(), args.clone(), *attached_deposit))
+            }
+            _ => None,
+        });
+        let (method_name, args, deposit) = call.expect("function call");
+        assert_eq!(method_name, "get_price_paid");
+        assert_eq!(args, br#"{"pair":{"base":"NEAR","quote":"USD"}}"#);
+        assert_eq!(deposit, NearToken::from_yoctonear(1000));
+    }
+
+    #[test]
+    fn test_round_answer_reads_as_price() {
+        let answer: RoundAnswer = near_sdk::serde_json::from_str(
+            r#"{"round_id":3,"price":"5000000","decimals":6,"confidence":"100","submissions":2,"started_at":1000,"finalized_at":2000,"finalized_at_block":7}"#,
+        )
+        .unwrap();
+        assert_eq!(answer.to_price(), Price { price: 5000000, decimals: 6, confidence: 100 });
+    }
+}
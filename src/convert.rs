@@ -0,0 +1,100 @@
+use near_sdk::json_types::U128;
+use near_sdk::near;
+
+use crate::math::{mul_div_pow10, Rounding};
+use crate::{AssetId, Pair, PriceOracle, PriceOracleExt, DEFAULT_DECIMALS};
+
+#[near]
+impl PriceOracle {
+    /// Convert an amount of an asset into USD at its latest USD answer
+    /// `amount` is in the asset's token units (10^-token_decimals, or 10^-decimals for
+    /// assets without a token) and the result is scaled by 10^DEFAULT_DECIMALS, rounded
+    /// down unless another `rounding` is requested
+    pub fn convert_to_usd(&self, asset_id: AssetId, amount: U128, rounding: Option<Rounding>) -> U128 {
+        let (price, price_decimals, amount_decimals) = self.conversion_rate(&asset_id);
+        let up = DEFAULT_DECIMALS as u32;
+        let down = amount_decimals + price_decimals;
+        mul_div_pow10(amount.0, price, up, 1, down, rounding.unwrap_or_default())
+            .map(U128)
+            .unwrap_or_else(|| panic!("USD value of {} {} overflows", amount.0, asset_id))
+    }
+
+    /// Convert a USD amount, scaled by 10^DEFAULT_DECIMALS, into the asset's token units
+    /// at its latest USD answer; the inverse of convert_to_usd, rounded down unless
+    /// another `rounding` is requested
+    pub fn convert_from_usd(&self, asset_id: AssetId, usd_amount: U128, rounding: Option<Rounding>) -> U128 {
+        let (price, price_decimals, amount_decimals) = self.conversion_rate(&asset_id);
+        assert!(price > 0, "Cannot convert USD into {} at a zero price", asset_id);
+        let up = amount_decimals + price_decimals;
+        let down = DEFAULT_DECIMALS as u32;
+        mul_div_pow10(usd_amount.0, 1, up, price, down, rounding.unwrap_or_default())
+            .map(U128)
+            .unwrap_or_else(|| panic!("{} value of {} USD overflows", asset_id, usd_amount.0))
+    }
+}
+
+impl PriceOracle {
+    /// USD price of an asset, its decimals and the decimals of the asset's amounts
+    fn conversion_rate(&self, asset_id: &AssetId) -> (u128, u32, u32) {
+        let config = self.asset_config(asset_id);
+        let amount_decimals = config.token_decimals.unwrap_or(config.decimals) as u32;
+        let price = self.get_price(Pair::usd(asset_id.clone()));
+        (price.price, price.decimals as u32, amount_decimals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn setup() -> PriceOracle {
+        testing_env!(VMContextBuilder::new().build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        let near = AssetConfig::new("NEAR", "NEAR Protocol", 6).with_token("wrap.near").with_token_decimals(24);
+        contract.add_asset("NEAR".to_string(), near);
+        contract.add_asset("BTC".to_string(), AssetConfig::new("BTC", "Bitcoin", 8));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5280000), 6);
+        contract.report_price(Pair::usd("BTC"), "binance".to_string(), U128(6_500_000_000_000), 8);
+        contract
+    }
+
+    #[test]
+    fn test_convert_token_amounts() {
+        let contract = setup();
+        let one_near = 10u128.pow(24);
+
+        // 2.5 NEAR at $5.28 is $13.20
+        let usd = contract.convert_to_usd("NEAR".to_string(), U128(one_near * 5 / 2), None);
+        assert_eq!(usd, U128(13_200_000));
+        assert_eq!(contract.convert_from_usd("NEAR".to_string(), usd, None), U128(one_near * 5 / 2));
+
+        // $1 buys 1 / 5.28 NEAR, which doesn't divide evenly
+        let down = contract.convert_from_usd("NEAR".to_string(), U128(1_000_000), None).0;
+        let up = contract.convert_from_usd("NEAR".to_string(), U128(1_000_000), Some(Rounding::Up)).0;
+        assert_eq!(down, 189_393_939_393_939_393_939_393);
+        assert_eq!(up, down + 1);
+    }
+
+    #[test]
+    fn test_convert_without_token_decimals() {
+        let contract = setup();
+        // BTC amounts fall back to the asset's 8 decimals: 0.001 BTC at $65,000
+        assert_eq!(contract.convert_to_usd("BTC".to_string(), U128(100_000), None), U128(65_000_000));
+        assert_eq!(contract.convert_from_usd("BTC".to_string(), U128(65_000_000), None), U128(100_000));
+        assert_eq!(contract.convert_to_usd("BTC".to_string(), U128(1), None), U128(650));
+        // Dust below a micro-dollar rounds to zero unless rounded up
+        assert_eq!(contract.convert_to_usd("NEAR".to_string(), U128(1), None), U128(0));
+        assert_eq!(contract.convert_to_usd("NEAR".to_string(), U128(1), Some(Rounding::Up)), U128(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "USD value of 340282366920938463463374607431768211455 BTC overflows")]
+    fn test_convert_overflow() {
+        let contract = setup();
+        contract.convert_to_usd("BTC".to_string(), U128(u128::MAX), None);
+    }
+}
@@ -0,0 +1,272 @@
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::{env, near, AccountId};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::{now_ms, AssetId, ConfigChange, PriceOracle, PriceOracleExt};
+
+/// Accounts that approve sensitive operations in place of the owner
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct Council {
+    pub members: Vec<String>,  // Sorted, no duplicates
+    pub threshold: u8,         // Approvals needed to execute a proposal (M of N)
+    pub proposal_ttl: u64,     // Seconds a proposal stays open for approval and execution
+}
+
+/// Sensitive operation that only the council can perform once installed
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub enum CouncilAction {
+    ClearPrices,
+    SetMinSources { min_sources: u8 },
+    Pause,
+    Unpause,
+    SetCouncil { members: Vec<String>, threshold: u8, proposal_ttl: u64 },
+    SetAssetMinSources { asset_id: AssetId, min_sources: Option<u8> },
+    Upgrade { code_hash: Base64VecU8 }, // Deploys the staged code, which must hash to code_hash
+}
+
+/// Council proposal collecting approvals
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct Proposal {
+    pub proposal_id: u64,
+    pub proposer: String,
+    pub action: CouncilAction,
+    pub approvals: Vec<String>, // Members that approved, the proposer first
+    pub created_at: u64,
+    pub expires_at: u64,
+}
+
+#[near]
+impl PriceOracle {
+    /// Hand the sensitive operations to an M-of-N council (owner only, while none is
+    /// installed); afterwards the council changes itself through a SetCouncil proposal
+    pub fn set_council(&mut self, members: Vec<AccountId>, threshold: u8, proposal_ttl_seconds: u64) {
+        self.assert_owner();
+        assert!(self.council.is_none(), "Council already installed, propose SetCouncil instead");
+        let members = members.iter().map(|m| m.to_string()).collect();
+        self.install_council(members, threshold, proposal_ttl_seconds);
+    }
+
+    /// Get the council, if one is installed
+    pub fn get_council(&self) -> Option<Council> {
+        self.council.clone()
+    }
+
+    /// Propose a sensitive operation, approved by the proposer (council member)
+    /// Expired proposals are pruned. Returns the proposal id
+    pub fn propose(&mut self, action: CouncilAction) -> u64 {
+        let proposer = self.assert_council_member();
+        let now = now_ms();
+        self.proposals.retain(|_, proposal| proposal.expires_at > now);
+
+        self.next_proposal_id += 1;
+        let proposal = Proposal {
+            proposal_id: self.next_proposal_id,
+            proposer: proposer.clone(),
+            action,
+            approvals: vec![proposer],
+            created_at: now,
+            expires_at: now + self.council.as_ref().expect("member checked").proposal_ttl * 1000,
+        };
+        near_sdk::log!("Proposal {} created by {}: {:?}", proposal.proposal_id, proposal.proposer, proposal.action);
+        self.proposals.insert(proposal.proposal_id, proposal);
+        self.next_proposal_id
+    }
+
+    /// Approve a pending proposal (council member)
+    pub fn approve(&mut self, proposal_id: u64) {
+        let member = self.assert_council_member();
+        let proposal = self.live_proposal(proposal_id);
+        assert!(
+            !proposal.approvals.contains(&member),
+            "{} already approved proposal {}",
+            member,
+            proposal_id
+        );
+        proposal.approvals.push(member.clone());
+        near_sdk::log!("Proposal {} approved by {}", proposal_id, member);
+    }
+
+    /// Execute a proposal that reached the council's threshold (council member)
+    pub fn execute(&mut self, proposal_id: u64) {
+        self.assert_council_member();
+        let threshold = self.council.as_ref().expect("member checked").threshold as usize;
+        let approvals = self.live_proposal(proposal_id).approvals.len();
+        assert!(
+            approvals >= threshold,
+            "Proposal {} has {} of {} approvals",
+            proposal_id,
+            approvals,
+            threshold
+        );
+
+        let proposal = self.proposals.remove(&proposal_id).expect("checked above");
+        match proposal.action {
+            CouncilAction::ClearPrices => self.clear_all_prices(),
+            CouncilAction::SetMinSources { min_sources } => {
+                self.request_config_change(ConfigChange::MinSources { min_sources })
+            }
+            CouncilAction::Pause => self.set_paused(true),
+            CouncilAction::Unpause => self.set_paused(false),
+            CouncilAction::SetCouncil { members, threshold, proposal_ttl } => {
+                self.install_council(members, threshold, proposal_ttl);
+                self.proposals.clear(); // Approvals from the old council no longer count
+            }
+            CouncilAction::SetAssetMinSources { asset_id, min_sources } => {
+                assert!(min_sources != Some(0), "Min sources must be positive");
+                self.request_config_change(ConfigChange::AssetMinSources { asset_id, min_sources })
+            }
+            CouncilAction::Upgrade { code_hash } => self.deploy_staged_code(code_hash).detach(),
+        }
+        near_sdk::log!("Proposal {} executed", proposal_id);
+    }
+
+    /// Get a proposal, if it exists and hasn't been pruned
+    pub fn get_proposal(&self, proposal_id: u64) -> Option<Proposal> {
+        self.proposals.get(&proposal_id).cloned()
+    }
+
+    /// Get proposals that can still be approved, by id
+    pub fn get_proposals(&self) -> Vec<Proposal> {
+        let now = now_ms();
+        let mut proposals: Vec<Proposal> = self
+            .proposals
+            .values()
+            .filter(|proposal| proposal.expires_at > now)
+            .cloned()
+            .collect();
+        proposals.sort_by_key(|proposal| proposal.proposal_id);
+        proposals
+    }
+}
+
+impl PriceOracle {
+    /// Panics once a council is installed, naming the operation that needs its approval
+    pub(crate) fn assert_without_council(&self, operation: &str) {
+        assert!(self.council.is_none(), "{} requires council approval", operation);
+    }
+
+    /// Panics unless the caller sits on the council; returns the caller
+    fn assert_council_member(&self) -> String {
+        let caller = env::predecessor_account_id().to_string();
+        let council = self.council.as_ref().expect("No council installed");
+        assert!(council.members.contains(&caller), "{} is not a council member", caller);
+        caller
+    }
+
+    /// Pending proposal that hasn't expired
+    fn live_proposal(&mut self, proposal_id: u64) -> &mut Proposal {
+        let now = now_ms();
+        let proposal = self
+            .proposals
+            .get_mut(&proposal_id)
+            .unwrap_or_else(|| panic!("Unknown proposal {}", proposal_id));
+        assert!(now < proposal.expires_at, "Proposal {} expired at {}", proposal_id, proposal.expires_at);
+        proposal
+    }
+
+    fn install_council(&mut self, mut members: Vec<String>, threshold: u8, proposal_ttl: u64) {
+        members.sort();
+        members.dedup();
+        for member in &members {
+            assert!(member.parse::<AccountId>().is_ok(), "Invalid council member {}", member);
+        }
+        assert!(
+            threshold > 0 && threshold as usize <= members.len(),
+            "Threshold must be between 1 and {}, got {}",
+            members.len(),
+            threshold
+        );
+        assert!(proposal_ttl > 0, "Proposal TTL must be positive");
+        near_sdk::log!("Council installed: {} of {}", threshold, members.len());
+        self.council = Some(Council { members, threshold, proposal_ttl });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn as_account_at(account_id: AccountId, seconds: u64) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id)
+            .block_timestamp(seconds * 1_000_000_000)
+            .build());
+    }
+
+    fn setup() -> PriceOracle {
+        as_account_at(accounts(0), 1000);
+        let mut contract = PriceOracle::for_tests();
+        contract.set_council(vec![accounts(1), accounts(2), accounts(3)], 2, 3600);
+        contract
+    }
+
+    #[test]
+    fn test_council_executes_with_threshold() {
+        let mut contract = setup();
+        as_account_at(accounts(1), 1000);
+        let id = contract.propose(CouncilAction::SetMinSources { min_sources: 5 });
+        assert_eq!(contract.get_proposal(id).unwrap().approvals, vec!["bob".to_string()]);
+
+        as_account_at(accounts(2), 1100);
+        contract.approve(id);
+        contract.execute(id);
+        assert_eq!(contract.get_min_sources(), 5);
+        assert_eq!(contract.get_proposal(id), None);
+    }
+
+    #[test]
+    fn test_council_sets_asset_min_sources() {
+        let mut contract = setup();
+        contract.add_asset("BTC".to_string(), crate::AssetConfig::new("BTC", "Bitcoin", 2));
+        as_account_at(accounts(1), 1000);
+        let id = contract.propose(CouncilAction::SetAssetMinSources { asset_id: "BTC".to_string(), min_sources: Some(7) });
+        as_account_at(accounts(2), 1100);
+        contract.approve(id);
+        contract.execute(id);
+        assert_eq!(contract.get_asset_info("BTC".to_string()).min_sources, 7);
+    }
+
+    #[test]
+    #[should_panic(expected = "Proposal 1 has 1 of 2 approvals")]
+    fn test_execute_below_threshold() {
+        let mut contract = setup();
+        as_account_at(accounts(1), 1000);
+        let id = contract.propose(CouncilAction::ClearPrices);
+        contract.execute(id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Proposal 1 expired at 4600000")]
+    fn test_stale_proposal_expires() {
+        let mut contract = setup();
+        as_account_at(accounts(1), 1000);
+        let id = contract.propose(CouncilAction::ClearPrices);
+        as_account_at(accounts(2), 4600);
+        assert!(contract.get_proposals().is_empty());
+        contract.approve(id);
+    }
+
+    #[test]
+    #[should_panic(expected = "set_min_sources requires council approval")]
+    fn test_council_replaces_owner() {
+        let mut contract = setup();
+        contract.set_min_sources(2);
+    }
+
+    #[test]
+    #[should_panic(expected = "alice is not a council member")]
+    fn test_only_members_propose() {
+        let mut contract = setup();
+        contract.propose(CouncilAction::ClearPrices);
+    }
+}
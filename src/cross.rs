@@ -0,0 +1,277 @@
+use near_sdk::near;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::math::{mul_div, pow10, Rounding, MAX_POW10};
+use crate::{AssetId, Pair, Price, PriceOracle, PriceOracleExt, DEFAULT_QUOTE};
+
+/// Price of `base` in `quote`, possibly derived through a common quote asset
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct CrossRate {
+    pub base: AssetId,
+    pub quote: AssetId,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub price: u128,           // Scaled by 10^decimals of the base asset
+    pub decimals: u8,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub error: u128,           // Propagated uncertainty, same scale as price
+    pub timestamp: u64,        // Oldest report used in any leg
+    pub via: Option<AssetId>,  // Common quote used for the derivation, None if reported directly
+}
+
+/// One side of a derivation: aggregate price plus its uncertainty and age
+struct Leg {
+    price: u128,
+    decimals: u8,
+    error: u128,               // Confidence of the aggregate
+    timestamp: u64,
+}
+
+#[near]
+impl PriceOracle {
+    /// Get the price of `base` in `quote`, deriving it from `base/X` and `quote/X` when the
+    /// pair isn't reported directly (USD is tried first as X)
+    pub fn get_cross_rate(&self, base: AssetId, quote: AssetId) -> CrossRate {
+        self.assert_not_paused();
+        let direct = Pair::new(base.clone(), quote.clone());
+        if self.prices.contains_key(&direct) {
+            let leg = self.leg(&direct);
+            return CrossRate {
+                base,
+                quote,
+                price: leg.price,
+                decimals: leg.decimals,
+                error: leg.error,
+                timestamp: leg.timestamp,
+                via: None,
+            };
+        }
+
+        let via = self
+            .common_quote(&base, &quote)
+            .unwrap_or_else(|| panic!("No route to derive {}/{}", base, quote));
+        let numerator = self.leg(&Pair::new(base.clone(), via.clone()));
+        let denominator = self.leg(&Pair::new(quote.clone(), via.clone()));
+        assert!(denominator.price > 0, "Cannot derive {}/{}: {}/{} is zero", base, quote, quote, via);
+
+        // (n / 10^dn) / (d / 10^dd), rescaled to 10^dn
+        let scale = pow10(denominator.decimals as u32).expect("decimals bounded by MAX_DECIMALS");
+        let price = mul_div(numerator.price, scale, denominator.price, Rounding::Down)
+            .unwrap_or_else(|| panic!("Cross rate {}/{} overflows", base, quote));
+
+        // Relative errors add up under division
+        let error = relative(price, numerator.error, numerator.price)
+            .saturating_add(relative(price, denominator.error, denominator.price));
+
+        CrossRate {
+            base,
+            quote,
+            price,
+            decimals: numerator.decimals,
+            error,
+            timestamp: numerator.timestamp.min(denominator.timestamp),
+            via: Some(via),
+        }
+    }
+
+    /// Get the inverse of a pair's price, i.e. base units per one quote unit
+    /// (NEAR/USD -> NEAR per USD). The result is scaled by 10^`decimals`, defaulting to the
+    /// base asset's decimals, and rounded down unless another `rounding` is requested
+    pub fn get_inverse_price(
+        &self,
+        pair: Pair,
+        decimals: Option<u8>,
+        rounding: Option<Rounding>,
+    ) -> Price {
+        let price = self.get_price(pair.clone());
+        let decimals = decimals.unwrap_or(price.decimals);
+        assert!(price.price > 0, "Cannot invert a zero price for {}", pair);
+
+        // 1 / (p / 10^d) = 10^d / p, rescaled to 10^decimals
+        let exp = price.decimals as u32 + decimals as u32;
+        assert!(exp <= MAX_POW10, "Requested precision too high for {}", pair);
+        let one = pow10(exp).expect("bounded by MAX_POW10");
+        let inverse = mul_div(one, 1, price.price, rounding.unwrap_or_default())
+            .unwrap_or_else(|| panic!("Inverse price of {} overflows", pair));
+
+        // Same relative uncertainty as the price
+        let confidence = relative(inverse, price.confidence, price.price);
+        Price { price: inverse, decimals, confidence }
+    }
+}
+
+impl PriceOracle {
+    fn leg(&self, pair: &Pair) -> Leg {
+        let price = self.get_price(pair.clone());
+        let reports = self.live_reports(pair);
+
+        Leg {
+            price: price.price,
+            decimals: price.decimals,
+            error: price.confidence,
+            timestamp: reports.iter().map(|r| r.timestamp).min().unwrap_or(0),
+        }
+    }
+
+    /// Quote asset both `base` and `quote` are reported against, preferring USD
+    fn common_quote(&self, base: &AssetId, quote: &AssetId) -> Option<AssetId> {
+        let has = |b: &AssetId, q: &AssetId| self.prices.contains_key(&Pair::new(b.clone(), q.clone()));
+        if has(base, &DEFAULT_QUOTE.to_string()) && has(quote, &DEFAULT_QUOTE.to_string()) {
+            return Some(DEFAULT_QUOTE.to_string());
+        }
+
+        let mut candidates: Vec<AssetId> = self
+            .prices
+            .pairs_of(base)
+            .into_iter()
+            .map(|pair| pair.quote)
+            .filter(|via| has(quote, via))
+            .collect();
+        candidates.sort();
+        candidates.into_iter().next()
+    }
+}
+
+/// `value * error / reference`, i.e. `error` expressed relative to `value`
+fn relative(value: u128, error: u128, reference: u128) -> u128 {
+    if error == 0 || reference == 0 {
+        return 0;
+    }
+    value
+        .checked_mul(error)
+        .map(|scaled| scaled / reference)
+        .unwrap_or(u128::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn setup() -> PriceOracle {
+        testing_env!(VMContextBuilder::new().build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(2);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.add_asset("EUR".to_string(), AssetConfig::new("EUR", "Euro", 6));
+        contract.add_asset("BTC".to_string(), AssetConfig::new("BTC", "Bitcoin", 2));
+        contract
+    }
+
+    fn report(contract: &mut PriceOracle, pair: Pair, prices: &[u128], decimals: u8) {
+        for (i, price) in prices.iter().enumerate() {
+            contract.report_price(pair.clone(), format!("source{}", i), U128(*price), decimals);
+        }
+    }
+
+    #[test]
+    fn test_cross_rate_via_usd() {
+        let mut contract = setup();
+        testing_env!(VMContextBuilder::new().block_timestamp(1_000_000_000).build());
+        report(&mut contract, Pair::usd("EUR"), &[1240000, 1260000], 6);   // 1.25 ± 0.01
+        testing_env!(VMContextBuilder::new().block_timestamp(5_000_000_000).build());
+        report(&mut contract, Pair::usd("NEAR"), &[4900000, 5100000], 6);  // 5.00 ± 0.10
+
+        let rate = contract.get_cross_rate("NEAR".to_string(), "EUR".to_string());
+        assert_eq!(rate.price, 4000000);
+        assert_eq!(rate.decimals, 6);
+        // 4.00 * (0.10 / 5.00 + 0.01 / 1.25) = 0.112
+        assert_eq!(rate.error, 112000);
+        assert_eq!(rate.timestamp, 1000);
+        assert_eq!(rate.via, Some("USD".to_string()));
+    }
+
+    #[test]
+    fn test_cross_rate_mixed_decimals() {
+        let mut contract = setup();
+        report(&mut contract, Pair::usd("NEAR"), &[6000000, 6000000], 6);  // $6.00
+        report(&mut contract, Pair::usd("BTC"), &[6000000, 6000000], 2);   // $60000.00
+
+        let rate = contract.get_cross_rate("NEAR".to_string(), "BTC".to_string());
+        assert_eq!(rate.price, 100);                                        // 0.0001 BTC
+        assert_eq!(rate.decimals, 6);
+        assert_eq!(rate.error, 0);
+    }
+
+    #[test]
+    fn test_cross_rate_prefers_direct_pair() {
+        let mut contract = setup();
+        report(&mut contract, Pair::new("NEAR", "EUR"), &[4100000, 4100000], 6);
+        report(&mut contract, Pair::usd("NEAR"), &[5000000, 5000000], 6);
+        report(&mut contract, Pair::usd("EUR"), &[1250000, 1250000], 6);
+
+        let rate = contract.get_cross_rate("NEAR".to_string(), "EUR".to_string());
+        assert_eq!(rate.price, 4100000);
+        assert_eq!(rate.via, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Need at least 2 price sources for EUR/USD, have 1")]
+    fn test_cross_rate_propagates_leg_errors() {
+        let mut contract = setup();
+        report(&mut contract, Pair::usd("NEAR"), &[5000000, 5000000], 6);
+        report(&mut contract, Pair::usd("EUR"), &[1250000], 6);
+        contract.get_cross_rate("NEAR".to_string(), "EUR".to_string());
+    }
+
+    #[test]
+    fn test_inverse_price_rounding() {
+        let mut contract = setup();
+        report(&mut contract, Pair::usd("NEAR"), &[5250000, 5250000], 6);   // $5.25
+
+        // 1 / 5.25 = 0.19047619...
+        let down = contract.get_inverse_price(Pair::usd("NEAR"), None, None);
+        assert_eq!(down, Price { price: 190476, decimals: 6, confidence: 0 });
+        let up = contract.get_inverse_price(Pair::usd("NEAR"), None, Some(Rounding::Up));
+        assert_eq!(up.price, 190477);
+        let precise = contract.get_inverse_price(Pair::usd("NEAR"), Some(10), Some(Rounding::Nearest));
+        assert_eq!(precise, Price { price: 1904761905, decimals: 10, confidence: 0 });
+    }
+
+    #[test]
+    fn test_inverse_price_confidence() {
+        let mut contract = setup();
+        report(&mut contract, Pair::usd("NEAR"), &[4900000, 5100000], 6);   // $5.00 +- 0.10
+
+        // 0.2 NEAR per USD, +- 2%
+        let inverse = contract.get_inverse_price(Pair::usd("NEAR"), None, None);
+        assert_eq!(inverse, Price { price: 200000, decimals: 6, confidence: 4000 });
+    }
+
+    #[test]
+    fn test_inverse_price_low_decimals_base() {
+        let mut contract = setup();
+        report(&mut contract, Pair::usd("BTC"), &[6000000, 6000000], 2);    // $60000.00
+
+        // 1 / 60000 = 0.0000166..., invisible at BTC's 2 decimals
+        assert_eq!(contract.get_inverse_price(Pair::usd("BTC"), None, None).price, 0);
+        let inverse = contract.get_inverse_price(Pair::usd("BTC"), Some(8), Some(Rounding::Nearest));
+        assert_eq!(inverse, Price { price: 1667, decimals: 8, confidence: 0 });
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot invert a zero price for NEAR/USD")]
+    fn test_inverse_of_zero_price() {
+        let mut contract = setup();
+        report(&mut contract, Pair::usd("NEAR"), &[0, 0], 6);
+        contract.get_inverse_price(Pair::usd("NEAR"), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "No route to derive NEAR/EUR")]
+    fn test_cross_rate_without_route() {
+        let mut contract = setup();
+        report(&mut contract, Pair::usd("NEAR"), &[5000000, 5000000], 6);
+        contract.get_cross_rate("NEAR".to_string(), "EUR".to_string());
+    }
+}
@@ -0,0 +1,257 @@
+use near_sdk::json_types::U128;
+use near_sdk::{env, near, AccountId, Gas, Promise, PromiseError};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::guard::deviation_bps;
+use crate::lp::{ext_ref_exchange, RefPoolInfo, SIMPLE_POOL};
+use crate::math::{pow10, U256};
+use crate::{now_ms, Pair, PriceOracle, PriceOracleExt, PriceReport, Role};
+
+/// Prefix of the source name Ref pool prices are recorded under, e.g. "ref-finance:3"
+pub const REF_SOURCE_PREFIX: &str = "ref-finance";
+
+const GAS_FOR_GET_POOL: Gas = Gas::from_tgas(10);
+const GAS_FOR_ON_REF_PRICE: Gas = Gas::from_tgas(20);
+
+/// Ref Finance simple pool whose spot price is recorded as a source of a pair
+/// A spot price moves with a single swap, so it's only recorded while the pool holds
+/// min_quote_reserve and the price is within max_deviation_bps of the pair's answer
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct RefSource {
+    pub pair: Pair,
+    pub base_token: String,    // Pool token priced, e.g. "wrap.near" for NEAR
+    pub base_decimals: u8,     // FT decimals of the base token
+    pub quote_token: String,   // Pool token it's priced in, e.g. "usdc.near" for USD
+    pub quote_decimals: u8,    // FT decimals of the quote token
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub min_quote_reserve: u128, // Quote tokens the pool must hold, in their FT units
+    pub max_deviation_bps: u16,  // Furthest the spot may be from the pair's last answer
+}
+
+#[near]
+impl PriceOracle {
+    /// Record the spot price of a Ref Finance simple pool as a source of `source.pair`
+    /// (ConfigManager). The quote token stands in for the pair's quote, so pick a
+    /// deep pool against a token that tracks it
+    pub fn add_ref_source(&mut self, pool_id: u64, source: RefSource) {
+        self.assert_role(Role::ConfigManager);
+        self.asset_config(&source.pair.base);
+        for token in [&source.base_token, &source.quote_token] {
+            assert!(token.parse::<AccountId>().is_ok(), "Invalid token id {}", token);
+        }
+        assert!(source.base_token != source.quote_token, "Pool tokens must differ");
+        assert!(source.min_quote_reserve > 0, "A Ref source needs a min_quote_reserve");
+        self.ref_sources.insert(pool_id, source);
+    }
+
+    /// Stop recording a pool's price (ConfigManager); its last report ages out as usual
+    pub fn remove_ref_source(&mut self, pool_id: u64) {
+        self.assert_role(Role::ConfigManager);
+        assert!(self.ref_sources.remove(&pool_id).is_some(), "Unknown Ref source {}", pool_id);
+    }
+
+    /// Get the Ref pools recorded as sources, by pool id
+    pub fn get_ref_sources(&self) -> Vec<(u64, RefSource)> {
+        let mut sources: Vec<(u64, RefSource)> =
+            self.ref_sources.iter().map(|(pool_id, source)| (*pool_id, source.clone())).collect();
+        sources.sort_by_key(|(pool_id, _)| *pool_id);
+        sources
+    }
+
+    /// Read a registered pool and submit its spot price as source "ref-finance:{pool_id}",
+    /// through the same checks as reported prices (Reporter, so keepers can run it)
+    /// A pool too shallow, or priced too far from the pair's answer, is not recorded;
+    /// neither is one whose pair has no answer yet, so a pool never sets a price alone
+    pub fn pull_ref_price(&mut self, pool_id: u64) -> Promise {
+        self.assert_not_paused();
+        self.assert_role(Role::Reporter);
+        self.ref_source(pool_id);
+        ext_ref_exchange::ext(self.ref_exchange_id())
+            .with_static_gas(GAS_FOR_GET_POOL)
+            .get_pool(pool_id)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_ON_REF_PRICE)
+                    .on_ref_price(pool_id),
+            )
+    }
+
+    #[private]
+    pub fn on_ref_price(
+        &mut self,
+        pool_id: u64,
+        #[callback_result] pool: Result<RefPoolInfo, PromiseError>,
+    ) -> U128 {
+        let pool = pool.unwrap_or_else(|_| panic!("Failed to fetch Ref pool {}", pool_id));
+        let source = self.ref_source(pool_id).clone();
+        let decimals = self.asset_config(&source.pair.base).decimals;
+        let price = spot_price(&source, &pool, decimals)
+            .unwrap_or_else(|reason| panic!("Cannot price Ref pool {}: {}", pool_id, reason));
+        let last = self
+            .latest_answer(&source.pair)
+            .unwrap_or_else(|| panic!("Cannot price Ref pool {}: {} has no answer to check it against", pool_id, source.pair));
+        let deviation_bps = deviation_bps(price, last.price);
+        assert!(
+            deviation_bps <= source.max_deviation_bps as u128,
+            "Cannot price Ref pool {}: {} is {} bps from round {} answer {}, {} bps allowed",
+            pool_id,
+            price,
+            deviation_bps,
+            last.round_id,
+            last.price,
+            source.max_deviation_bps
+        );
+
+        let report = PriceReport {
+            source: format!("{}:{}", REF_SOURCE_PREFIX, pool_id),
+            price,
+            decimals,
+            timestamp: now_ms(),
+            reporter: env::current_account_id().to_string(),
+            volume: None,
+            attestation: None,
+        };
+        self.ingest_report(source.pair, report);
+        U128(price)
+    }
+}
+
+impl PriceOracle {
+    fn ref_source(&self, pool_id: u64) -> &RefSource {
+        self.ref_sources
+            .get(&pool_id)
+            .unwrap_or_else(|| panic!("Unknown Ref source {}", pool_id))
+    }
+}
+
+/// Quote tokens per base token in a simple pool, scaled by 10^decimals
+fn spot_price(source: &RefSource, pool: &RefPoolInfo, decimals: u8) -> Result<u128, &'static str> {
+    if pool.pool_kind != SIMPLE_POOL {
+        return Err("not a simple pool");
+    }
+    if pool.amounts.len() != pool.token_account_ids.len() {
+        return Err("malformed reserves");
+    }
+    let reserve = |token: &str| {
+        pool.token_account_ids
+            .iter()
+            .position(|id| id.as_str() == token)
+            .map(|index| pool.amounts[index].0)
+            .ok_or("token not in pool")
+    };
+    let (base, quote) = (reserve(&source.base_token)?, reserve(&source.quote_token)?);
+    if base == 0 || quote == 0 {
+        return Err("no liquidity");
+    }
+    if quote < source.min_quote_reserve {
+        return Err("pool holds less than its min_quote_reserve");
+    }
+
+    let scale = |d: u8| U256::from(pow10(d as u32).expect("decimals bounded"));
+    let price = U256::from(quote) * scale(source.base_decimals) * scale(decimals)
+        / (U256::from(base) * scale(source.quote_decimals));
+    if price > U256::from(u128::MAX) {
+        return Err("price overflows");
+    }
+    Ok(price.as_u128())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn setup() -> PriceOracle {
+        testing_env!(VMContextBuilder::new().build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.set_ref_exchange("v2.ref-finance.near".parse().unwrap());
+        contract.add_ref_source(3, near_usdc());
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5200000), 6);
+        contract
+    }
+
+    fn near_usdc() -> RefSource {
+        RefSource {
+            pair: Pair::usd("NEAR"),
+            base_token: "wrap.near".to_string(),
+            base_decimals: 24,
+            quote_token: "usdc.near".to_string(),
+            quote_decimals: 6,
+            min_quote_reserve: 1000 * 10u128.pow(6),
+            max_deviation_bps: 500,
+        }
+    }
+
+    fn pool(near: u128, usdc: u128) -> RefPoolInfo {
+        RefPoolInfo {
+            pool_kind: SIMPLE_POOL.to_string(),
+            token_account_ids: vec!["usdc.near".parse().unwrap(), "wrap.near".parse().unwrap()],
+            amounts: vec![U128(usdc), U128(near)],
+            shares_total_supply: U128(1),
+        }
+    }
+
+    #[test]
+    fn test_ref_price_recorded_as_source() {
+        let mut contract = setup();
+        // 1000 NEAR against 5250 USDC
+        let price = contract.on_ref_price(3, Ok(pool(1000 * 10u128.pow(24), 5250 * 10u128.pow(6))));
+        assert_eq!(price, U128(5250000));
+        let reports = contract.get_price_details(Pair::usd("NEAR"));
+        let report = reports.iter().find(|report| report.source == "ref-finance:3").unwrap();
+        assert_eq!(report.price, 5250000);
+        assert_eq!(report.reporter, env::current_account_id().to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot price Ref pool 3: pool holds less than its min_quote_reserve")]
+    fn test_shallow_pool_not_recorded() {
+        let mut contract = setup();
+        contract.on_ref_price(3, Ok(pool(100 * 10u128.pow(24), 525 * 10u128.pow(6))));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot price Ref pool 3: 7000000 is 3461 bps from round 1 answer 5200000, 500 bps allowed")]
+    fn test_skewed_pool_not_recorded() {
+        let mut contract = setup();
+        // A swap pushed the pool to 7 USDC per NEAR
+        contract.on_ref_price(3, Ok(pool(1000 * 10u128.pow(24), 7000 * 10u128.pow(6))));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot price Ref pool 3: NEAR/USD has no answer to check it against")]
+    fn test_pool_needs_answer() {
+        testing_env!(VMContextBuilder::new().build());
+        let mut contract = PriceOracle::for_tests();
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.set_ref_exchange("v2.ref-finance.near".parse().unwrap());
+        contract.add_ref_source(3, near_usdc());
+        contract.on_ref_price(3, Ok(pool(1000 * 10u128.pow(24), 5250 * 10u128.pow(6))));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot price Ref pool 3: no liquidity")]
+    fn test_empty_pool_not_recorded() {
+        let mut contract = setup();
+        contract.on_ref_price(3, Ok(pool(0, 0)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to fetch Ref pool 3")]
+    fn test_failed_pool_fetch() {
+        let mut contract = setup();
+        contract.on_ref_price(3, Err(PromiseError::Failed));
+    }
+}
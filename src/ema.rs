@@ -0,0 +1,131 @@
+use near_sdk::near;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::math::{half_life_decay, DECAY_ONE, U256};
+use crate::{now_ms, Pair, Price, PriceOracle, PriceOracleExt, Role};
+
+/// Half-life used until the owner configures one
+pub const DEFAULT_EMA_HALF_LIFE_SECONDS: u64 = 300;
+
+/// Smoothed aggregate of a pair
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct EmaState {
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub price: u128,
+    pub last_update: u64,
+}
+
+#[near]
+impl PriceOracle {
+    /// Get the exponential moving average of a pair's aggregate price, where an
+    /// observation's weight halves every half-life
+    /// The confidence is that of the current aggregate
+    pub fn get_ema_price(&self, pair: Pair) -> Price {
+        self.assert_not_paused();
+        let ema = self
+            .ema
+            .get(&pair)
+            .unwrap_or_else(|| panic!("No EMA recorded for {}", pair));
+        Price {
+            price: ema.price,
+            decimals: self.asset_config(&pair.base).decimals,
+            confidence: self.try_price(&pair).map(|p| p.confidence).unwrap_or(0),
+        }
+    }
+
+    /// Get the EMA half-life in seconds
+    pub fn get_ema_half_life(&self) -> u64 {
+        self.ema_half_life
+    }
+
+    /// Set the EMA half-life in seconds (ConfigManager)
+    /// Longer half-lives smooth more but follow real moves more slowly
+    pub fn set_ema_half_life(&mut self, half_life_seconds: u64) {
+        self.assert_role(Role::ConfigManager);
+        assert!(half_life_seconds > 0, "Half-life must be positive");
+        self.ema_half_life = half_life_seconds;
+    }
+}
+
+impl PriceOracle {
+    /// Folds the latest aggregate into the pair's EMA
+    pub(crate) fn track_ema(&mut self, pair: &Pair) {
+        let Some(price) = self.try_price(pair) else {
+            return;
+        };
+        let now = now_ms();
+        let half_life = self.ema_half_life * 1000;
+
+        let ema = self.ema.entry(pair.clone()).or_insert(EmaState { price: price.price, last_update: now });
+        let decay = half_life_decay(now.saturating_sub(ema.last_update), half_life);
+        let smoothed = (U256::from(ema.price) * U256::from(decay)
+            + U256::from(price.price) * U256::from(DECAY_ONE - decay))
+            / U256::from(DECAY_ONE);
+
+        ema.price = smoothed.as_u128();
+        ema.last_update = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn at(seconds: u64) {
+        testing_env!(VMContextBuilder::new().block_timestamp(seconds * 1_000_000_000).build());
+    }
+
+    fn setup() -> PriceOracle {
+        at(1000);
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.set_ema_half_life(60);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract
+    }
+
+    fn report(contract: &mut PriceOracle, price: u128) {
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(price), 6);
+    }
+
+    #[test]
+    fn test_ema_halves_toward_new_price() {
+        let mut contract = setup();
+        report(&mut contract, 4000000);
+        assert_eq!(contract.get_ema_price(Pair::usd("NEAR")).price, 4000000);
+
+        at(1060);
+        report(&mut contract, 6000000);                 // One half-life later
+        assert_eq!(contract.get_ema_price(Pair::usd("NEAR")).price, 5000000);
+
+        at(1180);
+        report(&mut contract, 6000000);                 // Two more: gap shrinks to 1/4
+        assert_eq!(contract.get_ema_price(Pair::usd("NEAR")).price, 5750000);
+    }
+
+    #[test]
+    fn test_same_block_reports_do_not_move_ema() {
+        let mut contract = setup();
+        report(&mut contract, 4000000);
+        contract.report_price(Pair::usd("NEAR"), "coingecko".to_string(), U128(8000000), 6);
+        assert_eq!(contract.get_ema_price(Pair::usd("NEAR")).price, 4000000);
+    }
+
+    #[test]
+    #[should_panic(expected = "No EMA recorded for NEAR/USD")]
+    fn test_ema_requires_reports() {
+        let contract = setup();
+        contract.get_ema_price(Pair::usd("NEAR"));
+    }
+}
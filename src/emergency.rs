@@ -0,0 +1,146 @@
+use near_sdk::json_types::U128;
+use near_sdk::near;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::{now_ms, AssetId, OracleEvent, Pair, PriceOracle, PriceOracleExt, PriceResponse, DEFAULT_QUOTE};
+
+/// Longest an override may last, so an emergency price can't quietly become the permanent one
+pub const MAX_OVERRIDE_SECONDS: u64 = 24 * 60 * 60;
+
+/// USD price the owner set for an asset while its sources are out
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct PriceOverride {
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub price: u128,           // Scaled by the asset's decimals
+    pub decimals: u8,
+    pub set_at: u64,
+    pub expires_at: u64,       // Unix ms after which the aggregated answer is served again
+}
+
+impl PriceOverride {
+    fn is_active(&self) -> bool {
+        now_ms() < self.expires_at
+    }
+}
+
+#[near]
+impl PriceOracle {
+    /// Serve `price` as an asset's USD price from get_price and get_prices until
+    /// `expires_at` (Unix ms, at most MAX_OVERRIDE_SECONDS ahead), during a source outage
+    /// (owner only). Responses carry is_override; replaces any earlier override
+    pub fn set_override_price(&mut self, asset_id: AssetId, price: U128, expires_at: u64) {
+        self.assert_owner();
+        let decimals = self.asset_config(&asset_id).decimals;
+        assert!(price.0 > 0, "Override price must be positive");
+        let now = now_ms();
+        assert!(expires_at > now, "Override must expire after {}, got {}", now, expires_at);
+        assert!(
+            expires_at - now <= MAX_OVERRIDE_SECONDS * 1000,
+            "Overrides last at most {}s",
+            MAX_OVERRIDE_SECONDS
+        );
+        near_sdk::log!("Price of {} overridden to {} until {}", asset_id, price.0, expires_at);
+        OracleEvent::PriceOverridden { asset_id: asset_id.clone(), price: price.0, expires_at }.emit();
+        self.price_overrides.insert(asset_id, PriceOverride { price: price.0, decimals, set_at: now, expires_at });
+    }
+
+    /// End an asset's override before it expires (owner only)
+    pub fn clear_override_price(&mut self, asset_id: AssetId) {
+        self.assert_owner();
+        let cleared = self.price_overrides.remove(&asset_id).is_some_and(|price_override| price_override.is_active());
+        assert!(cleared, "No price override for {}", asset_id);
+        near_sdk::log!("Price override of {} cleared", asset_id);
+    }
+
+    /// Get an asset's override while it's in effect
+    pub fn get_override_price(&self, asset_id: AssetId) -> Option<PriceOverride> {
+        self.price_overrides.get(&asset_id).filter(|price_override| price_override.is_active()).cloned()
+    }
+}
+
+impl PriceOracle {
+    /// The in-effect override of a USD pair as get_price serves it
+    pub(crate) fn override_response(&self, pair: &Pair) -> Option<PriceResponse> {
+        if pair.quote != DEFAULT_QUOTE {
+            return None;
+        }
+        let price_override = self.price_overrides.get(&pair.base).filter(|price_override| price_override.is_active())?;
+        Some(PriceResponse {
+            price: price_override.price,
+            decimals: price_override.decimals,
+            timestamp: price_override.set_at,
+            n_sources: self.live_source_count(pair),
+            sources_used: 0,
+            confidence: 0,
+            is_override: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AssetConfig, PriceResult};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn at(seconds: u64) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(accounts(0))
+            .block_timestamp(seconds * 1_000_000_000)
+            .build());
+    }
+
+    fn setup() -> PriceOracle {
+        at(1000);
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(2);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract
+    }
+
+    #[test]
+    fn test_override_served_until_expiry() {
+        let mut contract = setup();
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6); // Short of sources
+        contract.set_override_price("NEAR".to_string(), U128(5100000), 1_600_000);
+
+        let response = contract.get_price(Pair::usd("NEAR"));
+        assert_eq!((response.price, response.n_sources, response.sources_used), (5100000, 1, 0));
+        assert!(response.is_override);
+        match &contract.get_prices(vec!["NEAR".to_string()])["NEAR"] {
+            PriceResult::Price(response) => assert!(response.is_override),
+            result => panic!("Expected the override, got {:?}", result),
+        }
+
+        // Back to the sources once it expires
+        at(1600);
+        assert_eq!(contract.get_override_price("NEAR".to_string()), None);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        contract.report_price(Pair::usd("NEAR"), "kraken".to_string(), U128(5200000), 6);
+        let response = contract.get_price(Pair::usd("NEAR"));
+        assert_eq!((response.price, response.is_override), (5100000, false));
+    }
+
+    #[test]
+    #[should_panic(expected = "Overrides last at most 86400s")]
+    fn test_override_cannot_outlast_max() {
+        let mut contract = setup();
+        contract.set_override_price("NEAR".to_string(), U128(5100000), 1_000_000 + 86_401_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner")]
+    fn test_override_owner_only() {
+        let mut contract = setup();
+        testing_env!(VMContextBuilder::new().predecessor_account_id(accounts(1)).build());
+        contract.set_override_price("NEAR".to_string(), U128(5100000), 1_600_000);
+    }
+}
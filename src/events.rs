@@ -0,0 +1,145 @@
+use near_sdk::near;
+
+use crate::{AlertThreshold, ConfigChange, Pair, Price, PriceReport, RejectReason, RoundAnswer};
+
+/// NEP-297 events of the oracle, logged as `EVENT_JSON:{"standard":"price-oracle",...}`
+/// Version 2.0.0 of each writes prices and volumes as decimal strings
+#[near(event_json(standard = "price-oracle"))]
+pub enum OracleEvent {
+    /// A report passed the checks and was submitted to its pair's round
+    #[event_version("2.0.0")]
+    PriceReported { pair: Pair, report: PriceReport },
+
+    /// A report was turned away; `code` says why for monitoring, `reason` in words
+    #[event_version("2.0.0")]
+    ReportRejected {
+        pair: Pair,
+        source: String,
+        reporter: String,
+        #[serde(with = "crate::json::u128_string")]
+        price: u128,
+        code: RejectReason,
+        reason: String,
+    },
+
+    /// A round finalized into the pair's new answer
+    #[event_version("2.0.0")]
+    RoundFinalized { pair: Pair, answer: RoundAnswer },
+
+    /// A finalized answer crossed one of its asset's alert thresholds
+    #[event_version("2.0.0")]
+    ThresholdCrossed {
+        pair: Pair,
+        threshold: AlertThreshold,
+        round_id: u64,
+        #[serde(with = "crate::json::u128_string")]
+        previous_price: u128,
+        #[serde(with = "crate::json::u128_string")]
+        price: u128,
+    },
+
+    /// A finalized answer tripped its pair's circuit breaker; reads fail until it's reset
+    #[event_version("2.0.0")]
+    BreakerTripped { pair: Pair, round_id: u64, reason: String },
+
+    /// The owner set an emergency USD price for an asset, served until `expires_at`
+    #[event_version("2.0.0")]
+    PriceOverridden {
+        asset_id: String,
+        #[serde(with = "crate::json::u128_string")]
+        price: u128,
+        expires_at: u64,
+    },
+
+    /// A reporter's stake was slashed for a submission `deviation_bps` from the round answer
+    #[event_version("2.0.0")]
+    ReporterSlashed {
+        pair: Pair,
+        reporter: String,
+        round_id: u64,
+        #[serde(with = "crate::json::u128_string")]
+        amount: u128,
+        #[serde(with = "crate::json::u128_string")]
+        deviation_bps: u128,
+    },
+
+    /// A consumer asked reporters for a one-off price
+    #[event_version("2.0.0")]
+    PriceRequested { request_id: u64, pair: Pair, requester: String },
+
+    /// Enough reporters answered a request for its price to be aggregated
+    #[event_version("2.0.0")]
+    RequestFulfilled { request_id: u64, pair: Pair, price: Price },
+
+    /// A parameter change was scheduled to apply once the timelock passes `eta`
+    #[event_version("2.0.0")]
+    ConfigChangeScheduled { change_id: u64, change: ConfigChange, eta: u64 },
+
+    /// A pair's feed was flagged for missing its heartbeat since the answer at `finalized_at`
+    #[event_version("2.0.0")]
+    StaleFeedFlagged {
+        pair: Pair,
+        flagged_by: String,
+        finalized_at: u64,
+        heartbeat_seconds: u64,
+        #[serde(with = "crate::json::u128_string")]
+        bounty: u128,
+    },
+
+    /// The proposed owner accepted ownership of the contract
+    #[event_version("2.0.0")]
+    OwnershipTransferred { previous_owner: String, new_owner: String },
+}
+
+impl OracleEvent {
+    /// report_rejected for a report of `reporter` turned away with `code`
+    pub(crate) fn rejected(pair: &Pair, source: &str, reporter: &str, price: u128, code: RejectReason, reason: &str) -> Self {
+        OracleEvent::ReportRejected {
+            pair: pair.clone(),
+            source: source.to_string(),
+            reporter: reporter.to_string(),
+            price,
+            code,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AssetConfig, Pair, PriceBounds, PriceOracle};
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    #[test]
+    fn test_report_and_round_events() {
+        testing_env!(VMContextBuilder::new().block_timestamp(1_000_000_000).block_height(7).build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+
+        let pair = r#"{"base":"NEAR","quote":"USD"}"#;
+        assert!(get_logs().contains(&format!(
+            r#"EVENT_JSON:{{"standard":"price-oracle","version":"2.0.0","event":"price_reported","data":{{"pair":{},"report":{{"source":"binance","price":"5000000","decimals":6,"timestamp":1000,"reporter":"bob.near","volume":null}}}}}}"#,
+            pair
+        )));
+        assert!(get_logs().contains(&format!(
+            r#"EVENT_JSON:{{"standard":"price-oracle","version":"2.0.0","event":"round_finalized","data":{{"pair":{},"answer":{{"round_id":1,"price":"5000000","decimals":6,"confidence":"0","submissions":1,"started_at":1000,"finalized_at":1000,"finalized_at_block":7}}}}}}"#,
+            pair
+        )));
+    }
+
+    #[test]
+    fn test_report_rejected_event_carries_code() {
+        testing_env!(VMContextBuilder::new().block_timestamp(1_000_000_000).build());
+        let mut contract = PriceOracle::for_tests();
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.set_price_bounds("NEAR".to_string(), Some(PriceBounds { min: 100000, max: 1000000000 }));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(50), 6);
+
+        assert!(get_logs().contains(&r#"EVENT_JSON:{"standard":"price-oracle","version":"2.0.0","event":"report_rejected","data":{"pair":{"base":"NEAR","quote":"USD"},"source":"binance","reporter":"bob.near","price":"50","code":"OutOfBounds","reason":"outside bounds [100000, 1000000000]"}}"#.to_string()));
+        assert!(!get_logs().iter().any(|log| log.contains("price_reported")));
+    }
+}
@@ -0,0 +1,315 @@
+use near_sdk::json_types::U128;
+use near_sdk::{env, near, AccountId, NearToken, Promise};
+
+use crate::reporter::reporter_account;
+use crate::subscription::balance_entry_cost;
+use crate::{AssetId, Pair, PriceOracle, PriceOracleExt, PriceResponse, Role};
+
+#[near]
+impl PriceOracle {
+    /// Get the latest answer of a pair as a fee-backed query: the base asset's query
+    /// fee must be attached, and any surplus is refunded
+    /// get_price stays free; this path is for consumers that need the fee-backed SLA
+    #[payable]
+    pub fn get_price_paid(&mut self, pair: Pair) -> PriceResponse {
+        let fee = self.get_query_fee(pair.base.clone()).0;
+        let attached = env::attached_deposit().as_yoctonear();
+        assert!(attached >= fee, "Attach {} yoctoNEAR to query {}, got {}", fee, pair, attached);
+        let price = self.get_price(pair.clone());
+
+        self.collect_fee(&pair.base, fee);
+        if attached > fee {
+            Promise::new(env::predecessor_account_id())
+                .transfer(NearToken::from_yoctonear(attached - fee))
+                .detach();
+        }
+        price
+    }
+
+    /// Set the deposit every report must attach, in yoctoNEAR (ConfigManager)
+    /// It's refunded for accepted and quarantined reports and kept in the reward pool
+    /// for rejected ones, so flooding the oracle with junk costs the sender
+    pub fn set_report_deposit(&mut self, deposit: U128) {
+        self.assert_role(Role::ConfigManager);
+        self.report_deposit = deposit.0;
+    }
+
+    /// Get the deposit every report must attach, in yoctoNEAR
+    pub fn get_report_deposit(&self) -> U128 {
+        U128(self.report_deposit)
+    }
+
+    /// Prepay report deposits and storage for an account, the caller unless `account_id`
+    /// is set. Reports draw on it for whatever they don't attach, so a reporter can send
+    /// them through a NEP-366 relayer without attaching a deposit. The deposit opening a
+    /// credit must cover its storage, like one opening a balance (see
+    /// get_min_subscription_deposit)
+    #[payable]
+    pub fn deposit_report_credit(&mut self, account_id: Option<AccountId>) {
+        let amount = env::attached_deposit().as_yoctonear();
+        assert!(amount > 0, "Attach the credit to deposit");
+        let account_id = account_id.unwrap_or_else(reporter_account);
+        if !self.report_credits.contains_key(&account_id) {
+            let min = balance_entry_cost(&account_id);
+            assert!(amount >= min, "Attach at least {} yoctoNEAR to open a report credit for {}", min, account_id);
+        }
+        *self.report_credits.entry(account_id.clone()).or_default() += amount;
+        near_sdk::log!("Report credit of {} topped up by {}", account_id, amount);
+    }
+
+    /// Withdraw the caller's report credit, all of it unless `amount` is set
+    pub fn withdraw_report_credit(&mut self, amount: Option<U128>) -> Promise {
+        let caller = reporter_account();
+        let credit = self.get_report_credit(caller.clone()).0;
+        let amount = amount.map(|amount| amount.0).unwrap_or(credit);
+        assert!(amount > 0 && amount <= credit, "Cannot withdraw {} of a {} report credit", amount, credit);
+        self.set_report_credit(&caller, credit - amount);
+        Promise::new(caller).transfer(NearToken::from_yoctonear(amount))
+    }
+
+    /// Get an account's prepaid report credit, in yoctoNEAR
+    pub fn get_report_credit(&self, account_id: AccountId) -> U128 {
+        U128(self.report_credits.get(&account_id).copied().unwrap_or(0))
+    }
+
+    /// Set the fee of a paid query of an asset's pairs, in yoctoNEAR (ConfigManager)
+    /// A fee of 0 makes the paid path free
+    pub fn set_query_fee(&mut self, asset_id: AssetId, fee: U128) {
+        self.assert_role(Role::ConfigManager);
+        self.asset_config(&asset_id);
+        if fee.0 == 0 {
+            self.query_fees.remove(&asset_id);
+        } else {
+            self.query_fees.insert(asset_id, fee.0);
+        }
+    }
+
+    /// Get the fee of a paid query of an asset's pairs, in yoctoNEAR
+    pub fn get_query_fee(&self, asset_id: AssetId) -> U128 {
+        U128(self.query_fees.get(&asset_id).copied().unwrap_or(0))
+    }
+
+    /// Get the query fees collected for an asset and not yet withdrawn, in yoctoNEAR
+    pub fn get_collected_fees(&self, asset_id: AssetId) -> U128 {
+        U128(self.collected_fees.get(&asset_id).copied().unwrap_or(0))
+    }
+
+    /// Send collected fees of an asset to the owner, all of them unless `amount` is set
+    /// (owner only)
+    pub fn withdraw_fees(&mut self, asset_id: AssetId, amount: Option<U128>) -> Promise {
+        self.assert_owner();
+        let amount = self.take_fees(&asset_id, amount.map(|amount| amount.0));
+        near_sdk::log!("Fees of {} withdrawn: {}", asset_id, amount);
+        Promise::new(self.owner.clone()).transfer(NearToken::from_yoctonear(amount))
+    }
+
+    /// Move collected fees of an asset into the reporter reward pool, all of them
+    /// unless `amount` is set (owner only)
+    pub fn fees_to_rewards(&mut self, asset_id: AssetId, amount: Option<U128>) {
+        self.assert_owner();
+        let amount = self.take_fees(&asset_id, amount.map(|amount| amount.0));
+        self.add_to_reward_pool(amount);
+        near_sdk::log!("Fees of {} moved to the reward pool: {}", asset_id, amount);
+    }
+}
+
+impl PriceOracle {
+    pub(crate) fn collect_fee(&mut self, asset_id: &AssetId, fee: u128) {
+        if fee > 0 {
+            *self.collected_fees.entry(asset_id.clone()).or_default() += fee;
+        }
+    }
+
+    /// Panics unless the report deposit of `reports` reports is attached or covered by
+    /// the caller's report credit
+    pub(crate) fn assert_report_deposit(&self, reports: u128) {
+        let attached = env::attached_deposit().as_yoctonear();
+        let credit = self.get_report_credit(reporter_account()).0;
+        let required = self.report_deposit * reports;
+        assert!(attached + credit >= required, "Attach {} yoctoNEAR to report, got {}", required, attached + credit);
+    }
+
+    /// Refunds the attached deposit of the caller's reports, keeping the report deposit
+    /// of the `rejected` ones and the cost of any storage the caller's reports grew by
+    pub(crate) fn settle_report_deposit(&mut self, rejected: u128) {
+        self.settle_relayed_deposit(rejected, &[reporter_account()]);
+    }
+
+    /// settle_report_deposit for reports of `reporters` the caller relayed: the caller's
+    /// deposit also covers any storage the reporters' reports grew by, while the storage
+    /// deposit a reporter holds beyond its reports' cost stays with that reporter
+    /// The attached deposit is spent before the caller's report credit, and refunds go to
+    /// the caller, i.e. a reporter behind a NEP-366 relayer rather than the relayer
+    pub(crate) fn settle_relayed_deposit(&mut self, rejected: u128, reporters: &[AccountId]) {
+        let caller = reporter_account();
+        let credit = self.get_report_credit(caller.clone()).0;
+        let mut available = env::attached_deposit().as_yoctonear() + credit;
+        let kept = self.report_deposit * rejected;
+        if kept > 0 {
+            available -= kept;
+            self.add_to_reward_pool(kept);
+            near_sdk::log!("Report deposit of {} kept", kept);
+        }
+        for reporter in reporters {
+            available = if *reporter == caller {
+                self.settle_storage(reporter, available)
+            } else {
+                self.cover_storage(reporter, available)
+            };
+        }
+        let credit_left = available.min(credit);
+        self.set_report_credit(&caller, credit_left);
+        let refund = available - credit_left;
+        if refund > 0 {
+            Promise::new(caller).transfer(NearToken::from_yoctonear(refund)).detach();
+        }
+    }
+
+    fn take_fees(&mut self, asset_id: &AssetId, amount: Option<u128>) -> u128 {
+        let collected = self.get_collected_fees(asset_id.clone()).0;
+        let amount = amount.unwrap_or(collected);
+        assert!(
+            amount > 0 && amount <= collected,
+            "Cannot take {} of {} fees collected for {}",
+            amount,
+            collected,
+            asset_id
+        );
+        if amount == collected {
+            self.collected_fees.remove(asset_id);
+        } else {
+            self.collected_fees.insert(asset_id.clone(), collected - amount);
+        }
+        amount
+    }
+
+    fn set_report_credit(&mut self, account_id: &AccountId, credit: u128) {
+        if credit == 0 {
+            self.report_credits.remove(account_id);
+        } else {
+            self.report_credits.insert(account_id.clone(), credit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+    use near_sdk::AccountId;
+
+    const FEE: u128 = 10u128.pow(21);
+
+    fn call(account_id: AccountId, deposit: u128) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id)
+            .attached_deposit(NearToken::from_yoctonear(deposit))
+            .build());
+    }
+
+    fn setup() -> PriceOracle {
+        call(accounts(0), 0);
+        let mut contract = PriceOracle::new(accounts(0), 1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        contract.set_query_fee("NEAR".to_string(), U128(FEE));
+        contract
+    }
+
+    #[test]
+    fn test_paid_query_collects_fee() {
+        let mut contract = setup();
+        call(accounts(1), FEE * 3);
+        assert_eq!(contract.get_price_paid(Pair::usd("NEAR")).price, 5000000);
+        assert_eq!(contract.get_collected_fees("NEAR".to_string()).0, FEE);  // Surplus refunded
+
+        call(accounts(0), 0);
+        contract.fees_to_rewards("NEAR".to_string(), Some(U128(FEE / 4)));
+        contract.withdraw_fees("NEAR".to_string(), None).detach();
+        assert_eq!(contract.get_reward_pool().0, FEE / 4);
+        assert_eq!(contract.get_collected_fees("NEAR".to_string()).0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Attach 1000000000000000000000 yoctoNEAR to query NEAR/USD, got 1")]
+    fn test_paid_query_requires_fee() {
+        let mut contract = setup();
+        call(accounts(1), 1);
+        contract.get_price_paid(Pair::usd("NEAR"));
+    }
+
+    #[test]
+    fn test_rejected_report_keeps_deposit() {
+        let mut contract = setup();
+        contract.set_report_deposit(U128(FEE));
+        contract.set_price_bounds("NEAR".to_string(), Some(crate::PriceBounds { min: 1000000, max: 9000000 }));
+
+        call(accounts(0), FEE);
+        contract.report_price(Pair::usd("NEAR"), "coingecko".to_string(), U128(5100000), 6);
+        assert_eq!(contract.get_reward_pool().0, 0);
+        contract.report_price(Pair::usd("NEAR"), "kraken".to_string(), U128(90000000), 6);
+        assert_eq!(contract.get_reward_pool().0, FEE);
+    }
+
+    #[test]
+    #[should_panic(expected = "Attach 1000000000000000000000 yoctoNEAR to report, got 0")]
+    fn test_report_requires_deposit() {
+        let mut contract = setup();
+        contract.set_report_deposit(U128(FEE));
+        contract.report_price(Pair::usd("NEAR"), "coingecko".to_string(), U128(5100000), 6);
+    }
+
+    #[test]
+    fn test_relayed_report_draws_on_credit() {
+        let mut contract = setup();
+        contract.set_report_deposit(U128(FEE));
+        contract.set_price_bounds("NEAR".to_string(), Some(crate::PriceBounds { min: 1000000, max: 9000000 }));
+        call(accounts(3), FEE * 3);
+        contract.deposit_report_credit(Some(accounts(0)));
+
+        // A delegate action: the relayer signs, the reporter is the predecessor
+        testing_env!(VMContextBuilder::new()
+            .signer_account_id(accounts(3))
+            .predecessor_account_id(accounts(0))
+            .build());
+        contract.report_price(Pair::usd("NEAR"), "coingecko".to_string(), U128(5100000), 6);
+        contract.report_price(Pair::usd("NEAR"), "kraken".to_string(), U128(90000000), 6);
+        assert_eq!(contract.get_report_credit(accounts(0)).0, FEE * 2);
+        assert_eq!(contract.get_report_credit(accounts(3)).0, 0);
+        assert_eq!(contract.get_price_details(Pair::usd("NEAR"))[1].reporter, "alice");
+
+        contract.withdraw_report_credit(Some(U128(FEE))).detach();
+        assert_eq!(contract.get_report_credit(accounts(0)).0, FEE);
+    }
+
+    #[test]
+    fn test_attached_deposit_spent_before_credit() {
+        let mut contract = setup();
+        contract.set_report_deposit(U128(FEE));
+        contract.set_price_bounds("NEAR".to_string(), Some(crate::PriceBounds { min: 1000000, max: 9000000 }));
+        call(accounts(0), FEE);
+        contract.deposit_report_credit(None);
+        contract.report_price(Pair::usd("NEAR"), "kraken".to_string(), U128(90000000), 6);
+        assert_eq!(contract.get_report_credit(accounts(0)).0, FEE);
+        assert_eq!(contract.get_reward_pool().0, FEE);
+    }
+
+    #[test]
+    #[should_panic(expected = "Attach at least 630000000000000000000 yoctoNEAR to open a report credit for charlie")]
+    fn test_dust_cannot_open_report_credit() {
+        let mut contract = setup();
+        call(accounts(3), 1);
+        contract.deposit_report_credit(Some(accounts(2)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner")]
+    fn test_withdraw_fees_owner_only() {
+        let mut contract = setup();
+        call(accounts(1), FEE);
+        contract.get_price_paid(Pair::usd("NEAR"));
+        contract.withdraw_fees("NEAR".to_string(), None).detach();
+    }
+}
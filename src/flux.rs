@@ -0,0 +1,228 @@
+use near_sdk::{env, ext_contract, near, AccountId, Gas, Promise, PromiseError};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, U64};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::math::{mul_div, pow10, Rounding};
+use crate::{Pair, PriceOracle, PriceOracleExt, PriceReport, Role};
+
+/// Prefix of the source name Flux prices are recorded under, e.g. "flux:provider.near"
+pub const FLUX_SOURCE_PREFIX: &str = "flux";
+
+const GAS_FOR_GET_ENTRY: Gas = Gas::from_tgas(10);
+const GAS_FOR_ON_FLUX_PRICE: Gas = Gas::from_tgas(20);
+
+/// Flux First-Party Oracle provider feed mixed into a pair as its own source
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct FluxFeed {
+    pub flux_pair: String,     // Pair name on the FPO contract, e.g. "NEAR/USD"
+    pub provider: String,      // Account that pushes the pair to the FPO contract
+}
+
+/// Subset of a Flux FPO `get_entry` response we rely on
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "abi", derive(JsonSchema))]
+pub struct FluxPriceEntry {
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub price: U128,
+    pub decimals: u16,
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub last_update: U64,      // Nanoseconds
+}
+
+#[allow(dead_code)]
+#[ext_contract(ext_flux_oracle)]
+trait FluxOracle {
+    fn get_entry(&self, pair: String, provider: AccountId) -> Option<FluxPriceEntry>;
+}
+
+#[near]
+impl PriceOracle {
+    /// Set the Flux First-Party Oracle contract feeds are read from (ConfigManager)
+    pub fn set_flux_oracle(&mut self, oracle_id: AccountId) {
+        self.assert_role(Role::ConfigManager);
+        self.flux_oracle = Some(oracle_id.to_string());
+    }
+
+    /// Read a Flux provider's feed into a pair as source "flux:{provider}" (ConfigManager)
+    pub fn add_flux_feed(&mut self, pair: Pair, feed: FluxFeed) {
+        self.assert_role(Role::ConfigManager);
+        self.asset_config(&pair.base);
+        assert!(feed.provider.parse::<AccountId>().is_ok(), "Invalid provider {}", feed.provider);
+        self.flux_feeds.insert(pair, feed);
+    }
+
+    /// Stop reading a pair's Flux feed (ConfigManager)
+    pub fn remove_flux_feed(&mut self, pair: Pair) {
+        self.assert_role(Role::ConfigManager);
+        assert!(self.flux_feeds.remove(&pair).is_some(), "No Flux feed for {}", pair);
+    }
+
+    /// Get the Flux feed read into a pair
+    pub fn get_flux_feed(&self, pair: Pair) -> Option<FluxFeed> {
+        self.flux_feeds.get(&pair).cloned()
+    }
+
+    /// Read a pair's Flux feed and submit it through the same checks as reported prices
+    /// (Reporter, so keepers can run it)
+    pub fn pull_flux_price(&mut self, pair: Pair) -> Promise {
+        self.assert_not_paused();
+        self.assert_role(Role::Reporter);
+        let feed = self.flux_feed(&pair).clone();
+        let oracle: AccountId = self
+            .flux_oracle
+            .as_ref()
+            .expect("Flux oracle not configured")
+            .parse()
+            .expect("invalid oracle id");
+        ext_flux_oracle::ext(oracle)
+            .with_static_gas(GAS_FOR_GET_ENTRY)
+            .get_entry(feed.flux_pair, feed.provider.parse().expect("validated on registration"))
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_ON_FLUX_PRICE)
+                    .on_flux_price(pair),
+            )
+    }
+
+    /// Records the entry read by pull_flux_price, or logs why it was skipped: the call
+    /// failed, the provider has no entry, it hasn't updated since the last read, or its
+    /// decimals can't be mapped. An entry dated ahead of the block is rejected like any
+    /// future-dated report. Returns the price recorded
+    #[private]
+    pub fn on_flux_price(
+        &mut self,
+        pair: Pair,
+        #[callback_result] entry: Result<Option<FluxPriceEntry>, PromiseError>,
+    ) -> Option<U128> {
+        let entry = match entry {
+            Ok(Some(entry)) => entry,
+            Ok(None) => {
+                near_sdk::log!("Flux has no entry for {}", pair);
+                return None;
+            }
+            Err(_) => {
+                near_sdk::log!("Flux read of {} failed", pair);
+                return None;
+            }
+        };
+        let source = format!("{}:{}", FLUX_SOURCE_PREFIX, self.flux_feed(&pair).provider);
+        let timestamp = entry.last_update.0 / 1_000_000;
+        let last = self.prices.get(&pair).and_then(|reports| reports.get(&source));
+        if last.is_some_and(|report| report.timestamp >= timestamp) {
+            near_sdk::log!("Flux entry of {} unchanged since {}", pair, timestamp);
+            return None;
+        }
+
+        let decimals = self.asset_config(&pair.base).decimals;
+        let Some(price) = rescale(entry.price.0, entry.decimals, decimals) else {
+            near_sdk::log!("Flux price of {} can't be mapped from {} decimals", pair, entry.decimals);
+            return None;
+        };
+        let report = PriceReport {
+            source,
+            price,
+            decimals,
+            timestamp,
+            reporter: env::current_account_id().to_string(),
+            volume: None,
+            attestation: None,
+        };
+        (!self.ingest_report(pair, report).is_rejected()).then_some(U128(price))
+    }
+}
+
+impl PriceOracle {
+    fn flux_feed(&self, pair: &Pair) -> &FluxFeed {
+        self.flux_feeds
+            .get(pair)
+            .unwrap_or_else(|| panic!("No Flux feed for {}", pair))
+    }
+}
+
+/// Price scaled by 10^from re-expressed with `to` decimals, rounding to the nearest
+fn rescale(price: u128, from: u16, to: u8) -> Option<u128> {
+    let to = to as u16;
+    if from >= to {
+        mul_div(price, 1, pow10((from - to) as u32)?, Rounding::Nearest)
+    } else {
+        price.checked_mul(pow10((to - from) as u32)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::test_utils::{get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn setup() -> PriceOracle {
+        testing_env!(VMContextBuilder::new().block_timestamp(100 * 1_000_000_000).build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.set_flux_oracle("fpo.opfilabs.near".parse().unwrap());
+        contract.add_flux_feed(
+            Pair::usd("NEAR"),
+            FluxFeed { flux_pair: "NEAR/USD".to_string(), provider: "provider.near".to_string() },
+        );
+        contract
+    }
+
+    fn entry(price: u128, decimals: u16, seconds: u64) -> Result<Option<FluxPriceEntry>, PromiseError> {
+        Ok(Some(FluxPriceEntry { price: U128(price), decimals, last_update: U64(seconds * 1_000_000_000) }))
+    }
+
+    #[test]
+    fn test_flux_entry_mapped_to_source() {
+        let mut contract = setup();
+        // Flux quotes 8 decimals; NEAR/USD here uses 6
+        assert_eq!(contract.on_flux_price(Pair::usd("NEAR"), entry(525000050, 8, 90)), Some(U128(5250001)));
+        let report = &contract.get_price_details(Pair::usd("NEAR"))[0];
+        assert_eq!(report.source, "flux:provider.near");
+        assert_eq!(report.timestamp, 90000);
+
+        assert_eq!(contract.on_flux_price(Pair::usd("NEAR"), entry(530000000, 8, 90)), None);
+        assert!(get_logs().contains(&"Flux entry of NEAR/USD unchanged since 90000".to_string()));
+    }
+
+    #[test]
+    fn test_future_flux_entry_rejected() {
+        let mut contract = setup();
+        assert_eq!(contract.on_flux_price(Pair::usd("NEAR"), entry(525000000, 8, 3600)), None);
+        assert!(contract.get_price_details(Pair::usd("NEAR")).is_empty());
+        // Within the drift allowance it's kept with its own timestamp
+        assert_eq!(contract.on_flux_price(Pair::usd("NEAR"), entry(525000000, 8, 103)), Some(U128(5250000)));
+        assert_eq!(contract.get_price_details(Pair::usd("NEAR"))[0].timestamp, 103000);
+    }
+
+    #[test]
+    fn test_flux_failures_skipped() {
+        let mut contract = setup();
+        assert_eq!(contract.on_flux_price(Pair::usd("NEAR"), Err(PromiseError::Failed)), None);
+        assert_eq!(contract.on_flux_price(Pair::usd("NEAR"), Ok(None)), None);
+        assert_eq!(contract.on_flux_price(Pair::usd("NEAR"), entry(1, 60, 90)), None);
+        assert_eq!(
+            get_logs()[get_logs().len() - 3..],
+            [
+                "Flux read of NEAR/USD failed",
+                "Flux has no entry for NEAR/USD",
+                "Flux price of NEAR/USD can't be mapped from 60 decimals",
+            ]
+        );
+        assert!(contract.get_price_details(Pair::usd("NEAR")).is_empty());
+    }
+
+    #[test]
+    fn test_rescale() {
+        assert_eq!(rescale(5250000, 6, 6), Some(5250000));
+        assert_eq!(rescale(525, 2, 6), Some(5250000));
+        assert_eq!(rescale(u128::MAX, 0, 6), None);
+    }
+}
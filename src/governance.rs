@@ -0,0 +1,204 @@
+use near_sdk::{env, ext_contract, near, AccountId, Gas, Promise, PromiseError};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{Base64VecU8, U128, U64};
+use near_sdk::serde_json::{self, Value};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::{PriceOracle, PriceOracleExt, Role, DEFAULT_PAGE_LIMIT};
+
+/// Status of a Sputnik proposal that is still being voted on
+pub const DAO_IN_PROGRESS: &str = "InProgress";
+
+const GAS_FOR_GET_PROPOSALS: Gas = Gas::from_tgas(20);
+const GAS_FOR_ON_DAO_PROPOSALS: Gas = Gas::from_tgas(20);
+
+/// Subset of a Sputnik DAO v2 `get_proposals` entry we rely on
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "abi", derive(JsonSchema))]
+pub struct DaoProposal {
+    pub id: u64,
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub proposer: AccountId,
+    pub description: String,
+    pub kind: Value,           // Externally tagged ProposalKind; only FunctionCall is read
+    pub status: String,
+}
+
+/// Body of a FunctionCall proposal kind
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DaoFunctionCall {
+    pub receiver_id: AccountId,
+    pub actions: Vec<DaoActionCall>,
+}
+
+/// One call a FunctionCall proposal makes once approved
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DaoActionCall {
+    pub method_name: String,
+    pub args: Base64VecU8,
+    pub deposit: U128,
+    pub gas: U64,
+}
+
+/// Call on this oracle that the owning DAO is voting on
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct GovernanceAction {
+    pub proposal_id: u64,
+    pub proposer: String,
+    pub description: String,
+    pub method_name: String,
+    pub args: String,          // JSON arguments as the DAO will send them
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub deposit: u128,         // Must be 0 unless the method is payable, or the call fails
+}
+
+#[allow(dead_code)]
+#[ext_contract(ext_sputnik_dao)]
+trait SputnikDao {
+    fn get_proposals(&self, from_index: u64, limit: u64) -> Vec<DaoProposal>;
+}
+
+#[near]
+impl PriceOracle {
+    /// Re-read the owner's open proposals (the owner must be a Sputnik DAO) and cache the
+    /// calls they would make on this contract, scanning `limit` proposals (at most
+    /// DEFAULT_PAGE_LIMIT) from `from_index` (Admin)
+    /// The DAO governs like any owner: an approved FunctionCall proposal calls the
+    /// oracle with the DAO as predecessor, so owner and role checks see the DAO
+    pub fn refresh_governance_actions(&mut self, from_index: u64, limit: Option<u64>) -> Promise {
+        self.assert_role(Role::Admin);
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(DEFAULT_PAGE_LIMIT);
+        ext_sputnik_dao::ext(self.owner.clone())
+            .with_static_gas(GAS_FOR_GET_PROPOSALS)
+            .get_proposals(from_index, limit)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_ON_DAO_PROPOSALS)
+                    .on_dao_proposals(),
+            )
+    }
+
+    #[private]
+    pub fn on_dao_proposals(
+        &mut self,
+        #[callback_result] proposals: Result<Vec<DaoProposal>, PromiseError>,
+    ) -> Vec<GovernanceAction> {
+        let proposals = proposals.unwrap_or_else(|_| panic!("Failed to read proposals of {}", self.owner));
+        let oracle = env::current_account_id();
+        self.governance_actions = proposals
+            .iter()
+            .filter(|proposal| proposal.status == DAO_IN_PROGRESS)
+            .filter_map(|proposal| Some((proposal, function_call(&proposal.kind)?)))
+            .filter(|(_, call)| call.receiver_id == oracle)
+            .flat_map(|(proposal, call)| {
+                call.actions.into_iter().map(move |action| GovernanceAction {
+                    proposal_id: proposal.id,
+                    proposer: proposal.proposer.to_string(),
+                    description: proposal.description.clone(),
+                    method_name: action.method_name,
+                    args: String::from_utf8_lossy(&action.args.0).into_owned(),
+                    deposit: action.deposit.0,
+                })
+            })
+            .collect();
+        near_sdk::log!("Governance actions refreshed: {} pending", self.governance_actions.len());
+        self.governance_actions.clone()
+    }
+
+    /// Get the calls on this oracle the owning DAO was voting on at the last refresh
+    pub fn get_pending_governance_actions(&self) -> Vec<GovernanceAction> {
+        self.governance_actions.clone()
+    }
+}
+
+/// Body of a FunctionCall proposal kind, None for every other kind
+fn function_call(kind: &Value) -> Option<DaoFunctionCall> {
+    serde_json::from_value(kind.get("FunctionCall")?.clone()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::mock::MockAction;
+    use near_sdk::test_utils::{accounts, get_created_receipts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn proposal(id: u64, kind: Value, status: &str) -> DaoProposal {
+        DaoProposal {
+            id,
+            proposer: "alice.near".parse().unwrap(),
+            description: format!("Proposal {}", id),
+            kind,
+            status: status.to_string(),
+        }
+    }
+
+    fn call(receiver_id: &str, method_name: &str, args: &str) -> Value {
+        serde_json::json!({ "FunctionCall": {
+            "receiver_id": receiver_id,
+            "actions": [{
+                "method_name": method_name,
+                "args": Base64VecU8(args.as_bytes().to_vec()),
+                "deposit": "0",
+                "gas": "30000000000000",
+            }],
+        }})
+    }
+
+    #[test]
+    fn test_pending_actions_on_this_oracle() {
+        testing_env!(VMContextBuilder::new().current_account_id("oracle.near".parse().unwrap()).build());
+        let mut contract = PriceOracle::for_tests();
+        contract.on_dao_proposals(Ok(vec![
+            proposal(7, call("oracle.near", "set_min_sources", r#"{"min_sources":5}"#), "InProgress"),
+            proposal(8, call("oracle.near", "clear_prices", "{}"), "Approved"),
+            proposal(9, call("other.near", "set_min_sources", "{}"), "InProgress"),
+            proposal(10, serde_json::json!("Vote"), "InProgress"),
+        ]));
+
+        assert_eq!(contract.get_pending_governance_actions(), vec![GovernanceAction {
+            proposal_id: 7,
+            proposer: "alice.near".to_string(),
+            description: "Proposal 7".to_string(),
+            method_name: "set_min_sources".to_string(),
+            args: r#"{"min_sources":5}"#.to_string(),
+            deposit: 0,
+        }]);
+    }
+
+    #[test]
+    fn test_refresh_reads_at_most_a_page() {
+        testing_env!(VMContextBuilder::new().build());
+        let mut contract = PriceOracle::for_tests();
+        contract.refresh_governance_actions(0, Some(u64::MAX)).detach();
+
+        let args = get_created_receipts()[0].actions.iter().find_map(|action| match action {
+            MockAction::FunctionCallWeight { args, .. } => Some(args.clone()),
+            _ => None,
+        });
+        assert_eq!(args.expect("function call"), br#"{"from_index":0,"limit":100}"#);
+    }
+
+    #[test]
+    #[should_panic(expected = "charlie lacks role Admin")]
+    fn test_refresh_needs_admin() {
+        testing_env!(VMContextBuilder::new().build());
+        let mut contract = PriceOracle::for_tests();
+        testing_env!(VMContextBuilder::new().predecessor_account_id(accounts(2)).build());
+        contract.refresh_governance_actions(0, None).detach();
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to read proposals of bob.near")]
+    fn test_owner_not_a_dao() {
+        testing_env!(VMContextBuilder::new().build());
+        let mut contract = PriceOracle::for_tests();
+        contract.on_dao_proposals(Err(PromiseError::Failed));
+    }
+}
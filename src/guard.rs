@@ -0,0 +1,450 @@
+use near_sdk::near;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::stablecoin::BPS_DENOMINATOR;
+use crate::{now_ms, AssetId, ConfigChange, Pair, PriceOracle, PriceOracleExt, PriceReport, Role, RoundAnswer, DEFAULT_QUOTE};
+
+/// How far ahead of the block time a report's timestamp may be, for feeders whose
+/// clocks run slightly fast
+pub const MAX_FUTURE_DRIFT_SECONDS: u64 = 5;
+
+/// Hard limits on an asset's USD price, scaled by the asset's decimals
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct PriceBounds {
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub min: u128,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub max: u128,
+}
+
+/// Limit on how far a single report may be from the pair's last finalized answer
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct DeviationGate {
+    pub max_deviation_bps: u16,
+    pub quarantine: bool,      // Hold offending reports for owner review instead of dropping them
+}
+
+/// Cap on how fast a pair's published answer may move
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct RateLimit {
+    pub max_move_bps: u16,     // Allowed move per period, prorated by the time since the last answer
+    pub period_seconds: u64,
+    pub confirmations: u8,     // Consecutive rounds beyond the cap before the move is published
+}
+
+/// Why a report was turned away, as counted in reporter statistics and carried as the
+/// code of report_rejected events
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub enum RejectReason {
+    Invalid,          // Batch entry for an unknown asset, with the wrong decimals or a bad source
+    FutureTimestamp,  // Timestamped too far ahead of the block
+    StaleTimestamp,   // Older than the source's last report of the pair
+    OutOfBounds,      // Outside the asset's price bounds
+    Deviation,        // Too far from the pair's last answer
+    Unauthorized,     // Not whitelisted, not signed by the reporter's key or for a source bound to another reporter
+    Duplicate,        // Replays a packet nonce or a Merkle leaf already taken, or its source's round submission
+}
+
+/// Outcome of the sanity checks on a report
+pub(crate) enum Verdict {
+    Accept,
+    Reject(RejectReason, String),
+    Quarantine(String),
+}
+
+impl PriceBounds {
+    pub(crate) fn validate(&self) {
+        assert!(self.min <= self.max, "Price bounds are inverted: {} > {}", self.min, self.max);
+    }
+}
+
+#[near]
+impl PriceOracle {
+    /// Set or clear the sanity bounds of an asset's USD price (ConfigManager)
+    /// Reports outside them are rejected with a log event instead of being aggregated
+    /// Subject to the timelock delay
+    pub fn set_price_bounds(&mut self, asset_id: AssetId, bounds: Option<PriceBounds>) {
+        self.assert_role(Role::ConfigManager);
+        self.asset_config(&asset_id);
+        if let Some(bounds) = &bounds {
+            bounds.validate();
+        }
+        self.request_config_change(ConfigChange::PriceBounds { asset_id, bounds });
+    }
+
+    /// Set or clear the deviation gate (ConfigManager): reports further than
+    /// `max_deviation_bps` from the pair's last finalized answer are rejected, or
+    /// quarantined for review if `quarantine` is set
+    pub fn set_deviation_gate(&mut self, gate: Option<DeviationGate>) {
+        self.assert_role(Role::ConfigManager);
+        self.deviation_gate = gate;
+    }
+
+    /// Get the deviation gate, if configured
+    pub fn get_deviation_gate(&self) -> Option<DeviationGate> {
+        self.deviation_gate.clone()
+    }
+
+    /// Set or clear the rate limit on published answers (ConfigManager), e.g. at most
+    /// 1000 bps per 300 seconds; faster moves are published once `confirmations`
+    /// consecutive rounds agree, each within `max_move_bps` of the answer held before it
+    pub fn set_rate_limit(&mut self, limit: Option<RateLimit>) {
+        self.assert_role(Role::ConfigManager);
+        if let Some(limit) = &limit {
+            assert!(limit.period_seconds > 0, "Rate limit period must be positive");
+            assert!(limit.confirmations >= 2, "A faster move needs at least 2 confirming rounds");
+        }
+        self.rate_limit = limit;
+    }
+
+    /// Get the rate limit on published answers, if configured
+    pub fn get_rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limit.clone()
+    }
+
+    /// Get the reports of a pair held for review, by source
+    pub fn get_quarantined(&self, pair: Pair) -> Vec<PriceReport> {
+        let mut reports: Vec<PriceReport> = self
+            .quarantine
+            .get(&pair)
+            .map(|reports| reports.values().cloned().collect())
+            .unwrap_or_default();
+        reports.sort_by(|a, b| a.source.cmp(&b.source));
+        reports
+    }
+
+    /// Accept a quarantined report as if it had passed the gate (Admin)
+    pub fn release_quarantined(&mut self, pair: Pair, source: String) {
+        self.assert_role(Role::Admin);
+        let report = self.take_quarantined(&pair, &source);
+        if let Some(last) = self.newer_report_timestamp(&pair, &report) {
+            panic!("Quarantined report from {} predates its last report of {} at {}", source, pair, last);
+        }
+        near_sdk::log!("Price released for {}: {} from {}", pair, report.price, source);
+        self.accept_report(&pair, report);
+    }
+
+    /// Drop a quarantined report (Admin)
+    pub fn discard_quarantined(&mut self, pair: Pair, source: String) {
+        self.assert_role(Role::Admin);
+        self.take_quarantined(&pair, &source);
+    }
+}
+
+impl PriceOracle {
+    /// Whether a new report of `price` may be accepted, and why not
+    pub(crate) fn check_report(&self, pair: &Pair, report: &PriceReport) -> Verdict {
+        let now = now_ms();
+        if report.timestamp > now + MAX_FUTURE_DRIFT_SECONDS * 1000 {
+            return Verdict::Reject(RejectReason::FutureTimestamp, format!("timestamp {} ahead of block time {}", report.timestamp, now));
+        }
+        if let Some(last) = self.newer_report_timestamp(pair, report) {
+            let reason = format!("timestamp {} older than the last report at {}", report.timestamp, last);
+            return Verdict::Reject(RejectReason::StaleTimestamp, reason);
+        }
+
+        let price = report.price;
+        if pair.quote == DEFAULT_QUOTE {
+            if let Some(bounds) = &self.asset_config(&pair.base).bounds {
+                if price < bounds.min || price > bounds.max {
+                    return Verdict::Reject(RejectReason::OutOfBounds, format!("outside bounds [{}, {}]", bounds.min, bounds.max));
+                }
+            }
+        }
+
+        if let Some(round_id) = self.submitted_round(pair, &report.source) {
+            return Verdict::Reject(RejectReason::Duplicate, format!("already reported in round {}", round_id));
+        }
+
+        if let (Some(gate), Some(last)) = (&self.deviation_gate, self.latest_answer(pair)) {
+            let deviation_bps = deviation_bps(price, last.price);
+            if deviation_bps > gate.max_deviation_bps as u128 {
+                let reason = format!("{} bps from round {} answer {}", deviation_bps, last.round_id, last.price);
+                return if gate.quarantine {
+                    Verdict::Quarantine(reason)
+                } else {
+                    Verdict::Reject(RejectReason::Deviation, reason)
+                };
+            }
+        }
+        Verdict::Accept
+    }
+
+    /// Timestamp of the source's last report of the pair if it's newer than `report`, so
+    /// a replayed or delayed report can't overwrite fresher data. A last report stamped
+    /// ahead of the block counts as stamped now, so drift doesn't lock out the next reports
+    pub(crate) fn newer_report_timestamp(&self, pair: &Pair, report: &PriceReport) -> Option<u64> {
+        self.prices
+            .get(pair)
+            .and_then(|reports| reports.get(&report.source))
+            .map(|last| last.timestamp.min(now_ms()))
+            .filter(|last| *last > report.timestamp)
+    }
+
+    /// Move of `candidate` from the published answer and the move allowed by the rate
+    /// limit, if the former exceeds the latter
+    pub(crate) fn rate_limit_excess(&self, pair: &Pair, candidate: &RoundAnswer) -> Option<(u128, u128)> {
+        let limit = self.rate_limit.as_ref()?;
+        let last = self.latest_answer(pair)?;
+
+        let elapsed = candidate.finalized_at.saturating_sub(last.finalized_at) as u128;
+        let allowed_bps = (limit.max_move_bps as u128).saturating_mul(elapsed) / (limit.period_seconds as u128 * 1000);
+        let move_bps = deviation_bps(candidate.price, last.price);
+        (move_bps > allowed_bps).then_some((move_bps, allowed_bps))
+    }
+
+    fn take_quarantined(&mut self, pair: &Pair, source: &String) -> PriceReport {
+        self.quarantine
+            .get_mut(pair)
+            .and_then(|reports| reports.remove(source))
+            .unwrap_or_else(|| panic!("No quarantined report from {} for {}", source, pair))
+    }
+}
+
+/// Distance of `price` from `reference` in basis points (saturating for a zero reference)
+pub(crate) fn deviation_bps(price: u128, reference: u128) -> u128 {
+    price
+        .abs_diff(reference)
+        .saturating_mul(BPS_DENOMINATOR)
+        .checked_div(reference)
+        .unwrap_or(u128::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AssetConfig, ReportStatus};
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn setup() -> PriceOracle {
+        testing_env!(VMContextBuilder::new().build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.set_price_bounds(
+            "NEAR".to_string(),
+            Some(PriceBounds { min: 100000, max: 1000000000 }),      // $0.10 - $1000
+        );
+        contract
+    }
+
+    #[test]
+    fn test_out_of_bounds_report_rejected() {
+        let mut contract = setup();
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        contract.report_price(Pair::usd("NEAR"), "fat-finger".to_string(), U128(5000000000), 6);
+
+        assert_eq!(contract.get_source_count(Pair::usd("NEAR")), 1);
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5000000);
+        assert!(get_logs().contains(
+            &"Price rejected for NEAR/USD: 5000000000 from fat-finger (outside bounds [100000, 1000000000])"
+                .to_string()
+        ));
+    }
+
+    #[test]
+    fn test_bounds_only_apply_to_usd() {
+        let mut contract = setup();
+        contract.report_price(Pair::new("NEAR", "BTC"), "binance".to_string(), U128(50), 6);
+        assert_eq!(contract.get_source_count(Pair::new("NEAR", "BTC")), 1);
+
+        contract.set_price_bounds("NEAR".to_string(), None);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(50), 6);
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 50);
+    }
+
+    #[test]
+    fn test_deviation_gate_rejects() {
+        let mut contract = setup();
+        contract.set_deviation_gate(Some(DeviationGate { max_deviation_bps: 1000, quarantine: false }));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6); // No answer yet
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5400000), 6); // +8%
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(6000000), 6); // +11%
+
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5400000);
+        assert!(get_logs().contains(
+            &"Price rejected for NEAR/USD: 6000000 from binance (1111 bps from round 2 answer 5400000)".to_string()
+        ));
+        assert!(contract.get_quarantined(Pair::usd("NEAR")).is_empty());
+    }
+
+    #[test]
+    fn test_deviation_gate_quarantines_for_review() {
+        let mut contract = setup();
+        contract.set_deviation_gate(Some(DeviationGate { max_deviation_bps: 1000, quarantine: true }));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(8000000), 6);
+        contract.report_price(Pair::usd("NEAR"), "kraken".to_string(), U128(9000000), 6);
+
+        let held = contract.get_quarantined(Pair::usd("NEAR"));
+        assert_eq!(held.iter().map(|r| r.price).collect::<Vec<_>>(), vec![8000000, 9000000]);
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5000000);
+
+        contract.release_quarantined(Pair::usd("NEAR"), "binance".to_string());
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 8000000);
+        contract.discard_quarantined(Pair::usd("NEAR"), "kraken".to_string());
+        assert!(contract.get_quarantined(Pair::usd("NEAR")).is_empty());
+    }
+
+    fn at(seconds: u64) {
+        testing_env!(VMContextBuilder::new().block_timestamp(seconds * 1_000_000_000).build());
+    }
+
+    #[test]
+    fn test_rate_limit_requires_confirmation() {
+        let mut contract = setup();
+        contract.set_rate_limit(Some(RateLimit { max_move_bps: 1000, period_seconds: 300, confirmations: 3 }));
+        at(1000);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+
+        at(1150);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5240000), 6);  // 4.8%, 5% allowed
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5240000);
+
+        at(1180);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(7000000), 6);  // Held, 1 of 3
+        at(1190);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(7000000), 6);  // Held, 2 of 3
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5240000);
+        assert_eq!(contract.get_held_round(Pair::usd("NEAR")).unwrap().round_id, 4);
+        assert!(get_logs().contains(
+            &"Round 4 of NEAR/USD held: 3358 bps move, 133 bps allowed (2 of 3 confirmations)".to_string()
+        ));
+
+        at(1200);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(7000000), 6);  // Confirmed
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 7000000);
+        assert_eq!(contract.get_held_round(Pair::usd("NEAR")), None);
+    }
+
+    #[test]
+    fn test_rate_limit_streak_resets() {
+        let mut contract = setup();
+        contract.set_rate_limit(Some(RateLimit { max_move_bps: 1000, period_seconds: 300, confirmations: 2 }));
+        at(1000);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        at(1010);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(9000000), 6);  // Held
+        at(1300);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5100000), 6);  // Within limit
+        at(1310);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(9000000), 6);  // Held again
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5100000);
+    }
+
+    #[test]
+    fn test_held_round_released_when_next_agrees() {
+        let mut contract = setup();
+        contract.set_rate_limit(Some(RateLimit { max_move_bps: 1000, period_seconds: 300, confirmations: 2 }));
+        at(1000);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        at(1010);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(7000000), 6);  // Held
+        at(1020);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(7300000), 6);  // 4.3% from it
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 7300000);
+        assert_eq!(contract.get_held_round(Pair::usd("NEAR")), None);
+    }
+
+    #[test]
+    fn test_held_round_not_released_when_next_disagrees() {
+        let mut contract = setup();
+        contract.set_rate_limit(Some(RateLimit { max_move_bps: 1000, period_seconds: 300, confirmations: 2 }));
+        at(1000);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        at(1010);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(7000000), 6);  // Held
+        at(1020);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(9000000), 6);  // 28.6% from it
+        assert!(get_logs().contains(
+            &"Round 3 of NEAR/USD disagrees with held round 2: 2857 bps apart, 1000 bps allowed".to_string()
+        ));
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5000000);
+        assert_eq!(contract.get_held_round(Pair::usd("NEAR")).unwrap().price, 9000000);
+
+        at(1030);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(9000000), 6);  // Confirms the new one
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 9000000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Price bounds are inverted: 2 > 1")]
+    fn test_inverted_bounds() {
+        let mut contract = setup();
+        contract.set_price_bounds("NEAR".to_string(), Some(PriceBounds { min: 2, max: 1 }));
+    }
+
+    fn report_at(timestamp: u64, price: u128) -> PriceReport {
+        PriceReport {
+            source: "relay".to_string(),
+            price,
+            decimals: 6,
+            timestamp,
+            reporter: "alice".to_string(),
+            volume: None,
+            attestation: None,
+        }
+    }
+
+    #[test]
+    fn test_regressing_timestamp_rejected() {
+        let mut contract = setup();
+        at(10);
+        assert_eq!(contract.ingest_report(Pair::usd("NEAR"), report_at(2000, 5000000)), ReportStatus::Accepted);
+        assert_eq!(
+            contract.ingest_report(Pair::usd("NEAR"), report_at(1000, 4000000)),
+            ReportStatus::Rejected("timestamp 1000 older than the last report at 2000".to_string())
+        );
+        assert_eq!(contract.ingest_report(Pair::usd("NEAR"), report_at(2000, 5100000)), ReportStatus::Accepted);
+        assert_eq!(contract.get_price_details(Pair::usd("NEAR"))[0].price, 5100000);
+    }
+
+    #[test]
+    fn test_future_timestamp_rejected() {
+        let mut contract = setup();
+        testing_env!(VMContextBuilder::new().block_timestamp(10 * 1_000_000_000).build());
+        assert_eq!(contract.ingest_report(Pair::usd("NEAR"), report_at(15000, 5000000)), ReportStatus::Accepted);
+        assert_eq!(
+            contract.ingest_report(Pair::usd("NEAR"), report_at(15001, 5000000)),
+            ReportStatus::Rejected("timestamp 15001 ahead of block time 10000".to_string())
+        );
+    }
+
+    #[test]
+    fn test_future_timestamp_doesnt_lock_out_source() {
+        let mut contract = setup();
+        at(10);
+        assert_eq!(contract.ingest_report(Pair::usd("NEAR"), report_at(15000, 5000000)), ReportStatus::Accepted);
+        assert_eq!(contract.ingest_report(Pair::usd("NEAR"), report_at(10000, 5100000)), ReportStatus::Accepted);
+        assert_eq!(
+            contract.ingest_report(Pair::usd("NEAR"), report_at(9000, 5200000)),
+            ReportStatus::Rejected("timestamp 9000 older than the last report at 10000".to_string())
+        );
+        assert_eq!(contract.get_price_details(Pair::usd("NEAR"))[0].price, 5100000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Quarantined report from relay predates its last report of NEAR/USD at 3000")]
+    fn test_stale_quarantined_report_not_released() {
+        let mut contract = setup();
+        at(10);
+        contract.set_deviation_gate(Some(DeviationGate { max_deviation_bps: 1000, quarantine: true }));
+        contract.ingest_report(Pair::usd("NEAR"), report_at(1000, 5000000));
+        contract.ingest_report(Pair::usd("NEAR"), report_at(2000, 8000000));
+        contract.ingest_report(Pair::usd("NEAR"), report_at(3000, 5100000));
+        contract.release_quarantined(Pair::usd("NEAR"), "relay".to_string());
+    }
+}
@@ -0,0 +1,331 @@
+use near_sdk::json_types::U128;
+use near_sdk::{env, near, NearToken, Promise};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::reporter::normalize_source;
+use crate::{now_ms, paginate, AssetId, OracleEvent, Pair, PriceOracle, PriceOracleExt, PriceReport, Role};
+
+/// Missed heartbeats after which a source is excluded until it reports again
+pub const DEFAULT_MAX_MISSED_HEARTBEATS: u8 = 3;
+
+/// Reporting health of one source of a pair
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct HeartbeatStatus {
+    pub source: String,
+    pub interval: u64,         // Expected seconds between reports
+    pub last_report: u64,
+    pub missed: u64,           // Whole intervals elapsed since the last report
+    pub excluded: bool,        // Missed at least max_missed_heartbeats in a row
+}
+
+/// Staleness rules in effect for the pairs of an asset
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct StalenessPolicy {
+    pub asset_id: AssetId,
+    pub max_report_age: Option<u64>,     // Seconds after which a report stops counting
+    pub heartbeat_interval: Option<u64>, // None: only sources with their own interval are tracked
+    pub max_missed_heartbeats: u8,
+    pub max_answer_age: Option<u64>,     // Seconds after which is_valid distrusts the answer
+}
+
+#[near]
+impl PriceOracle {
+    /// Set or clear how often a source is expected to report each pair, in seconds (ConfigManager)
+    pub fn set_source_heartbeat(&mut self, source: String, interval_seconds: Option<u64>) {
+        self.assert_role(Role::ConfigManager);
+        let source = normalize_source(&source);
+        match interval_seconds {
+            Some(interval) => {
+                assert!(interval > 0, "Heartbeat interval must be positive");
+                self.heartbeats.insert(source, interval);
+            }
+            None => {
+                self.heartbeats.remove(&source);
+            }
+        }
+        self.invalidate_aggregates();
+    }
+
+    /// Get the expected reporting interval of a source in seconds
+    pub fn get_source_heartbeat(&self, source: String) -> Option<u64> {
+        self.heartbeats.get(&normalize_source(&source)).copied()
+    }
+
+    /// Set or clear how often every source is expected to report an asset's pairs, in
+    /// seconds (ConfigManager). Takes precedence over the sources' own intervals
+    pub fn set_asset_heartbeat(&mut self, asset_id: AssetId, interval_seconds: Option<u64>) {
+        self.assert_role(Role::ConfigManager);
+        self.asset_config(&asset_id);
+        assert!(interval_seconds != Some(0), "Heartbeat interval must be positive");
+        self.assets.get_mut(&asset_id).expect("checked above").heartbeat_interval = interval_seconds;
+        self.invalidate_aggregates();
+    }
+
+    /// Set how many heartbeats in a row a source may miss before it's excluded (ConfigManager)
+    pub fn set_max_missed_heartbeats(&mut self, max_missed: u8) {
+        self.assert_role(Role::ConfigManager);
+        assert!(max_missed > 0, "Max missed heartbeats must be positive");
+        self.max_missed_heartbeats = max_missed;
+        self.invalidate_aggregates();
+    }
+
+    /// Get how many heartbeats in a row a source may miss before it's excluded
+    pub fn get_max_missed_heartbeats(&self) -> u8 {
+        self.max_missed_heartbeats
+    }
+
+    /// Get the heartbeat status of every source of a pair that has an interval, by source
+    pub fn get_heartbeat_status(&self, pair: Pair) -> Vec<HeartbeatStatus> {
+        let mut statuses: Vec<HeartbeatStatus> = self
+            .reports(&pair)
+            .into_iter()
+            .filter_map(|report| {
+                let interval = self.heartbeat_interval(&pair, &report.source)?;
+                let missed = self.missed_heartbeats(&pair, report)?;
+                Some(HeartbeatStatus {
+                    source: report.source.clone(),
+                    interval,
+                    last_report: report.timestamp,
+                    missed,
+                    excluded: missed >= self.max_missed_heartbeats as u64,
+                })
+            })
+            .collect();
+        statuses.sort_by(|a, b| a.source.cmp(&b.source));
+        statuses
+    }
+
+    /// Report that an asset's USD feed missed its heartbeat: its latest answer is older
+    /// than the asset's heartbeat interval. Anyone can call this; the first caller per
+    /// outage receives the stale bounty from the reward pool (as much of it as the pool
+    /// holds) and a stale_feed_flagged event is emitted
+    /// Returns the bounty paid
+    pub fn flag_stale(&mut self, asset_id: AssetId) -> U128 {
+        let pair = Pair::usd(asset_id.clone());
+        let interval = self
+            .asset_config(&asset_id)
+            .heartbeat_interval
+            .unwrap_or_else(|| panic!("{} has no heartbeat", asset_id));
+        let finalized_at = self
+            .latest_answer(&pair)
+            .unwrap_or_else(|| panic!("No answer for {}", pair))
+            .finalized_at;
+        assert!(
+            now_ms().saturating_sub(finalized_at) > interval * 1000,
+            "Feed of {} is within its {}s heartbeat",
+            pair,
+            interval
+        );
+        assert!(
+            self.stale_flags.get(&asset_id).is_none_or(|flagged| *flagged < finalized_at),
+            "Feed of {} was already flagged since its answer at {}",
+            pair,
+            finalized_at
+        );
+        self.stale_flags.insert(asset_id, now_ms());
+
+        let caller = env::predecessor_account_id();
+        let bounty = self.stale_bounty.min(self.reward_pool);
+        near_sdk::log!(
+            "Stale feed flagged for {} by {}: last answer at {}, heartbeat {}s, bounty {}",
+            pair,
+            caller,
+            finalized_at,
+            interval,
+            bounty
+        );
+        OracleEvent::StaleFeedFlagged {
+            pair,
+            flagged_by: caller.to_string(),
+            finalized_at,
+            heartbeat_seconds: interval,
+            bounty,
+        }
+        .emit();
+        if bounty > 0 {
+            self.reward_pool -= bounty;
+            Promise::new(caller).transfer(NearToken::from_yoctonear(bounty)).detach();
+        }
+        U128(bounty)
+    }
+
+    /// Set the bounty paid from the reward pool for flagging a stale feed, in yoctoNEAR
+    /// (ConfigManager)
+    pub fn set_stale_bounty(&mut self, bounty: U128) {
+        self.assert_role(Role::ConfigManager);
+        self.stale_bounty = bounty.0;
+    }
+
+    /// Get the bounty paid for flagging a stale feed, in yoctoNEAR
+    pub fn get_stale_bounty(&self) -> U128 {
+        U128(self.stale_bounty)
+    }
+
+    /// Get the staleness rules applied to the pairs of an asset
+    pub fn get_staleness_policy(&self, asset_id: AssetId) -> StalenessPolicy {
+        let config = self.asset_config(&asset_id);
+        StalenessPolicy {
+            max_report_age: config.max_report_age,
+            heartbeat_interval: config.heartbeat_interval,
+            max_missed_heartbeats: self.max_missed_heartbeats,
+            max_answer_age: self.max_answer_age,
+            asset_id,
+        }
+    }
+
+    /// Get the staleness rules of all registered assets, ordered by asset id
+    pub fn get_staleness_policies(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<StalenessPolicy> {
+        let mut assets: Vec<AssetId> = self.assets.keys().cloned().collect();
+        assets.sort();
+        paginate(assets, from_index, limit)
+            .into_iter()
+            .map(|asset_id| self.get_staleness_policy(asset_id))
+            .collect()
+    }
+}
+
+impl PriceOracle {
+    /// Expected seconds between a source's reports of a pair: the base asset's interval,
+    /// else the source's own
+    pub(crate) fn heartbeat_interval(&self, pair: &Pair, source: &str) -> Option<u64> {
+        self.asset_config(&pair.base)
+            .heartbeat_interval
+            .or_else(|| self.heartbeats.get(source).copied())
+    }
+
+    /// Whole heartbeat intervals since the report, None if no interval applies to it
+    pub(crate) fn missed_heartbeats(&self, pair: &Pair, report: &PriceReport) -> Option<u64> {
+        let interval = self.heartbeat_interval(pair, &report.source)? * 1000;
+        Some(now_ms().saturating_sub(report.timestamp) / interval)
+    }
+
+    /// Whether the report's source missed too many heartbeats to be aggregated
+    pub(crate) fn is_flatlined(&self, pair: &Pair, report: &PriceReport) -> bool {
+        self.missed_heartbeats(pair, report)
+            .is_some_and(|missed| missed >= self.max_missed_heartbeats as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::test_utils::{accounts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn at(seconds: u64) {
+        testing_env!(VMContextBuilder::new().block_timestamp(seconds * 1_000_000_000).build());
+    }
+
+    fn stale_feed() -> PriceOracle {
+        let mut contract = setup();
+        contract.set_asset_heartbeat("NEAR".to_string(), Some(600));
+        contract.set_stale_bounty(U128(100));
+        testing_env!(VMContextBuilder::new().attached_deposit(NearToken::from_yoctonear(150)).build());
+        contract.fund_rewards();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(accounts(4))
+            .block_timestamp(1601 * 1_000_000_000)
+            .build());
+        contract
+    }
+
+    fn setup() -> PriceOracle {
+        at(1000);
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.set_source_heartbeat("binance".to_string(), Some(60));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        contract.report_price(Pair::usd("NEAR"), "coingecko".to_string(), U128(5400000), 6);
+        contract
+    }
+
+    #[test]
+    fn test_missed_heartbeats_exclude_source() {
+        let mut contract = setup();
+        at(1150);
+        let status = &contract.get_heartbeat_status(Pair::usd("NEAR"))[0];
+        assert_eq!((status.source.as_str(), status.missed, status.excluded), ("binance", 2, false));
+        assert_eq!(contract.get_mean_price(Pair::usd("NEAR")).price, 5200000);
+
+        at(1180);
+        assert!(contract.get_heartbeat_status(Pair::usd("NEAR"))[0].excluded);
+        assert_eq!(contract.get_mean_price(Pair::usd("NEAR")).price, 5400000);
+
+        // Reporting again brings the source back
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5200000), 6);
+        assert_eq!(contract.get_heartbeat_status(Pair::usd("NEAR"))[0].missed, 0);
+        assert_eq!(contract.get_mean_price(Pair::usd("NEAR")).price, 5300000);
+    }
+
+    #[test]
+    fn test_sources_without_heartbeat_are_not_tracked() {
+        let mut contract = setup();
+        contract.set_source_heartbeat("binance".to_string(), None);
+        at(100000);
+        assert!(contract.get_heartbeat_status(Pair::usd("NEAR")).is_empty());
+        assert_eq!(contract.get_mean_price(Pair::usd("NEAR")).price, 5200000);
+    }
+
+    #[test]
+    fn test_flag_stale_pays_bounty_once() {
+        let mut contract = stale_feed();
+        assert_eq!(contract.flag_stale("NEAR".to_string()).0, 100);
+        assert!(get_logs().contains(
+            &"Stale feed flagged for NEAR/USD by eugene: last answer at 1000000, heartbeat 600s, bounty 100".to_string()
+        ));
+        assert!(get_logs().contains(&r#"EVENT_JSON:{"standard":"price-oracle","version":"2.0.0","event":"stale_feed_flagged","data":{"pair":{"base":"NEAR","quote":"USD"},"flagged_by":"eugene","finalized_at":1000000,"heartbeat_seconds":600,"bounty":"100"}}"#.to_string()));
+        assert_eq!(contract.get_reward_pool().0, 50);
+    }
+
+    #[test]
+    #[should_panic(expected = "Feed of NEAR/USD was already flagged since its answer at 1000000")]
+    fn test_flag_stale_twice() {
+        let mut contract = stale_feed();
+        contract.flag_stale("NEAR".to_string());
+        contract.flag_stale("NEAR".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Feed of NEAR/USD is within its 600s heartbeat")]
+    fn test_flag_fresh_feed() {
+        let mut contract = stale_feed();
+        at(1600);
+        contract.flag_stale("NEAR".to_string());
+    }
+
+    #[test]
+    fn test_asset_heartbeat_overrides_source() {
+        let mut contract = setup();
+        contract.add_asset("BTC".to_string(), AssetConfig::new("BTC", "Bitcoin", 6));
+        contract.set_asset_heartbeat("NEAR".to_string(), Some(600));
+        contract.set_max_report_age("NEAR".to_string(), Some(3600));
+        at(1180);
+        let statuses = contract.get_heartbeat_status(Pair::usd("NEAR"));
+        assert_eq!(statuses.len(), 2);                  // Tracks coingecko too
+        assert!(statuses.iter().all(|s| s.interval == 600 && !s.excluded));
+        assert_eq!(contract.get_mean_price(Pair::usd("NEAR")).price, 5200000);
+
+        at(2800);
+        assert!(contract.get_heartbeat_status(Pair::usd("NEAR")).iter().all(|s| s.excluded));
+
+        let policies = contract.get_staleness_policies(None, None);
+        assert_eq!(policies.iter().map(|p| p.asset_id.as_str()).collect::<Vec<_>>(), ["BTC", "NEAR"]);
+        assert_eq!(policies[0].heartbeat_interval, None);
+        assert_eq!(policies[1], StalenessPolicy {
+            asset_id: "NEAR".to_string(),
+            max_report_age: Some(3600),
+            heartbeat_interval: Some(600),
+            max_missed_heartbeats: 3,
+            max_answer_age: None,
+        });
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::VecDeque;
+
+use near_sdk::json_types::U128;
+use near_sdk::near;
+
+use crate::{now_ms, paginate, AssetId, Pair, PriceOracle, PriceOracleExt, Role, RoundAnswer, DEFAULT_PAGE_LIMIT, DEFAULT_QUOTE};
+
+/// Finalized answers kept per asset until the owner configures a length
+pub const DEFAULT_HISTORY_LENGTH: u64 = 100;
+
+#[near]
+impl PriceOracle {
+    /// Get up to `limit` of the latest finalized USD answers of an asset, newest first
+    pub fn get_recent_answers(&self, asset_id: AssetId, limit: Option<u64>) -> Vec<RoundAnswer> {
+        self.assert_not_paused();
+        self.asset_config(&asset_id);
+        self.assert_breaker_closed(&Pair::usd(asset_id.clone()));
+        self.history
+            .get(&asset_id)
+            .map(|answers| {
+                answers
+                    .iter()
+                    .rev()
+                    .take(limit.unwrap_or(self.history_length) as usize)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Get the kept USD answers of an asset as (round_id, price, timestamp, n_sources),
+    /// oldest first, so index 0 is the oldest answer still kept
+    pub fn get_price_history(
+        &self,
+        asset_id: AssetId,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<(u64, U128, u64, u8)> {
+        self.assert_not_paused();
+        self.asset_config(&asset_id);
+        self.assert_breaker_closed(&Pair::usd(asset_id.clone()));
+        let entries = self
+            .history
+            .get(&asset_id)
+            .map(|answers| {
+                answers
+                    .iter()
+                    .map(|answer| (answer.round_id, U128(answer.price), answer.finalized_at, answer.submissions))
+                    .collect()
+            })
+            .unwrap_or_default();
+        paginate(entries, from_index, limit)
+    }
+
+    /// Get the latest USD answer of an asset that finalized at or before `block_height`,
+    /// so disputes can be settled against the chain's own ordering
+    pub fn get_price_at_block(&self, asset_id: AssetId, block_height: u64) -> RoundAnswer {
+        self.assert_not_paused();
+        self.asset_config(&asset_id);
+        self.assert_breaker_closed(&Pair::usd(asset_id.clone()));
+        self.history
+            .get(&asset_id)
+            .and_then(|answers| {
+                let after = answers.partition_point(|answer| answer.finalized_at_block <= block_height);
+                answers.get(after.checked_sub(1)?).cloned()
+            })
+            .unwrap_or_else(|| panic!("No kept answer of {} at or before block {}", asset_id, block_height))
+    }
+
+    /// Get how many finalized answers are kept per asset
+    pub fn get_history_length(&self) -> u64 {
+        self.history_length
+    }
+
+    /// Set how many finalized answers are kept per asset (ConfigManager)
+    /// Shrinking drops the oldest answers right away
+    pub fn set_history_length(&mut self, length: u64) {
+        self.assert_role(Role::ConfigManager);
+        assert!(length > 0, "History length must be positive");
+        self.history_length = length;
+        let cutoff = self.history_cutoff();
+        for answers in self.history.values_mut() {
+            prune_answers(answers, length, cutoff, u64::MAX);
+        }
+    }
+
+    /// Get the seconds after which a kept answer is pruned, if answers expire
+    pub fn get_history_max_age(&self) -> Option<u64> {
+        self.history_max_age
+    }
+
+    /// Set or clear the seconds after which a kept answer is pruned (ConfigManager)
+    /// Expired answers are pruned as new ones are recorded, or with prune_history
+    pub fn set_history_max_age(&mut self, max_age_seconds: Option<u64>) {
+        self.assert_role(Role::ConfigManager);
+        assert!(max_age_seconds != Some(0), "History max age must be positive");
+        self.history_max_age = max_age_seconds;
+    }
+
+    /// Drop up to `limit` of an asset's oldest answers that are past the retention
+    /// length or max age, for feeds that stopped updating. Anyone can call this
+    /// Returns how many answers were dropped
+    pub fn prune_history(&mut self, asset_id: AssetId, limit: Option<u64>) -> u64 {
+        let cutoff = self.history_cutoff();
+        let length = self.history_length;
+        let Some(answers) = self.history.get_mut(&asset_id) else {
+            return 0;
+        };
+        let pruned = prune_answers(answers, length, cutoff, limit.unwrap_or(DEFAULT_PAGE_LIMIT));
+        if answers.is_empty() {
+            self.history.remove(&asset_id);
+        }
+        near_sdk::log!("History of {} pruned: {} answers", asset_id, pruned);
+        pruned
+    }
+}
+
+impl PriceOracle {
+    /// Appends a USD pair's latest answer to its base asset's history
+    pub(crate) fn track_history(&mut self, pair: &Pair) {
+        if pair.quote != DEFAULT_QUOTE {
+            return;
+        }
+        let Some(answer) = self.latest_answer(pair).cloned() else {
+            return;
+        };
+        let (cutoff, length) = (self.history_cutoff(), self.history_length);
+        let answers = self.history.entry(pair.base.clone()).or_default();
+        answers.push_back(answer);
+        prune_answers(answers, length, cutoff, u64::MAX);
+    }
+
+    /// Answers that finalized before this have expired
+    fn history_cutoff(&self) -> u64 {
+        self.history_max_age
+            .map(|max_age| now_ms().saturating_sub(max_age * 1000))
+            .unwrap_or(0)
+    }
+}
+
+/// Drops up to `limit` answers from the front while there are more than `length` or
+/// the oldest finalized before `cutoff`
+fn prune_answers(answers: &mut VecDeque<RoundAnswer>, length: u64, cutoff: u64, limit: u64) -> u64 {
+    let mut pruned = 0;
+    while pruned < limit {
+        let expired = answers.front().is_some_and(|oldest| oldest.finalized_at < cutoff);
+        if answers.len() as u64 <= length && !expired {
+            break;
+        }
+        answers.pop_front();
+        pruned += 1;
+    }
+    pruned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn setup() -> PriceOracle {
+        testing_env!(VMContextBuilder::new().build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.set_history_length(3);
+        for price in [5000000, 5100000, 5200000, 5300000] {
+            contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(price), 6);
+        }
+        contract
+    }
+
+    #[test]
+    fn test_history_keeps_latest_answers() {
+        let contract = setup();
+        let prices: Vec<u128> = contract
+            .get_recent_answers("NEAR".to_string(), None)
+            .iter()
+            .map(|answer| answer.price)
+            .collect();
+        assert_eq!(prices, [5300000, 5200000, 5100000]);
+        assert_eq!(contract.get_recent_answers("NEAR".to_string(), Some(1))[0].round_id, 4);
+    }
+
+    #[test]
+    fn test_price_history_pages() {
+        let contract = setup();
+        assert_eq!(
+            contract.get_price_history("NEAR".to_string(), Some(1), Some(5)),
+            [(3, U128(5200000), 0, 1), (4, U128(5300000), 0, 1)]
+        );
+        assert!(contract.get_price_history("NEAR".to_string(), Some(3), None).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown asset BTC")]
+    fn test_price_history_unknown_asset() {
+        let contract = setup();
+        contract.get_price_history("BTC".to_string(), None, None);
+    }
+
+    #[test]
+    fn test_price_at_block() {
+        let mut contract = setup();
+        for (block, price) in [(100, 6000000), (110, 6100000)] {
+            testing_env!(VMContextBuilder::new().block_height(block).build());
+            contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(price), 6);
+        }
+        assert_eq!(contract.get_price_at_block("NEAR".to_string(), 109).price, 6000000);
+        assert_eq!(contract.get_price_at_block("NEAR".to_string(), 110).price, 6100000);
+        assert_eq!(contract.get_price_at_block("NEAR".to_string(), 5000).finalized_at_block, 110);
+    }
+
+    #[test]
+    #[should_panic(expected = "No kept answer of NEAR at or before block 99")]
+    fn test_price_before_kept_blocks() {
+        let mut contract = setup();
+        testing_env!(VMContextBuilder::new().block_height(100).build());
+        for price in [6000000, 6100000, 6200000] {
+            contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(price), 6);
+        }
+        contract.get_price_at_block("NEAR".to_string(), 99);
+    }
+
+    #[test]
+    fn test_expired_answers_pruned() {
+        let mut contract = setup();
+        contract.set_history_max_age(Some(60));
+        testing_env!(VMContextBuilder::new().block_timestamp(30 * 1_000_000_000).build());
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(6000000), 6);
+        assert_eq!(contract.get_price_history("NEAR".to_string(), None, None).len(), 3);
+
+        // The feed stops: nothing is written, so the expired answers wait for a prune
+        testing_env!(VMContextBuilder::new().block_timestamp(61 * 1_000_000_000).build());
+        assert_eq!(contract.prune_history("NEAR".to_string(), Some(1)), 1);
+        assert_eq!(contract.prune_history("NEAR".to_string(), None), 1);
+        assert_eq!(contract.get_recent_answers("NEAR".to_string(), None)[0].price, 6000000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Oracle paused")]
+    fn test_recent_answers_closed_while_paused() {
+        let mut contract = setup();
+        contract.set_paused(true);
+        contract.get_recent_answers("NEAR".to_string(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Circuit breaker tripped for NEAR/USD")]
+    fn test_recent_answers_closed_while_tripped() {
+        let mut contract = setup();
+        contract.trip_breaker_for_tests(&Pair::usd("NEAR"));
+        contract.get_recent_answers("NEAR".to_string(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Oracle paused")]
+    fn test_price_history_closed_while_paused() {
+        let mut contract = setup();
+        contract.set_paused(true);
+        contract.get_price_history("NEAR".to_string(), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Circuit breaker tripped for NEAR/USD")]
+    fn test_price_history_closed_while_tripped() {
+        let mut contract = setup();
+        contract.trip_breaker_for_tests(&Pair::usd("NEAR"));
+        contract.get_price_history("NEAR".to_string(), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Oracle paused")]
+    fn test_price_at_block_closed_while_paused() {
+        let mut contract = setup();
+        contract.set_paused(true);
+        contract.get_price_at_block("NEAR".to_string(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Circuit breaker tripped for NEAR/USD")]
+    fn test_price_at_block_closed_while_tripped() {
+        let mut contract = setup();
+        contract.trip_breaker_for_tests(&Pair::usd("NEAR"));
+        contract.get_price_at_block("NEAR".to_string(), 0);
+    }
+
+    #[test]
+    fn test_shrinking_history_drops_oldest() {
+        let mut contract = setup();
+        contract.set_history_length(1);
+        let answers = contract.get_recent_answers("NEAR".to_string(), None);
+        assert_eq!(answers.len(), 1);
+        assert_eq!(answers[0].price, 5300000);
+    }
+}
@@ -0,0 +1,64 @@
+use near_sdk::json_types::U128;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Serde adapter writing a u128 field as a near_sdk U128, i.e. a decimal string, so
+/// JS clients don't round prices and balances through a float
+/// Fields using it declare `#[schemars(with = "String")]` to match
+pub(crate) mod u128_string {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+        U128(*value).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+        U128::deserialize(deserializer).map(|value| value.0)
+    }
+}
+
+/// u128_string for an optional field; None stays null
+/// Fields using it declare `#[schemars(with = "Option<String>")]` to match
+pub(crate) mod option_u128_string {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &Option<u128>, serializer: S) -> Result<S::Ok, S::Error> {
+        value.map(U128).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u128>, D::Error> {
+        Option::<U128>::deserialize(deserializer).map(|value| value.map(|value| value.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BatchReport, Pair, Price, PriceReport};
+    use near_sdk::serde_json;
+
+    #[test]
+    fn test_amounts_written_as_strings() {
+        let price = Price { price: u128::MAX, decimals: 6, confidence: 25000 };
+        let json = serde_json::to_string(&price).unwrap();
+        assert_eq!(json, format!(r#"{{"price":"{}","decimals":6,"confidence":"25000"}}"#, u128::MAX));
+        assert_eq!(serde_json::from_str::<Price>(&json).unwrap(), price);
+    }
+
+    #[test]
+    fn test_optional_amounts() {
+        let json = r#"{"source":"binance","price":"5250000","decimals":6,"timestamp":1000,"reporter":"bob.near"}"#;
+        let report: PriceReport = serde_json::from_str(json).unwrap();
+        assert_eq!((report.price, report.volume), (5250000, None));
+
+        let report = PriceReport { volume: Some(48000000), ..report };
+        assert!(serde_json::to_string(&report).unwrap().ends_with(r#""volume":"48000000"}"#));
+        assert!(serde_json::from_str::<PriceReport>(r#"{"source":"binance","price":5250000,"decimals":6,"timestamp":1000,"reporter":"bob.near"}"#).is_err());
+    }
+
+    #[test]
+    fn test_batch_amounts_read_as_strings() {
+        let json = r#"{"pair":{"base":"NEAR","quote":"USD"},"source":"binance","price":"5250000","decimals":6}"#;
+        let report: BatchReport = serde_json::from_str(json).unwrap();
+        assert_eq!(report, BatchReport { pair: Pair::usd("NEAR"), source: "binance".to_string(), price: 5250000, decimals: 6, volume: None });
+        assert!(serde_json::from_str::<BatchReport>(&json.replace(r#""5250000""#, "5250000")).is_err());
+    }
+}
@@ -1,96 +1,747 @@
-use near_sdk::{env, near, AccountId};
+#[cfg(feature = "contract")]
+use near_sdk::{env, near, AccountId, BorshStorageKey, PanicOnDefault, PublicKey};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
 use borsh::BorshSchema;
+#[cfg(feature = "abi")]
 use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
-use std::collections::HashMap;
+#[cfg(feature = "contract")]
+use near_sdk::json_types::U128;
+#[cfg(feature = "contract")]
+use near_sdk::store::{IterableMap, LazyOption, LookupMap};
+#[cfg(feature = "contract")]
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+#[cfg(feature = "contract")]
+use aggregation::{Aggregator, Sample};
+#[cfg(feature = "contract")]
+use guard::Verdict;
+#[cfg(feature = "contract")]
+use book::PriceBook;
+#[cfg(feature = "contract")]
+use round::FeedRounds;
+
+#[cfg(feature = "contract")]
+mod access;
+#[cfg(feature = "contract")]
+mod aggregation;
+#[cfg(feature = "contract")]
+mod alerts;
+#[cfg(feature = "contract")]
+mod asset;
+#[cfg(feature = "contract")]
+mod batch;
+#[cfg(feature = "contract")]
+mod breaker;
+#[cfg(feature = "contract")]
+mod book;
+#[cfg(feature = "contract")]
+mod cache;
+#[cfg(feature = "contract")]
+mod chainlink;
+#[cfg(feature = "contract")]
+mod commit;
+mod compact;
+#[cfg(any(feature = "consumer", test))]
+pub mod consumer;
+#[cfg(feature = "contract")]
+mod convert;
+#[cfg(feature = "contract")]
+mod council;
+#[cfg(feature = "contract")]
+mod cross;
+#[cfg(feature = "contract")]
+mod dex;
+#[cfg(feature = "contract")]
+mod ema;
+#[cfg(feature = "contract")]
+mod emergency;
+#[cfg(feature = "contract")]
+mod events;
+#[cfg(feature = "contract")]
+mod fees;
+#[cfg(feature = "contract")]
+mod flux;
+#[cfg(feature = "contract")]
+mod governance;
+#[cfg(feature = "contract")]
+mod guard;
+#[cfg(feature = "contract")]
+mod heartbeat;
+#[cfg(feature = "contract")]
+mod history;
+mod json;
+#[cfg(feature = "contract")]
+mod lp;
+#[cfg(feature = "contract")]
+mod math;
+#[cfg(feature = "contract")]
+mod merkle;
+#[cfg(feature = "contract")]
+mod migrate;
+#[cfg(feature = "contract")]
+mod outlier;
+mod pair;
+#[cfg(feature = "contract")]
+mod pause;
+#[cfg(feature = "contract")]
+mod price_data;
+#[cfg(feature = "contract")]
+mod push;
+#[cfg(feature = "contract")]
+mod pyth;
+#[cfg(feature = "contract")]
+mod rebate;
+#[cfg(feature = "contract")]
+mod relay;
+#[cfg(feature = "contract")]
+mod reporter;
+#[cfg(feature = "contract")]
+mod request;
+#[cfg(feature = "contract")]
+mod rewards;
+#[cfg(feature = "contract")]
+mod round;
+#[cfg(feature = "contract")]
+mod signed;
+#[cfg(feature = "contract")]
+mod stablecoin;
+#[cfg(feature = "contract")]
+mod staking;
+#[cfg(feature = "contract")]
+mod state;
+#[cfg(feature = "contract")]
+mod stats;
+#[cfg(feature = "contract")]
+mod storage;
+#[cfg(feature = "contract")]
+mod subscription;
+#[cfg(feature = "contract")]
+mod tee;
+#[cfg(feature = "contract")]
+mod timelock;
+#[cfg(feature = "contract")]
+mod twap;
+#[cfg(feature = "contract")]
+mod upgrade;
+#[cfg(feature = "contract")]
+mod weighted;
+
+#[cfg(feature = "contract")]
+pub use access::Role;
+#[cfg(feature = "contract")]
+pub use aggregation::AggregationMethod;
+#[cfg(feature = "contract")]
+pub use alerts::AlertThreshold;
+#[cfg(feature = "contract")]
+pub use asset::{AssetConfig, AssetInfo, DEFAULT_DECIMALS, MAX_DECIMALS};
+#[cfg(feature = "contract")]
+pub use batch::{BatchReport, ReportStatus, MAX_BATCH_REPORTS};
+#[cfg(feature = "contract")]
+pub use breaker::{BreakerTrip, CircuitBreaker};
+#[cfg(feature = "contract")]
+pub use cache::CachedAggregate;
+#[cfg(feature = "contract")]
+pub use chainlink::AggregatorRoundData;
+#[cfg(feature = "contract")]
+pub use commit::{commitment_hash, CommitPhase, CommitReveal, Commitment};
+#[cfg(any(feature = "consumer", test))]
+pub use consumer::ext_price_oracle;
+#[cfg(feature = "contract")]
+pub use council::{Council, CouncilAction, Proposal};
+#[cfg(feature = "contract")]
+pub use cross::CrossRate;
+#[cfg(feature = "contract")]
+pub use dex::{RefSource, REF_SOURCE_PREFIX};
+#[cfg(feature = "contract")]
+pub use ema::{EmaState, DEFAULT_EMA_HALF_LIFE_SECONDS};
+#[cfg(feature = "contract")]
+pub use emergency::{PriceOverride, MAX_OVERRIDE_SECONDS};
+#[cfg(feature = "contract")]
+pub use events::OracleEvent;
+#[cfg(feature = "contract")]
+pub use flux::{FluxFeed, FLUX_SOURCE_PREFIX};
+#[cfg(feature = "contract")]
+pub use governance::{GovernanceAction, DAO_IN_PROGRESS};
+#[cfg(feature = "contract")]
+pub use guard::{DeviationGate, PriceBounds, RateLimit, RejectReason, MAX_FUTURE_DRIFT_SECONDS};
+#[cfg(feature = "contract")]
+pub use heartbeat::{HeartbeatStatus, StalenessPolicy, DEFAULT_MAX_MISSED_HEARTBEATS};
+#[cfg(feature = "contract")]
+pub use history::DEFAULT_HISTORY_LENGTH;
+#[cfg(feature = "contract")]
+pub use lp::{LpPrice, LpToken, RefPoolInfo};
+#[cfg(feature = "contract")]
+pub use math::Rounding;
+#[cfg(feature = "contract")]
+pub use merkle::{merkle_leaf_hash, merkle_node_hash, merkle_root_message, MerkleBatch, MerkleLeaf, SignedRoot, MAX_MERKLE_LEAVES};
+#[cfg(feature = "contract")]
+pub use outlier::{ExcludedReport, OutlierAnalysis, MAD_MULTIPLIER_SCALE};
+pub use pair::{Pair, DEFAULT_QUOTE};
+#[cfg(feature = "contract")]
+pub use price_data::{AssetOptionalPrice, AssetPrice, PriceData, DEFAULT_RECENCY_DURATION_SECONDS};
+#[cfg(feature = "contract")]
+pub use push::{PushSubscriber, MAX_PUSH_FAILURES, MAX_PUSH_GAS_TGAS, MAX_PUSH_SUBSCRIBERS, PUSH_SUBSCRIBER_OVERHEAD_BYTES};
+#[cfg(feature = "contract")]
+pub use pyth::{PriceIdentifier, PythPrice};
+#[cfg(feature = "contract")]
+pub use rebate::{GasRebate, RebateAccount};
+#[cfg(feature = "contract")]
+pub use relay::{GuardianSet, RelayEmitter, RELAY_PRICE_PAYLOAD};
+#[cfg(feature = "contract")]
+pub use reporter::{PendingReporter, SourceInfo, MAX_SOURCE_LENGTH, REPORTER_RECORD_BYTES};
+#[cfg(feature = "contract")]
+pub use request::{PriceRequest, DEFAULT_REQUEST_TIMEOUT_SECONDS, MAX_OPEN_REQUESTS, REQUEST_STORAGE_BYTES};
+#[cfg(feature = "contract")]
+pub use rewards::{RewardAccount, RewardConfig, DEFAULT_REWARD_BAND_BPS, DEFAULT_REWARD_PERIOD_SECONDS};
+#[cfg(feature = "contract")]
+pub use round::{Round, DEFAULT_ROUND_TIMEOUT_SECONDS};
+#[cfg(feature = "contract")]
+pub use signed::{packet_message, SignedPrice};
+#[cfg(feature = "contract")]
+pub use stablecoin::{DepegStatus, StablecoinConfig};
+#[cfg(feature = "contract")]
+pub use staking::{SlashingConfig, Unbonding, DEFAULT_UNBONDING_PERIOD_SECONDS};
+#[cfg(feature = "contract")]
+pub use state::{VersionedState, STATE_VERSION};
+#[cfg(feature = "contract")]
+pub use stats::ReporterStats;
+#[cfg(feature = "contract")]
+pub use storage::{StorageAccount, REPORT_OVERHEAD_BYTES};
+#[cfg(feature = "contract")]
+pub use subscription::{Subscription, BALANCE_ENTRY_BYTES};
+#[cfg(feature = "contract")]
+pub use tee::MAX_ATTESTATION_BYTES;
+#[cfg(feature = "contract")]
+pub use timelock::{ConfigChange, PendingChange, MAX_TIMELOCK_DELAY_SECONDS};
+#[cfg(feature = "contract")]
+pub use twap::{TwapAccumulator, TwapCheckpoint, TWAP_GRANULARITY_SECONDS, TWAP_MAX_CHECKPOINTS};
+#[cfg(feature = "contract")]
+pub use upgrade::MIGRATE_GAS_TGAS;
+#[cfg(feature = "contract")]
+pub use weighted::{SourceWeight, WeightedPrice};
+
+/// Identifier of a priced asset, e.g. "NEAR", "ETH", "BTC" or a NEP-141 token
+pub type AssetId = String;
+
+/// Page size used by paginated views when no limit is given
+#[cfg(feature = "contract")]
+pub const DEFAULT_PAGE_LIMIT: u64 = 100;
 
 /// Price data from a single source
-#[derive(BorshSerialize, BorshDeserialize, BorshSchema, JsonSchema, Serialize, Deserialize, Clone)]
+/// Stored in the compact encoding of src/compact.rs; its JSON keeps these fields
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(JsonSchema))]
 pub struct PriceReport {
     pub source: String,        // e.g., "coingecko", "binance", "coinmarketcap"
-    pub price_usd: u128,       // Price in micro-dollars (multiply by 1e6 for actual USD)
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub price: u128,           // Price in quote units scaled by 10^decimals
+    pub decimals: u8,          // Decimals of the base asset at report time
     pub timestamp: u64,        // Unix timestamp
     pub reporter: String,      // Account that submitted the price
+    #[serde(default)]
+    #[serde(with = "crate::json::option_u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "Option<String>"))]
+    pub volume: Option<u128>,  // Trading volume behind the price, in a unit shared by all sources
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attestation: Option<Attestation>, // TEE evidence, from report_attested_price
+}
+
+/// Evidence a report was produced inside a TEE, e.g. an SGX enclave or Nitro instance
+/// The oracle records it as given; consumers trust reports by their measurement
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct Attestation {
+    pub measurement: String,   // Hex enclave measurement: SGX MRENCLAVE, Nitro PCR0, ...
+    pub quote_hash: String,    // Hex hash of the attestation quote or document, to audit off chain
+}
+
+/// Aggregated price of a pair
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct Price {
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub price: u128,           // Price in quote units scaled by 10^decimals
+    pub decimals: u8,          // e.g. 6 means 5250000 = 5.25
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub confidence: u128,      // Half the spread between the sources, same scale as price
+}
+
+/// Latest answer of a pair with what a consumer needs to judge it in one call
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct PriceResponse {
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub price: u128,           // Price in quote units scaled by 10^decimals
+    pub decimals: u8,
+    pub timestamp: u64,        // When the answer's round finalized, Unix ms
+    pub n_sources: u8,         // Sources of the pair live right now
+    pub sources_used: u8,      // Submissions the answer aggregated
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub confidence: u128,      // Half the spread between the sources, same scale as price
+    #[serde(default)]
+    pub is_override: bool,     // Set by the owner during an outage instead of aggregated from sources
+}
+
+/// Entry of a get_prices response: the asset's latest USD answer, or why it has none
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub enum PriceResult {
+    Price(PriceResponse),
+    Error(String),        // What get_price would have panicked with
 }
 
-/// Main oracle state
-#[near(contract_state)]
+/// Immutable result of a finalized round
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct RoundAnswer {
+    pub round_id: u64,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub price: u128,
+    pub decimals: u8,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub confidence: u128,
+    pub submissions: u8,
+    pub started_at: u64,
+    pub finalized_at: u64,
+    pub finalized_at_block: u64, // Block height the round finalized in
+}
+
+impl RoundAnswer {
+    pub fn to_price(&self) -> Price {
+        Price { price: self.price, decimals: self.decimals, confidence: self.confidence }
+    }
+}
+
+/// Storage prefixes of the collections kept outside the contract's own state entry
+#[cfg(feature = "contract")]
+#[derive(BorshSerialize, BorshStorageKey)]
+pub(crate) enum StorageKey {
+    Prices,
+    AssetPrices { asset_id: AssetId },
+    Twap,
+    History,
+    StagedCode,
+    Rounds,
+    Quarantine,
+    Aggregates,
+    Ema,
+    Stakes,
+    Alerts,
+    PushSubscribers,
+    RewardAccounts,
+    RewardDistributions,
+    Subscriptions,
+    ReportCredits,
+}
+
+/// Main oracle state, stored as a VersionedState so its layout can change without a
+/// migration; methods reach the fields of the current layout through it
+/// Its NEP-330 `contract_source_metadata` reports the crate version stamped with the git
+/// commit it was built from (see build.rs), the repository and the standards implemented
+#[cfg(feature = "contract")]
+#[near(contract_state, contract_metadata(standard(standard = "nep297", version = "1.0.0")))]
+#[derive(PanicOnDefault)]
 pub struct PriceOracle {
+    state: VersionedState,
+}
+
+/// Fields of the current state layout
+#[cfg(feature = "contract")]
+#[near(serializers = [borsh])]
+pub struct OracleState {
     owner: AccountId,
-    prices: HashMap<String, PriceReport>,  // source -> latest price
+    pending_owner: Option<AccountId>,                      // Proposed owner awaiting acceptance
+    prices: PriceBook,                                     // base -> quote -> source -> latest price
+    rounds: IterableMap<Pair, FeedRounds>,                 // pair -> open round and latest answer
+    quarantine: IterableMap<Pair, HashMap<String, PriceReport>>, // pair -> source -> report held for review
+    assets: HashMap<AssetId, AssetConfig>,                 // asset -> configuration
+    token_assets: HashMap<AccountId, AssetId>,             // NEP-141 contract -> asset
+    stablecoins: HashMap<AssetId, StablecoinConfig>,       // stablecoin -> depeg parameters
+    peg_status: HashMap<AssetId, DepegStatus>,             // stablecoin -> tracked peg state
+    ref_exchange: Option<String>,                          // Ref Finance exchange contract
+    lp_pools: HashMap<u64, Vec<LpToken>>,                  // Ref pool id -> pool tokens
+    ref_sources: HashMap<u64, RefSource>,                  // Ref pool id -> pair its spot price feeds
+    flux_oracle: Option<String>,                           // Flux First-Party Oracle contract
+    flux_feeds: HashMap<Pair, FluxFeed>,                   // pair -> Flux provider feed read into it
+    aggregates: IterableMap<Pair, CachedAggregate>,        // pair -> aggregate of its latest reports
+    twap: IterableMap<Pair, TwapAccumulator>,              // pair -> cumulative price
+    ema: IterableMap<Pair, EmaState>,                      // pair -> smoothed price
+    stakes: LookupMap<AccountId, u128>,                    // reporter -> bonded stake (yoctoNEAR)
+    unbonding: HashMap<AccountId, Unbonding>,              // reporter -> stake leaving the bond
+    heartbeats: HashMap<String, u64>,                      // source -> expected seconds between reports
+    roles: HashMap<Role, HashSet<AccountId>>,              // role -> accounts granted it
+    source_reporters: HashMap<String, AccountId>,          // source -> only account that may report it
+    pending_reporters: HashMap<AccountId, PendingReporter>, // account -> registration awaiting approval
+    storage_deposits: HashMap<AccountId, u128>,            // approved reporter -> storage deposit held
+    storage_charging: bool,   // Reporters pay for the storage their reports take
+    storage_accounts: HashMap<AccountId, StorageAccount>,  // reporter -> bytes its reports take and deposit held
+    asset_storage: HashMap<AssetId, u64>,                  // asset -> bytes its pairs' reports take
+    council: Option<Council>,                              // Approves sensitive operations once installed
+    proposals: HashMap<u64, Proposal>,                     // proposal id -> pending council proposal
+    next_proposal_id: u64,
+    pending_changes: HashMap<u64, PendingChange>,          // change id -> parameter change awaiting its eta
+    next_change_id: u64,
+    timelock_delay: u64,      // Seconds parameter changes wait before applying (0 = immediate)
+    paused: bool,             // Reports and price views are halted
+    governance_actions: Vec<GovernanceAction>, // Owner DAO's open calls on this oracle, as last read
+    unbonding_period: u64,    // Seconds unbonded stake stays slashable
+    slashing: Option<SlashingConfig>,      // Penalty for submissions far from the answer
+    slashed_balance: u128,    // Slashed stake held by the contract (yoctoNEAR)
+    reward_config: RewardConfig,           // Accuracy band and distribution period
+    reward_pool: u128,        // Fees awaiting the next distribution (yoctoNEAR)
+    reward_accounts: IterableMap<AccountId, RewardAccount>, // reporter -> accurate submissions and rewards
+    reward_distributions: LookupMap<u64, (u128, u64)>,  // distribution -> pool paid out and accurate submissions sharing it
+    distributions: u64,       // Distributions that paid out so far
+    accurate_submissions: u64, // Accurate submissions counted toward the next distribution
+    reporter_stats: HashMap<AccountId, ReporterStats>,  // reporter -> report outcomes and accuracy
+    gas_rebate: Option<GasRebate>,                      // Rebate of the gas accepted reports burn
+    rebate_accounts: HashMap<AccountId, RebateAccount>, // reporter -> gas not yet rebated
+    reporter_keys: HashMap<AccountId, PublicKey>,       // reporter -> key signing its relayed packets
+    packet_nonces: HashMap<AccountId, u64>,             // reporter -> nonce of its last relayed packet
+    merkle_batches: HashMap<AccountId, MerkleBatch>,    // reporter -> its open signed Merkle root
+    guardian_set: Option<GuardianSet>,                  // Wormhole guardians relayed prices are signed by
+    relay_emitters: Vec<RelayEmitter>,                  // Contracts on other chains relaying prices
+    last_distribution: u64,   // When rewards were last distributed
+    query_fees: HashMap<AssetId, u128>,     // asset -> fee of a paid price query (yoctoNEAR)
+    collected_fees: HashMap<AssetId, u128>, // asset -> paid query fees not yet withdrawn
+    subscriptions: IterableMap<AccountId, u128>, // consumer -> prepaid balance for queries
+    report_deposit: u128,     // Attached to every report, kept if it's rejected (yoctoNEAR)
+    report_credits: LookupMap<AccountId, u128>, // account -> prepaid report deposits and storage
+    stale_bounty: u128,       // Paid from the reward pool for flagging a stale feed (yoctoNEAR)
+    stale_flags: HashMap<AssetId, u64>, // asset -> when its feed was last flagged stale
+    history: IterableMap<AssetId, VecDeque<RoundAnswer>>, // asset -> latest USD answers, oldest first
+    history_length: u64,      // Answers kept per asset
+    history_max_age: Option<u64>, // Seconds after which kept answers are pruned
+    alerts: LookupMap<AssetId, Vec<AlertThreshold>>, // asset -> thresholds its answers are watched for
+    push_subscribers: LookupMap<Pair, HashMap<String, PushSubscriber>>, // pair -> consumer -> push registration
+    price_requests: HashMap<u64, PriceRequest>, // request id -> open one-off price request
+    next_request_id: u64,
+    trusted_enclaves: BTreeMap<String, String>, // hex measurement -> description of the enclave
+    pyth_feeds: HashMap<String, Pair>,    // Pyth price identifier -> pair served under it
     last_update: u64,
     min_sources: u8,          // Minimum sources required for valid price
+    mad_multiplier: u16,      // Outlier cutoff in MADs x 100 (0 = no filtering)
+    ema_half_life: u64,       // Seconds after which an observation's EMA weight halves
+    round_quorum: Option<u8>, // Submissions that finalize a round (None = min_sources)
+    round_timeout: u64,       // Seconds before a round can close short of its quorum
+    deviation_gate: Option<DeviationGate>, // Limit on a report's distance from the last answer
+    rate_limit: Option<RateLimit>,         // Limit on how fast published answers move
+    circuit_breaker: Option<CircuitBreaker>, // Limits on new answers that trip their pair's breaker
+    tripped_breakers: HashMap<Pair, BreakerTrip>, // pair -> why its reads fail until reset
+    price_overrides: HashMap<AssetId, PriceOverride>, // asset -> USD price the owner set for an outage
+    disabled_sources: HashMap<AssetId, HashSet<String>>, // asset -> sources excluded from its prices
+    source_weights: HashMap<String, u32>,  // source -> weight a ConfigManager assigned it, 1 if absent
+    asset_min_sources: HashMap<AssetId, u8>, // asset -> its own min_sources, the global one if absent
+    commit_reveal: Option<CommitReveal>,   // Reports are committed, then revealed
+    commit_phases: HashMap<Pair, CommitPhase>, // pair -> current commit-reveal cycle
+    max_missed_heartbeats: u8,             // Missed heartbeats that exclude a source
+    max_answer_age: Option<u64>,           // Seconds after which is_valid distrusts the answer
+    staged_code: LazyOption<Vec<u8>>,      // Wasm awaiting deployment by upgrade
 }
 
-impl Default for PriceOracle {
-    fn default() -> Self {
+#[cfg(feature = "contract")]
+impl OracleState {
+    /// State of a fresh deployment owned by `owner`
+    fn initial(owner: AccountId) -> Self {
         Self {
-            owner: env::predecessor_account_id(),
-            prices: HashMap::new(),
+            owner,
+            pending_owner: None,
+            prices: PriceBook::new(),
+            rounds: IterableMap::new(StorageKey::Rounds),
+            quarantine: IterableMap::new(StorageKey::Quarantine),
+            assets: HashMap::new(),
+            token_assets: HashMap::new(),
+            stablecoins: HashMap::new(),
+            peg_status: HashMap::new(),
+            ref_exchange: None,
+            lp_pools: HashMap::new(),
+            ref_sources: HashMap::new(),
+            flux_oracle: None,
+            flux_feeds: HashMap::new(),
+            aggregates: IterableMap::new(StorageKey::Aggregates),
+            twap: IterableMap::new(StorageKey::Twap),
+            ema: IterableMap::new(StorageKey::Ema),
+            stakes: LookupMap::new(StorageKey::Stakes),
+            unbonding: HashMap::new(),
+            heartbeats: HashMap::new(),
+            roles: HashMap::new(),
+            source_reporters: HashMap::new(),
+            pending_reporters: HashMap::new(),
+            storage_deposits: HashMap::new(),
+            storage_charging: false,
+            storage_accounts: HashMap::new(),
+            asset_storage: HashMap::new(),
+            council: None,
+            proposals: HashMap::new(),
+            next_proposal_id: 0,
+            pending_changes: HashMap::new(),
+            next_change_id: 0,
+            timelock_delay: 0,
+            paused: false,
+            governance_actions: Vec::new(),
+            unbonding_period: DEFAULT_UNBONDING_PERIOD_SECONDS,
+            slashing: None,
+            slashed_balance: 0,
+            reward_config: RewardConfig::default(),
+            reward_pool: 0,
+            reward_accounts: IterableMap::new(StorageKey::RewardAccounts),
+            reward_distributions: LookupMap::new(StorageKey::RewardDistributions),
+            distributions: 0,
+            accurate_submissions: 0,
+            reporter_stats: HashMap::new(),
+            gas_rebate: None,
+            rebate_accounts: HashMap::new(),
+            reporter_keys: HashMap::new(),
+            packet_nonces: HashMap::new(),
+            merkle_batches: HashMap::new(),
+            guardian_set: None,
+            relay_emitters: Vec::new(),
+            last_distribution: 0,
+            query_fees: HashMap::new(),
+            collected_fees: HashMap::new(),
+            subscriptions: IterableMap::new(StorageKey::Subscriptions),
+            report_deposit: 0,
+            report_credits: LookupMap::new(StorageKey::ReportCredits),
+            stale_bounty: 0,
+            stale_flags: HashMap::new(),
+            history: IterableMap::new(StorageKey::History),
+            history_length: DEFAULT_HISTORY_LENGTH,
+            history_max_age: None,
+            alerts: LookupMap::new(StorageKey::Alerts),
+            push_subscribers: LookupMap::new(StorageKey::PushSubscribers),
+            price_requests: HashMap::new(),
+            next_request_id: 0,
+            trusted_enclaves: BTreeMap::new(),
+            pyth_feeds: HashMap::new(),
             last_update: 0,
             min_sources: 3,
+            mad_multiplier: 0,
+            ema_half_life: DEFAULT_EMA_HALF_LIFE_SECONDS,
+            round_quorum: None,
+            round_timeout: DEFAULT_ROUND_TIMEOUT_SECONDS,
+            deviation_gate: None,
+            rate_limit: None,
+            circuit_breaker: None,
+            tripped_breakers: HashMap::new(),
+            price_overrides: HashMap::new(),
+            disabled_sources: HashMap::new(),
+            source_weights: HashMap::new(),
+            asset_min_sources: HashMap::new(),
+            commit_reveal: None,
+            commit_phases: HashMap::new(),
+            max_missed_heartbeats: DEFAULT_MAX_MISSED_HEARTBEATS,
+            max_answer_age: None,
+            staged_code: LazyOption::new(StorageKey::StagedCode, None),
         }
     }
 }
 
+#[cfg(feature = "contract")]
 #[near]
 impl PriceOracle {
-    /// Initialize with custom min sources
+    /// Create the contract with an explicit owner, in the deploy transaction
+    /// Every other method fails until it has run, and it fails once state exists, so
+    /// nobody can claim an uninitialized or deployed contract by calling it
+    #[init]
+    pub fn new(owner: AccountId, min_sources: u8) -> Self {
+        OracleState { min_sources, ..OracleState::initial(owner) }.into()
+    }
+
+    /// Reset min sources after deployment (Admin, or a council proposal once installed)
+    /// Unlike `new` it never touches ownership, which only moves through
+    /// propose_owner/accept_ownership
     pub fn init(&mut self, min_sources: u8) {
-        assert_eq!(env::predecessor_account_id(), self.owner, "Only owner");
+        self.assert_role(Role::Admin);
+        self.assert_without_council("init");
         self.min_sources = min_sources;
+        self.invalidate_aggregates();
     }
 
-    /// Submit a price report from an external source
-    /// price_usd should be in micro-dollars (e.g., $5.25 = 5250000)
-    pub fn report_price(&mut self, source: String, price_usd: u128) {
-        let reporter = env::predecessor_account_id();
-        let timestamp = env::block_timestamp() / 1_000_000; // Convert from nanoseconds
+    /// Submit a price report for a pair from an external source
+    /// The caller must hold the Reporter role, and the pair's base
+    /// must be a registered asset
+    /// Reports failing a sanity check are dropped with a "Price rejected" log, or held
+    /// for Admin review with a "Price quarantined" log
+    /// price is scaled by the base asset's decimals, which the reporter must state
+    /// (e.g., NEAR/USD at $5.25 with 6 decimals = 5250000)
+    /// The report deposit must be attached; it's refunded unless the report is rejected
+    #[payable]
+    pub fn report_price(&mut self, pair: Pair, source: String, price: U128, decimals: u8) {
+        self.report_price_with_volume(pair, source, price, decimals, None);
+    }
 
-        let report = PriceReport {
-            source: source.clone(),
-            price_usd,
-            timestamp,
-            reporter: reporter.to_string(),
-        };
+    /// Submit a price report along with the trading volume behind it, which weights
+    /// the source in `get_vwap` (e.g. 24h USD volume; use one unit across sources)
+    #[payable]
+    pub fn report_price_with_volume(
+        &mut self,
+        pair: Pair,
+        source: String,
+        price: U128,
+        decimals: u8,
+        volume: Option<U128>,
+    ) {
+        self.assert_not_paused();
+        self.assert_direct_reports();
+        self.report_now(pair, source, price.0, decimals, volume.map(|volume| volume.0), None);
+    }
 
-        let src = source.clone();
-        self.prices.insert(source, report);
-        self.last_update = timestamp;
-        
-        near_sdk::log!("Price reported: {} USD from {}", price_usd, src);
+    /// Get the answer of a pair's latest finalized round, which combines the round's
+    /// submissions with the base asset's aggregation method (median by default, so a
+    /// single outlier can't move it)
+    /// Returns price scaled by the base asset's decimals, with a confidence of half the
+    /// spread between sources that consumers can widen their margins by, when the
+    /// answer finalized and how many sources it and the pair have, so consumers can
+    /// check freshness and quorum in the same call
+    /// While the owner overrides the pair's USD price, the override is served instead,
+    /// marked is_override
+    pub fn get_price(&self, pair: Pair) -> PriceResponse {
+        self.assert_not_paused();
+        self.price_result(&pair).unwrap_or_else(|err| panic!("{}", err))
     }
 
-    /// Get the aggregated NEAR price (average of all sources)
-    /// Returns price in micro-dollars
-    pub fn get_price(&self) -> u128 {
-        assert!(
-            self.prices.len() >= self.min_sources as usize,
-            "Need at least {} price sources, have {}",
-            self.min_sources,
-            self.prices.len()
-        );
+    /// Get the latest USD answers of several assets in one call, keyed by asset id
+    /// An asset without one (unknown, or short of sources) gets the error get_price
+    /// would have failed with instead of failing the whole call
+    pub fn get_prices(&self, asset_ids: Vec<AssetId>) -> BTreeMap<AssetId, PriceResult> {
+        self.assert_not_paused();
+        asset_ids
+            .into_iter()
+            .map(|asset_id| {
+                let pair = Pair::usd(asset_id.clone());
+                let result = if !self.assets.contains_key(&asset_id) {
+                    PriceResult::Error(format!("Unknown asset {}", asset_id))
+                } else {
+                    match self.price_result(&pair) {
+                        Ok(response) => PriceResult::Price(response),
+                        Err(err) => PriceResult::Error(err),
+                    }
+                };
+                (asset_id, result)
+            })
+            .collect()
+    }
 
-        let total: u128 = self.prices.values().map(|p| p.price_usd).sum();
-        let count = self.prices.len() as u128;
-        
-        total / count
+    /// Deprecated: use get_price, which returns the same fields and more
+    /// Get the price, decimals and confidence of a pair's latest answer
+    pub fn get_price_legacy(&self, pair: Pair) -> Price {
+        self.assert_not_paused();
+        self.assert_breaker_closed(&pair);
+        self.try_price(&pair).unwrap_or_else(|| self.panic_insufficient_sources(&pair))
+    }
+
+    /// Get the latest answer of a pair, panicking if it finalized more than
+    /// `max_age_seconds` ago, so consumers can enforce their own freshness in one call
+    pub fn get_price_with_max_age(&self, pair: Pair, max_age_seconds: u64) -> Price {
+        self.assert_not_paused();
+        self.assert_breaker_closed(&pair);
+        let answer = self
+            .latest_answer(&pair)
+            .unwrap_or_else(|| self.panic_insufficient_sources(&pair));
+        let age = now_ms().saturating_sub(answer.finalized_at) / 1000;
+        assert!(age <= max_age_seconds, "Price of {} is {}s old, max {}s", pair, age, max_age_seconds);
+        answer.to_price()
+    }
+
+    /// Get the average price of a pair across all sources
+    pub fn get_mean_price(&self, pair: Pair) -> Price {
+        self.assert_not_paused();
+        self.try_aggregate(&pair, &aggregation::Mean)
+            .unwrap_or_else(|| self.panic_insufficient_sources(&pair))
+    }
+
+    /// Get the volume-weighted average price of a pair, so thin venues count for less
+    /// Reports without a volume get weight 1
+    pub fn get_vwap(&self, pair: Pair) -> Price {
+        self.assert_not_paused();
+        let reports = self.accepted_reports(&pair);
+        if self.live_reports(&pair).len() < self.min_sources_of(&pair.base) as usize || reports.is_empty() {
+            self.panic_insufficient_sources(&pair);
+        }
+
+        let points: Vec<(u128, u128)> = reports.iter().map(|r| (r.price, r.volume.unwrap_or(1))).collect();
+        let prices: Vec<u128> = reports.iter().map(|r| r.price).collect();
+        Price {
+            price: aggregation::weighted_mean(&points),
+            decimals: self.asset_config(&pair.base).decimals,
+            confidence: aggregation::half_spread(&prices),
+        }
+    }
+
+    /// Get detailed price info from all sources of a pair
+    pub fn get_price_details(&self, pair: Pair) -> Vec<PriceReport> {
+        self.reports(&pair).into_iter().cloned().collect()
+    }
+
+    /// Get the reports of every pair based on an asset a page at a time, ordered by quote
+    /// then source so pages stay stable between calls
+    pub fn get_price_details_paginated(
+        &self,
+        asset_id: AssetId,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<(Pair, PriceReport)> {
+        let mut pairs = self.prices.pairs_of(&asset_id);
+        pairs.sort();
+        let mut details = Vec::new();
+        for pair in pairs {
+            let mut reports = self.reports(&pair);
+            reports.sort_by(|a, b| a.source.cmp(&b.source));
+            details.extend(reports.into_iter().map(|report| (pair.clone(), report)));
+        }
+        paginate(details, from_index, limit)
+            .into_iter()
+            .map(|(pair, report)| (pair, report.clone()))
+            .collect()
+    }
+
+    /// Get all pairs that have received at least one report
+    pub fn get_pairs(&self) -> Vec<Pair> {
+        let mut pairs = self.prices.pairs();
+        pairs.sort();
+        pairs
+    }
+
+    /// Get number of price sources for a pair, including expired ones
+    pub fn get_source_count(&self, pair: Pair) -> u8 {
+        self.reports(&pair).len() as u8
     }
 
-    /// Get detailed price info from all sources
-    pub fn get_price_details(&self) -> Vec<PriceReport> {
-        self.prices.values().cloned().collect()
+    /// Check if a pair has a trustworthy price: at least min_sources live reports and a
+    /// finalized answer no older than max_answer_age, while the oracle isn't paused and
+    /// the pair's circuit breaker isn't tripped
+    pub fn is_valid(&self, pair: Pair) -> bool {
+        if self.paused || self.breaker_tripped(&pair) {
+            return false;
+        }
+        let Some(answer) = self.latest_answer(&pair) else {
+            return false;
+        };
+        let fresh = self
+            .max_answer_age
+            .is_none_or(|max_age| now_ms().saturating_sub(answer.finalized_at) <= max_age * 1000);
+        fresh && self.live_reports(&pair).len() >= self.min_sources_of(&pair.base) as usize
     }
 
-    /// Get number of price sources
-    pub fn get_source_count(&self) -> u8 {
-        self.prices.len() as u8
+    /// Get the age in seconds after which is_valid stops trusting the latest answer
+    pub fn get_max_answer_age(&self) -> Option<u64> {
+        self.max_answer_age
     }
 
-    /// Check if we have enough sources for a valid price
-    pub fn is_valid(&self) -> bool {
-        self.prices.len() >= self.min_sources as usize
+    /// Set or clear the age in seconds after which is_valid stops trusting the latest
+    /// answer (ConfigManager)
+    pub fn set_max_answer_age(&mut self, max_age_seconds: Option<u64>) {
+        self.assert_role(Role::ConfigManager);
+        self.max_answer_age = max_age_seconds;
     }
 
     /// Get the last update timestamp
@@ -98,26 +749,290 @@ impl PriceOracle {
         self.last_update
     }
 
-    /// Get minimum sources required
+    /// Get minimum sources required of assets without their own min_sources
     pub fn get_min_sources(&self) -> u8 {
         self.min_sources
     }
 
-    /// Set minimum sources (owner only)
+    /// Set minimum sources (ConfigManager, or a council proposal once installed)
+    /// Subject to the timelock delay
     pub fn set_min_sources(&mut self, min_sources: u8) {
-        assert_eq!(env::predecessor_account_id(), self.owner, "Only owner");
-        self.min_sources = min_sources;
+        self.assert_role(Role::ConfigManager);
+        self.assert_without_council("set_min_sources");
+        self.request_config_change(ConfigChange::MinSources { min_sources });
     }
 
-    /// Clear all prices (for reset)
+    /// Clear all prices (for reset, Admin, or a council proposal once installed)
     pub fn clear_prices(&mut self) {
-        assert_eq!(env::predecessor_account_id(), self.owner, "Only owner");
-        self.prices.clear();
+        self.assert_role(Role::Admin);
+        self.assert_without_council("clear_prices");
+        self.clear_all_prices();
+    }
+}
+
+#[cfg(feature = "contract")]
+impl PriceOracle {
+    /// Takes the caller's report of a price observed now, as report_price_with_volume
+    pub(crate) fn report_now(
+        &mut self,
+        pair: Pair,
+        source: String,
+        price: u128,
+        decimals: u8,
+        volume: Option<u128>,
+        attestation: Option<Attestation>,
+    ) -> ReportStatus {
+        let source = reporter::normalize_source(&source);
+        self.assert_reporter(&source);
+        self.assert_report_deposit(1);
+        let expected = self.asset_config(&pair.base).decimals;
+        assert_eq!(
+            decimals, expected,
+            "{} prices use {} decimals, got {}",
+            pair.base, expected, decimals
+        );
+
+        let report = PriceReport {
+            source: source.clone(),
+            price,
+            decimals,
+            timestamp: now_ms(),
+            reporter: reporter::reporter_account().to_string(),
+            volume,
+            attestation,
+        };
+
+        let status = self.ingest_report(pair, report);
+        self.record_report_gas((status == ReportStatus::Accepted) as usize);
+        self.settle_report_deposit(status.is_rejected() as u128);
+        status
+    }
+
+    /// Drops every report, round and derived feed
+    pub(crate) fn clear_all_prices(&mut self) {
+        let removed = self.prices.clear();
+        self.release_reports_storage(removed);
+        self.invalidate_aggregates();
+        self.reset_rounds(|_| true);
+        self.quarantine.clear();
+        self.twap.clear();
+        self.ema.clear();
+        self.history.clear();
         self.last_update = 0;
     }
+
+    /// Runs a report through the sanity checks and accepts, rejects or quarantines it
+    pub(crate) fn ingest_report(&mut self, pair: Pair, report: PriceReport) -> ReportStatus {
+        let (price, source, reporter) = (report.price, report.source.clone(), report.reporter.clone());
+        let verdict = self.check_report(&pair, &report);
+        self.record_verdict(&reporter, &verdict);
+        match verdict {
+            Verdict::Accept => {
+                self.accept_report(&pair, report);
+                ReportStatus::Accepted
+            }
+            Verdict::Reject(code, reason) => {
+                near_sdk::log!("Price rejected for {}: {} from {} ({})", pair, price, source, reason);
+                OracleEvent::rejected(&pair, &source, &reporter, price, code, &reason).emit();
+                ReportStatus::Rejected(reason)
+            }
+            Verdict::Quarantine(reason) => {
+                near_sdk::log!("Price quarantined for {}: {} from {} ({})", pair, price, source, reason);
+                self.quarantine.entry(pair).or_default().insert(source, report);
+                ReportStatus::Quarantined(reason)
+            }
+        }
+    }
+
+    /// Records a report that passed the checks, emits price_reported and submits it to
+    /// the pair's round unless its source is disabled
+    pub(crate) fn accept_report(&mut self, pair: &Pair, report: PriceReport) {
+        self.last_update = report.timestamp;
+        let previous = self.prices.entry(pair).insert(report.source.clone(), report.clone());
+        self.track_report_storage(pair, previous.as_ref(), &report);
+        self.refresh_aggregate(pair);
+        OracleEvent::PriceReported { pair: pair.clone(), report: report.clone() }.emit();
+        if self.is_source_enabled(&pair.base, &report.source) {
+            self.submit_to_round(pair, report);
+        }
+    }
+
+    /// Answer of the latest finalized round of a pair, None until a round finalizes
+    pub(crate) fn try_price(&self, pair: &Pair) -> Option<Price> {
+        self.latest_answer(pair).map(|answer| answer.to_price())
+    }
+
+    /// Aggregate of the latest report of every source of a pair
+    pub(crate) fn try_aggregate(&self, pair: &Pair, aggregator: &dyn Aggregator) -> Option<Price> {
+        self.aggregate(pair, self.reports(pair), aggregator)
+    }
+
+    /// Aggregate of the live `reports` after outlier filtering, None if fewer than
+    /// min_sources are live
+    pub(crate) fn aggregate(
+        &self,
+        pair: &Pair,
+        reports: Vec<&PriceReport>,
+        aggregator: &dyn Aggregator,
+    ) -> Option<Price> {
+        let decimals = self.asset_config(&pair.base).decimals;
+        let reports: Vec<&PriceReport> = reports.into_iter().filter(|r| self.is_live(pair, r)).collect();
+        if reports.len() < self.min_sources_of(&pair.base) as usize {
+            return None;
+        }
+        let reports = self.without_outliers(reports);
+        if reports.is_empty() {
+            return None;
+        }
+
+        let prices: Vec<u128> = reports.iter().map(|p| p.price).collect();
+        let samples: Vec<Sample> = reports
+            .iter()
+            .zip(self.stake_weights(&reports))
+            .map(|(report, weight)| Sample {
+                price: report.price,
+                weight,
+                source_weight: self.source_weight(&report.source) as u128,
+            })
+            .collect();
+        Some(Price {
+            price: aggregator.aggregate(&samples),
+            decimals,
+            confidence: aggregation::half_spread(&prices),
+        })
+    }
+
+    pub(crate) fn panic_insufficient_sources(&self, pair: &Pair) -> ! {
+        panic!("{}", self.insufficient_sources(pair))
+    }
+
+    fn insufficient_sources(&self, pair: &Pair) -> String {
+        format!(
+            "Need at least {} price sources for {}, have {}",
+            self.min_sources_of(&pair.base),
+            pair,
+            self.live_reports(pair).len()
+        )
+    }
+
+    /// What get_price serves for a pair: an active override, else the latest answer unless
+    /// the pair's circuit breaker is tripped, or why there's nothing to serve
+    fn price_result(&self, pair: &Pair) -> Result<PriceResponse, String> {
+        if let Some(response) = self.override_response(pair) {
+            return Ok(response);
+        }
+        if let Some(err) = self.breaker_error(pair) {
+            return Err(err);
+        }
+        self.price_response(pair).ok_or_else(|| self.insufficient_sources(pair))
+    }
+
+    /// Latest answer of a pair with the pair's live source count
+    fn price_response(&self, pair: &Pair) -> Option<PriceResponse> {
+        let answer = self.latest_answer(pair)?;
+        Some(PriceResponse {
+            price: answer.price,
+            decimals: answer.decimals,
+            timestamp: answer.finalized_at,
+            n_sources: self.live_source_count(pair),
+            sources_used: answer.submissions,
+            confidence: answer.confidence,
+            is_override: false,
+        })
+    }
+
+    /// Sources an asset's prices need: its own min_sources, else the global one
+    pub(crate) fn min_sources_of(&self, asset_id: &AssetId) -> u8 {
+        self.asset_min_sources.get(asset_id).copied().unwrap_or(self.min_sources)
+    }
+
+    pub(crate) fn live_source_count(&self, pair: &Pair) -> u8 {
+        self.live_reports(pair).len().min(u8::MAX as usize) as u8
+    }
+
+    /// Runs after every finalized round so subsystems can react to the new answer
+    pub(crate) fn on_round_finalized(&mut self, pair: &Pair) {
+        self.track_peg(pair);
+        self.track_twap(pair);
+        self.track_ema(pair);
+        self.track_history(pair);
+        self.push_updates(pair);
+    }
+
+    /// Drops prices and derived feed state of every pair based on an asset
+    pub(crate) fn drop_asset_feeds(&mut self, asset_id: &AssetId) {
+        let removed = self.prices.remove_asset(asset_id);
+        self.release_reports_storage(removed);
+        remove_where(&mut self.aggregates, |pair, _| &pair.base == asset_id);
+        self.reset_rounds(|pair| &pair.base == asset_id);
+        remove_where(&mut self.quarantine, |pair, _| &pair.base == asset_id);
+        remove_where(&mut self.twap, |pair, _| &pair.base == asset_id);
+        remove_where(&mut self.ema, |pair, _| &pair.base == asset_id);
+        self.history.remove(asset_id);
+    }
+
+    /// Latest report from each source of a pair that still counts
+    pub(crate) fn live_reports(&self, pair: &Pair) -> Vec<&PriceReport> {
+        self.reports(pair).into_iter().filter(|r| self.is_live(pair, r)).collect()
+    }
+
+    /// Whether a report still counts: not older than its base asset's max_report_age and
+    /// from a source that's enabled and hasn't missed too many heartbeats
+    pub(crate) fn is_live(&self, pair: &Pair, report: &PriceReport) -> bool {
+        let expired = self
+            .asset_config(&pair.base)
+            .max_report_age
+            .is_some_and(|max_age| now_ms().saturating_sub(report.timestamp) > max_age * 1000);
+        !expired && self.is_source_enabled(&pair.base, &report.source) && !self.is_flatlined(pair, report)
+    }
+
+    /// Latest report from each source of a pair (empty if the pair is unknown)
+    pub(crate) fn reports(&self, pair: &Pair) -> Vec<&PriceReport> {
+        self.prices
+            .get(pair)
+            .map(|reports| reports.values().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Current block time in milliseconds
+#[cfg(feature = "contract")]
+pub(crate) fn now_ms() -> u64 {
+    env::block_timestamp() / 1_000_000
+}
+
+/// Slice of `items` starting at `from_index`, at most `limit` long
+#[cfg(feature = "contract")]
+pub(crate) fn paginate<T>(items: Vec<T>, from_index: Option<u64>, limit: Option<u64>) -> Vec<T> {
+    items
+        .into_iter()
+        .skip(from_index.unwrap_or(0) as usize)
+        .take(limit.unwrap_or(DEFAULT_PAGE_LIMIT) as usize)
+        .collect()
+}
+
+/// Removes the entries of `map` that match, since store collections have no retain
+#[cfg(feature = "contract")]
+pub(crate) fn remove_where<K, V>(map: &mut IterableMap<K, V>, matches: impl Fn(&K, &V) -> bool)
+where
+    K: BorshSerialize + BorshDeserialize + Ord + Clone,
+    V: BorshSerialize + BorshDeserialize,
+{
+    let keys: Vec<K> = map.iter().filter(|(key, value)| matches(key, value)).map(|(key, _)| key.clone()).collect();
+    for key in keys {
+        map.remove(&key);
+    }
+}
+
+/// Freshly deployed contract owned by the current predecessor, as tests start from
+#[cfg(all(test, feature = "contract"))]
+impl PriceOracle {
+    pub(crate) fn for_tests() -> Self {
+        OracleState::initial(env::predecessor_account_id()).into()
+    }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "contract"))]
 mod tests {
     use super::*;
     use near_sdk::test_utils::VMContextBuilder;
@@ -127,15 +1042,48 @@ mod tests {
         VMContextBuilder::new()
     }
 
+    fn new_contract(assets: &[&str]) -> PriceOracle {
+        let mut contract = PriceOracle::for_tests();
+        for asset in assets {
+            contract.add_asset(asset.to_string(), AssetConfig::new(*asset, *asset, DEFAULT_DECIMALS));
+        }
+        contract
+    }
+
     #[test]
     fn test_initialization() {
         let context = get_context().build();
         testing_env!(context);
         
-        let contract = PriceOracle::default();
+        let contract = PriceOracle::for_tests();
         assert_eq!(contract.get_min_sources(), 3);
-        assert_eq!(contract.get_source_count(), 0);
-        assert!(!contract.is_valid());
+        assert_eq!(contract.get_source_count(Pair::usd("NEAR")), 0);
+        assert!(!contract.is_valid(Pair::usd("NEAR")));
+    }
+
+    #[test]
+    fn test_new_sets_owner() {
+        testing_env!(get_context().build());
+        let contract = PriceOracle::new("gorked.near".parse().unwrap(), 2);
+        assert_eq!(contract.get_owner().as_str(), "gorked.near");
+        assert_eq!(contract.get_min_sources(), 2);
+        assert!(!contract.has_role(Role::Admin, "bob.near".parse().unwrap()));
+    }
+
+    #[test]
+    #[should_panic(expected = "The contract is not initialized")]
+    fn test_state_not_created_implicitly() {
+        testing_env!(get_context().build());
+        PriceOracle::default();
+    }
+
+    #[test]
+    fn test_init_keeps_owner() {
+        testing_env!(get_context().build());
+        let mut contract = PriceOracle::new("bob.near".parse().unwrap(), 2);
+        contract.init(4);
+        assert_eq!(contract.get_owner().as_str(), "bob.near");
+        assert_eq!(contract.get_min_sources(), 4);
     }
 
     #[test]
@@ -143,27 +1091,247 @@ mod tests {
         let context = get_context().build();
         testing_env!(context);
         
-        let mut contract = PriceOracle::default();
-        contract.report_price("coingecko".to_string(), 5250000); // $5.25
+        let mut contract = new_contract(&["NEAR"]);
+        contract.report_price(Pair::usd("NEAR"), "coingecko".to_string(), U128(5250000), 6); // $5.25
         
-        assert_eq!(contract.get_source_count(), 1);
+        assert_eq!(contract.get_source_count(Pair::usd("NEAR")), 1);
     }
 
     #[test]
-    fn test_get_price_average() {
+    fn test_get_mean_price() {
         let context = get_context().build();
         testing_env!(context);
         
-        let mut contract = PriceOracle::default();
-        contract.report_price("coingecko".to_string(), 5000000);  // $5.00
-        contract.report_price("binance".to_string(), 5200000);    // $5.20
-        contract.report_price("coinmarketcap".to_string(), 5400000); // $5.40
+        let mut contract = new_contract(&["NEAR"]);
+        contract.report_price(Pair::usd("NEAR"), "coingecko".to_string(), U128(5000000), 6);  // $5.00
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5200000), 6);    // $5.20
+        contract.report_price(Pair::usd("NEAR"), "coinmarketcap".to_string(), U128(5400000), 6); // $5.40
         
-        assert_eq!(contract.get_source_count(), 3);
-        assert!(contract.is_valid());
+        assert_eq!(contract.get_source_count(Pair::usd("NEAR")), 3);
+        assert!(contract.is_valid(Pair::usd("NEAR")));
         
         // Average should be $5.20
-        let price = contract.get_price();
+        let price = contract.get_mean_price(Pair::usd("NEAR")).price;
         assert_eq!(price, 5200000);
     }
+
+    #[test]
+    fn test_pairs_are_independent() {
+        let context = get_context().build();
+        testing_env!(context);
+        
+        let mut contract = new_contract(&["NEAR", "BTC"]);
+        contract.set_min_sources(1);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        contract.report_price(Pair::usd("BTC"), "binance".to_string(), U128(60000000000), 6);
+        
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5000000);
+        assert_eq!(contract.get_price(Pair::usd("BTC")).price, 60000000000);
+
+        contract.report_price(Pair::new("NEAR", "USDT"), "binance".to_string(), U128(5010000), 6);
+        assert_eq!(contract.get_price(Pair::new("NEAR", "USDT")).price, 5010000);
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5000000);
+        assert_eq!(
+            contract.get_pairs(),
+            vec![Pair::usd("BTC"), Pair::usd("NEAR"), Pair::new("NEAR", "USDT")]
+        );
+    }
+
+    #[test]
+    fn test_get_price_median() {
+        let context = get_context().build();
+        testing_env!(context);
+        
+        let mut contract = new_contract(&["NEAR"]);
+        contract.report_price(Pair::usd("NEAR"), "coingecko".to_string(), U128(5000000), 6);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5100000), 6);
+        contract.report_price(Pair::usd("NEAR"), "rogue".to_string(), U128(500000000), 6);   // $500
+        
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5100000);
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).confidence, 247500000);
+        assert_eq!(contract.get_mean_price(Pair::usd("NEAR")).price, 170033333);
+    }
+
+    #[test]
+    fn test_get_vwap() {
+        let context = get_context().build();
+        testing_env!(context);
+        
+        let mut contract = new_contract(&["NEAR"]);
+        let pair = Pair::usd("NEAR");
+        contract.report_price_with_volume(pair.clone(), "binance".to_string(), U128(5000000), 6, Some(U128(900)));
+        contract.report_price_with_volume(pair.clone(), "thin-dex".to_string(), U128(6000000), 6, Some(U128(100)));
+        contract.report_price_with_volume(pair.clone(), "zero-volume".to_string(), U128(9000000), 6, Some(U128(0)));
+        assert_eq!(contract.get_vwap(pair.clone()).price, 5100000);
+
+        contract.report_price(pair.clone(), "zero-volume".to_string(), U128(4000000), 6); // No volume: weight 1
+        assert_eq!(contract.get_vwap(pair).price, 5098901);
+    }
+
+    #[test]
+    fn test_get_price_with_max_age() {
+        testing_env!(get_context().block_timestamp(1_000_000_000_000).build());
+        let mut contract = new_contract(&["NEAR"]);
+        contract.set_min_sources(1);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+
+        testing_env!(get_context().block_timestamp(1_060_000_000_000).build());
+        assert_eq!(contract.get_price_with_max_age(Pair::usd("NEAR"), 60).price, 5000000);
+    }
+
+    #[test]
+    fn test_is_valid_requires_fresh_answer() {
+        testing_env!(get_context().block_timestamp(1_000_000_000_000).build());
+        let mut contract = new_contract(&["NEAR"]);
+        contract.set_min_sources(2);
+        contract.set_round_config(Some(3), 60);
+        contract.set_max_answer_age(Some(300));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        contract.report_price(Pair::usd("NEAR"), "coingecko".to_string(), U128(5100000), 6);
+        assert!(!contract.is_valid(Pair::usd("NEAR")));         // Enough sources, no answer yet
+
+        contract.report_price(Pair::usd("NEAR"), "kraken".to_string(), U128(5200000), 6);
+        assert!(contract.is_valid(Pair::usd("NEAR")));
+
+        testing_env!(get_context().block_timestamp(1_301_000_000_000).build());
+        assert!(!contract.is_valid(Pair::usd("NEAR")));         // Answer too old
+        contract.set_max_answer_age(None);
+        assert!(contract.is_valid(Pair::usd("NEAR")));
+    }
+
+    #[test]
+    #[should_panic(expected = "Price of NEAR/USD is 61s old, max 60s")]
+    fn test_get_price_with_max_age_stale() {
+        testing_env!(get_context().block_timestamp(1_000_000_000_000).build());
+        let mut contract = new_contract(&["NEAR"]);
+        contract.set_min_sources(1);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+
+        testing_env!(get_context().block_timestamp(1_061_000_000_000).build());
+        contract.get_price_with_max_age(Pair::usd("NEAR"), 60);
+    }
+
+    #[test]
+    #[should_panic(expected = "Need at least 3 price sources for ETH/USD, have 0")]
+    fn test_get_price_unknown_pair() {
+        let context = get_context().build();
+        testing_env!(context);
+        
+        let contract = new_contract(&["ETH"]);
+        contract.get_price(Pair::usd("ETH"));
+    }
+
+    #[test]
+    fn test_asset_decimals() {
+        let context = get_context().build();
+        testing_env!(context);
+        
+        let mut contract = new_contract(&["NEAR"]);
+        contract.set_min_sources(1);
+        contract.add_asset("BTC".to_string(), AssetConfig::new("BTC", "Bitcoin", 2));
+        assert_eq!(contract.get_decimals("BTC".to_string()), 2);
+        assert_eq!(contract.get_decimals("NEAR".to_string()), DEFAULT_DECIMALS);
+        
+        contract.report_price(Pair::usd("BTC"), "binance".to_string(), U128(6000050), 2); // $60000.50
+        let price = contract.get_price_legacy(Pair::usd("BTC"));
+        assert_eq!(price, Price { price: 6000050, decimals: 2, confidence: 0 });
+        assert_eq!(contract.get_price_details(Pair::usd("BTC"))[0].decimals, 2);
+    }
+
+    #[test]
+    fn test_price_response() {
+        testing_env!(get_context().block_timestamp(1_000_000_000).build());
+        let mut contract = new_contract(&["NEAR"]);
+        contract.set_min_sources(2);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        contract.report_price(Pair::usd("NEAR"), "kraken".to_string(), U128(5200000), 6);
+
+        testing_env!(get_context().block_timestamp(2_000_000_000).build());
+        contract.report_price(Pair::usd("NEAR"), "coinbase".to_string(), U128(5100000), 6);
+        assert_eq!(
+            contract.get_price(Pair::usd("NEAR")),
+            PriceResponse {
+                price: 5100000,
+                decimals: 6,
+                timestamp: 1000,
+                n_sources: 3,
+                sources_used: 2,
+                confidence: 100000,
+                is_override: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_contract_source_metadata() {
+        let metadata: near_sdk::serde_json::Value = near_sdk::serde_json::from_str(CONTRACT_SOURCE_METADATA).unwrap();
+        assert!(metadata["version"].as_str().unwrap().starts_with(env!("CARGO_PKG_VERSION")));
+        assert_eq!(metadata["link"], "https://github.com/Kampouse/near-price-oracle");
+        let standards: Vec<&str> = metadata["standards"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|standard| standard["standard"].as_str().unwrap())
+            .collect();
+        assert_eq!(standards, vec!["nep297", "nep330"]);
+    }
+
+    #[test]
+    fn test_get_prices() {
+        testing_env!(get_context().build());
+        let mut contract = new_contract(&["NEAR", "ETH"]);
+        contract.set_min_sources(2);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        contract.report_price(Pair::usd("NEAR"), "kraken".to_string(), U128(5200000), 6);
+        contract.report_price(Pair::usd("ETH"), "binance".to_string(), U128(3000000000), 6);
+
+        let prices = contract.get_prices(vec!["NEAR".to_string(), "ETH".to_string(), "DOGE".to_string()]);
+        assert_eq!(prices.len(), 3);
+        assert_eq!(prices["NEAR"], PriceResult::Price(contract.get_price(Pair::usd("NEAR"))));
+        assert_eq!(prices["ETH"], PriceResult::Error("Need at least 2 price sources for ETH/USD, have 1".to_string()));
+        assert_eq!(prices["DOGE"], PriceResult::Error("Unknown asset DOGE".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Oracle paused")]
+    fn test_get_prices_paused() {
+        testing_env!(get_context().build());
+        let mut contract = new_contract(&["NEAR"]);
+        contract.pause();
+        contract.get_prices(vec!["NEAR".to_string()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "BTC prices use 2 decimals, got 6")]
+    fn test_report_price_wrong_decimals() {
+        let context = get_context().build();
+        testing_env!(context);
+        
+        let mut contract = PriceOracle::for_tests();
+        contract.add_asset("BTC".to_string(), AssetConfig::new("BTC", "Bitcoin", 2));
+        contract.report_price(Pair::usd("BTC"), "binance".to_string(), U128(60000500000), 6);
+    }
+
+    #[test]
+    fn test_price_details_paginated() {
+        testing_env!(get_context().build());
+        let mut contract = new_contract(&["NEAR", "BTC"]);
+        for source in ["kraken", "binance", "coinbase"] {
+            contract.report_price(Pair::usd("NEAR"), source.to_string(), U128(5000000), 6);
+        }
+        contract.report_price(Pair::new("NEAR", "USDT"), "binance".to_string(), U128(5010000), 6);
+        contract.report_price(Pair::usd("BTC"), "binance".to_string(), U128(60000000000), 6);
+
+        let page = |from, limit| -> Vec<(String, String)> {
+            contract
+                .get_price_details_paginated("NEAR".to_string(), Some(from), Some(limit))
+                .into_iter()
+                .map(|(pair, report)| (pair.to_string(), report.source))
+                .collect()
+        };
+        let entry = |pair: &str, source: &str| (pair.to_string(), source.to_string());
+        assert_eq!(page(0, 2), [entry("NEAR/USD", "binance"), entry("NEAR/USD", "coinbase")]);
+        assert_eq!(page(2, 2), [entry("NEAR/USD", "kraken"), entry("NEAR/USDT", "binance")]);
+        assert!(page(4, 2).is_empty());
+    }
 }
@@ -0,0 +1,259 @@
+use near_sdk::{env, ext_contract, near, AccountId, Gas, Promise, PromiseError};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::math::{pow10, U256};
+use crate::{now_ms, AssetId, Pair, PriceOracle, PriceOracleExt, Role, DEFAULT_DECIMALS};
+
+/// Decimals of Ref Finance pool shares
+pub const LP_SHARE_DECIMALS: u8 = 24;
+
+/// Ref Finance pool kind the fair-LP formula applies to (x * y = k, equal weights)
+pub const SIMPLE_POOL: &str = "SIMPLE_POOL";
+
+const GAS_FOR_GET_POOL: Gas = Gas::from_tgas(10);
+const GAS_FOR_ON_LP_POOL: Gas = Gas::from_tgas(10);
+
+/// One side of a registered LP pool
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct LpToken {
+    pub token_id: String,      // NEP-141 contract held by the pool, e.g. "wrap.near"
+    pub asset_id: AssetId,     // Registered asset whose USD price values the reserve
+    pub decimals: u8,          // FT decimals of the token (not its price decimals)
+}
+
+/// Fair USD value of one pool share
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct LpPrice {
+    pub pool_id: u64,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub price: u128,           // USD per whole share, scaled by 10^decimals
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub tvl: u128,             // Manipulation-resistant pool value, scaled by 10^decimals
+    pub decimals: u8,
+    pub timestamp: u64,
+}
+
+/// Subset of Ref Finance's `get_pool` response we rely on
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "abi", derive(JsonSchema))]
+pub struct RefPoolInfo {
+    pub pool_kind: String,
+    #[cfg_attr(feature = "abi", schemars(with = "Vec<String>"))]
+    pub token_account_ids: Vec<AccountId>,
+    #[cfg_attr(feature = "abi", schemars(with = "Vec<String>"))]
+    pub amounts: Vec<U128>,
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub shares_total_supply: U128,
+}
+
+#[allow(dead_code)]
+#[ext_contract(ext_ref_exchange)]
+pub(crate) trait RefExchange {
+    fn get_pool(&self, pool_id: u64) -> RefPoolInfo;
+}
+
+#[near]
+impl PriceOracle {
+    /// Set the Ref Finance exchange contract pools are read from (ConfigManager)
+    pub fn set_ref_exchange(&mut self, exchange_id: AccountId) {
+        self.assert_role(Role::ConfigManager);
+        self.ref_exchange = Some(exchange_id.to_string());
+    }
+
+    /// Register a two-token simple pool whose shares can be priced (ConfigManager)
+    /// Tokens must be listed in the pool's order
+    pub fn add_lp_pool(&mut self, pool_id: u64, tokens: Vec<LpToken>) {
+        self.assert_role(Role::ConfigManager);
+        assert_eq!(tokens.len(), 2, "Only two-token pools are supported");
+        for token in &tokens {
+            assert!(token.token_id.parse::<AccountId>().is_ok(), "Invalid token id {}", token.token_id);
+            self.asset_config(&token.asset_id);
+        }
+        self.lp_pools.insert(pool_id, tokens);
+    }
+
+    /// Stop pricing a pool (ConfigManager)
+    pub fn remove_lp_pool(&mut self, pool_id: u64) {
+        self.assert_role(Role::ConfigManager);
+        assert!(self.lp_pools.remove(&pool_id).is_some(), "Unknown LP pool {}", pool_id);
+    }
+
+    /// Get the tokens of a registered pool
+    pub fn get_lp_pool(&self, pool_id: u64) -> Option<Vec<LpToken>> {
+        self.lp_pools.get(&pool_id).cloned()
+    }
+
+    /// Price one share of a Ref Finance pool from its reserves and the constituent feeds,
+    /// using the fair-LP formula 2 * sqrt(v0 * v1) / supply, which a swap can't inflate.
+    /// A change method because it has to read the pool through a cross-contract call
+    pub fn get_lp_price(&mut self, pool_id: u64) -> Promise {
+        let tokens = self.lp_tokens(pool_id);
+        for token in tokens {
+            self.get_price(Pair::usd(token.asset_id.clone()));
+        }
+        ext_ref_exchange::ext(self.ref_exchange_id())
+            .with_static_gas(GAS_FOR_GET_POOL)
+            .get_pool(pool_id)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_ON_LP_POOL)
+                    .on_lp_pool(pool_id),
+            )
+    }
+
+    #[private]
+    pub fn on_lp_pool(
+        &self,
+        pool_id: u64,
+        #[callback_result] pool: Result<RefPoolInfo, PromiseError>,
+    ) -> LpPrice {
+        let pool = pool.unwrap_or_else(|_| panic!("Failed to fetch LP pool {}", pool_id));
+        let tokens = self.lp_tokens(pool_id);
+
+        assert_eq!(pool.pool_kind, SIMPLE_POOL, "Pool {} is not a simple pool", pool_id);
+        let ids: Vec<&str> = pool.token_account_ids.iter().map(|id| id.as_str()).collect();
+        let expected: Vec<&str> = tokens.iter().map(|t| t.token_id.as_str()).collect();
+        assert_eq!(ids, expected, "Pool {} tokens don't match its registration", pool_id);
+        assert_eq!(pool.amounts.len(), tokens.len(), "Pool {} returned malformed reserves", pool_id);
+
+        let values: Vec<u128> = tokens
+            .iter()
+            .zip(&pool.amounts)
+            .map(|(token, amount)| {
+                let price = self.get_price(Pair::usd(token.asset_id.clone()));
+                reserve_value(amount.0, token.decimals, price.price, price.decimals)
+            })
+            .collect();
+        let (price, tvl) = fair_lp_price(values[0], values[1], pool.shares_total_supply.0)
+            .unwrap_or_else(|| panic!("Cannot price pool {}", pool_id));
+
+        LpPrice {
+            pool_id,
+            price,
+            tvl,
+            decimals: DEFAULT_DECIMALS,
+            timestamp: now_ms(),
+        }
+    }
+}
+
+impl PriceOracle {
+    pub(crate) fn ref_exchange_id(&self) -> AccountId {
+        self.ref_exchange
+            .as_ref()
+            .expect("Ref exchange not configured")
+            .parse()
+            .expect("invalid exchange id")
+    }
+
+    fn lp_tokens(&self, pool_id: u64) -> &Vec<LpToken> {
+        self.lp_pools
+            .get(&pool_id)
+            .unwrap_or_else(|| panic!("Unknown LP pool {}", pool_id))
+    }
+}
+
+/// USD value of a raw token amount, scaled by 10^DEFAULT_DECIMALS
+fn reserve_value(amount: u128, token_decimals: u8, price: u128, price_decimals: u8) -> u128 {
+    let scale = |d: u32| U256::from(pow10(d).expect("decimals bounded"));
+    let value = U256::from(amount) * U256::from(price) * scale(DEFAULT_DECIMALS as u32)
+        / scale(token_decimals as u32)
+        / scale(price_decimals as u32);
+    assert!(value <= U256::from(u128::MAX), "Reserve value overflows");
+    value.as_u128()
+}
+
+/// Fair share price and pool value from the USD value of both reserves
+fn fair_lp_price(value0: u128, value1: u128, supply: u128) -> Option<(u128, u128)> {
+    if supply == 0 {
+        return None;
+    }
+    let tvl = (U256::from(value0) * U256::from(value1)).integer_sqrt() * U256::from(2u8);
+    let share = U256::from(pow10(LP_SHARE_DECIMALS as u32)?);
+    let price = tvl * share / U256::from(supply);
+    if price > U256::from(u128::MAX) || tvl > U256::from(u128::MAX) {
+        return None;
+    }
+    Some((price.as_u128(), tvl.as_u128()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn setup() -> PriceOracle {
+        testing_env!(VMContextBuilder::new().build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.add_asset("USDC".to_string(), AssetConfig::new("USDC", "USD Coin", 6));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(4000000), 6);
+        contract.report_price(Pair::usd("USDC"), "binance".to_string(), U128(1000000), 6);
+        contract.set_ref_exchange("v2.ref-finance.near".parse().unwrap());
+        contract.add_lp_pool(
+            3,
+            vec![
+                LpToken { token_id: "wrap.near".to_string(), asset_id: "NEAR".to_string(), decimals: 24 },
+                LpToken { token_id: "usdc.near".to_string(), asset_id: "USDC".to_string(), decimals: 6 },
+            ],
+        );
+        contract
+    }
+
+    fn pool(amounts: [u128; 2], supply: u128) -> RefPoolInfo {
+        RefPoolInfo {
+            pool_kind: SIMPLE_POOL.to_string(),
+            token_account_ids: vec!["wrap.near".parse().unwrap(), "usdc.near".parse().unwrap()],
+            amounts: amounts.iter().map(|a| U128(*a)).collect(),
+            shares_total_supply: U128(supply),
+        }
+    }
+
+    #[test]
+    fn test_fair_lp_price() {
+        // 1000 NEAR at $4 and 4000 USDC: $8000 pool, 100 shares -> $80 per share
+        let contract = setup();
+        let one_near = 10u128.pow(24);
+        let price = contract.on_lp_pool(3, Ok(pool([1000 * one_near, 4000 * 1000000], 100 * one_near)));
+        assert_eq!(price.tvl, 8000 * 1000000);
+        assert_eq!(price.price, 80 * 1000000);
+        assert_eq!(price.decimals, 6);
+    }
+
+    #[test]
+    fn test_fair_lp_price_resists_skewed_reserves() {
+        // Same k as above but pushed off the market price by a swap: spot value would be
+        // 250 * 4 + 16000 = $17000, the fair value stays at $8000
+        let (_, tvl) = fair_lp_price(250 * 4 * 1000000, 16000 * 1000000, 1).unwrap();
+        assert_eq!(tvl, 8000 * 1000000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Pool 3 tokens don't match its registration")]
+    fn test_lp_pool_token_mismatch() {
+        let contract = setup();
+        let mut info = pool([1, 1], 1);
+        info.token_account_ids.reverse();
+        contract.on_lp_pool(3, Ok(info));
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to fetch LP pool 3")]
+    fn test_lp_pool_fetch_failure() {
+        let contract = setup();
+        contract.on_lp_pool(3, Err(PromiseError::Failed));
+    }
+}
@@ -0,0 +1,173 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+// construct_uint! expands to code clippy objects to; keep it out of the lint scope
+#[allow(clippy::all)]
+mod u256 {
+    uint::construct_uint! {
+        /// 256-bit unsigned integer for intermediate products of u128 values
+        pub struct U256(4);
+    }
+
+    uint::construct_uint! {
+        /// 512-bit unsigned integer for sums of u128 x u128 products
+        pub struct U512(8);
+    }
+}
+
+pub(crate) use u256::{U256, U512};
+
+/// Largest power of ten representable in a u128
+pub const MAX_POW10: u32 = 38;
+
+/// How to round the result of a fixed-point division
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub enum Rounding {
+    /// Toward zero; never overstates the result
+    #[default]
+    Down,
+    /// Away from zero; never understates the result
+    Up,
+    /// To the closest value, halves rounding up
+    Nearest,
+}
+
+/// Fixed-point 1.0 for decay factors
+pub(crate) const DECAY_ONE: u128 = 1_000_000_000_000_000_000;
+
+/// 2^(-2^-i) for i = 1..=20, scaled by DECAY_ONE
+const HALF_ROOTS: [u128; 20] = [
+    707_106_781_186_547_524,
+    840_896_415_253_714_543,
+    917_004_043_204_671_232,
+    957_603_280_698_573_647,
+    978_572_062_087_700_135,
+    989_228_013_193_975_484,
+    994_599_423_483_633_176,
+    997_296_056_085_470_126,
+    998_647_112_890_970_174,
+    999_323_327_502_650_752,
+    999_661_606_496_243_684,
+    999_830_788_931_929_063,
+    999_915_390_886_613_498,
+    999_957_694_548_431_133,
+    999_978_847_050_491_930,
+    999_989_423_469_314_464,
+    999_994_711_720_674_283,
+    999_997_355_856_841_395,
+    999_998_677_927_546_760,
+    999_999_338_963_554_895,
+];
+
+/// 0.5^(elapsed / half_life) scaled by DECAY_ONE, exact for whole half-lives and within
+/// about 1e-6 in between
+pub(crate) fn half_life_decay(elapsed: u64, half_life: u64) -> u128 {
+    assert!(half_life > 0, "Half-life must be positive");
+    let halvings = elapsed / half_life;
+    if halvings >= 64 {
+        return 0;
+    }
+
+    // Walk the binary expansion of the fractional part
+    let mut decay = DECAY_ONE >> halvings;
+    let (mut rest, half_life) = ((elapsed % half_life) as u128, half_life as u128);
+    for root in HALF_ROOTS {
+        rest *= 2;
+        if rest >= half_life {
+            rest -= half_life;
+            decay = decay * root / DECAY_ONE;
+        }
+    }
+    decay
+}
+
+/// `a * b / denominator` with the requested rounding, `None` on overflow or division by zero
+pub(crate) fn mul_div(a: u128, b: u128, denominator: u128, rounding: Rounding) -> Option<u128> {
+    if denominator == 0 {
+        return None;
+    }
+    let product = a.checked_mul(b)?;
+    let quotient = product / denominator;
+    let remainder = product % denominator;
+
+    let round_up = match rounding {
+        Rounding::Down => false,
+        Rounding::Up => remainder > 0,
+        Rounding::Nearest => remainder >= denominator - remainder,
+    };
+    if round_up {
+        quotient.checked_add(1)
+    } else {
+        Some(quotient)
+    }
+}
+
+/// 10^exp, `None` if it doesn't fit in a u128
+pub(crate) fn pow10(exp: u32) -> Option<u128> {
+    10u128.checked_pow(exp)
+}
+
+/// `a * b * 10^up / (divisor * 10^down)` with the requested rounding, computed in 512 bits
+/// so exponents past MAX_POW10 work; `None` if the result overflows a u128 or `divisor` is 0
+pub(crate) fn mul_div_pow10(a: u128, b: u128, up: u32, divisor: u128, down: u32, rounding: Rounding) -> Option<u128> {
+    if divisor == 0 {
+        return None;
+    }
+    let ten = U512::from(10u8);
+    let numerator = U512::from(a) * U512::from(b) * ten.pow(U512::from(up));
+    let denominator = U512::from(divisor) * ten.pow(U512::from(down));
+    let (quotient, remainder) = numerator.div_mod(denominator);
+
+    let round_up = match rounding {
+        Rounding::Down => false,
+        Rounding::Up => !remainder.is_zero(),
+        Rounding::Nearest => remainder >= denominator - remainder,
+    };
+    let quotient = if round_up { quotient + 1 } else { quotient };
+    (quotient <= U512::from(u128::MAX)).then(|| quotient.as_u128())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mul_div_rounding() {
+        assert_eq!(mul_div(10, 1, 3, Rounding::Down), Some(3));
+        assert_eq!(mul_div(10, 1, 3, Rounding::Up), Some(4));
+        assert_eq!(mul_div(10, 1, 3, Rounding::Nearest), Some(3));
+        assert_eq!(mul_div(5, 1, 2, Rounding::Nearest), Some(3));
+        assert_eq!(mul_div(9, 1, 3, Rounding::Up), Some(3));
+        assert_eq!(mul_div(1, 1, 0, Rounding::Down), None);
+        assert_eq!(mul_div(u128::MAX, 2, 2, Rounding::Down), None);
+        assert_eq!(pow10(MAX_POW10), Some(10u128.pow(38)));
+        assert_eq!(pow10(MAX_POW10 + 1), None);
+    }
+
+    #[test]
+    fn test_mul_div_pow10() {
+        // 2.5 tokens of 24 decimals at $5.28 (6 decimals) -> $13.20 (6 decimals)
+        let amount = 25 * 10u128.pow(23);
+        assert_eq!(mul_div_pow10(amount, 5280000, 6, 1, 30, Rounding::Down), Some(13200000));
+        assert_eq!(mul_div_pow10(1, 1, 48, 10u128.pow(20), 28, Rounding::Down), Some(1));
+        assert_eq!(mul_div_pow10(10, 1, 0, 3, 0, Rounding::Up), Some(4));
+        assert_eq!(mul_div_pow10(u128::MAX, 1, 1, 1, 0, Rounding::Down), None);
+        assert_eq!(mul_div_pow10(1, 1, 0, 0, 0, Rounding::Down), None);
+    }
+
+    #[test]
+    fn test_half_life_decay() {
+        assert_eq!(half_life_decay(0, 60), DECAY_ONE);
+        assert_eq!(half_life_decay(60, 60), DECAY_ONE / 2);
+        assert_eq!(half_life_decay(180, 60), DECAY_ONE / 8);
+        assert_eq!(half_life_decay(30, 60), 707_106_781_186_547_524);
+        // 2^-0.1 = 0.933032991...
+        assert!(half_life_decay(6, 60).abs_diff(933_032_991_536_807_415) < DECAY_ONE / 1_000_000);
+        assert_eq!(half_life_decay(u64::MAX, 1), 0);
+    }
+}
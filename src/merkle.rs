@@ -0,0 +1,309 @@
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::{env, near, AccountId};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::{BatchReport, OracleEvent, Pair, PriceOracle, PriceOracleExt, RejectReason, ReportStatus, MAX_BATCH_REPORTS};
+
+/// Most leaves a signed root can cover, so proofs stay at most 10 hashes long
+pub const MAX_MERKLE_LEAVES: u32 = 1024;
+
+/// A Merkle root of prices a reporter signed off chain, for anyone to relay
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(JsonSchema))]
+pub struct SignedRoot {
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub reporter: AccountId,
+    pub root: Base64VecU8,      // 32-byte root of the tree of merkle_leaf_hash
+    pub leaf_count: u32,
+    pub nonce: u64,             // Shares the reporter's packet nonces
+    pub signature: Base64VecU8, // ed25519 signature of merkle_root_message
+}
+
+/// One price under a signed root, with the sibling hashes from its leaf up
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(JsonSchema))]
+pub struct MerkleLeaf {
+    pub index: u32,
+    pub pair: Pair,
+    pub source: String,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub price: u128,            // Hashed as the u128 itself, whatever its JSON form
+    pub decimals: u8,
+    pub timestamp: u64,         // When the reporter observed the price, Unix ms
+    pub proof: Vec<Base64VecU8>,
+}
+
+/// A reporter's signed root and which of its leaves were taken so far
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct MerkleBatch {
+    pub root: Base64VecU8,
+    pub leaf_count: u32,
+    pub nonce: u64,
+    pub submitted_at: u64,
+    pub committed: Base64VecU8, // Bitmap of the leaves taken, leaf i at bit i % 8 of byte i / 8
+    pub leaves_committed: u32,
+}
+
+impl MerkleBatch {
+    fn is_committed(&self, index: u32) -> bool {
+        self.committed.0[index as usize / 8] & (1 << (index % 8)) != 0
+    }
+
+    fn commit(&mut self, index: u32) {
+        self.committed.0[index as usize / 8] |= 1 << (index % 8);
+        self.leaves_committed += 1;
+    }
+}
+
+#[near]
+impl PriceOracle {
+    /// Open a batch of prices a reporter signed as one Merkle root; anyone can relay it
+    /// Its leaves are then taken with submit_merkle_leaves, across as many calls as
+    /// needed. A newer root from the same reporter replaces the batch
+    pub fn submit_merkle_root(&mut self, root: SignedRoot) {
+        self.assert_not_paused();
+        self.assert_direct_reports();
+        assert!(root.root.0.len() == 32, "Merkle roots are 32 bytes, got {}", root.root.0.len());
+        assert!(
+            root.leaf_count > 0 && root.leaf_count <= MAX_MERKLE_LEAVES,
+            "A root covers 1 to {} leaves, got {}",
+            MAX_MERKLE_LEAVES,
+            root.leaf_count
+        );
+        let message = merkle_root_message(&env::current_account_id(), &root);
+        self.verify_reporter_signature(&root.reporter, root.nonce, &message, &root.signature.0)
+            .unwrap_or_else(|(_, reason)| panic!("Root rejected from {}: {}", root.reporter, reason));
+        self.packet_nonces.insert(root.reporter.clone(), root.nonce);
+        near_sdk::log!("Merkle root of {} opened: {} leaves", root.reporter, root.leaf_count);
+        self.merkle_batches.insert(
+            root.reporter,
+            MerkleBatch {
+                root: root.root,
+                leaf_count: root.leaf_count,
+                nonce: root.nonce,
+                submitted_at: crate::now_ms(),
+                committed: Base64VecU8(vec![0; root.leaf_count.div_ceil(8) as usize]),
+                leaves_committed: 0,
+            },
+        );
+    }
+
+    /// Submit up to MAX_BATCH_REPORTS leaves of a reporter's open batch, from any account
+    /// Each is checked against the signed root and taken once, then checked like a
+    /// report_prices entry of the reporter, timestamped when it was signed. The report
+    /// deposit is due per leaf, and the caller pays for the storage the reports take
+    /// Returns the status of every leaf, in order
+    #[payable]
+    pub fn submit_merkle_leaves(&mut self, reporter: AccountId, leaves: Vec<MerkleLeaf>) -> Vec<ReportStatus> {
+        self.assert_not_paused();
+        self.assert_direct_reports();
+        assert!(!leaves.is_empty(), "No leaves to submit");
+        assert!(
+            leaves.len() <= MAX_BATCH_REPORTS,
+            "At most {} leaves per call, got {}",
+            MAX_BATCH_REPORTS,
+            leaves.len()
+        );
+        assert!(self.merkle_batches.contains_key(&reporter), "No open Merkle batch of {}", reporter);
+        assert!(self.is_reporter(reporter.clone()), "Reporter {} is not whitelisted", reporter);
+        self.assert_report_deposit(leaves.len() as u128);
+
+        let statuses: Vec<ReportStatus> = leaves
+            .into_iter()
+            .map(|leaf| {
+                let batch = self.merkle_batches.get_mut(&reporter).expect("checked above");
+                if let Err((code, reason)) = verify_leaf(batch, &leaf) {
+                    near_sdk::log!("Leaf {} of {} rejected: {}", leaf.index, reporter, reason);
+                    OracleEvent::rejected(&leaf.pair, &leaf.source, reporter.as_str(), leaf.price, code, &reason).emit();
+                    return ReportStatus::Rejected(reason);
+                }
+                batch.commit(leaf.index);
+                let entry = BatchReport {
+                    pair: leaf.pair,
+                    source: leaf.source,
+                    price: leaf.price,
+                    decimals: leaf.decimals,
+                    volume: None,
+                };
+                self.ingest_batch_report(entry, &reporter, leaf.timestamp)
+            })
+            .collect();
+        let batch = self.merkle_batches.get(&reporter).expect("checked above");
+        if batch.leaves_committed == batch.leaf_count {
+            self.merkle_batches.remove(&reporter);
+        }
+        let accepted = statuses.iter().filter(|status| **status == ReportStatus::Accepted).count();
+        self.record_report_gas(accepted);
+        let rejected = statuses.iter().filter(|status| status.is_rejected()).count();
+        self.settle_relayed_deposit(rejected as u128, &[reporter]);
+        statuses
+    }
+
+    /// Get a reporter's open Merkle batch
+    pub fn get_merkle_batch(&self, reporter: AccountId) -> Option<MerkleBatch> {
+        self.merkle_batches.get(&reporter).cloned()
+    }
+}
+
+/// Why a leaf isn't an untaken leaf of the batch, if it isn't
+fn verify_leaf(batch: &MerkleBatch, leaf: &MerkleLeaf) -> Result<(), (RejectReason, String)> {
+    if leaf.index >= batch.leaf_count {
+        return Err((RejectReason::Invalid, format!("Leaf {} is outside the {} leaves of the root", leaf.index, batch.leaf_count)));
+    }
+    if batch.is_committed(leaf.index) {
+        return Err((RejectReason::Duplicate, format!("Leaf {} already committed", leaf.index)));
+    }
+    let depth = batch.leaf_count.next_power_of_two().trailing_zeros() as usize;
+    if leaf.proof.len() != depth {
+        return Err((RejectReason::Invalid, format!("Proofs of this root are {} hashes, got {}", depth, leaf.proof.len())));
+    }
+    let mut node = merkle_leaf_hash(leaf);
+    let mut index = leaf.index;
+    for sibling in &leaf.proof {
+        node = if index & 1 == 0 {
+            merkle_node_hash(&node, &sibling.0)
+        } else {
+            merkle_node_hash(&sibling.0, &node)
+        };
+        index >>= 1;
+    }
+    if node != batch.root.0 {
+        return Err((RejectReason::Unauthorized, "Invalid proof".to_string()));
+    }
+    Ok(())
+}
+
+/// Bytes a reporter signs for a root to `oracle`: the borsh encoding of
+/// (oracle, "merkle-root", reporter, root, leaf_count, nonce)
+pub fn merkle_root_message(oracle: &AccountId, root: &SignedRoot) -> Vec<u8> {
+    borsh::to_vec(&(oracle, "merkle-root", &root.reporter, &root.root.0, root.leaf_count, root.nonce))
+        .expect("roots serialize")
+}
+
+/// Hash of a leaf: sha256 of 0x00 and the borsh encoding of
+/// (pair, source, price, decimals, timestamp)
+/// Trees are padded to a power of two with 32 zero bytes as leaf hashes
+pub fn merkle_leaf_hash(leaf: &MerkleLeaf) -> Vec<u8> {
+    let mut bytes = vec![0];
+    let fields = (&leaf.pair, &leaf.source, leaf.price, leaf.decimals, leaf.timestamp);
+    bytes.extend(borsh::to_vec(&fields).expect("leaves serialize"));
+    env::sha256(&bytes)
+}
+
+/// Hash of an inner node: sha256 of 0x01, the left child and the right child
+pub fn merkle_node_hash(left: &[u8], right: &[u8]) -> Vec<u8> {
+    env::sha256([&[1], left, right].concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use ed25519_dalek::{Signer, SigningKey};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, CurveType, PublicKey};
+
+    fn leaf(index: u32, asset: &str, price: u128) -> MerkleLeaf {
+        MerkleLeaf {
+            index,
+            pair: Pair::usd(asset),
+            source: "binance".to_string(),
+            price,
+            decimals: 6,
+            timestamp: 9000,
+            proof: Vec::new(),
+        }
+    }
+
+    /// Three leaves padded to four, with their proofs filled in, and the signed root
+    fn tree(key: &SigningKey) -> (Vec<MerkleLeaf>, SignedRoot) {
+        let mut leaves = vec![leaf(0, "NEAR", 5250000), leaf(1, "ETH", 3000000000), leaf(2, "NEAR", 5300000)];
+        let mut hashes: Vec<Vec<u8>> = leaves.iter().map(merkle_leaf_hash).collect();
+        hashes.push(vec![0; 32]);
+        let (left, right) = (merkle_node_hash(&hashes[0], &hashes[1]), merkle_node_hash(&hashes[2], &hashes[3]));
+        let proofs = [
+            [hashes[1].clone(), right.clone()],
+            [hashes[0].clone(), right.clone()],
+            [hashes[3].clone(), left.clone()],
+        ];
+        for (leaf, proof) in leaves.iter_mut().zip(proofs) {
+            leaf.proof = proof.into_iter().map(Base64VecU8).collect();
+        }
+        let mut root = SignedRoot {
+            reporter: accounts(1),
+            root: Base64VecU8(merkle_node_hash(&left, &right)),
+            leaf_count: 3,
+            nonce: 1,
+            signature: Base64VecU8(Vec::new()),
+        };
+        let message = merkle_root_message(&env::current_account_id(), &root);
+        root.signature = Base64VecU8(key.sign(&message).to_bytes().to_vec());
+        (leaves, root)
+    }
+
+    fn setup(key: &SigningKey) -> PriceOracle {
+        testing_env!(VMContextBuilder::new().predecessor_account_id(accounts(0)).build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.add_asset("ETH".to_string(), AssetConfig::new("ETH", "Ether", 6));
+        contract.add_reporter(accounts(1));
+        testing_env!(VMContextBuilder::new().predecessor_account_id(accounts(1)).build());
+        let public_key = PublicKey::from_parts(CurveType::ED25519, key.verifying_key().to_bytes().to_vec()).unwrap();
+        contract.set_reporter_key(Some(public_key));
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(10_000_000_000)
+            .build());
+        contract
+    }
+
+    #[test]
+    fn test_leaves_committed_across_calls() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let mut contract = setup(&key);
+        let (leaves, root) = tree(&key);
+        contract.submit_merkle_root(root);
+
+        let statuses = contract.submit_merkle_leaves(accounts(1), vec![leaves[1].clone(), leaves[1].clone()]);
+        assert_eq!(statuses, [ReportStatus::Accepted, ReportStatus::Rejected("Leaf 1 already committed".to_string())]);
+        assert_eq!(contract.get_price(Pair::usd("ETH")).price, 3000000000);
+        assert_eq!(contract.get_merkle_batch(accounts(1)).unwrap().leaves_committed, 1);
+
+        let mut forged = leaves[0].clone();
+        forged.price = 9990000;
+        let statuses = contract.submit_merkle_leaves(accounts(1), vec![forged, leaves[0].clone(), leaves[2].clone()]);
+        assert_eq!(statuses[0], ReportStatus::Rejected("Invalid proof".to_string()));
+        assert_eq!(contract.get_price_details(Pair::usd("NEAR"))[0].reporter, "bob");
+        assert_eq!(contract.get_merkle_batch(accounts(1)), None);
+    }
+
+    #[test]
+    fn test_leaf_price_relayed_as_string() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let mut contract = setup(&key);
+        let (leaves, root) = tree(&key);
+        contract.submit_merkle_root(root);
+
+        let json = near_sdk::serde_json::to_value(&leaves[1]).unwrap();
+        assert_eq!(json["price"], "3000000000");
+        let relayed: MerkleLeaf = near_sdk::serde_json::from_value(json).unwrap();
+        assert_eq!(contract.submit_merkle_leaves(accounts(1), vec![relayed]), [ReportStatus::Accepted]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Root rejected from bob: Invalid signature")]
+    fn test_root_needs_reporter_signature() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let mut contract = setup(&key);
+        let (_, root) = tree(&SigningKey::from_bytes(&[2; 32]));
+        contract.submit_merkle_root(root);
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+use near_sdk::borsh::{self, BorshDeserialize};
+use near_sdk::{env, near, AccountId};
+
+use crate::{AssetConfig, OracleState, Pair, PriceOracle, PriceOracleExt, PriceReport, Role, DEFAULT_DECIMALS};
+
+/// Key the contract state is stored under
+const STATE_KEY: &[u8] = b"STATE";
+
+/// Asset the baseline contract priced: its reports were NEAR/USD in micro-dollars
+const BASELINE_ASSET: &str = "NEAR";
+
+/// Report of the baseline contract, in USD micro-dollars
+#[derive(BorshDeserialize)]
+#[cfg_attr(test, derive(borsh::BorshSerialize))]
+struct BaselineReport {
+    source: String,
+    price_usd: u128,
+    timestamp: u64,
+    reporter: String,
+}
+
+/// Layout of the first deployed contract: one flat map of NEAR/USD reports by source
+#[derive(BorshDeserialize)]
+#[cfg_attr(test, derive(borsh::BorshSerialize))]
+struct BaselineOracle {
+    owner: AccountId,
+    prices: HashMap<String, BaselineReport>,
+    last_update: u64,
+    min_sources: u8,
+}
+
+#[near]
+impl PriceOracle {
+    /// Move the state of the baseline deployment into the current layout. Call it in
+    /// the upgrade's deploy transaction, before anything else reads the state
+    /// Versioned state upgrades itself (see VersionedState) and is returned as it is
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let state = env::storage_read(STATE_KEY).expect("No state to migrate");
+        if let Ok(current) = PriceOracle::try_from_slice(&state) {
+            near_sdk::log!("State already versioned, in layout {}", current.get_state_version());
+            return current;
+        }
+        match BaselineOracle::try_from_slice(&state) {
+            Ok(old) => Self::from_baseline(old),
+            Err(_) => env::panic_str("State matches no known layout"),
+        }
+    }
+}
+
+impl PriceOracle {
+    /// Registers the baseline's asset and files its reports under its USD pair; their
+    /// reporters are whitelisted so they keep reporting after the upgrade
+    fn from_baseline(old: BaselineOracle) -> Self {
+        near_sdk::log!("State migrated: {} {} reports moved into the price book", old.prices.len(), Pair::usd(BASELINE_ASSET));
+        let mut state = OracleState {
+            last_update: old.last_update,
+            min_sources: old.min_sources,
+            ..OracleState::initial(old.owner)
+        };
+        state.assets.insert(
+            BASELINE_ASSET.to_string(),
+            AssetConfig::new(BASELINE_ASSET, "NEAR Protocol", DEFAULT_DECIMALS),
+        );
+
+        let mut reports: Vec<BaselineReport> = old.prices.into_values().collect();
+        reports.sort_by(|a, b| a.source.cmp(&b.source));
+        let book = state.prices.entry(&Pair::usd(BASELINE_ASSET));
+        let mut reporters = Vec::new();
+        for report in reports {
+            reporters.push(report.reporter.clone());
+            // Sources are kept verbatim, as they were stored before names were normalized
+            book.insert(
+                report.source.clone(),
+                PriceReport {
+                    source: report.source,
+                    price: report.price_usd,
+                    decimals: DEFAULT_DECIMALS,
+                    timestamp: report.timestamp,
+                    reporter: report.reporter,
+                    volume: None,
+                    attestation: None,
+                },
+            );
+        }
+        for reporter in reporters.into_iter().filter_map(|id| id.parse::<AccountId>().ok()) {
+            state.roles.entry(Role::Reporter).or_default().insert(reporter);
+        }
+        state.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::STATE_VERSION;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn baseline_report(source: &str, price_usd: u128, timestamp: u64) -> BaselineReport {
+        BaselineReport { source: source.to_string(), price_usd, timestamp, reporter: "feeder.near".to_string() }
+    }
+
+    #[test]
+    fn test_migrate_from_baseline() {
+        testing_env!(VMContextBuilder::new().build());
+        let baseline = BaselineOracle {
+            owner: "owner.near".parse().unwrap(),
+            prices: HashMap::from([
+                ("coingecko".to_string(), baseline_report("coingecko", 5000000, 1000)),
+                ("binance".to_string(), baseline_report("binance", 5200000, 2000)),
+            ]),
+            last_update: 2000,
+            min_sources: 2,
+        };
+        env::state_write(&baseline);
+
+        let migrated = PriceOracle::migrate();
+        assert!(get_logs().contains(&"State migrated: 2 NEAR/USD reports moved into the price book".to_string()));
+        assert_eq!(migrated.get_state_version(), STATE_VERSION);
+        assert_eq!(migrated.get_owner().as_str(), "owner.near");
+        assert_eq!((migrated.get_min_sources(), migrated.get_last_update()), (2, 2000));
+        assert_eq!(migrated.get_decimals("NEAR".to_string()), 6);
+        let details = migrated.get_price_details_paginated("NEAR".to_string(), None, None);
+        let reports: Vec<_> = details.iter().map(|(pair, r)| (pair.to_string(), r.source.as_str(), r.price)).collect();
+        assert_eq!(reports, vec![("NEAR/USD".to_string(), "binance", 5200000), ("NEAR/USD".to_string(), "coingecko", 5000000)]);
+        assert!(migrated.is_reporter("feeder.near".parse().unwrap()));
+        env::state_write(&migrated);
+
+        // Migrating again leaves versioned state as it is
+        let again = PriceOracle::migrate();
+        assert_eq!(again.get_min_sources(), 2);
+        assert!(get_logs().contains(&"State already versioned, in layout 1".to_string()));
+    }
+
+    #[test]
+    fn test_migrated_baseline_keeps_reporting() {
+        testing_env!(VMContextBuilder::new().build());
+        let baseline = BaselineOracle {
+            owner: "owner.near".parse().unwrap(),
+            prices: HashMap::from([("binance".to_string(), baseline_report("binance", 5200000, 0))]),
+            last_update: 0,
+            min_sources: 1,
+        };
+        env::state_write(&baseline);
+        let mut migrated = PriceOracle::migrate();
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id("feeder.near".parse().unwrap())
+            .block_timestamp(1_000_000_000)
+            .build());
+        migrated.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5300000), 6);
+        assert_eq!(migrated.get_price(Pair::usd("NEAR")).price, 5300000);
+    }
+
+    #[test]
+    #[should_panic(expected = "State matches no known layout")]
+    fn test_migrate_unknown_layout() {
+        testing_env!(VMContextBuilder::new().build());
+        env::state_write(&(1u8, 2u64));
+        PriceOracle::migrate();
+    }
+
+    #[test]
+    #[should_panic(expected = "No state to migrate")]
+    fn test_migrate_without_state() {
+        testing_env!(VMContextBuilder::new().build());
+        PriceOracle::migrate();
+    }
+}
@@ -0,0 +1,175 @@
+use near_sdk::near;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::aggregation::median_absolute_deviation;
+use crate::math::{mul_div, Rounding};
+use crate::{Pair, PriceOracle, PriceOracleExt, PriceReport, Role};
+
+/// `mad_multiplier` value of 1x MAD
+pub const MAD_MULTIPLIER_SCALE: u128 = 100;
+
+/// A report left out of the aggregate
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct ExcludedReport {
+    pub source: String,
+    pub reporter: String,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub price: u128,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub deviation: u128,       // Distance from the median, beyond `OutlierAnalysis::limit`
+}
+
+/// How the outlier filter treated a pair's current reports
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct OutlierAnalysis {
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub median: u128,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub mad: u128,             // Median absolute deviation from `median`
+    pub mad_multiplier: u16,   // k, scaled by MAD_MULTIPLIER_SCALE (0 = filter disabled)
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub limit: u128,           // k * MAD; reports further from the median are excluded
+    pub excluded: Vec<ExcludedReport>,
+}
+
+#[near]
+impl PriceOracle {
+    /// Drop reports more than k * MAD away from the median before aggregating (ConfigManager)
+    /// `mad_multiplier` is k scaled by 100 (e.g. 300 = 3 x MAD); 0 disables the filter
+    pub fn set_mad_multiplier(&mut self, mad_multiplier: u16) {
+        self.assert_role(Role::ConfigManager);
+        self.mad_multiplier = mad_multiplier;
+        self.invalidate_aggregates();
+    }
+
+    /// Get the outlier filter's k, scaled by 100 (0 = disabled)
+    pub fn get_mad_multiplier(&self) -> u16 {
+        self.mad_multiplier
+    }
+
+    /// Get the reports the outlier filter currently excludes from a pair's aggregate,
+    /// with the median, MAD and limit they were judged against
+    pub fn get_outliers(&self, pair: Pair) -> OutlierAnalysis {
+        let reports = self.live_reports(&pair);
+        let prices: Vec<u128> = reports.iter().map(|r| r.price).collect();
+        let (median, mad) = median_absolute_deviation(&prices);
+        let limit = self.mad_limit(mad);
+
+        let mut excluded: Vec<ExcludedReport> = reports
+            .iter()
+            .filter(|r| self.is_outlier(r.price, median, limit))
+            .map(|r| ExcludedReport {
+                source: r.source.clone(),
+                reporter: r.reporter.clone(),
+                price: r.price,
+                deviation: r.price.abs_diff(median),
+            })
+            .collect();
+        excluded.sort_by(|a, b| a.source.cmp(&b.source));
+
+        OutlierAnalysis {
+            median,
+            mad,
+            mad_multiplier: self.mad_multiplier,
+            limit,
+            excluded,
+        }
+    }
+}
+
+impl PriceOracle {
+    /// Reports of a pair that pass the outlier filter
+    pub(crate) fn accepted_reports(&self, pair: &Pair) -> Vec<&PriceReport> {
+        self.without_outliers(self.live_reports(pair))
+    }
+
+    /// `reports` minus those more than k * MAD from their median
+    pub(crate) fn without_outliers<'a>(&self, reports: Vec<&'a PriceReport>) -> Vec<&'a PriceReport> {
+        if self.mad_multiplier == 0 {
+            return reports;
+        }
+        let prices: Vec<u128> = reports.iter().map(|r| r.price).collect();
+        let (median, mad) = median_absolute_deviation(&prices);
+        let limit = self.mad_limit(mad);
+        reports
+            .into_iter()
+            .filter(|r| !self.is_outlier(r.price, median, limit))
+            .collect()
+    }
+
+    fn mad_limit(&self, mad: u128) -> u128 {
+        mul_div(mad, self.mad_multiplier as u128, MAD_MULTIPLIER_SCALE, Rounding::Down).unwrap_or(u128::MAX)
+    }
+
+    fn is_outlier(&self, price: u128, median: u128, limit: u128) -> bool {
+        self.mad_multiplier > 0 && price.abs_diff(median) > limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn setup() -> PriceOracle {
+        testing_env!(VMContextBuilder::new().build());
+        let mut contract = PriceOracle::for_tests();
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        for (source, price) in [("binance", 5000000), ("coingecko", 5100000), ("kraken", 5200000), ("rogue", 9000000)] {
+            contract.report_price(Pair::usd("NEAR"), source.to_string(), U128(price), 6);
+        }
+        contract
+    }
+
+    #[test]
+    fn test_outlier_excluded_from_aggregates() {
+        let mut contract = setup();
+        assert_eq!(contract.get_mean_price(Pair::usd("NEAR")).price, 6075000);
+
+        contract.set_mad_multiplier(300);
+        assert_eq!(contract.get_mean_price(Pair::usd("NEAR")).price, 5100000);
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5100000);
+        assert_eq!(contract.get_weighted_price(Pair::usd("NEAR")).weights.len(), 3);
+    }
+
+    #[test]
+    fn test_get_outliers_explains_exclusion() {
+        let mut contract = setup();
+        assert!(contract.get_outliers(Pair::usd("NEAR")).excluded.is_empty());
+
+        contract.set_mad_multiplier(300);
+        let analysis = contract.get_outliers(Pair::usd("NEAR"));
+        assert_eq!(analysis.median, 5150000);
+        assert_eq!(analysis.mad, 100000);
+        assert_eq!(analysis.limit, 300000);
+        assert_eq!(analysis.excluded, vec![ExcludedReport {
+            source: "rogue".to_string(),
+            reporter: "bob.near".to_string(),
+            price: 9000000,
+            deviation: 3850000,
+        }]);
+    }
+
+    #[test]
+    #[should_panic(expected = "eve.near lacks role ConfigManager")]
+    fn test_set_mad_multiplier_requires_config_manager() {
+        let mut contract = setup();
+        testing_env!(VMContextBuilder::new().predecessor_account_id("eve.near".parse().unwrap()).build());
+        contract.set_mad_multiplier(300);
+    }
+}
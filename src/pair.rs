@@ -0,0 +1,40 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+use crate::AssetId;
+
+/// Quote asset used when a pair is created from a bare asset id
+pub const DEFAULT_QUOTE: &str = "USD";
+
+/// A trading pair: the price of one `base` unit expressed in `quote`, e.g. NEAR/USD
+#[derive(
+    BorshSerialize, BorshDeserialize, Serialize, Deserialize,
+    Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord,
+)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct Pair {
+    pub base: AssetId,
+    pub quote: AssetId,
+}
+
+impl Pair {
+    pub fn new(base: impl Into<AssetId>, quote: impl Into<AssetId>) -> Self {
+        Self { base: base.into(), quote: quote.into() }
+    }
+
+    /// The `base`/USD pair
+    pub fn usd(base: impl Into<AssetId>) -> Self {
+        Self::new(base, DEFAULT_QUOTE)
+    }
+}
+
+impl fmt::Display for Pair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.base, self.quote)
+    }
+}
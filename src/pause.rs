@@ -0,0 +1,112 @@
+use near_sdk::near;
+
+use crate::{PriceOracle, PriceOracleExt, Role};
+
+#[near]
+impl PriceOracle {
+    /// Halt the oracle during an incident (PauseGuardian): reports are refused and
+    /// price views fail until it's unpaused
+    pub fn pause(&mut self) {
+        self.assert_role(Role::PauseGuardian);
+        assert!(!self.paused, "Oracle already paused");
+        self.set_paused(true);
+    }
+
+    /// Resume reports and price views (PauseGuardian, or a council proposal once installed)
+    pub fn unpause(&mut self) {
+        self.assert_role(Role::PauseGuardian);
+        self.assert_without_council("unpause");
+        assert!(self.paused, "Oracle not paused");
+        self.set_paused(false);
+    }
+
+    /// Check if the oracle is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+}
+
+impl PriceOracle {
+    /// Panics while the oracle is paused
+    pub(crate) fn assert_not_paused(&self) {
+        assert!(!self.paused, "Oracle paused");
+    }
+
+    pub(crate) fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        near_sdk::log!("Oracle {}", if paused { "paused" } else { "unpaused" });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AssetConfig, CouncilAction, Pair};
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+    use near_sdk::AccountId;
+
+    fn as_account(account_id: AccountId) {
+        testing_env!(VMContextBuilder::new().predecessor_account_id(account_id).build());
+    }
+
+    fn setup() -> PriceOracle {
+        as_account(accounts(0));
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        contract.grant_role(Role::PauseGuardian, accounts(1));
+        contract
+    }
+
+    #[test]
+    fn test_pause_and_resume() {
+        let mut contract = setup();
+        as_account(accounts(1));
+        contract.pause();
+        assert!(contract.is_paused());
+        assert!(!contract.is_valid(Pair::usd("NEAR")));
+
+        contract.unpause();
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5000000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Oracle paused")]
+    fn test_paused_views_fail() {
+        let mut contract = setup();
+        contract.pause();
+        contract.get_price(Pair::usd("NEAR"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Oracle paused")]
+    fn test_paused_reports_refused() {
+        let mut contract = setup();
+        contract.pause();
+        contract.report_price(Pair::usd("NEAR"), "coingecko".to_string(), U128(5000000), 6);
+    }
+
+    #[test]
+    fn test_council_unpauses() {
+        let mut contract = setup();
+        contract.set_council(vec![accounts(2)], 1, 3600);
+        as_account(accounts(1));
+        contract.pause();                               // Guardians still pause instantly
+
+        as_account(accounts(2));
+        let id = contract.propose(CouncilAction::Unpause);
+        contract.execute(id);
+        assert!(!contract.is_paused());
+    }
+
+    #[test]
+    #[should_panic(expected = "charlie lacks role PauseGuardian")]
+    fn test_pause_requires_guardian() {
+        let mut contract = setup();
+        as_account(accounts(2));
+        contract.pause();
+    }
+}
@@ -0,0 +1,237 @@
+use near_sdk::json_types::{U128, U64};
+use near_sdk::{env, ext_contract, near, AccountId, Gas, NearToken, Promise, PromiseError};
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::{now_ms, AssetId, Pair, PriceOracle, PriceOracleExt};
+
+/// Recency window of get_price_data while max_answer_age is unset, as on priceoracle.near
+pub const DEFAULT_RECENCY_DURATION_SECONDS: u64 = 90;
+
+const GAS_FOR_ON_ORACLE_CALL: Gas = Gas::from_tgas(5);
+
+/// Least gas oracle_call needs on top of what the receiver is given
+const GAS_FOR_ORACLE_CALL: Gas = Gas::from_tgas(10);
+
+/// Price in the shape of priceoracle.near: `multiplier / 10^decimals` USD per smallest
+/// unit of the asset's token
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(JsonSchema))]
+pub struct AssetPrice {
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub multiplier: U128,
+    pub decimals: u8,
+}
+
+/// Entry of a priceoracle.near `get_price_data` response
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(JsonSchema))]
+pub struct AssetOptionalPrice {
+    pub asset_id: String,
+    pub price: Option<AssetPrice>, // None without a recent enough answer
+}
+
+/// Response of priceoracle.near's `get_price_data`, as Burrow reads it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(JsonSchema))]
+pub struct PriceData {
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub timestamp: U64,            // Block timestamp in nanoseconds
+    pub recency_duration_sec: u32,
+    pub prices: Vec<AssetOptionalPrice>,
+}
+
+/// Consumer side of priceoracle.near's `oracle_call`, as implemented by Burrow
+#[allow(dead_code)]
+#[ext_contract(ext_price_receiver)]
+trait PriceReceiver {
+    fn oracle_on_call(&mut self, sender_id: AccountId, data: PriceData, msg: String);
+}
+
+#[near]
+impl PriceOracle {
+    /// Get USD prices in priceoracle.near's format, so its consumers can read this oracle
+    /// unmodified. `asset_ids` are NEP-141 token contracts bound to assets, or asset ids,
+    /// and default to every asset's token (its asset id if it has none)
+    /// Answers older than max_answer_age (90s if unset) are reported as None
+    pub fn get_price_data(&self, asset_ids: Option<Vec<String>>) -> PriceData {
+        self.assert_not_paused();
+        let asset_ids = asset_ids.unwrap_or_else(|| {
+            let mut ids: Vec<String> = self
+                .assets
+                .iter()
+                .map(|(asset_id, config)| config.token_id.clone().unwrap_or_else(|| asset_id.clone()))
+                .collect();
+            ids.sort();
+            ids
+        });
+        let recency = self.max_answer_age.unwrap_or(DEFAULT_RECENCY_DURATION_SECONDS);
+        PriceData {
+            timestamp: U64(env::block_timestamp()),
+            recency_duration_sec: recency.min(u32::MAX as u64) as u32,
+            prices: asset_ids
+                .into_iter()
+                .map(|asset_id| AssetOptionalPrice {
+                    price: self.asset_price(&asset_id, recency),
+                    asset_id,
+                })
+                .collect(),
+        }
+    }
+
+    /// Call `oracle_on_call` on `receiver_id` with the caller, the current get_price_data
+    /// of `asset_ids` and `msg`, like priceoracle.near does for Burrow. The attached
+    /// deposit (at least 1 yoctoNEAR, so only a full access key can call) is forwarded,
+    /// and refunded to the caller if the receiver fails. The receiver gets all the gas
+    /// left after this call and its callback
+    #[payable]
+    pub fn oracle_call(&mut self, receiver_id: AccountId, asset_ids: Option<Vec<String>>, msg: String) -> Promise {
+        let deposit = env::attached_deposit();
+        assert!(!deposit.is_zero(), "Attach at least 1 yoctoNEAR to oracle_call");
+        let remaining = env::prepaid_gas().saturating_sub(env::used_gas());
+        assert!(
+            remaining >= GAS_FOR_ORACLE_CALL.saturating_add(GAS_FOR_ON_ORACLE_CALL),
+            "oracle_call needs at least {} Tgas",
+            GAS_FOR_ORACLE_CALL.saturating_add(GAS_FOR_ON_ORACLE_CALL).as_tgas()
+        );
+        let sender_id = env::predecessor_account_id();
+        let data = self.get_price_data(asset_ids);
+        ext_price_receiver::ext(receiver_id.clone())
+            .with_attached_deposit(deposit)
+            .with_unused_gas_weight(1)
+            .oracle_on_call(sender_id.clone(), data, msg)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_ON_ORACLE_CALL)
+                    .with_unused_gas_weight(0)
+                    .on_oracle_call(sender_id, receiver_id, deposit),
+            )
+    }
+
+    #[private]
+    pub fn on_oracle_call(&mut self, sender_id: AccountId, receiver_id: AccountId, deposit: NearToken) -> bool {
+        let failed = matches!(env::promise_result_checked(0, 0), Err(PromiseError::Failed));
+        settle_oracle_call(sender_id, receiver_id, deposit, !failed)
+    }
+}
+
+impl PriceOracle {
+    /// Recent USD answer of the asset behind a token contract or asset id, per token unit
+    fn asset_price(&self, id: &str, recency: u64) -> Option<AssetPrice> {
+        let asset_id: AssetId = id
+            .parse::<AccountId>()
+            .ok()
+            .and_then(|token_id| self.token_assets.get(&token_id).cloned())
+            .unwrap_or_else(|| id.to_string());
+        let config = self.assets.get(&asset_id)?;
+        let pair = Pair::usd(asset_id.clone());
+        if self.breaker_tripped(&pair) {
+            return None;
+        }
+        let answer = self.latest_answer(&pair)?;
+        if now_ms().saturating_sub(answer.finalized_at) > recency * 1000 {
+            return None;
+        }
+        Some(AssetPrice {
+            multiplier: U128(answer.price),
+            decimals: answer.decimals + config.token_decimals.unwrap_or(0),
+        })
+    }
+}
+
+/// Refunds the forwarded deposit of a failed oracle_call; returns whether it succeeded
+fn settle_oracle_call(sender_id: AccountId, receiver_id: AccountId, deposit: NearToken, succeeded: bool) -> bool {
+    if !succeeded {
+        near_sdk::log!("oracle_call to {} failed, {} refunded to {}", receiver_id, deposit.as_yoctonear(), sender_id);
+        Promise::new(sender_id).transfer(deposit).detach();
+    }
+    succeeded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::mock::MockAction;
+    use near_sdk::test_utils::{accounts, get_created_receipts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn setup() -> PriceOracle {
+        testing_env!(VMContextBuilder::new().block_timestamp(10 * 1_000_000_000).build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset(
+            "NEAR".to_string(),
+            AssetConfig::new("NEAR", "NEAR Protocol", 6).with_token("wrap.near").with_token_decimals(24),
+        );
+        contract.add_asset("BTC".to_string(), AssetConfig::new("BTC", "Bitcoin", 2));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(2810000), 6);
+        contract
+    }
+
+    #[test]
+    fn test_price_data_format() {
+        let contract = setup();
+        let data = contract.get_price_data(None);
+        assert_eq!(
+            near_sdk::serde_json::to_string(&data).unwrap(),
+            r#"{"timestamp":"10000000000","recency_duration_sec":90,"prices":[{"asset_id":"BTC","price":null},{"asset_id":"wrap.near","price":{"multiplier":"2810000","decimals":30}}]}"#
+        );
+        let by_asset_id = contract.get_price_data(Some(vec!["NEAR".to_string()]));
+        assert_eq!(by_asset_id.prices[0].price, Some(AssetPrice { multiplier: U128(2810000), decimals: 30 }));
+    }
+
+    #[test]
+    fn test_price_data_drops_old_answers() {
+        let mut contract = setup();
+        contract.set_max_answer_age(Some(30));
+        testing_env!(VMContextBuilder::new().block_timestamp(41 * 1_000_000_000).build());
+        let data = contract.get_price_data(Some(vec!["wrap.near".to_string(), "unknown.near".to_string()]));
+        assert_eq!(data.recency_duration_sec, 30);
+        assert_eq!(data.prices[0].price, None);
+        assert_eq!(data.prices[1].price, None);
+    }
+
+    #[test]
+    fn test_oracle_call_forwards_deposit() {
+        let mut contract = setup();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .prepaid_gas(Gas::from_tgas(100))
+            .build());
+        contract
+            .oracle_call(accounts(2), Some(vec!["wrap.near".to_string()]), "{}".to_string())
+            .detach();
+
+        let receipts = get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, accounts(2));
+        let call = receipts[0].actions.iter().find_map(|action| match action {
+            MockAction::FunctionCallWeight { method_name, args, attached_deposit, .. } => {
+                Some((String::from_utf8(method_name.clone()).unwrap(), String::from_utf8(args.clone()).unwrap(), *attached_deposit))
+            }
+            _ => None,
+        });
+        let (method_name, args, deposit) = call.expect("function call");
+        assert_eq!(method_name, "oracle_on_call");
+        assert!(args.starts_with(r#"{"sender_id":"bob","data":{"timestamp":"0","recency_duration_sec":90,"prices":[{"asset_id":"wrap.near","price":{"multiplier":"2810000""#));
+        assert_eq!(deposit, NearToken::from_yoctonear(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Attach at least 1 yoctoNEAR to oracle_call")]
+    fn test_oracle_call_requires_deposit() {
+        let mut contract = setup();
+        contract.oracle_call(accounts(2), None, String::new()).detach();
+    }
+
+    #[test]
+    fn test_failed_oracle_call_refunds() {
+        testing_env!(VMContextBuilder::new().build());
+        assert!(settle_oracle_call(accounts(1), accounts(2), NearToken::from_yoctonear(1), true));
+        assert!(!settle_oracle_call(accounts(1), accounts(2), NearToken::from_yoctonear(1), false));
+        assert_eq!(get_logs(), ["oracle_call to charlie failed, 1 refunded to bob"]);
+        assert_eq!(get_created_receipts()[0].receiver_id, accounts(1));
+    }
+}
@@ -0,0 +1,330 @@
+use near_sdk::json_types::U128;
+use near_sdk::{env, near, AccountId, Gas, NearToken, Promise, PromiseError};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde_json::json;
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::guard::deviation_bps;
+use crate::{Pair, PriceOracle, PriceOracleExt};
+
+/// Most gas a subscriber may ask for per push
+pub const MAX_PUSH_GAS_TGAS: u64 = 30;
+
+/// Subscribers per pair, so a finalizing report can afford every push
+pub const MAX_PUSH_SUBSCRIBERS: usize = 8;
+
+/// Consecutive failed pushes after which a subscriber is dropped
+pub const MAX_PUSH_FAILURES: u8 = 3;
+
+/// Bytes a push registration takes besides its encoding and account key (map entry bookkeeping)
+pub const PUSH_SUBSCRIBER_OVERHEAD_BYTES: u64 = 40;
+
+const GAS_FOR_ON_PUSH: Gas = Gas::from_tgas(5);
+
+/// Gas kept back for finishing the call that finalized the round
+const GAS_RESERVE: Gas = Gas::from_tgas(20);
+
+/// Consumer contract that receives a pair's answers as they finalize
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct PushSubscriber {
+    pub account_id: String,
+    pub method_name: String,       // Called with {"pair": ..., "answer": RoundAnswer}
+    pub gas_tgas: u64,             // Gas attached to each push
+    pub min_change_bps: Option<u16>, // Only push answers this far from the last pushed one
+    #[serde(with = "crate::json::option_u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "Option<String>"))]
+    pub last_pushed: Option<u128>, // Price of the last answer pushed
+    pub failures: u8,              // Consecutive failed pushes
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub deposit: u128,             // Storage deposit in yoctoNEAR, refunded when the subscriber leaves
+}
+
+#[near]
+impl PriceOracle {
+    /// Have the caller's `method_name` called with every finalized answer of a pair, or
+    /// only with answers at least `min_change_bps` from the last one pushed
+    /// The storage of the registration must be attached (see get_push_subscription_cost);
+    /// the surplus tops up the caller's prepaid balance. Each push draws the base asset's
+    /// query fee from that balance (see deposit_for); a push it can't cover counts as a
+    /// failed one
+    #[payable]
+    pub fn subscribe_push(&mut self, pair: Pair, method_name: String, gas_tgas: u64, min_change_bps: Option<u16>) {
+        self.asset_config(&pair.base);
+        assert!(
+            gas_tgas > 0 && gas_tgas <= MAX_PUSH_GAS_TGAS,
+            "Push gas must be between 1 and {} Tgas",
+            MAX_PUSH_GAS_TGAS
+        );
+        let caller = env::predecessor_account_id();
+        let cost = push_subscription_cost(&caller, &method_name);
+        let subscribers = self.push_subscribers.entry(pair.clone()).or_default();
+        let previous = subscribers.get(caller.as_str()).map(|subscriber| subscriber.deposit);
+        assert!(
+            previous.is_some() || subscribers.len() < MAX_PUSH_SUBSCRIBERS,
+            "{} already has {} push subscribers",
+            pair,
+            MAX_PUSH_SUBSCRIBERS
+        );
+        // Resubscribing carries the deposit already held over
+        let available = env::attached_deposit().as_yoctonear() + previous.unwrap_or(0);
+        assert!(available >= cost, "Attach at least {} yoctoNEAR for storage, got {}", cost, available);
+
+        near_sdk::log!("Push subscribed for {}: {}.{}", pair, caller, method_name);
+        subscribers.insert(
+            caller.to_string(),
+            PushSubscriber {
+                account_id: caller.to_string(),
+                method_name,
+                gas_tgas,
+                min_change_bps,
+                last_pushed: None,
+                failures: 0,
+                deposit: cost,
+            },
+        );
+        if available > cost {
+            let balance = self.get_subscription_balance(caller.clone()).0;
+            self.set_subscription_balance(&caller, balance + available - cost);
+        }
+    }
+
+    /// Get the storage deposit subscribe_push requires from an account for `method_name`,
+    /// in yoctoNEAR
+    pub fn get_push_subscription_cost(&self, account_id: AccountId, method_name: String) -> U128 {
+        U128(push_subscription_cost(&account_id, &method_name))
+    }
+
+    /// Stop pushing a pair's answers to the caller, refunding its storage deposit
+    pub fn unsubscribe_push(&mut self, pair: Pair) {
+        let caller = env::predecessor_account_id();
+        let removed = self
+            .push_subscribers
+            .get_mut(&pair)
+            .and_then(|subscribers| subscribers.remove(caller.as_str()))
+            .unwrap_or_else(|| panic!("{} is not subscribed to {}", caller, pair));
+        refund_push_deposit(caller, removed.deposit);
+    }
+
+    /// Get the subscribers a pair's answers are pushed to, by account id
+    pub fn get_push_subscribers(&self, pair: Pair) -> Vec<PushSubscriber> {
+        let mut subscribers: Vec<PushSubscriber> = self
+            .push_subscribers
+            .get(&pair)
+            .map(|subscribers| subscribers.values().cloned().collect())
+            .unwrap_or_default();
+        subscribers.sort_by(|a, b| a.account_id.cmp(&b.account_id));
+        subscribers
+    }
+
+    #[private]
+    pub fn on_push(&mut self, account_id: AccountId, pair: Pair) {
+        // Whatever the subscriber returns, only whether its call failed matters
+        let failed = matches!(env::promise_result_checked(0, 0), Err(PromiseError::Failed));
+        self.record_push_result(&account_id, &pair, !failed);
+    }
+}
+
+impl PriceOracle {
+    /// Pushes a pair's latest answer to its subscribers, each in its own receipt so a
+    /// failing subscriber can't affect the others or the round
+    pub(crate) fn push_updates(&mut self, pair: &Pair) {
+        let Some(answer) = self.latest_answer(pair).cloned() else {
+            return;
+        };
+        let fee = self.get_query_fee(pair.base.clone()).0;
+        let mut subscribers = self.get_push_subscribers(pair.clone());
+        subscribers.retain(|subscriber| match (subscriber.min_change_bps, subscriber.last_pushed) {
+            (Some(bps), Some(last)) => deviation_bps(answer.price, last) >= bps as u128,
+            _ => true,
+        });
+
+        for subscriber in subscribers {
+            let account_id: AccountId = subscriber.account_id.parse().expect("caller account id");
+            let gas = Gas::from_tgas(subscriber.gas_tgas);
+            let remaining = env::prepaid_gas().saturating_sub(env::used_gas());
+            if remaining < gas.saturating_add(GAS_FOR_ON_PUSH).saturating_add(GAS_RESERVE) {
+                near_sdk::log!("Push of {} to {} skipped: out of gas", pair, account_id);
+                continue;
+            }
+            let balance = self.get_subscription_balance(account_id.clone()).0;
+            if balance < fee {
+                near_sdk::log!("Push of {} to {} skipped: subscription exhausted", pair, account_id);
+                self.record_push_result(&account_id, pair, false);
+                continue;
+            }
+            if fee > 0 {
+                self.set_subscription_balance(&account_id, balance - fee);
+                self.collect_fee(&pair.base, fee);
+            }
+
+            let args = json!({ "pair": pair, "answer": answer }).to_string().into_bytes();
+            Promise::new(account_id.clone())
+                .function_call(subscriber.method_name, args, NearToken::from_yoctonear(0), gas)
+                .then(
+                    Self::ext(env::current_account_id())
+                        .with_static_gas(GAS_FOR_ON_PUSH)
+                        .on_push(account_id.clone(), pair.clone()),
+                )
+                .detach();
+            let subscriber = self.push_subscriber_mut(pair, &account_id).expect("listed above");
+            subscriber.last_pushed = Some(answer.price);
+        }
+    }
+
+    /// Resets a subscriber's failure count, or drops it after too many failures in a row
+    pub(crate) fn record_push_result(&mut self, account_id: &AccountId, pair: &Pair, succeeded: bool) {
+        let Some(subscriber) = self.push_subscriber_mut(pair, account_id) else {
+            return;
+        };
+        if succeeded {
+            subscriber.failures = 0;
+            return;
+        }
+        subscriber.failures += 1;
+        near_sdk::log!("Push of {} to {} failed ({} in a row)", pair, account_id, subscriber.failures);
+        if subscriber.failures >= MAX_PUSH_FAILURES {
+            let removed = self.push_subscribers.get_mut(pair).expect("found above").remove(account_id.as_str());
+            near_sdk::log!("Push subscriber {} of {} dropped", account_id, pair);
+            refund_push_deposit(account_id.clone(), removed.expect("found above").deposit);
+        }
+    }
+
+    fn push_subscriber_mut(&mut self, pair: &Pair, account_id: &AccountId) -> Option<&mut PushSubscriber> {
+        self.push_subscribers.get_mut(pair)?.get_mut(account_id.as_str())
+    }
+}
+
+/// Deposit covering the storage of an account's push registration
+fn push_subscription_cost(account_id: &AccountId, method_name: &str) -> u128 {
+    let subscriber = PushSubscriber {
+        account_id: account_id.to_string(),
+        method_name: method_name.to_string(),
+        gas_tgas: 0,
+        min_change_bps: Some(0),
+        last_pushed: Some(0),
+        failures: 0,
+        deposit: 0,
+    };
+    let encoded = borsh::object_length(&subscriber).expect("subscribers serialize");
+    let key = borsh::object_length(&subscriber.account_id).expect("strings serialize");
+    let bytes = PUSH_SUBSCRIBER_OVERHEAD_BYTES + (encoded + key) as u64;
+    env::storage_byte_cost().as_yoctonear() * bytes as u128
+}
+
+fn refund_push_deposit(account_id: AccountId, deposit: u128) {
+    if deposit > 0 {
+        Promise::new(account_id).transfer(NearToken::from_yoctonear(deposit)).detach();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::test_utils::{accounts, get_created_receipts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    const FEE: u128 = 10u128.pow(21);
+
+    fn call(account_id: AccountId, deposit: u128) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id)
+            .attached_deposit(NearToken::from_yoctonear(deposit))
+            .build());
+    }
+
+    fn setup() -> PriceOracle {
+        call(accounts(0), 0);
+        let mut contract = PriceOracle::new(accounts(0), 1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.set_query_fee("NEAR".to_string(), U128(FEE));
+        call(accounts(1), cost() + FEE);
+        contract.subscribe_push(Pair::usd("NEAR"), "on_price".to_string(), 10, Some(100));
+        call(accounts(0), 0);
+        contract
+    }
+
+    fn cost() -> u128 {
+        push_subscription_cost(&accounts(1), "on_price")
+    }
+
+    fn report(contract: &mut PriceOracle, price: u128) {
+        call(accounts(0), 0);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(price), 6);
+    }
+
+    #[test]
+    fn test_push_draws_subscription() {
+        let mut contract = setup();
+        report(&mut contract, 5000000);
+        assert_eq!(contract.get_push_subscribers(Pair::usd("NEAR"))[0].last_pushed, Some(5000000));
+        assert_eq!(contract.get_subscription_balance(accounts(1)).0, 0);
+
+        // Moves under 1% aren't pushed; bigger ones are skipped once the balance runs out
+        report(&mut contract, 5010000);
+        assert!(!get_logs().iter().any(|log| log.starts_with("Push of")));
+        report(&mut contract, 5500000);
+        assert!(get_logs().contains(&"Push of NEAR/USD to bob skipped: subscription exhausted".to_string()));
+    }
+
+    #[test]
+    fn test_exhausted_subscriber_dropped() {
+        let mut contract = setup();
+        let pair = Pair::usd("NEAR");
+        for price in [5000000, 5500000, 6000000] {
+            report(&mut contract, price);
+        }
+        assert_eq!(contract.get_push_subscribers(pair.clone())[0].failures, 2);
+
+        report(&mut contract, 6600000);
+        assert!(get_logs().contains(&"Push subscriber bob of NEAR/USD dropped".to_string()));
+        assert!(contract.get_push_subscribers(pair).is_empty());
+        let refund = get_created_receipts().into_iter().find(|receipt| receipt.receiver_id == accounts(1));
+        assert!(refund.is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "Attach at least")]
+    fn test_subscription_needs_storage_deposit() {
+        let mut contract = setup();
+        call(accounts(2), 0);
+        contract.subscribe_push(Pair::usd("NEAR"), "on_price".to_string(), 10, None);
+    }
+
+    #[test]
+    fn test_unsubscribe_refunds_deposit() {
+        let mut contract = setup();
+        call(accounts(1), 0);
+        contract.unsubscribe_push(Pair::usd("NEAR"));
+        assert!(contract.get_push_subscribers(Pair::usd("NEAR")).is_empty());
+        assert_eq!(get_created_receipts()[0].receiver_id, accounts(1));
+    }
+
+    #[test]
+    fn test_failing_subscriber_dropped() {
+        let mut contract = setup();
+        let pair = Pair::usd("NEAR");
+        contract.record_push_result(&accounts(1), &pair, false);
+        contract.record_push_result(&accounts(1), &pair, true);
+        assert_eq!(contract.get_push_subscribers(pair.clone())[0].failures, 0);
+
+        for _ in 0..MAX_PUSH_FAILURES {
+            contract.record_push_result(&accounts(1), &pair, false);
+        }
+        assert!(contract.get_push_subscribers(pair).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Push gas must be between 1 and 30 Tgas")]
+    fn test_push_gas_capped() {
+        let mut contract = setup();
+        call(accounts(1), 0);
+        contract.subscribe_push(Pair::usd("NEAR"), "on_price".to_string(), 100, None);
+    }
+}
@@ -0,0 +1,215 @@
+use std::fmt;
+use std::str::FromStr;
+
+use near_sdk::json_types::{I64, U64};
+use near_sdk::near;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize, Deserializer, Serializer};
+
+use crate::{now_ms, Pair, PriceOracle, PriceOracleExt, Role};
+
+/// 32-byte Pyth price identifier, written in JSON as hex with or without its 0x prefix
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PriceIdentifier(pub [u8; 32]);
+
+impl FromStr for PriceIdentifier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid Pyth price identifier {}", s);
+        let hex = s.strip_prefix("0x").unwrap_or(s);
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err(invalid());
+        }
+        let mut bytes = [0u8; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).map_err(|_| invalid())?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+impl fmt::Display for PriceIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+impl Serialize for PriceIdentifier {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for PriceIdentifier {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(feature = "abi")]
+impl JsonSchema for PriceIdentifier {
+    fn schema_name() -> String {
+        "PriceIdentifier".to_string()
+    }
+
+    fn json_schema(generator: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        String::json_schema(generator)
+    }
+}
+
+/// Price in the shape returned by Pyth's NEAR contract
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(JsonSchema))]
+pub struct PythPrice {
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub price: I64,
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub conf: U64,
+    pub expo: i32,             // price is scaled by 10^expo, i.e. -decimals
+    pub publish_time: i64,     // Unix seconds
+}
+
+impl PythPrice {
+    fn new(pair: &Pair, price: u128, confidence: u128, decimals: u8, timestamp: u64) -> Self {
+        let price = i64::try_from(price).unwrap_or_else(|_| panic!("Price of {} overflows Pyth's i64", pair));
+        Self {
+            price: I64(price),
+            conf: U64(u64::try_from(confidence).unwrap_or(u64::MAX)),
+            expo: -(decimals as i32),
+            publish_time: (timestamp / 1000) as i64,
+        }
+    }
+}
+
+#[near]
+impl PriceOracle {
+    /// Serve a pair under a Pyth price identifier (ConfigManager), so consumers of Pyth
+    /// can read it with their existing ids; `None` unmaps the identifier
+    pub fn set_pyth_feed(&mut self, price_identifier: PriceIdentifier, pair: Option<Pair>) {
+        self.assert_role(Role::ConfigManager);
+        let price_identifier = price_identifier.to_string();
+        match pair {
+            Some(pair) => {
+                self.asset_config(&pair.base);
+                self.pyth_feeds.insert(price_identifier, pair);
+            }
+            None => {
+                self.pyth_feeds.remove(&price_identifier);
+            }
+        }
+    }
+
+    /// Get the Pyth price identifiers served, with their pairs
+    pub fn get_pyth_feeds(&self) -> Vec<(String, Pair)> {
+        let mut feeds: Vec<(String, Pair)> = self.pyth_feeds.iter().map(|(id, pair)| (id.clone(), pair.clone())).collect();
+        feeds.sort_by(|a, b| a.0.cmp(&b.0));
+        feeds
+    }
+
+    /// Whether a Pyth price identifier is served (Pyth interface)
+    pub fn price_feed_exists(&self, price_identifier: PriceIdentifier) -> bool {
+        self.pyth_feeds.contains_key(&price_identifier.to_string())
+    }
+
+    /// Get the latest answer under a Pyth price identifier, however old (Pyth interface)
+    pub fn get_price_unsafe(&self, price_identifier: PriceIdentifier) -> Option<PythPrice> {
+        self.assert_not_paused();
+        let pair = self.pyth_pair(price_identifier)?;
+        if self.breaker_tripped(pair) {
+            return None;
+        }
+        let answer = self.latest_answer(pair)?;
+        Some(PythPrice::new(pair, answer.price, answer.confidence, answer.decimals, answer.finalized_at))
+    }
+
+    /// Get the latest answer under a Pyth price identifier, or None if it finalized
+    /// more than `age` seconds ago (Pyth interface)
+    pub fn get_price_no_older_than(&self, price_id: PriceIdentifier, age: u64) -> Option<PythPrice> {
+        self.get_price_unsafe(price_id).filter(|price| is_within_age(price, age))
+    }
+
+    /// Get the EMA under a Pyth price identifier, however old (Pyth interface)
+    pub fn get_ema_price_unsafe(&self, price_identifier: PriceIdentifier) -> Option<PythPrice> {
+        self.assert_not_paused();
+        let pair = self.pyth_pair(price_identifier)?;
+        let ema = self.ema.get(pair)?;
+        let confidence = self.try_price(pair).map(|p| p.confidence).unwrap_or(0);
+        let decimals = self.asset_config(&pair.base).decimals;
+        Some(PythPrice::new(pair, ema.price, confidence, decimals, ema.last_update))
+    }
+
+    /// Get the EMA under a Pyth price identifier, or None if it was last updated more
+    /// than `age` seconds ago (Pyth interface)
+    pub fn get_ema_price_no_older_than(&self, price_id: PriceIdentifier, age: u64) -> Option<PythPrice> {
+        self.get_ema_price_unsafe(price_id).filter(|price| is_within_age(price, age))
+    }
+}
+
+impl PriceOracle {
+    fn pyth_pair(&self, price_identifier: PriceIdentifier) -> Option<&Pair> {
+        self.pyth_feeds.get(&price_identifier.to_string())
+    }
+}
+
+fn is_within_age(price: &PythPrice, age: u64) -> bool {
+    (now_ms() / 1000).saturating_sub(price.publish_time as u64) <= age
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    const NEAR_USD: &str = "c415de8d2eba7db216527dff4b60e8f3a5311c740dadb233e13e12547e226750";
+
+    fn near_usd() -> PriceIdentifier {
+        NEAR_USD.parse().unwrap()
+    }
+
+    fn setup() -> PriceOracle {
+        testing_env!(VMContextBuilder::new().block_timestamp(10 * 1_000_000_000).build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        let id: PriceIdentifier = near_sdk::serde_json::from_str(&format!(r#""0x{}""#, NEAR_USD.to_uppercase())).unwrap();
+        contract.set_pyth_feed(id, Some(Pair::usd("NEAR")));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5250000), 6);
+        contract
+    }
+
+    #[test]
+    fn test_pyth_price_shape() {
+        let contract = setup();
+        assert!(contract.price_feed_exists(near_usd()));
+        let price = contract.get_price_unsafe(near_usd()).unwrap();
+        assert_eq!(
+            near_sdk::serde_json::to_string(&price).unwrap(),
+            r#"{"price":"5250000","conf":"0","expo":-6,"publish_time":10}"#
+        );
+        assert_eq!(contract.get_ema_price_unsafe(near_usd()).unwrap().price, I64(5250000));
+        assert_eq!(near_sdk::serde_json::to_string(&near_usd()).unwrap(), format!(r#""{}""#, NEAR_USD));
+    }
+
+    #[test]
+    fn test_pyth_price_age() {
+        let mut contract = setup();
+        testing_env!(VMContextBuilder::new().block_timestamp(70 * 1_000_000_000).build());
+        assert!(contract.get_price_no_older_than(near_usd(), 60).is_some());
+        assert!(contract.get_price_no_older_than(near_usd(), 59).is_none());
+
+        contract.set_pyth_feed(near_usd(), None);
+        assert!(contract.get_price_unsafe(near_usd()).is_none());
+    }
+
+    #[test]
+    fn test_invalid_price_identifier() {
+        let err = near_sdk::serde_json::from_str::<PriceIdentifier>(r#""0xc415""#).unwrap_err();
+        assert!(err.to_string().starts_with("Invalid Pyth price identifier 0xc415"));
+        assert!("zz15de8d2eba7db216527dff4b60e8f3a5311c740dadb233e13e12547e226750".parse::<PriceIdentifier>().is_err());
+    }
+}
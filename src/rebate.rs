@@ -0,0 +1,158 @@
+use near_sdk::json_types::U64;
+use near_sdk::{env, near, AccountId, Gas, NearToken, Promise};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::math::U256;
+use crate::reporter::reporter_account;
+use crate::{PriceOracle, PriceOracleExt, Role};
+
+/// Rebate from the reward pool for the gas calls with accepted reports burn
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct GasRebate {
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub yocto_per_tgas: u128,      // Paid per Tgas burned
+    pub max_tgas_per_call: u64,    // Most gas of one call that counts
+}
+
+/// Gas a reporter's calls with accepted reports burned and the rebates it claimed
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct RebateAccount {
+    pub account_id: String,
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub unclaimed_gas: U64,        // Gas not yet rebated
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub claimed: u128,             // Rebates paid out so far (yoctoNEAR)
+}
+
+#[near]
+impl PriceOracle {
+    /// Set or clear the gas rebate (ConfigManager): calls with accepted reports are
+    /// credited the gas they burned, up to `max_tgas_per_call`, which claim_gas_rebate
+    /// pays at `yocto_per_tgas` from the reward pool. Gas counted so far is kept
+    pub fn set_gas_rebate(&mut self, rebate: Option<GasRebate>) {
+        self.assert_role(Role::ConfigManager);
+        if let Some(rebate) = &rebate {
+            assert!(rebate.max_tgas_per_call > 0, "Rebated gas per call must be positive");
+        }
+        self.gas_rebate = rebate;
+    }
+
+    /// Get the gas rebate, if configured
+    pub fn get_gas_rebate(&self) -> Option<GasRebate> {
+        self.gas_rebate.clone()
+    }
+
+    /// Get the gas and rebates of a reporter, if any of its calls was rebated
+    pub fn get_rebate_account(&self, account_id: AccountId) -> Option<RebateAccount> {
+        self.rebate_accounts.get(&account_id).cloned()
+    }
+
+    /// Pay out the rebate of the caller's unclaimed gas at the current rate
+    /// If the reward pool can't cover it all, the pool is paid out and the gas it
+    /// didn't cover stays claimable
+    pub fn claim_gas_rebate(&mut self) -> Promise {
+        let caller = reporter_account();
+        let rate = self.gas_rebate.as_ref().map(|rebate| rebate.yocto_per_tgas).unwrap_or(0);
+        let pool = self.reward_pool;
+        let account = self
+            .rebate_accounts
+            .get_mut(&caller)
+            .filter(|account| account.unclaimed_gas.0 > 0 && rate > 0)
+            .unwrap_or_else(|| panic!("No gas rebate to claim for {}", caller));
+        let gas = U256::from(account.unclaimed_gas.0);
+        let owed = gas * U256::from(rate) / U256::from(Gas::from_tgas(1).as_gas());
+        let amount = owed.min(U256::from(pool));
+        assert!(!amount.is_zero(), "Reward pool is empty, gas rebate of {} not paid", caller);
+        let covered = if amount == owed { gas } else { gas * amount / owed };
+        account.unclaimed_gas = U64(account.unclaimed_gas.0 - covered.as_u64());
+        let amount = amount.as_u128();
+        account.claimed += amount;
+        self.reward_pool -= amount;
+        near_sdk::log!("Gas rebate claimed by {}: {}", caller, amount);
+        Promise::new(caller).transfer(NearToken::from_yoctonear(amount))
+    }
+}
+
+impl PriceOracle {
+    /// Credits the caller with the gas its call burned so far, if the gas rebate is on
+    /// and `accepted` of the call's reports were accepted
+    pub(crate) fn record_report_gas(&mut self, accepted: usize) {
+        let Some(rebate) = self.gas_rebate.as_ref().filter(|_| accepted > 0) else {
+            return;
+        };
+        let gas = env::used_gas().as_gas().min(Gas::from_tgas(rebate.max_tgas_per_call).as_gas());
+        let caller = reporter_account();
+        let account = self.rebate_accounts.entry(caller.clone()).or_insert_with(|| RebateAccount {
+            account_id: caller.to_string(),
+            unclaimed_gas: U64(0),
+            claimed: 0,
+        });
+        account.unclaimed_gas = U64(account.unclaimed_gas.0.saturating_add(gas));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AssetConfig, Pair, PriceBounds};
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn call(account_id: AccountId, deposit: u128) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id)
+            .attached_deposit(NearToken::from_yoctonear(deposit))
+            .build());
+    }
+
+    fn setup() -> PriceOracle {
+        call(accounts(0), 0);
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.set_price_bounds("NEAR".to_string(), Some(PriceBounds { min: 1000000, max: 10000000 }));
+        contract.add_reporter(accounts(1));
+        contract.set_gas_rebate(Some(GasRebate { yocto_per_tgas: 1000, max_tgas_per_call: 10 }));
+        contract
+    }
+
+    #[test]
+    fn test_only_accepted_reports_rebated() {
+        let mut contract = setup();
+        call(accounts(1), 0);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(20000000), 6);
+        assert_eq!(contract.get_rebate_account(accounts(1)), None);
+
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        let gas = contract.get_rebate_account(accounts(1)).unwrap().unclaimed_gas.0;
+        assert!(gas > 0 && gas <= Gas::from_tgas(10).as_gas());
+    }
+
+    #[test]
+    fn test_claim_limited_by_pool() {
+        let mut contract = setup();
+        contract.rebate_accounts.insert(
+            accounts(1),
+            RebateAccount { account_id: "bob".to_string(), unclaimed_gas: U64(Gas::from_tgas(8).as_gas()), claimed: 0 },
+        );
+        call(accounts(2), 2000);
+        contract.fund_rewards();
+
+        // 8 Tgas is owed 8000, of which the pool covers a quarter
+        call(accounts(1), 0);
+        contract.claim_gas_rebate().detach();
+        let account = contract.get_rebate_account(accounts(1)).unwrap();
+        assert_eq!((account.unclaimed_gas.0, account.claimed), (Gas::from_tgas(6).as_gas(), 2000));
+        assert_eq!(contract.get_reward_pool(), U128(0));
+    }
+}
@@ -0,0 +1,376 @@
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::{env, near};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::math::{mul_div_pow10, Rounding};
+use crate::{Pair, PriceOracle, PriceOracleExt, PriceReport, ReportStatus};
+
+/// Payload id of a relayed price, the first byte of the payload
+pub const RELAY_PRICE_PAYLOAD: u8 = 1;
+
+/// Length of a relayed price payload: id, symbol, answer, decimals and updated_at
+const PRICE_PAYLOAD_BYTES: usize = 1 + 32 + 32 + 1 + 8;
+
+/// Wormhole guardians whose signatures make a VAA valid
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct GuardianSet {
+    pub index: u32,
+    pub guardians: Vec<String>, // Ethereum addresses, 20 bytes of lowercase hex
+}
+
+/// A contract on another chain whose price messages are taken as a source
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct RelayEmitter {
+    pub chain_id: u16,         // Wormhole chain id, e.g. 2 for Ethereum
+    pub address: String,       // Emitter address, 32 bytes of lowercase hex
+    pub chain: String,         // Origin chain tagged on the source, e.g. "ethereum"
+    pub source: String,        // Feed it relays, e.g. "chainlink"; recorded as "{chain}:{source}"
+    pub next_sequence: u64,    // Messages below it were taken or skipped
+}
+
+/// The parts of a VAA the oracle checks
+struct Vaa<'a> {
+    guardian_set_index: u32,
+    signatures: Vec<(usize, &'a [u8])>, // Guardian index, 65-byte signature
+    body: &'a [u8],
+    emitter_chain: u16,
+    emitter_address: &'a [u8],
+    sequence: u64,
+    payload: &'a [u8],
+}
+
+/// A price decoded from a relayed payload
+struct RelayedPrice {
+    asset: String,
+    answer: u128,
+    decimals: u8,
+    updated_at: u64,           // Unix seconds
+}
+
+#[near]
+impl PriceOracle {
+    /// Set the Wormhole guardian set VAAs are checked against (owner only)
+    pub fn set_guardian_set(&mut self, index: u32, guardians: Vec<String>) {
+        self.assert_owner();
+        assert!(!guardians.is_empty(), "A guardian set needs guardians");
+        let guardians: Vec<String> = guardians.iter().map(|guardian| normalize_hex(guardian, 20, "Guardian")).collect();
+        near_sdk::log!("Guardian set {} of {} guardians", index, guardians.len());
+        self.guardian_set = Some(GuardianSet { index, guardians });
+    }
+
+    /// Get the Wormhole guardian set, if set
+    pub fn get_guardian_set(&self) -> Option<GuardianSet> {
+        self.guardian_set.clone()
+    }
+
+    /// Take the price messages of an emitter on another chain as a source (owner only)
+    /// Messages from before `next_sequence` are refused
+    pub fn add_relay_emitter(&mut self, emitter: RelayEmitter) {
+        self.assert_owner();
+        let emitter = RelayEmitter {
+            address: normalize_hex(&emitter.address, 32, "Emitter address"),
+            chain: emitter.chain.trim().to_lowercase(),
+            source: emitter.source.trim().to_lowercase(),
+            ..emitter
+        };
+        crate::reporter::normalize_source(&relay_source(&emitter));
+        assert!(
+            self.relay_emitter(emitter.chain_id, &emitter.address).is_none(),
+            "Emitter {} of chain {} is already added",
+            emitter.address,
+            emitter.chain_id
+        );
+        self.relay_emitters.push(emitter);
+    }
+
+    /// Stop taking an emitter's messages (owner only); its last report ages out as usual
+    pub fn remove_relay_emitter(&mut self, chain_id: u16, address: String) {
+        self.assert_owner();
+        let address = normalize_hex(&address, 32, "Emitter address");
+        let before = self.relay_emitters.len();
+        self.relay_emitters.retain(|emitter| !(emitter.chain_id == chain_id && emitter.address == address));
+        assert!(self.relay_emitters.len() < before, "Unknown emitter {} of chain {}", address, chain_id);
+    }
+
+    /// Get the emitters whose messages are taken
+    pub fn get_relay_emitters(&self) -> Vec<RelayEmitter> {
+        self.relay_emitters.clone()
+    }
+
+    /// Submit a Wormhole VAA carrying a price from another chain, from any account
+    /// It must be signed by a quorum of the guardian set and emitted by an added
+    /// emitter, with a sequence not taken before. The payload is the asset's USD price
+    /// as an emitter reading e.g. a Chainlink feed publishes it:
+    /// id 1 (1 byte), symbol (32 bytes, zero-padded ASCII), answer (int256), decimals
+    /// (1 byte) and updated_at (uint64, Unix seconds), big-endian
+    /// The price is rescaled to the asset's decimals and checked like a reported one,
+    /// under source "{chain}:{source}" of the emitter, timestamped at updated_at
+    pub fn submit_relayed_price(&mut self, vaa: Base64VecU8) -> ReportStatus {
+        self.assert_not_paused();
+        let vaa = parse_vaa(&vaa.0).unwrap_or_else(|reason| panic!("Invalid VAA: {}", reason));
+        self.verify_guardian_signatures(&vaa);
+        let address = to_hex(vaa.emitter_address);
+        let emitter = self
+            .relay_emitter(vaa.emitter_chain, &address)
+            .unwrap_or_else(|| panic!("Unknown emitter {} of chain {}", address, vaa.emitter_chain));
+        assert!(
+            vaa.sequence >= emitter.next_sequence,
+            "Sequence {} of emitter {} was already taken, next is {}",
+            vaa.sequence,
+            address,
+            emitter.next_sequence
+        );
+        let relayed = parse_price_payload(vaa.payload).unwrap_or_else(|reason| panic!("Invalid price payload: {}", reason));
+        let source = relay_source(emitter);
+        let index = self
+            .relay_emitters
+            .iter()
+            .position(|known| known.chain_id == vaa.emitter_chain && known.address == address)
+            .expect("found above");
+        self.relay_emitters[index].next_sequence = vaa.sequence + 1;
+
+        let decimals = self.asset_config(&relayed.asset).decimals;
+        let price = mul_div_pow10(relayed.answer, 1, decimals as u32, 1, relayed.decimals as u32, Rounding::Nearest)
+            .unwrap_or_else(|| panic!("Relayed price of {} overflows", relayed.asset));
+        let report = PriceReport {
+            source,
+            price,
+            decimals,
+            timestamp: relayed.updated_at.saturating_mul(1000),
+            reporter: env::current_account_id().to_string(),
+            volume: None,
+            attestation: None,
+        };
+        self.ingest_report(Pair::usd(relayed.asset), report)
+    }
+}
+
+impl PriceOracle {
+    fn relay_emitter(&self, chain_id: u16, address: &str) -> Option<&RelayEmitter> {
+        self.relay_emitters
+            .iter()
+            .find(|emitter| emitter.chain_id == chain_id && emitter.address == address)
+    }
+
+    /// Panics unless more than two thirds of the guardian set signed the VAA
+    fn verify_guardian_signatures(&self, vaa: &Vaa) {
+        let set = self.guardian_set.as_ref().expect("No guardian set");
+        assert!(
+            vaa.guardian_set_index == set.index,
+            "VAA is signed by guardian set {}, expected {}",
+            vaa.guardian_set_index,
+            set.index
+        );
+        let quorum = set.guardians.len() * 2 / 3 + 1;
+        assert!(vaa.signatures.len() >= quorum, "VAA has {} of {} guardian signatures", vaa.signatures.len(), quorum);
+        let digest = env::keccak256_array(env::keccak256_array(vaa.body));
+        let mut previous = None;
+        for (index, signature) in &vaa.signatures {
+            assert!(previous < Some(*index), "Guardian signatures must be in increasing index order");
+            previous = Some(*index);
+            let guardian = set
+                .guardians
+                .get(*index)
+                .unwrap_or_else(|| panic!("No guardian {} in set {}", index, set.index));
+            let signer = env::ecrecover(&digest, &signature[..64], signature[64], true)
+                .map(|key| to_hex(&env::keccak256_array(key)[12..]));
+            assert!(signer.as_ref() == Some(guardian), "Invalid signature of guardian {}", index);
+        }
+    }
+}
+
+/// Source name an emitter's prices are recorded under
+fn relay_source(emitter: &RelayEmitter) -> String {
+    format!("{}:{}", emitter.chain, emitter.source)
+}
+
+/// Splits a version 1 VAA into its signatures and body fields
+fn parse_vaa(bytes: &[u8]) -> Result<Vaa<'_>, &'static str> {
+    let mut reader = Reader(bytes);
+    if reader.take(1)? != [1] {
+        return Err("unsupported version");
+    }
+    let guardian_set_index = reader.u64(4)? as u32;
+    let count = reader.take(1)?[0];
+    let signatures = (0..count)
+        .map(|_| Ok((reader.take(1)?[0] as usize, reader.take(65)?)))
+        .collect::<Result<Vec<_>, &'static str>>()?;
+    let body = reader.0;
+    reader.take(8)?; // timestamp and nonce
+    let emitter_chain = reader.u64(2)? as u16;
+    let emitter_address = reader.take(32)?;
+    let sequence = reader.u64(8)?;
+    reader.take(1)?; // consistency level
+    Ok(Vaa { guardian_set_index, signatures, body, emitter_chain, emitter_address, sequence, payload: reader.0 })
+}
+
+fn parse_price_payload(payload: &[u8]) -> Result<RelayedPrice, &'static str> {
+    if payload.len() != PRICE_PAYLOAD_BYTES {
+        return Err("wrong length");
+    }
+    let mut reader = Reader(payload);
+    if reader.take(1)? != [RELAY_PRICE_PAYLOAD] {
+        return Err("not a price");
+    }
+    let symbol = reader.take(32)?;
+    let symbol = &symbol[..symbol.iter().position(|byte| *byte == 0).unwrap_or(32)];
+    let asset = std::str::from_utf8(symbol).map_err(|_| "symbol isn't utf-8")?.to_string();
+    let answer = reader.take(32)?;
+    if answer[..16].iter().any(|byte| *byte != 0) {
+        return Err("answer is negative or overflows");
+    }
+    let answer = u128::from_be_bytes(answer[16..].try_into().expect("16 bytes"));
+    if answer == 0 {
+        return Err("answer is zero");
+    }
+    Ok(RelayedPrice {
+        asset,
+        answer,
+        decimals: reader.take(1)?[0],
+        updated_at: reader.u64(8)?,
+    })
+}
+
+/// Reads big-endian fields off the front of a byte string
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], &'static str> {
+        if self.0.len() < len {
+            return Err("truncated");
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u64(&mut self, len: usize) -> Result<u64, &'static str> {
+        Ok(self.take(len)?.iter().fold(0, |value, byte| value << 8 | *byte as u64))
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Lowercase form of a hex string of `len` bytes, panicking if it isn't one
+fn normalize_hex(value: &str, len: usize, what: &str) -> String {
+    let hex = value.trim().trim_start_matches("0x").to_ascii_lowercase();
+    assert!(
+        hex.len() == 2 * len && hex.bytes().all(|byte| byte.is_ascii_hexdigit()),
+        "{} must be {} bytes of hex, got {}",
+        what,
+        len,
+        value
+    );
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+    use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+
+    const EMITTER: [u8; 32] = [7; 32];
+
+    fn guardian(seed: u8) -> SecretKey {
+        SecretKey::from_slice(&[seed; 32]).unwrap()
+    }
+
+    fn address(key: &SecretKey) -> String {
+        let public = PublicKey::from_secret_key(&Secp256k1::new(), key).serialize_uncompressed();
+        to_hex(&env::keccak256_array(&public[1..])[12..])
+    }
+
+    fn payload(symbol: &str, answer: u128, decimals: u8, updated_at: u64) -> Vec<u8> {
+        let mut payload = vec![RELAY_PRICE_PAYLOAD];
+        let mut padded = symbol.as_bytes().to_vec();
+        padded.resize(32, 0);
+        payload.extend(padded);
+        payload.extend([0; 16]);
+        payload.extend(answer.to_be_bytes());
+        payload.push(decimals);
+        payload.extend(updated_at.to_be_bytes());
+        payload
+    }
+
+    /// A VAA of `payload` from EMITTER on Ethereum, signed by the given guardians
+    fn vaa(signers: &[(u8, &SecretKey)], sequence: u64, payload: &[u8]) -> Base64VecU8 {
+        let mut body = vec![0; 8];
+        body.extend(2u16.to_be_bytes());
+        body.extend(EMITTER);
+        body.extend(sequence.to_be_bytes());
+        body.push(1);
+        body.extend(payload);
+        let digest = env::keccak256_array(env::keccak256_array(&body));
+        let mut bytes = vec![1, 0, 0, 0, 4, signers.len() as u8];
+        for (index, key) in signers {
+            let signature = Secp256k1::new().sign_ecdsa_recoverable(&Message::from_slice(&digest).unwrap(), key);
+            let (recovery_id, compact) = signature.serialize_compact();
+            bytes.push(*index);
+            bytes.extend(compact);
+            bytes.push(recovery_id.to_i32() as u8);
+        }
+        bytes.extend(body);
+        Base64VecU8(bytes)
+    }
+
+    fn setup() -> PriceOracle {
+        testing_env!(VMContextBuilder::new().block_timestamp(1_760_000_100 * 1_000_000_000).build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("ETH".to_string(), AssetConfig::new("ETH", "Ether", 6));
+        let guardians = (1..=3).map(|seed| address(&guardian(seed))).collect();
+        contract.set_guardian_set(4, guardians);
+        contract.add_relay_emitter(RelayEmitter {
+            chain_id: 2,
+            address: format!("0x{}", to_hex(&EMITTER)),
+            chain: "Ethereum".to_string(),
+            source: "chainlink".to_string(),
+            next_sequence: 10,
+        });
+        contract
+    }
+
+    #[test]
+    fn test_relayed_price_recorded_with_origin_chain() {
+        let mut contract = setup();
+        let keys = [guardian(1), guardian(2), guardian(3)];
+        // A Chainlink answer with 8 decimals, rescaled to ETH's 6
+        let message = vaa(&[(0, &keys[0]), (1, &keys[1]), (2, &keys[2])], 10, &payload("ETH", 300012345678, 8, 1_760_000_000));
+        assert_eq!(contract.submit_relayed_price(message), ReportStatus::Accepted);
+
+        let reports = contract.get_price_details(Pair::usd("ETH"));
+        assert_eq!((reports[0].source.as_str(), reports[0].price), ("ethereum:chainlink", 3000123457));
+        assert_eq!(reports[0].timestamp, 1_760_000_000_000);
+        assert_eq!(contract.get_relay_emitters()[0].next_sequence, 11);
+    }
+
+    #[test]
+    #[should_panic(expected = "VAA has 2 of 3 guardian signatures")]
+    fn test_relay_needs_guardian_quorum() {
+        let mut contract = setup();
+        let (first, third) = (guardian(1), guardian(3));
+        let message = vaa(&[(0, &first), (2, &third)], 10, &payload("ETH", 300000000000, 8, 1_760_000_000));
+        contract.submit_relayed_price(message);
+    }
+
+    #[test]
+    #[should_panic(expected = "Sequence 9 of emitter 0707070707070707070707070707070707070707070707070707070707070707 was already taken, next is 10")]
+    fn test_relayed_sequence_not_replayed() {
+        let mut contract = setup();
+        let keys = [guardian(1), guardian(2), guardian(3)];
+        let message = vaa(&[(0, &keys[0]), (1, &keys[1]), (2, &keys[2])], 9, &payload("ETH", 300000000000, 8, 1_760_000_000));
+        contract.submit_relayed_price(message);
+    }
+}
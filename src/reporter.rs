@@ -0,0 +1,544 @@
+use near_sdk::json_types::U128;
+use near_sdk::{env, near, AccountId, NearToken, Promise};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::{now_ms, remove_where, AssetId, PriceOracle, PriceOracleExt, PriceReport, Role};
+
+/// Bytes a reporter's records take besides its account id, which is stored twice
+/// (registration and role entries)
+pub const REPORTER_RECORD_BYTES: u64 = 64;
+
+/// Longest source name accepted, enough for "flux:" and a 64-character provider account
+pub const MAX_SOURCE_LENGTH: usize = 80;
+
+/// Self-registered reporter awaiting Admin approval
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct PendingReporter {
+    pub account_id: String,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub deposit: u128,         // Storage deposit in yoctoNEAR, refunded if rejected or removed
+    pub requested_at: u64,
+}
+
+/// Latest report of one source of an asset's pair and whether it still counts
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct SourceInfo {
+    pub source: String,
+    pub quote: AssetId,                // Quote asset of the pair the source reports
+    pub bound_reporter: Option<String>, // Account the source is reserved for, if any
+    pub last_reporter: String,
+    pub last_timestamp: u64,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub last_price: u128,
+    pub decimals: u8,
+    pub active: bool,                  // Counts toward prices: enabled, not expired or flatlined
+    pub enabled: bool,                 // Not excluded by an Admin
+    pub weight: u32,                   // Weight under SourceWeighted aggregation
+    pub quarantined: bool,             // Has a newer report held for review
+}
+
+#[near]
+impl PriceOracle {
+    /// Allow an account to submit prices by granting it the Reporter role (Admin)
+    pub fn add_reporter(&mut self, account_id: AccountId) {
+        self.assert_role(Role::Admin);
+        assert!(self.insert_role(Role::Reporter, account_id.clone()), "Reporter {} already whitelisted", account_id);
+        near_sdk::log!("Reporter added: {}", account_id);
+    }
+
+    /// Stop an account from submitting prices by revoking its Reporter role (Admin)
+    /// Its reports are deleted across all pairs, so its last submissions stop counting,
+    /// and its stake starts unbonding
+    /// A self-registered reporter gets its storage deposit back
+    pub fn remove_reporter(&mut self, account_id: AccountId) {
+        self.assert_role(Role::Admin);
+        assert!(self.remove_role(Role::Reporter, &account_id), "Unknown reporter {}", account_id);
+        near_sdk::log!("Reporter removed: {}", account_id);
+        self.retire_reporter(&account_id);
+    }
+
+    /// Apply to become a reporter, paying for the storage of the caller's records
+    /// (see get_registration_cost); the surplus is bonded as stake. The caller can report
+    /// once an Admin approves the application
+    #[payable]
+    pub fn register_reporter(&mut self) {
+        let caller = reporter_account();
+        assert!(!self.is_reporter(caller.clone()), "{} is already a reporter", caller);
+        assert!(!self.pending_reporters.contains_key(&caller), "{} already registered", caller);
+
+        let cost = self.registration_cost(&caller);
+        let attached = env::attached_deposit().as_yoctonear();
+        assert!(attached >= cost, "Attach at least {} yoctoNEAR for storage, got {}", cost, attached);
+
+        near_sdk::log!("Reporter registered: {}", caller);
+        if attached > cost {
+            self.add_stake(&caller, attached - cost);
+        }
+        let pending = PendingReporter { account_id: caller.to_string(), deposit: cost, requested_at: now_ms() };
+        self.pending_reporters.insert(caller, pending);
+    }
+
+    /// Get the storage deposit register_reporter requires from an account, in yoctoNEAR
+    pub fn get_registration_cost(&self, account_id: AccountId) -> U128 {
+        U128(self.registration_cost(&account_id))
+    }
+
+    /// Grant the Reporter role to a self-registered account (Admin)
+    /// Its deposit is kept for as long as it stays a reporter
+    pub fn approve_reporter(&mut self, account_id: AccountId) {
+        self.assert_role(Role::Admin);
+        let pending = self
+            .pending_reporters
+            .remove(&account_id)
+            .unwrap_or_else(|| panic!("No pending registration for {}", account_id));
+        self.insert_role(Role::Reporter, account_id.clone());
+        self.storage_deposits.insert(account_id.clone(), pending.deposit);
+        near_sdk::log!("Reporter added: {}", account_id);
+    }
+
+    /// Turn down a self-registered account and refund its deposit and stake (Admin)
+    pub fn reject_reporter(&mut self, account_id: AccountId) {
+        self.assert_role(Role::Admin);
+        let pending = self
+            .pending_reporters
+            .remove(&account_id)
+            .unwrap_or_else(|| panic!("No pending registration for {}", account_id));
+        near_sdk::log!("Reporter rejected: {}", account_id);
+        let refund = pending.deposit + self.take_stake(&account_id);
+        Promise::new(account_id).transfer(NearToken::from_yoctonear(refund)).detach();
+    }
+
+    /// Get registrations awaiting approval, by account
+    pub fn get_pending_reporters(&self) -> Vec<PendingReporter> {
+        let mut pending: Vec<PendingReporter> = self.pending_reporters.values().cloned().collect();
+        pending.sort_by(|a, b| a.account_id.cmp(&b.account_id));
+        pending
+    }
+
+    /// Get whitelisted reporter accounts in lexicographic order
+    /// The owner and Admins can always report and aren't listed unless added explicitly
+    pub fn get_reporters(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<AccountId> {
+        self.get_role_members(Role::Reporter, from_index, limit)
+    }
+
+    /// Check if an account may submit prices
+    pub fn is_reporter(&self, account_id: AccountId) -> bool {
+        self.has_role(Role::Reporter, account_id)
+    }
+
+    /// Reserve a source for one account, so nobody else can report under its name (Admin)
+    /// Unbound sources stay open to every reporter
+    pub fn bind_source(&mut self, source: String, account_id: AccountId) {
+        self.assert_role(Role::Admin);
+        let source = normalize_source(&source);
+        near_sdk::log!("Source {} bound to {}", source, account_id);
+        self.source_reporters.insert(source, account_id);
+    }
+
+    /// Open a bound source to every reporter again (Admin)
+    pub fn unbind_source(&mut self, source: String) {
+        self.assert_role(Role::Admin);
+        let source = normalize_source(&source);
+        assert!(self.source_reporters.remove(&source).is_some(), "Source {} is not bound", source);
+        near_sdk::log!("Source {} unbound", source);
+    }
+
+    /// Delete every report of a source across all pairs and drop its binding (Admin)
+    /// The name is matched verbatim, so sources stored before names were normalized can
+    /// still be cleaned up
+    pub fn remove_source(&mut self, source: String) {
+        self.assert_role(Role::Admin);
+        self.source_reporters.remove(&source);
+        let purged = self.purge_reports(|report| report.source == source);
+        near_sdk::log!("Source removed: {} ({} reports purged)", source, purged);
+    }
+
+    /// Get the account a source is bound to, if any
+    pub fn get_source_reporter(&self, source: String) -> Option<AccountId> {
+        self.source_reporters.get(&normalize_source(&source)).cloned()
+    }
+
+    /// Exclude a source from an asset's prices, or count it again (Admin)
+    /// A disabled source keeps reporting and its reports are kept, so it can be enabled
+    /// again without waiting for fresh reports
+    pub fn set_source_enabled(&mut self, asset_id: AssetId, source: String, enabled: bool) {
+        self.assert_role(Role::Admin);
+        self.asset_config(&asset_id);
+        let source = normalize_source(&source);
+        let disabled = self.disabled_sources.entry(asset_id.clone()).or_default();
+        let changed = if enabled { disabled.remove(&source) } else { disabled.insert(source.clone()) };
+        assert!(changed, "Source {} already {} for {}", source, if enabled { "enabled" } else { "disabled" }, asset_id);
+        if disabled.is_empty() {
+            self.disabled_sources.remove(&asset_id);
+        }
+        for pair in self.prices.pairs_of(&asset_id) {
+            self.refresh_aggregate(&pair);
+        }
+        near_sdk::log!("Source {} {} for {}", source, if enabled { "enabled" } else { "disabled" }, asset_id);
+    }
+
+    /// Get the sources excluded from an asset's prices, sorted
+    pub fn get_disabled_sources(&self, asset_id: AssetId) -> Vec<String> {
+        let mut sources: Vec<String> = self.disabled_sources.get(&asset_id).into_iter().flatten().cloned().collect();
+        sources.sort();
+        sources
+    }
+
+    /// Get every source reporting an asset's pairs with its latest report, binding and
+    /// state, ordered by quote and source
+    pub fn get_sources(&self, asset_id: AssetId) -> Vec<SourceInfo> {
+        self.asset_config(&asset_id);
+        let mut pairs = self.prices.pairs_of(&asset_id);
+        pairs.sort();
+        pairs
+            .iter()
+            .flat_map(|pair| {
+                let mut reports = self.reports(pair);
+                reports.sort_by(|a, b| a.source.cmp(&b.source));
+                reports.into_iter().map(move |report| SourceInfo {
+                    source: report.source.clone(),
+                    quote: pair.quote.clone(),
+                    bound_reporter: self.source_reporters.get(&report.source).map(|id| id.to_string()),
+                    last_reporter: report.reporter.clone(),
+                    last_timestamp: report.timestamp,
+                    last_price: report.price,
+                    decimals: report.decimals,
+                    active: self.is_live(pair, report),
+                    enabled: self.is_source_enabled(&pair.base, &report.source),
+                    weight: self.source_weight(&report.source),
+                    quarantined: self.quarantine.get(pair).is_some_and(|held| held.contains_key(&report.source)),
+                })
+            })
+            .collect()
+    }
+}
+
+impl PriceOracle {
+    /// Deletes every report an account submitted, starts unbonding its stake and refunds
+    /// its storage deposit if it self-registered. Runs whenever Reporter is taken away
+    pub(crate) fn retire_reporter(&mut self, account_id: &AccountId) {
+        let purged = self.purge_reports(|report| report.reporter == account_id.as_str());
+        near_sdk::log!("Reports purged for {}: {}", account_id, purged);
+        self.reporter_keys.remove(account_id);
+        let stake = self.get_reporter_stake(account_id.clone()).0;
+        if stake > 0 {
+            self.start_unbonding(account_id, stake);
+        }
+        if let Some(deposit) = self.storage_deposits.remove(account_id) {
+            Promise::new(account_id.clone()).transfer(NearToken::from_yoctonear(deposit)).detach();
+        }
+    }
+
+    /// Deletes matching reports from the latest prices, open rounds and quarantine;
+    /// returns how many were deleted. Finalized answers are left as they are
+    fn purge_reports(&mut self, matches: impl Fn(&PriceReport) -> bool) -> usize {
+        let removed = self.prices.remove_reports(&matches);
+        let purged = removed.len();
+        self.release_reports_storage(removed);
+        self.invalidate_aggregates();
+        for reports in self.quarantine.values_mut() {
+            reports.retain(|_, report| !matches(report));
+        }
+        remove_where(&mut self.quarantine, |_, reports| reports.is_empty());
+        self.retain_submissions(|report| !matches(report));
+        purged
+    }
+
+    fn registration_cost(&self, account_id: &AccountId) -> u128 {
+        let bytes = REPORTER_RECORD_BYTES + 2 * account_id.len() as u64;
+        env::storage_byte_cost().as_yoctonear() * bytes as u128
+    }
+
+    pub(crate) fn is_source_enabled(&self, asset_id: &AssetId, source: &str) -> bool {
+        self.disabled_sources.get(asset_id).is_none_or(|disabled| !disabled.contains(source))
+    }
+
+    /// Panics unless the caller may report and the source is unbound or bound to the caller
+    pub(crate) fn assert_reporter(&self, source: &str) {
+        let caller = reporter_account();
+        assert!(self.is_reporter(caller.clone()), "Reporter {} is not whitelisted", caller);
+        if let Some(bound) = self.source_reporters.get(source) {
+            assert!(bound == &caller, "Source {} is bound to {}", source, bound);
+        }
+    }
+}
+
+/// Account a reporter-facing call acts for, which reports, stake, credit and refunds
+/// belong to: the predecessor. Under a NEP-366 delegate action that's the reporter who
+/// signed the action, while the transaction's signer is the relayer paying for gas, so
+/// identity must never come from env::signer_account_id
+pub(crate) fn reporter_account() -> AccountId {
+    env::predecessor_account_id()
+}
+
+/// Source name in canonical form: trimmed and lowercased, so "Binance" and "binance "
+/// count as one source. Panics unless it's 1-80 characters of a-z, 0-9, '.', '_', '-', ':'
+pub(crate) fn normalize_source(source: &str) -> String {
+    try_normalize_source(source).unwrap_or_else(|reason| panic!("{}", reason))
+}
+
+/// As normalize_source, returning why the name is invalid instead of panicking
+pub(crate) fn try_normalize_source(source: &str) -> Result<String, String> {
+    let normalized = source.trim().to_lowercase();
+    if normalized.is_empty() {
+        return Err("Source name must not be empty".to_string());
+    }
+    if normalized.len() > MAX_SOURCE_LENGTH {
+        return Err(format!("Source name must be at most {} characters, got {}", MAX_SOURCE_LENGTH, normalized.len()));
+    }
+    if let Some(invalid) = normalized.chars().find(|c| !(c.is_ascii_alphanumeric() || "._-:".contains(*c))) {
+        return Err(format!("Invalid character {:?} in source {}", invalid, normalized));
+    }
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AssetConfig, Pair};
+    use near_sdk::test_utils::{accounts, get_created_receipts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn as_account(account_id: AccountId) {
+        testing_env!(VMContextBuilder::new().predecessor_account_id(account_id).build());
+    }
+
+    fn register_as(contract: &mut PriceOracle, account_id: AccountId, deposit: u128) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id)
+            .attached_deposit(NearToken::from_yoctonear(deposit))
+            .build());
+        contract.register_reporter();
+    }
+
+    fn setup() -> PriceOracle {
+        as_account(accounts(0));
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract
+    }
+
+    #[test]
+    fn test_whitelisted_reporter_can_report() {
+        let mut contract = setup();
+        contract.add_reporter(accounts(2));
+        contract.add_reporter(accounts(1));
+        assert_eq!(contract.get_reporters(None, None), vec![accounts(1), accounts(2)]);
+
+        as_account(accounts(1));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5000000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Reporter bob is not whitelisted")]
+    fn test_unlisted_reporter_rejected() {
+        let mut contract = setup();
+        contract.add_reporter(accounts(1));
+        contract.remove_reporter(accounts(1));
+        assert!(!contract.is_reporter(accounts(1)));
+
+        as_account(accounts(1));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+    }
+
+    #[test]
+    fn test_bound_source_accepts_its_reporter() {
+        let mut contract = setup();
+        contract.add_reporter(accounts(1));
+        contract.bind_source("binance".to_string(), accounts(1));
+        assert_eq!(contract.get_source_reporter("binance".to_string()), Some(accounts(1)));
+
+        as_account(accounts(1));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        assert_eq!(contract.get_price_details(Pair::usd("NEAR"))[0].reporter, "bob");
+    }
+
+    #[test]
+    #[should_panic(expected = "Source binance is bound to bob")]
+    fn test_bound_source_rejects_others() {
+        let mut contract = setup();
+        contract.add_reporter(accounts(1));
+        contract.add_reporter(accounts(2));
+        contract.bind_source("binance".to_string(), accounts(1));
+
+        as_account(accounts(2));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+    }
+
+    #[test]
+    fn test_get_sources() {
+        let mut contract = setup();
+        contract.add_reporter(accounts(1));
+        contract.bind_source("binance".to_string(), accounts(1));
+        contract.set_max_report_age("NEAR".to_string(), Some(60));
+        contract.report_price(Pair::usd("NEAR"), "kraken".to_string(), U128(5100000), 6);
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id(accounts(1)).block_timestamp(61_000_000_000).build());
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        contract.report_price(Pair::new("NEAR", "EUR"), "binance".to_string(), U128(4600000), 6);
+
+        let sources = contract.get_sources("NEAR".to_string());
+        let summary: Vec<_> = sources.iter().map(|s| (s.quote.as_str(), s.source.as_str(), s.active)).collect();
+        assert_eq!(summary, vec![("EUR", "binance", true), ("USD", "binance", true), ("USD", "kraken", false)]);
+        assert_eq!(sources[1].bound_reporter.as_deref(), Some("bob"));
+        assert_eq!((sources[1].last_price, sources[1].last_timestamp), (5000000, 61000));
+        assert_eq!((sources[2].bound_reporter.clone(), sources[2].last_reporter.as_str()), (None, "alice"));
+    }
+
+    #[test]
+    fn test_get_sources_flags_quarantined() {
+        let mut contract = setup();
+        contract.set_deviation_gate(Some(crate::DeviationGate { max_deviation_bps: 1000, quarantine: true }));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(8000000), 6);
+
+        let sources = contract.get_sources("NEAR".to_string());
+        assert_eq!(sources.len(), 1);
+        assert_eq!((sources[0].last_price, sources[0].quarantined), (5000000, true));
+    }
+
+    #[test]
+    fn test_disabled_source_kept_but_not_counted() {
+        let mut contract = setup();
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        contract.set_source_enabled("NEAR".to_string(), "Glitch".to_string(), false);
+        assert!(get_logs().contains(&"Source glitch disabled for NEAR".to_string()));
+        contract.report_price(Pair::usd("NEAR"), "glitch".to_string(), U128(9000000), 6);
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5000000);
+        assert_eq!(contract.get_disabled_sources("NEAR".to_string()), vec!["glitch"]);
+
+        let sources = contract.get_sources("NEAR".to_string());
+        assert_eq!((sources[1].source.as_str(), sources[1].last_price), ("glitch", 9000000));
+        assert_eq!((sources[1].active, sources[1].enabled), (false, false));
+
+        contract.set_source_enabled("NEAR".to_string(), "glitch".to_string(), true);
+        assert!(contract.get_sources("NEAR".to_string())[1].active);
+        contract.report_price(Pair::usd("NEAR"), "glitch".to_string(), U128(5100000), 6);
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5100000);
+        assert!(contract.get_disabled_sources("NEAR".to_string()).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Source binance already enabled for NEAR")]
+    fn test_enable_enabled_source_rejected() {
+        let mut contract = setup();
+        contract.set_source_enabled("NEAR".to_string(), "binance".to_string(), true);
+    }
+
+    #[test]
+    #[should_panic(expected = "charlie lacks role Admin")]
+    fn test_disable_source_requires_admin() {
+        let mut contract = setup();
+        as_account(accounts(2));
+        contract.set_source_enabled("NEAR".to_string(), "binance".to_string(), false);
+    }
+
+    #[test]
+    fn test_self_registration_needs_approval() {
+        let mut contract = setup();
+        let cost = contract.get_registration_cost(accounts(1)).0;
+        assert_eq!(cost, (64 + 2 * 3) * 10u128.pow(19));    // 1e19 yoctoNEAR per byte
+        register_as(&mut contract, accounts(1), cost * 2);
+        assert!(!contract.is_reporter(accounts(1)));
+        assert_eq!(contract.get_pending_reporters()[0].deposit, cost);
+        assert_eq!(contract.get_reporter_stake(accounts(1)).0, cost);     // Surplus is bonded
+
+        as_account(accounts(0));
+        contract.approve_reporter(accounts(1));
+        assert!(contract.get_pending_reporters().is_empty());
+        as_account(accounts(1));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+    }
+
+    #[test]
+    fn test_revoked_reporter_gets_deposit_back() {
+        let mut contract = setup();
+        let cost = contract.get_registration_cost(accounts(1)).0;
+        register_as(&mut contract, accounts(1), cost);
+        as_account(accounts(0));
+        contract.approve_reporter(accounts(1));
+        contract.revoke_role(Role::Reporter, accounts(1));
+
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].receiver_id, accounts(1));
+        contract.grant_role(Role::Reporter, accounts(1));
+        contract.revoke_role(Role::Reporter, accounts(1));
+        assert_eq!(get_created_receipts().len(), 1);   // Refunded once
+    }
+
+    #[test]
+    #[should_panic(expected = "Attach at least 700000000000000000000 yoctoNEAR for storage, got 1")]
+    fn test_registration_requires_deposit() {
+        let mut contract = setup();
+        register_as(&mut contract, accounts(1), 1);
+    }
+
+    #[test]
+    fn test_removed_reporter_reports_are_purged() {
+        let mut contract = setup();
+        contract.set_min_sources(3);
+        contract.add_asset("BTC".to_string(), AssetConfig::new("BTC", "Bitcoin", 2));
+        contract.add_reporter(accounts(1));
+        contract.report_price(Pair::usd("NEAR"), "coingecko".to_string(), U128(5000000), 6);
+        as_account(accounts(1));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(9000000), 6);
+        contract.report_price(Pair::usd("BTC"), "binance".to_string(), U128(6000000), 2);
+
+        as_account(accounts(0));
+        contract.remove_reporter(accounts(1));
+        assert!(get_logs().contains(&"Reports purged for bob: 2".to_string()));
+        assert_eq!(contract.get_pairs(), vec![Pair::usd("NEAR")]);
+        assert_eq!(contract.get_source_count(Pair::usd("NEAR")), 1);
+        assert_eq!(contract.get_open_round(Pair::usd("NEAR")).unwrap().submissions.len(), 1);
+    }
+
+    #[test]
+    fn test_removed_source_reports_are_purged() {
+        let mut contract = setup();
+        contract.bind_source("binance".to_string(), accounts(0));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        contract.remove_source("binance".to_string());
+        assert_eq!(contract.get_source_reporter("binance".to_string()), None);
+        assert!(contract.get_pairs().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "bob lacks role Admin")]
+    fn test_add_reporter_requires_admin() {
+        let mut contract = setup();
+        as_account(accounts(1));
+        contract.add_reporter(accounts(1));
+    }
+
+    #[test]
+    fn test_source_names_normalized() {
+        let mut contract = setup();
+        contract.report_price(Pair::usd("NEAR"), "Binance".to_string(), U128(5000000), 6);
+        contract.report_price(Pair::usd("NEAR"), " binance ".to_string(), U128(5100000), 6);
+        assert_eq!(contract.get_source_count(Pair::usd("NEAR")), 1);
+        assert_eq!(contract.get_price_details(Pair::usd("NEAR"))[0].source, "binance");
+        assert_eq!(try_normalize_source("flux:Provider.near"), Ok("flux:provider.near".to_string()));
+    }
+
+    #[test]
+    fn test_invalid_source_names() {
+        assert_eq!(try_normalize_source("  "), Err("Source name must not be empty".to_string()));
+        assert_eq!(
+            try_normalize_source(&"a".repeat(81)),
+            Err("Source name must be at most 80 characters, got 81".to_string())
+        );
+        assert_eq!(try_normalize_source("coin gecko"), Err("Invalid character ' ' in source coin gecko".to_string()));
+    }
+}
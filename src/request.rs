@@ -0,0 +1,263 @@
+use near_sdk::json_types::U128;
+use near_sdk::serde_json::json;
+use near_sdk::{env, near, AccountId, Gas, NearToken, Promise};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::push::MAX_PUSH_GAS_TGAS;
+use crate::reporter::{normalize_source, reporter_account};
+use crate::{now_ms, paginate, AssetId, OracleEvent, Pair, PriceOracle, PriceOracleExt, PriceReport, ReportStatus};
+
+/// Time reporters have to fulfill a request before the requester can cancel it
+pub const DEFAULT_REQUEST_TIMEOUT_SECONDS: u64 = 3600;
+
+/// Open requests at a time, so pending requests can't grow the state without bound
+pub const MAX_OPEN_REQUESTS: usize = 100;
+
+/// Bytes an open request is charged for: the request and the answers it collects
+pub const REQUEST_STORAGE_BYTES: u64 = 1500;
+
+/// A one-off request for an asset's USD price, answered once min_sources reporters
+/// fulfill it
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct PriceRequest {
+    pub request_id: u64,
+    pub pair: Pair,
+    pub requester: String,
+    pub callback: String,          // Called with {"request_id": ..., "pair": ..., "price": Price}
+    pub gas_tgas: u64,             // Gas attached to the callback
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub fee: u128,                 // Paid into the reward pool on fulfillment (yoctoNEAR)
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub deposit: u128,             // Storage deposit refunded once the request closes (yoctoNEAR)
+    pub expires_at: u64,           // Cancellable from then on
+    pub fulfillments: Vec<PriceReport>, // Accepted answers, at most one per source
+}
+
+#[near]
+impl PriceOracle {
+    /// Ask reporters for the USD price of an asset that isn't streamed continuously
+    /// The asset's query fee must be attached on top of the request's storage deposit
+    /// (see get_request_deposit), which is refunded once it's fulfilled or cancelled, so
+    /// filling the open slots isn't free. Logs a price_requested event reporters watch
+    /// for, and calls the caller's `callback` once the request is fulfilled
+    /// Returns the request id
+    #[payable]
+    pub fn request_price(&mut self, asset_id: AssetId, callback: String, gas_tgas: u64) -> u64 {
+        self.assert_not_paused();
+        self.asset_config(&asset_id);
+        assert!(
+            gas_tgas > 0 && gas_tgas <= MAX_PUSH_GAS_TGAS,
+            "Callback gas must be between 1 and {} Tgas",
+            MAX_PUSH_GAS_TGAS
+        );
+        assert!(self.price_requests.len() < MAX_OPEN_REQUESTS, "{} requests are already open", MAX_OPEN_REQUESTS);
+        let deposit = request_deposit();
+        let fee = self.get_query_fee(asset_id.clone()).0;
+        let attached = env::attached_deposit().as_yoctonear();
+        assert!(
+            attached >= fee + deposit,
+            "Attach {} yoctoNEAR to request {}, got {}",
+            fee + deposit,
+            asset_id,
+            attached
+        );
+
+        self.next_request_id += 1;
+        let request = PriceRequest {
+            request_id: self.next_request_id,
+            pair: Pair::usd(asset_id),
+            requester: env::predecessor_account_id().to_string(),
+            callback,
+            gas_tgas,
+            fee: attached - deposit,
+            deposit,
+            expires_at: now_ms() + DEFAULT_REQUEST_TIMEOUT_SECONDS * 1000,
+            fulfillments: Vec::new(),
+        };
+        OracleEvent::PriceRequested {
+            request_id: request.request_id,
+            pair: request.pair.clone(),
+            requester: request.requester.clone(),
+        }
+        .emit();
+        self.price_requests.insert(request.request_id, request);
+        self.next_request_id
+    }
+
+    /// Answer an open request with a price (Reporter); it's also reported like
+    /// report_price, with the report deposit attached. Once min_sources answers are
+    /// accepted they're aggregated with the asset's method, the requester's callback is
+    /// called with the result and the fee goes to the reward pool
+    #[payable]
+    pub fn fulfill_request(&mut self, request_id: u64, source: String, price: U128, decimals: u8) {
+        let price = price.0;
+        self.assert_not_paused();
+        self.assert_direct_reports();
+        let request = self
+            .price_requests
+            .get(&request_id)
+            .unwrap_or_else(|| panic!("Unknown request {}", request_id));
+        let source = normalize_source(&source);
+        assert!(
+            request.fulfillments.iter().all(|report| report.source != source),
+            "{} already fulfilled request {}",
+            source,
+            request_id
+        );
+        let pair = request.pair.clone();
+        if self.report_now(pair, source.clone(), price, decimals, None, None) != ReportStatus::Accepted {
+            return;
+        }
+
+        let report = PriceReport {
+            source,
+            price,
+            decimals,
+            timestamp: now_ms(),
+            reporter: reporter_account().to_string(),
+            volume: None,
+            attestation: None,
+        };
+        let request = self.price_requests.get_mut(&request_id).expect("found above");
+        request.fulfillments.push(report);
+        let request = request.clone();
+        let method = self.asset_config(&request.pair.base).aggregation;
+        let Some(answer) = self.aggregate(&request.pair, request.fulfillments.iter().collect(), method.aggregator().as_ref())
+        else {
+            return;
+        };
+
+        self.price_requests.remove(&request_id);
+        self.add_to_reward_pool(request.fee);
+        OracleEvent::RequestFulfilled { request_id, pair: request.pair.clone(), price: answer.clone() }.emit();
+        let args = json!({ "request_id": request_id, "pair": request.pair, "price": answer }).to_string().into_bytes();
+        let requester: AccountId = request.requester.parse().expect("requester account id");
+        Promise::new(requester.clone())
+            .function_call(request.callback, args, NearToken::from_yoctonear(0), Gas::from_tgas(request.gas_tgas))
+            .detach();
+        Promise::new(requester).transfer(NearToken::from_yoctonear(request.deposit)).detach();
+    }
+
+    /// Drop a request nobody fulfilled in time, refunding its fee and deposit to the
+    /// requester
+    /// Anyone can call this once the request has expired
+    pub fn cancel_request(&mut self, request_id: u64) -> Promise {
+        let request = self
+            .price_requests
+            .get(&request_id)
+            .unwrap_or_else(|| panic!("Unknown request {}", request_id));
+        assert!(now_ms() >= request.expires_at, "Request {} is open until {}", request_id, request.expires_at);
+        let request = self.price_requests.remove(&request_id).expect("found above");
+        near_sdk::log!("Request {} cancelled with {} answers", request_id, request.fulfillments.len());
+        Promise::new(request.requester.parse().expect("requester account id"))
+            .transfer(NearToken::from_yoctonear(request.fee + request.deposit))
+    }
+
+    /// Get the storage deposit request_price requires on top of the query fee, in yoctoNEAR
+    pub fn get_request_deposit(&self) -> U128 {
+        U128(request_deposit())
+    }
+
+    /// Get an open request
+    pub fn get_request(&self, request_id: u64) -> Option<PriceRequest> {
+        self.price_requests.get(&request_id).cloned()
+    }
+
+    /// Get the open requests, oldest first
+    pub fn get_open_requests(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<PriceRequest> {
+        let mut requests: Vec<PriceRequest> = self.price_requests.values().cloned().collect();
+        requests.sort_by_key(|request| request.request_id);
+        paginate(requests, from_index, limit)
+    }
+}
+
+fn request_deposit() -> u128 {
+    env::storage_byte_cost().as_yoctonear() * REQUEST_STORAGE_BYTES as u128
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::test_utils::{accounts, get_created_receipts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn call(account_id: AccountId, seconds: u64, deposit: u128) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id)
+            .block_timestamp(seconds * 1_000_000_000)
+            .attached_deposit(NearToken::from_yoctonear(deposit))
+            .build());
+    }
+
+    fn setup() -> PriceOracle {
+        call(accounts(0), 0, 0);
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(2);
+        contract.add_asset("AURORA".to_string(), AssetConfig::new("AURORA", "Aurora", 6));
+        contract.set_query_fee("AURORA".to_string(), U128(500));
+        contract.add_reporter(accounts(1));
+        contract.add_reporter(accounts(2));
+        contract
+    }
+
+    #[test]
+    fn test_request_fulfilled_at_quorum() {
+        let mut contract = setup();
+        call(accounts(3), 10, request_deposit() + 500);
+        let request_id = contract.request_price("AURORA".to_string(), "on_price".to_string(), 10);
+        assert!(get_logs()[0].contains(r#""event":"price_requested","data":{"request_id":1,"#));
+
+        call(accounts(1), 20, 0);
+        contract.fulfill_request(request_id, "binance".to_string(), U128(100000), 6);
+        assert_eq!(contract.get_request(request_id).unwrap().fulfillments.len(), 1);
+        call(accounts(2), 30, 0);
+        contract.fulfill_request(request_id, "kraken".to_string(), U128(110000), 6);
+
+        assert_eq!(contract.get_request(request_id), None);
+        assert_eq!(contract.get_reward_pool(), U128(500));
+        assert!(get_logs().iter().any(|log| log.contains(r#""event":"request_fulfilled""#)));
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.last().unwrap().receiver_id, accounts(3));
+        assert!(matches!(
+            receipts.last().unwrap().actions[0],
+            near_sdk::mock::MockAction::Transfer { deposit, .. } if deposit.as_yoctonear() == request_deposit()
+        ));
+    }
+
+    #[test]
+    fn test_expired_request_refunded() {
+        let mut contract = setup();
+        call(accounts(3), 10, request_deposit() + 500);
+        let request_id = contract.request_price("AURORA".to_string(), "on_price".to_string(), 10);
+        call(accounts(4), 10 + DEFAULT_REQUEST_TIMEOUT_SECONDS, 0);
+        contract.cancel_request(request_id).detach();
+        assert!(contract.get_open_requests(None, None).is_empty());
+        assert_eq!(get_created_receipts()[0].receiver_id, accounts(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "Attach 15000000000000000000500 yoctoNEAR to request AURORA, got 500")]
+    fn test_request_requires_fee_and_deposit() {
+        let mut contract = setup();
+        call(accounts(3), 10, 500);
+        contract.request_price("AURORA".to_string(), "on_price".to_string(), 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "Attach 15000000000000000000000 yoctoNEAR to request AURORA, got 0")]
+    fn test_free_request_still_pays_storage() {
+        let mut contract = setup();
+        contract.set_query_fee("AURORA".to_string(), U128(0));
+        call(accounts(3), 10, 0);
+        contract.request_price("AURORA".to_string(), "on_price".to_string(), 10);
+    }
+}
@@ -0,0 +1,289 @@
+use near_sdk::json_types::U128;
+use near_sdk::{env, near, AccountId, NearToken, Promise};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::guard::deviation_bps;
+use crate::math::U256;
+use crate::reporter::reporter_account;
+use crate::{now_ms, paginate, PriceOracle, PriceOracleExt, PriceReport, Role};
+
+/// Default distance from the answer a submission may be to count as accurate
+pub const DEFAULT_REWARD_BAND_BPS: u16 = 50;
+
+/// Default time between reward distributions
+pub const DEFAULT_REWARD_PERIOD_SECONDS: u64 = 24 * 3600;
+
+/// How accurate submissions are counted and how often the pool is paid out
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct RewardConfig {
+    pub band_bps: u16,              // Submissions within this of the finalized answer are accurate
+    pub distribution_period: u64,   // Seconds between distributions
+}
+
+impl Default for RewardConfig {
+    fn default() -> Self {
+        Self {
+            band_bps: DEFAULT_REWARD_BAND_BPS,
+            distribution_period: DEFAULT_REWARD_PERIOD_SECONDS,
+        }
+    }
+}
+
+/// Reward accounting of one reporter
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct RewardAccount {
+    pub account_id: String,
+    pub accurate_submissions: u64, // Since the last distribution
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub claimable: u128,           // Distributed and not yet claimed (yoctoNEAR)
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub claimed: u128,             // Paid out so far (yoctoNEAR)
+    pub distribution: u64,         // Distribution the accurate submissions count toward
+}
+
+#[near]
+impl PriceOracle {
+    /// Add the attached fees to the reward pool. Anyone can call this
+    #[payable]
+    pub fn fund_rewards(&mut self) {
+        let amount = env::attached_deposit().as_yoctonear();
+        assert!(amount > 0, "Attach the fees to fund rewards");
+        self.add_to_reward_pool(amount);
+        near_sdk::log!("Reward pool funded with {} by {}", amount, env::predecessor_account_id());
+    }
+
+    /// Split the pool between reporters in proportion to their accurate submissions
+    /// since the last distribution, once the distribution period has passed
+    /// Anyone can call this; with no accurate submissions the pool carries over
+    /// Only the pool and the submission count are recorded, so the gas doesn't grow with
+    /// the reporters; each account takes its share the next time it's touched
+    pub fn distribute_rewards(&mut self) {
+        let next = self.get_next_distribution();
+        assert!(now_ms() >= next, "Rewards can't be distributed before {}", next);
+        self.last_distribution = now_ms();
+
+        let total = self.accurate_submissions;
+        if total == 0 {
+            near_sdk::log!("No accurate submissions, reward pool of {} carried over", self.reward_pool);
+            return;
+        }
+
+        let pool = self.reward_pool;
+        let distribution = self.distributions;
+        self.reward_distributions.insert(distribution, (pool, total));
+        self.distributions += 1;
+        self.accurate_submissions = 0;
+        self.reward_pool = 0;
+        near_sdk::log!("Rewards distributed: {} for {} accurate submissions", pool, total);
+    }
+
+    /// Pay out the caller's distributed rewards
+    pub fn claim_rewards(&mut self) -> Promise {
+        let caller = reporter_account();
+        let mut account = self
+            .reward_accounts
+            .get(&caller)
+            .map(|account| self.settled(account))
+            .filter(|account| account.claimable > 0)
+            .unwrap_or_else(|| panic!("No rewards to claim for {}", caller));
+        let amount = account.claimable;
+        account.claimable = 0;
+        account.claimed += amount;
+        near_sdk::log!("Rewards claimed by {}: {}", caller, amount);
+        self.reward_accounts.insert(caller.clone(), account);
+        Promise::new(caller).transfer(NearToken::from_yoctonear(amount))
+    }
+
+    /// Get the fees waiting for the next distribution, in yoctoNEAR
+    pub fn get_reward_pool(&self) -> U128 {
+        U128(self.reward_pool)
+    }
+
+    /// Get the earliest time the next distribution can run
+    pub fn get_next_distribution(&self) -> u64 {
+        self.last_distribution + self.reward_config.distribution_period * 1000
+    }
+
+    /// Get the accuracy band and distribution period
+    pub fn get_reward_config(&self) -> RewardConfig {
+        self.reward_config.clone()
+    }
+
+    /// Set the accuracy band and distribution period (ConfigManager)
+    /// Submissions already counted keep counting
+    pub fn set_reward_config(&mut self, config: RewardConfig) {
+        self.assert_role(Role::ConfigManager);
+        self.reward_config = config;
+    }
+
+    /// Get the reward accounting of a reporter, if it ever submitted an accurate report
+    pub fn get_reward_account(&self, account_id: AccountId) -> Option<RewardAccount> {
+        self.reward_accounts.get(&account_id).map(|account| self.settled(account))
+    }
+
+    /// Get the reward accounting of every reporter, by account id
+    pub fn get_reward_accounts(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<RewardAccount> {
+        let mut accounts: Vec<RewardAccount> =
+            self.reward_accounts.values().map(|account| self.settled(account)).collect();
+        accounts.sort_by(|a, b| a.account_id.cmp(&b.account_id));
+        paginate(accounts, from_index, limit)
+    }
+}
+
+impl PriceOracle {
+    pub(crate) fn add_to_reward_pool(&mut self, amount: u128) {
+        self.reward_pool += amount;
+    }
+
+    /// Credits the reporters of a finalized round whose submissions landed within the band
+    pub(crate) fn record_accuracy(&mut self, answer: u128, submissions: &[PriceReport]) {
+        let band = self.reward_config.band_bps as u128;
+        for report in submissions {
+            if deviation_bps(report.price, answer) > band {
+                continue;
+            }
+            let Ok(reporter) = report.reporter.parse::<AccountId>() else {
+                continue;
+            };
+            let mut account = self.reward_accounts.get(&reporter).map_or_else(
+                || RewardAccount {
+                    account_id: report.reporter.clone(),
+                    accurate_submissions: 0,
+                    claimable: 0,
+                    claimed: 0,
+                    distribution: self.distributions,
+                },
+                |account| self.settled(account),
+            );
+            account.accurate_submissions += 1;
+            self.accurate_submissions += 1;
+            self.reward_accounts.insert(reporter, account);
+        }
+    }
+
+    /// An account with its share of the distribution its submissions counted toward
+    /// moved into claimable, once that distribution has run
+    fn settled(&self, account: &RewardAccount) -> RewardAccount {
+        let mut account = account.clone();
+        if account.distribution == self.distributions {
+            return account;
+        }
+        if let Some((pool, total)) = self.reward_distributions.get(&account.distribution) {
+            let share = U256::from(*pool) * U256::from(account.accurate_submissions) / U256::from(*total);
+            account.claimable += share.as_u128();
+        }
+        account.accurate_submissions = 0;
+        account.distribution = self.distributions;
+        account
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AssetConfig, Pair};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    const NEAR: u128 = 10u128.pow(24);
+
+    fn call(account_id: AccountId, seconds: u64, deposit: u128) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id)
+            .block_timestamp(seconds * 1_000_000_000)
+            .attached_deposit(NearToken::from_yoctonear(deposit))
+            .build());
+    }
+
+    fn report(contract: &mut PriceOracle, reporter: usize, source: &str, price: u128) {
+        report_at(contract, 1000, reporter, source, price);
+    }
+
+    fn report_at(contract: &mut PriceOracle, seconds: u64, reporter: usize, source: &str, price: u128) {
+        call(accounts(reporter), seconds, 0);
+        contract.report_price(Pair::usd("NEAR"), source.to_string(), U128(price), 6);
+    }
+
+    /// bob and charlie report within 0.5% of the 5010000 answer, danny 3.8% off
+    fn setup() -> PriceOracle {
+        call(accounts(0), 1000, 0);
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(3);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        for i in 1..=3 {
+            contract.add_reporter(accounts(i));
+        }
+        report(&mut contract, 1, "binance", 5000000);
+        report(&mut contract, 2, "coingecko", 5010000);
+        report(&mut contract, 3, "kraken", 5200000);
+
+        call(accounts(4), 1000, 9 * NEAR);
+        contract.fund_rewards();
+        contract
+    }
+
+    #[test]
+    fn test_rewards_split_by_accuracy() {
+        let mut contract = setup();
+        assert_eq!(contract.get_reward_account(accounts(1)).unwrap().accurate_submissions, 1);
+        assert_eq!(contract.get_reward_account(accounts(3)), None);
+
+        call(accounts(4), 1000 + DEFAULT_REWARD_PERIOD_SECONDS, 0);
+        contract.distribute_rewards();
+        assert_eq!(contract.get_reward_pool().0, 0);
+
+        call(accounts(2), 1000 + DEFAULT_REWARD_PERIOD_SECONDS, 0);
+        contract.claim_rewards().detach();
+        let account = contract.get_reward_account(accounts(2)).unwrap();
+        assert_eq!((account.claimable, account.claimed), (0, 9 * NEAR / 2));
+        assert_eq!(contract.get_reward_accounts(None, None)[0].claimable, 9 * NEAR / 2);
+    }
+
+    #[test]
+    fn test_shares_kept_across_distributions() {
+        let mut contract = setup();
+        let later = 1000 + DEFAULT_REWARD_PERIOD_SECONDS;
+        call(accounts(4), later, 0);
+        contract.distribute_rewards();
+
+        // bob's first share waits in claimable while its next submission counts toward
+        // the second distribution
+        report_at(&mut contract, later, 1, "binance", 5000000);
+        report_at(&mut contract, later, 2, "coingecko", 5010000);
+        report_at(&mut contract, later, 3, "kraken", 5200000);
+        call(accounts(4), later, 4 * NEAR);
+        contract.fund_rewards();
+        call(accounts(4), later + DEFAULT_REWARD_PERIOD_SECONDS, 0);
+        contract.distribute_rewards();
+
+        let account = contract.get_reward_account(accounts(1)).unwrap();
+        assert_eq!((account.accurate_submissions, account.claimable), (0, 9 * NEAR / 2 + 2 * NEAR));
+        call(accounts(1), later + DEFAULT_REWARD_PERIOD_SECONDS, 0);
+        contract.claim_rewards().detach();
+        assert_eq!(contract.get_reward_account(accounts(1)).unwrap().claimed, 13 * NEAR / 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Rewards can't be distributed before 86400000")]
+    fn test_distribution_waits_for_period() {
+        let mut contract = setup();
+        contract.distribute_rewards();
+    }
+
+    #[test]
+    #[should_panic(expected = "No rewards to claim for danny")]
+    fn test_claim_without_rewards() {
+        let mut contract = setup();
+        call(accounts(3), 1000, 0);
+        contract.claim_rewards().detach();
+    }
+}
@@ -0,0 +1,434 @@
+use near_sdk::{env, near};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::guard::deviation_bps;
+use crate::{now_ms, AssetId, OracleEvent, Pair, PriceOracle, PriceOracleExt, PriceReport, Role, RoundAnswer};
+
+/// Time a round stays open before it can close short of its quorum
+pub const DEFAULT_ROUND_TIMEOUT_SECONDS: u64 = 60;
+
+/// Round collecting submissions for a pair
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct Round {
+    pub round_id: u64,
+    pub started_at: u64,
+    pub submissions: Vec<PriceReport>, // In arrival order, at most one per source
+}
+
+/// Round bookkeeping of one pair
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default)]
+#[cfg_attr(feature = "abi", derive(BorshSchema))]
+pub(crate) struct FeedRounds {
+    open: Option<Round>,
+    latest: Option<RoundAnswer>,
+    held: Option<RoundAnswer>, // Latest answer held back by the rate limit
+    held_rounds: u8,           // Consecutive rounds held back
+    next_round_id: u64,
+}
+
+#[near]
+impl PriceOracle {
+    /// Get the round currently collecting submissions for a pair
+    pub fn get_open_round(&self, pair: Pair) -> Option<Round> {
+        self.rounds.get(&pair).and_then(|feed| feed.open.clone())
+    }
+
+    /// Get the latest finalized round of a pair
+    pub fn get_latest_round(&self, pair: Pair) -> Option<RoundAnswer> {
+        self.assert_not_paused();
+        self.assert_breaker_closed(&pair);
+        self.rounds.get(&pair).and_then(|feed| feed.latest.clone())
+    }
+
+    /// Get the id of the latest finalized round of an asset's USD pair
+    /// Round ids only increase, also across clear_prices, so an id pins one observation
+    pub fn latest_round(&self, asset_id: AssetId) -> u64 {
+        self.assert_not_paused();
+        let pair = Pair::usd(asset_id);
+        self.assert_breaker_closed(&pair);
+        self.latest_answer(&pair)
+            .unwrap_or_else(|| panic!("No answer for {}", pair))
+            .round_id
+    }
+
+    /// Get a finalized round of an asset's USD pair by id, while it's kept in the history
+    pub fn get_round_data(&self, asset_id: AssetId, round_id: u64) -> RoundAnswer {
+        self.assert_not_paused();
+        self.asset_config(&asset_id);
+        self.assert_breaker_closed(&Pair::usd(asset_id.clone()));
+        let answers = self.history.get(&asset_id);
+        answers
+            .and_then(|answers| {
+                let index = answers.binary_search_by_key(&round_id, |answer| answer.round_id).ok()?;
+                answers.get(index).cloned()
+            })
+            .unwrap_or_else(|| panic!("Round {} of {} is not kept", round_id, Pair::usd(asset_id)))
+    }
+
+    /// Get the latest answer of a pair the rate limit is holding back, awaiting confirmation
+    pub fn get_held_round(&self, pair: Pair) -> Option<RoundAnswer> {
+        self.rounds.get(&pair).and_then(|feed| feed.held.clone())
+    }
+
+    /// Close a pair's open round once its deadline has passed: it finalizes if it has
+    /// min_sources submissions and is discarded otherwise. Anyone can call this
+    pub fn finalize_round(&mut self, pair: Pair) {
+        self.assert_not_paused();
+        let round = self
+            .get_open_round(pair.clone())
+            .unwrap_or_else(|| panic!("No open round for {}", pair));
+        let deadline = self.round_deadline(&round);
+        assert!(now_ms() >= deadline, "Round {} of {} is open until {}", round.round_id, pair, deadline);
+        self.close_round(&pair);
+    }
+
+    /// Get the submissions that finalize a round early (defaults to min_sources) for
+    /// assets without their own min_sources
+    pub fn get_round_quorum(&self) -> u8 {
+        self.round_quorum.unwrap_or(self.min_sources).max(self.min_sources)
+    }
+
+    /// Get the seconds a round stays open before it can close short of its quorum
+    pub fn get_round_timeout(&self) -> u64 {
+        self.round_timeout
+    }
+
+    /// Configure rounds (ConfigManager): `quorum` submissions finalize a round immediately
+    /// (None follows min_sources, and it never goes below it); otherwise the round
+    /// closes `timeout_seconds` after it opened
+    pub fn set_round_config(&mut self, quorum: Option<u8>, timeout_seconds: u64) {
+        self.assert_role(Role::ConfigManager);
+        assert!(timeout_seconds > 0, "Round timeout must be positive");
+        self.round_quorum = quorum;
+        self.round_timeout = timeout_seconds;
+    }
+}
+
+impl PriceOracle {
+    /// Adds a report to the pair's open round, opening one if needed, and finalizes
+    /// the round once it reaches quorum
+    /// A source that already submitted to the round has its submission replaced; reports
+    /// are checked with submitted_round first, so only released quarantined ones get here
+    pub(crate) fn submit_to_round(&mut self, pair: &Pair, report: PriceReport) {
+        let now = now_ms();
+        let expired = self
+            .get_open_round(pair.clone())
+            .is_some_and(|round| now >= self.round_deadline(&round));
+        if expired {
+            self.close_round(pair);
+        }
+
+        let quorum = self.quorum_of(&pair.base) as usize;
+        let feed = self.rounds.entry(pair.clone()).or_default();
+        if feed.open.is_none() {
+            feed.next_round_id += 1;
+            feed.open = Some(Round {
+                round_id: feed.next_round_id,
+                started_at: now,
+                submissions: Vec::new(),
+            });
+        }
+        let round = feed.open.as_mut().expect("opened above");
+        round.submissions.retain(|s| s.source != report.source);
+        round.submissions.push(report);
+
+        if round.submissions.len() >= quorum {
+            self.close_round(pair);
+        }
+    }
+
+    /// Id of the pair's open round if `source` already submitted to it and the round
+    /// hasn't passed its deadline
+    pub(crate) fn submitted_round(&self, pair: &Pair, source: &str) -> Option<u64> {
+        let round = self.rounds.get(pair)?.open.as_ref()?;
+        let open = now_ms() < self.round_deadline(round);
+        (open && round.submissions.iter().any(|s| s.source == source)).then_some(round.round_id)
+    }
+
+    /// Submissions that finalize a round of an asset's pairs early; never fewer than its
+    /// min_sources
+    fn quorum_of(&self, asset_id: &AssetId) -> u8 {
+        let min_sources = self.min_sources_of(asset_id);
+        self.round_quorum.unwrap_or(min_sources).max(min_sources)
+    }
+
+    /// Drops the rounds of the pairs matching `drop`, keeping their round counters so
+    /// ids are never reused
+    pub(crate) fn reset_rounds(&mut self, drop: impl Fn(&Pair) -> bool) {
+        for (_, feed) in self.rounds.iter_mut().filter(|(pair, _)| drop(pair)) {
+            *feed = FeedRounds { next_round_id: feed.next_round_id, ..FeedRounds::default() };
+        }
+    }
+
+    /// Keeps only the open-round submissions that satisfy `keep`
+    pub(crate) fn retain_submissions(&mut self, keep: impl Fn(&PriceReport) -> bool) {
+        for round in self.rounds.values_mut().filter_map(|feed| feed.open.as_mut()) {
+            round.submissions.retain(&keep);
+        }
+    }
+
+    /// Latest finalized answer of a pair
+    pub(crate) fn latest_answer(&self, pair: &Pair) -> Option<&RoundAnswer> {
+        self.rounds.get(pair).and_then(|feed| feed.latest.as_ref())
+    }
+
+    fn round_deadline(&self, round: &Round) -> u64 {
+        round.started_at + self.round_timeout * 1000
+    }
+
+    /// Finalizes the open round into an answer, or discards it without enough submissions
+    fn close_round(&mut self, pair: &Pair) {
+        let Some(round) = self.rounds.get_mut(pair).and_then(|feed| feed.open.take()) else {
+            return;
+        };
+        let method = self.asset_config(&pair.base).aggregation;
+        let Some(price) = self.aggregate(pair, round.submissions.iter().collect(), method.aggregator().as_ref())
+        else {
+            near_sdk::log!(
+                "Round {} of {} discarded with {} submissions",
+                round.round_id,
+                pair,
+                round.submissions.len()
+            );
+            return;
+        };
+
+        let answer = RoundAnswer {
+            round_id: round.round_id,
+            price: price.price,
+            decimals: price.decimals,
+            confidence: price.confidence,
+            submissions: round.submissions.len() as u8,
+            started_at: round.started_at,
+            finalized_at: now_ms(),
+            finalized_at_block: env::block_height(),
+        };
+        let excess = self.rate_limit_excess(pair, &answer);
+        let (confirmations, agreement_bps) = self
+            .rate_limit
+            .as_ref()
+            .map_or((0, 0), |limit| (limit.confirmations, limit.max_move_bps as u128));
+        let feed = self.rounds.get_mut(pair).expect("taken above");
+        if let Some((move_bps, allowed_bps)) = excess {
+            // A round only confirms the held answer if it lands within the limit's bps of it;
+            // otherwise the streak starts over from this round
+            if let Some(held) = feed.held.as_ref() {
+                let apart_bps = deviation_bps(answer.price, held.price);
+                if apart_bps > agreement_bps {
+                    near_sdk::log!(
+                        "Round {} of {} disagrees with held round {}: {} bps apart, {} bps allowed",
+                        answer.round_id,
+                        pair,
+                        held.round_id,
+                        apart_bps,
+                        agreement_bps
+                    );
+                    feed.held_rounds = 0;
+                }
+            }
+            feed.held_rounds = feed.held_rounds.saturating_add(1);
+            if feed.held_rounds < confirmations {
+                near_sdk::log!(
+                    "Round {} of {} held: {} bps move, {} bps allowed ({} of {} confirmations)",
+                    answer.round_id,
+                    pair,
+                    move_bps,
+                    allowed_bps,
+                    feed.held_rounds,
+                    confirmations
+                );
+                feed.held = Some(answer);
+                return;
+            }
+        }
+
+        OracleEvent::RoundFinalized { pair: pair.clone(), answer: answer.clone() }.emit();
+        feed.held = None;
+        feed.held_rounds = 0;
+        let (round_id, price, confidence) = (answer.round_id, answer.price, answer.confidence);
+        let previous = feed.latest.replace(answer).map(|previous| previous.price);
+        self.slash_deviations(pair, round_id, price, &round.submissions);
+        self.record_accuracy(price, &round.submissions);
+        self.record_deviations(price, &round.submissions);
+        self.check_alerts(pair, previous, round_id, price);
+        self.check_breaker(pair, previous, round_id, price, confidence);
+        self.on_round_finalized(pair);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn at(seconds: u64) {
+        testing_env!(VMContextBuilder::new().block_timestamp(seconds * 1_000_000_000).build());
+    }
+
+    fn setup() -> PriceOracle {
+        at(1000);
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(2);
+        contract.set_round_config(Some(3), 60);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract
+    }
+
+    fn report(contract: &mut PriceOracle, source: &str, price: u128) {
+        contract.report_price(Pair::usd("NEAR"), source.to_string(), U128(price), 6);
+    }
+
+    #[test]
+    fn test_round_data_by_id() {
+        let mut contract = setup();
+        contract.set_round_config(None, 60);
+        for price in [5000000, 5100000, 5200000] {
+            report(&mut contract, "binance", price);
+            report(&mut contract, "coingecko", price);
+        }
+        assert_eq!(contract.latest_round("NEAR".to_string()), 3);
+        assert_eq!(contract.get_round_data("NEAR".to_string(), 2).price, 5100000);
+
+        // Clearing prices doesn't reuse ids
+        contract.clear_prices();
+        report(&mut contract, "binance", 5300000);
+        report(&mut contract, "coingecko", 5300000);
+        assert_eq!(contract.latest_round("NEAR".to_string()), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "Round 1 of NEAR/USD is not kept")]
+    fn test_round_data_not_kept() {
+        let mut contract = setup();
+        contract.set_round_config(None, 60);
+        contract.set_history_length(1);
+        for price in [5000000, 5100000] {
+            report(&mut contract, "binance", price);
+            report(&mut contract, "coingecko", price);
+        }
+        contract.get_round_data("NEAR".to_string(), 1);
+    }
+
+    #[test]
+    fn test_round_finalizes_at_quorum() {
+        let mut contract = setup();
+        report(&mut contract, "binance", 5000000);
+        report(&mut contract, "coingecko", 5200000);
+        assert_eq!(contract.get_open_round(Pair::usd("NEAR")).unwrap().submissions.len(), 2);
+        assert_eq!(contract.get_latest_round(Pair::usd("NEAR")), None);
+
+        report(&mut contract, "kraken", 5100000);
+        let answer = contract.get_latest_round(Pair::usd("NEAR")).unwrap();
+        assert_eq!((answer.round_id, answer.price, answer.submissions), (1, 5100000, 3));
+        assert_eq!(contract.get_open_round(Pair::usd("NEAR")), None);
+
+        // The next round doesn't touch the finalized answer until it finalizes itself
+        report(&mut contract, "binance", 9000000);
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5100000);
+        assert_eq!(contract.get_open_round(Pair::usd("NEAR")).unwrap().round_id, 2);
+    }
+
+    #[test]
+    fn test_round_closes_after_deadline() {
+        let mut contract = setup();
+        report(&mut contract, "binance", 5000000);
+        report(&mut contract, "coingecko", 5200000);
+
+        at(1060);
+        contract.finalize_round(Pair::usd("NEAR"));
+        let answer = contract.get_latest_round(Pair::usd("NEAR")).unwrap();
+        assert_eq!((answer.price, answer.submissions), (5100000, 2));
+
+        // Below min_sources the round is dropped when the next submission arrives late
+        report(&mut contract, "binance", 6000000);
+        at(1120);
+        report(&mut contract, "coingecko", 7000000);
+        assert!(get_logs().contains(&"Round 2 of NEAR/USD discarded with 1 submissions".to_string()));
+        assert_eq!(contract.get_open_round(Pair::usd("NEAR")).unwrap().round_id, 3);
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5100000);
+    }
+
+    #[test]
+    fn test_one_submission_per_source() {
+        let mut contract = setup();
+        report(&mut contract, "binance", 5000000);
+        report(&mut contract, "binance", 5100000);
+        assert!(get_logs().contains(&"Price rejected for NEAR/USD: 5100000 from binance (already reported in round 1)".to_string()));
+        let round = contract.get_open_round(Pair::usd("NEAR")).unwrap();
+        assert_eq!(round.submissions.len(), 1);
+        assert_eq!(round.submissions[0].price, 5000000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Round 1 of NEAR/USD is open until 1060000")]
+    fn test_finalize_before_deadline() {
+        let mut contract = setup();
+        report(&mut contract, "binance", 5000000);
+        contract.finalize_round(Pair::usd("NEAR"));
+    }
+
+    fn finalized() -> PriceOracle {
+        let mut contract = setup();
+        for source in ["binance", "coingecko", "kraken"] {
+            report(&mut contract, source, 5000000);
+        }
+        contract
+    }
+
+    #[test]
+    #[should_panic(expected = "Oracle paused")]
+    fn test_latest_round_closed_while_paused() {
+        let mut contract = finalized();
+        contract.set_paused(true);
+        contract.get_latest_round(Pair::usd("NEAR"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Circuit breaker tripped for NEAR/USD")]
+    fn test_latest_round_closed_while_tripped() {
+        let mut contract = finalized();
+        contract.trip_breaker_for_tests(&Pair::usd("NEAR"));
+        contract.get_latest_round(Pair::usd("NEAR"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Oracle paused")]
+    fn test_latest_round_id_closed_while_paused() {
+        let mut contract = finalized();
+        contract.set_paused(true);
+        contract.latest_round("NEAR".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Circuit breaker tripped for NEAR/USD")]
+    fn test_latest_round_id_closed_while_tripped() {
+        let mut contract = finalized();
+        contract.trip_breaker_for_tests(&Pair::usd("NEAR"));
+        contract.latest_round("NEAR".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Oracle paused")]
+    fn test_round_data_closed_while_paused() {
+        let mut contract = finalized();
+        contract.set_paused(true);
+        contract.get_round_data("NEAR".to_string(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Circuit breaker tripped for NEAR/USD")]
+    fn test_round_data_closed_while_tripped() {
+        let mut contract = finalized();
+        contract.trip_breaker_for_tests(&Pair::usd("NEAR"));
+        contract.get_round_data("NEAR".to_string(), 1);
+    }
+}
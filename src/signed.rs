@@ -0,0 +1,314 @@
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::{env, near, AccountId, CurveType, PublicKey};
+use near_sdk::borsh;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::reporter::reporter_account;
+use crate::{BatchReport, OracleEvent, Pair, PriceOracle, PriceOracleExt, RejectReason, ReportStatus, Role, MAX_BATCH_REPORTS};
+
+/// A price a reporter signed off chain with its packet key, for anyone to relay
+/// The signature covers packet_message: no relayer can alter, replay or redirect it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(JsonSchema))]
+pub struct SignedPrice {
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub reporter: AccountId,
+    pub pair: Pair,
+    pub source: String,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub price: u128,           // Signed as the u128 itself, whatever its JSON form
+    pub decimals: u8,
+    pub timestamp: u64,        // When the reporter observed the price, Unix ms
+    pub nonce: u64,            // Above every nonce of the reporter's packets taken so far
+    pub signature: Base64VecU8, // ed25519 signature of packet_message
+}
+
+#[near]
+impl PriceOracle {
+    /// Set or clear the ed25519 key the caller signs relayed price packets with (Reporter)
+    pub fn set_reporter_key(&mut self, public_key: Option<PublicKey>) {
+        self.assert_role(Role::Reporter);
+        let caller = reporter_account();
+        match public_key {
+            Some(key) => {
+                assert!(key.curve_type() == CurveType::ED25519, "Packet keys must be ed25519");
+                near_sdk::log!("Packet key of {} set: {}", caller, String::from(&key));
+                self.reporter_keys.insert(caller, key);
+            }
+            None => {
+                self.reporter_keys.remove(&caller);
+                near_sdk::log!("Packet key of {} cleared", caller);
+            }
+        }
+    }
+
+    /// Get the key a reporter signs relayed price packets with, if it set one
+    pub fn get_reporter_key(&self, account_id: AccountId) -> Option<PublicKey> {
+        self.reporter_keys.get(&account_id).cloned()
+    }
+
+    /// Get the nonce of a reporter's last packet taken; its next one must be higher
+    pub fn get_packet_nonce(&self, account_id: AccountId) -> u64 {
+        self.packet_nonces.get(&account_id).copied().unwrap_or(0)
+    }
+
+    /// Submit up to MAX_BATCH_REPORTS prices signed off chain by reporters, from any account
+    /// Each packet is verified against its reporter's key and nonce, then checked like a
+    /// report_prices entry of that reporter, timestamped when it was signed. The report
+    /// deposit is due per packet, and the caller pays for the storage the reports take
+    /// Returns the status of every packet, in order
+    #[payable]
+    pub fn submit_signed_prices(&mut self, packets: Vec<SignedPrice>) -> Vec<ReportStatus> {
+        self.assert_not_paused();
+        self.assert_direct_reports();
+        assert!(!packets.is_empty(), "No packets to submit");
+        assert!(
+            packets.len() <= MAX_BATCH_REPORTS,
+            "At most {} packets per call, got {}",
+            MAX_BATCH_REPORTS,
+            packets.len()
+        );
+        self.assert_report_deposit(packets.len() as u128);
+
+        let mut reporters: Vec<AccountId> = Vec::new();
+        let statuses: Vec<ReportStatus> = packets
+            .into_iter()
+            .map(|packet| {
+                if let Err((code, reason)) = self.verify_packet(&packet) {
+                    near_sdk::log!("Packet rejected from {}: {}", packet.reporter, reason);
+                    OracleEvent::rejected(&packet.pair, &packet.source, packet.reporter.as_str(), packet.price, code, &reason).emit();
+                    return ReportStatus::Rejected(reason);
+                }
+                self.packet_nonces.insert(packet.reporter.clone(), packet.nonce);
+                if !reporters.contains(&packet.reporter) {
+                    reporters.push(packet.reporter.clone());
+                }
+                let entry = BatchReport {
+                    pair: packet.pair,
+                    source: packet.source,
+                    price: packet.price,
+                    decimals: packet.decimals,
+                    volume: None,
+                };
+                self.ingest_batch_report(entry, &packet.reporter, packet.timestamp)
+            })
+            .collect();
+        let accepted = statuses.iter().filter(|status| **status == ReportStatus::Accepted).count();
+        self.record_report_gas(accepted);
+        let rejected = statuses.iter().filter(|status| status.is_rejected()).count();
+        self.settle_relayed_deposit(rejected as u128, &reporters);
+        statuses
+    }
+}
+
+impl PriceOracle {
+    /// Why a packet can't be taken as its reporter's, if it can't
+    fn verify_packet(&self, packet: &SignedPrice) -> Result<(), (RejectReason, String)> {
+        let message = packet_message(&env::current_account_id(), packet);
+        self.verify_reporter_signature(&packet.reporter, packet.nonce, &message, &packet.signature.0)
+    }
+
+    /// Why `signature` isn't a reporter's over `message` with a fresh `nonce`, if it isn't
+    pub(crate) fn verify_reporter_signature(
+        &self,
+        reporter: &AccountId,
+        nonce: u64,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), (RejectReason, String)> {
+        let unauthorized = |reason| (RejectReason::Unauthorized, reason);
+        if !self.is_reporter(reporter.clone()) {
+            return Err(unauthorized(format!("Reporter {} is not whitelisted", reporter)));
+        }
+        let key = self
+            .reporter_keys
+            .get(reporter)
+            .ok_or_else(|| unauthorized(format!("Reporter {} has no packet key", reporter)))?;
+        let last = self.get_packet_nonce(reporter.clone());
+        if nonce <= last {
+            return Err((RejectReason::Duplicate, format!("Nonce {} is not above {}", nonce, last)));
+        }
+        let signature: &[u8; 64] = signature
+            .try_into()
+            .map_err(|_| unauthorized(format!("Signatures are 64 bytes, got {}", signature.len())))?;
+        let key: &[u8; 32] = key.as_bytes()[1..].try_into().expect("ed25519 keys are 32 bytes");
+        if !env::ed25519_verify(signature, message, key) {
+            return Err(unauthorized("Invalid signature".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Bytes a reporter signs for a packet to `oracle`: the borsh encoding of
+/// (oracle, reporter, pair, source, price, decimals, timestamp, nonce)
+pub fn packet_message(oracle: &AccountId, packet: &SignedPrice) -> Vec<u8> {
+    borsh::to_vec(&(
+        oracle,
+        &packet.reporter,
+        &packet.pair,
+        &packet.source,
+        packet.price,
+        packet.decimals,
+        packet.timestamp,
+        packet.nonce,
+    ))
+    .expect("packets serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use ed25519_dalek::{Signer, SigningKey};
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{accounts, get_created_receipts, get_logs, VMContextBuilder};
+    use near_sdk::{testing_env, NearToken};
+
+    fn signing_key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    fn public_key(key: &SigningKey) -> PublicKey {
+        PublicKey::from_parts(CurveType::ED25519, key.verifying_key().to_bytes().to_vec()).unwrap()
+    }
+
+    fn packet(key: &SigningKey, reporter: AccountId, price: u128, timestamp: u64, nonce: u64) -> SignedPrice {
+        let mut packet = SignedPrice {
+            reporter,
+            pair: Pair::usd("NEAR"),
+            source: "binance".to_string(),
+            price,
+            decimals: 6,
+            timestamp,
+            nonce,
+            signature: Base64VecU8(Vec::new()),
+        };
+        let message = packet_message(&env::current_account_id(), &packet);
+        packet.signature = Base64VecU8(key.sign(&message).to_bytes().to_vec());
+        packet
+    }
+
+    fn setup() -> PriceOracle {
+        testing_env!(VMContextBuilder::new().predecessor_account_id(accounts(0)).build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.add_asset("ETH".to_string(), AssetConfig::new("ETH", "Ether", 6));
+        contract.add_reporter(accounts(1));
+        testing_env!(VMContextBuilder::new().predecessor_account_id(accounts(1)).build());
+        contract.set_reporter_key(Some(public_key(&signing_key(1))));
+        // Packets are relayed by danny, who holds no role
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(10_000_000_000)
+            .build());
+        contract
+    }
+
+    #[test]
+    fn test_relayed_packet_reported_as_signer() {
+        let mut contract = setup();
+        let statuses = contract.submit_signed_prices(vec![packet(&signing_key(1), accounts(1), 5250000, 9000, 1)]);
+        assert_eq!(statuses, [ReportStatus::Accepted]);
+
+        let reports = contract.get_price_details(Pair::usd("NEAR"));
+        assert_eq!((reports[0].reporter.as_str(), reports[0].timestamp), ("bob", 9000));
+        assert_eq!(contract.get_price(Pair::usd("NEAR")).price, 5250000);
+        assert_eq!(contract.get_packet_nonce(accounts(1)), 1);
+    }
+
+    #[test]
+    fn test_forged_and_replayed_packets_rejected() {
+        let mut contract = setup();
+        let signed = packet(&signing_key(1), accounts(1), 5250000, 9000, 1);
+        let tampered = SignedPrice { price: 9990000, ..signed.clone() };
+        let impostor = packet(&signing_key(2), accounts(1), 5250000, 9000, 2);
+        let unkeyed = packet(&signing_key(2), accounts(2), 5250000, 9000, 1);
+
+        let statuses = contract.submit_signed_prices(vec![tampered, impostor, unkeyed, signed.clone(), signed]);
+        assert_eq!(
+            statuses,
+            [
+                ReportStatus::Rejected("Invalid signature".to_string()),
+                ReportStatus::Rejected("Invalid signature".to_string()),
+                ReportStatus::Rejected("Reporter charlie is not whitelisted".to_string()),
+                ReportStatus::Accepted,
+                ReportStatus::Rejected("Nonce 1 is not above 1".to_string()),
+            ]
+        );
+        let codes: Vec<String> = get_logs()
+            .iter()
+            .filter_map(|log| log.strip_prefix("EVENT_JSON:"))
+            .map(|event| near_sdk::serde_json::from_str::<near_sdk::serde_json::Value>(event).unwrap())
+            .filter(|event| event["event"] == "report_rejected")
+            .map(|event| event["data"]["code"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(codes, ["Unauthorized", "Unauthorized", "Unauthorized", "Duplicate"]);
+        // Signatures only hold for the oracle they were made for
+        let other = packet(&signing_key(1), accounts(1), 5250000, 9500, 2);
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(accounts(4))
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(10_000_000_000)
+            .build());
+        assert_eq!(contract.submit_signed_prices(vec![other]), [ReportStatus::Rejected("Invalid signature".to_string())]);
+    }
+
+    #[test]
+    fn test_packet_price_relayed_as_string() {
+        let mut contract = setup();
+        let signed = packet(&signing_key(1), accounts(1), 5250000, 9000, 1);
+        let json = near_sdk::serde_json::to_value(&signed).unwrap();
+        assert_eq!(json["price"], "5250000");
+
+        let relayed: SignedPrice = near_sdk::serde_json::from_value(json).unwrap();
+        assert_eq!(contract.submit_signed_prices(vec![relayed]), [ReportStatus::Accepted]);
+    }
+
+    #[test]
+    fn test_relayer_pays_report_deposit() {
+        let mut contract = setup();
+        testing_env!(VMContextBuilder::new().predecessor_account_id(accounts(0)).build());
+        contract.set_report_deposit(U128(100));
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(NearToken::from_yoctonear(200))
+            .block_timestamp(10_000_000_000)
+            .build());
+        contract.submit_signed_prices(vec![
+            packet(&signing_key(1), accounts(1), 5250000, 9000, 1),
+            packet(&signing_key(2), accounts(1), 5250000, 9000, 2),
+        ]);
+        assert_eq!(contract.get_reward_pool().0, 100);
+        assert_eq!(contract.get_reporter_stats(accounts(1)).unwrap().accepted, 1);
+    }
+
+    #[test]
+    fn test_relayer_cannot_take_reporter_storage() {
+        let mut contract = setup();
+        testing_env!(VMContextBuilder::new().predecessor_account_id(accounts(0)).build());
+        contract.set_storage_charging(true);
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(NearToken::from_near(1))
+            .block_timestamp(10_000_000_000)
+            .build());
+        contract.report_price(Pair::usd("ETH"), "binance".to_string(), U128(3000000000), 6);
+        testing_env!(VMContextBuilder::new().predecessor_account_id(accounts(0)).build());
+        contract.set_storage_charging(false);
+        let held = contract.get_storage_account(accounts(1));
+        assert!(held.deposit > 0);
+
+        // With charging off all of bob's deposit is surplus, which danny mustn't collect
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(10_000_000_000)
+            .build());
+        contract.submit_signed_prices(vec![packet(&signing_key(1), accounts(1), 5250000, 9000, 1)]);
+        assert_eq!(contract.get_storage_account(accounts(1)).deposit, held.deposit);
+        assert!(get_created_receipts().is_empty());
+    }
+}
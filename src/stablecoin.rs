@@ -0,0 +1,187 @@
+use near_sdk::near;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::math::pow10;
+use crate::{AssetId, Pair, PriceOracle, PriceOracleExt, Role};
+
+/// Basis points in 100%
+pub const BPS_DENOMINATOR: u128 = 10_000;
+
+/// Depeg alerting parameters of a stablecoin tracked against $1
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct StablecoinConfig {
+    pub threshold_bps: u16,       // Deviation from $1 that counts as a breach
+    pub consecutive_updates: u8,  // Breaching updates in a row before the coin is depegged
+}
+
+/// Tracked peg state of a stablecoin
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct DepegStatus {
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub deviation_bps: u128,      // Deviation of the latest aggregate from $1
+    pub breaches: u8,             // Consecutive updates beyond the threshold
+    pub depegged: bool,           // Set once `consecutive_updates` breaches were seen
+}
+
+#[near]
+impl PriceOracle {
+    /// Track a registered asset as a USD stablecoin (ConfigManager)
+    pub fn add_stablecoin(&mut self, asset_id: AssetId, config: StablecoinConfig) {
+        self.assert_role(Role::ConfigManager);
+        self.asset_config(&asset_id);
+        assert!(config.consecutive_updates > 0, "consecutive_updates must be positive");
+
+        near_sdk::log!(
+            "Stablecoin added: {} ({} bps over {} updates)",
+            asset_id,
+            config.threshold_bps,
+            config.consecutive_updates
+        );
+        self.stablecoins.insert(asset_id.clone(), config);
+        self.peg_status.insert(asset_id, DepegStatus::default());
+    }
+
+    /// Stop tracking a stablecoin (ConfigManager)
+    pub fn remove_stablecoin(&mut self, asset_id: AssetId) {
+        self.assert_role(Role::ConfigManager);
+        assert!(self.stablecoins.remove(&asset_id).is_some(), "{} is not a tracked stablecoin", asset_id);
+        self.peg_status.remove(&asset_id);
+    }
+
+    /// Get tracked stablecoins with their depeg parameters
+    pub fn get_stablecoins(&self) -> Vec<(AssetId, StablecoinConfig)> {
+        let mut coins: Vec<_> = self.stablecoins.iter().map(|(id, c)| (id.clone(), c.clone())).collect();
+        coins.sort_by(|a, b| a.0.cmp(&b.0));
+        coins
+    }
+
+    /// Get the tracked peg state of a stablecoin
+    pub fn get_depeg_status(&self, asset_id: AssetId) -> DepegStatus {
+        self.peg_status
+            .get(&asset_id)
+            .cloned()
+            .unwrap_or_else(|| panic!("{} is not a tracked stablecoin", asset_id))
+    }
+
+    /// Check whether the current USD price of an asset deviates from $1 by more than
+    /// `threshold_bps`
+    pub fn is_depegged(&self, asset_id: AssetId, threshold_bps: u16) -> bool {
+        let pair = Pair::usd(asset_id);
+        let price = self.get_price(pair);
+        peg_deviation_bps(price.price, price.decimals) > threshold_bps as u128
+    }
+}
+
+impl PriceOracle {
+    /// Updates breach counters of a tracked stablecoin after a report to its USD pair
+    pub(crate) fn track_peg(&mut self, pair: &Pair) {
+        if pair != &Pair::usd(pair.base.clone()) {
+            return;
+        }
+        let Some(config) = self.stablecoins.get(&pair.base).cloned() else {
+            return;
+        };
+        let Some(price) = self.try_price(pair) else {
+            return;
+        };
+
+        let deviation_bps = peg_deviation_bps(price.price, price.decimals);
+        let status = self.peg_status.entry(pair.base.clone()).or_default();
+        status.deviation_bps = deviation_bps;
+
+        if deviation_bps > config.threshold_bps as u128 {
+            status.breaches = status.breaches.saturating_add(1);
+            if !status.depegged && status.breaches >= config.consecutive_updates {
+                status.depegged = true;
+                near_sdk::log!(
+                    "Stablecoin depegged: {} at {} ({} bps from peg)",
+                    pair.base,
+                    price.price,
+                    deviation_bps
+                );
+            }
+        } else {
+            status.breaches = 0;
+            if status.depegged {
+                status.depegged = false;
+                near_sdk::log!("Stablecoin repegged: {} at {}", pair.base, price.price);
+            }
+        }
+    }
+}
+
+/// Distance of a USD price from $1, in basis points
+fn peg_deviation_bps(price: u128, decimals: u8) -> u128 {
+    let peg = pow10(decimals as u32).expect("decimals bounded by MAX_DECIMALS");
+    price.abs_diff(peg).saturating_mul(BPS_DENOMINATOR) / peg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn setup() -> PriceOracle {
+        testing_env!(VMContextBuilder::new().build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("USDC".to_string(), AssetConfig::new("USDC", "USD Coin", 6));
+        contract.add_stablecoin(
+            "USDC".to_string(),
+            StablecoinConfig { threshold_bps: 100, consecutive_updates: 2 },
+        );
+        contract
+    }
+
+    fn report(contract: &mut PriceOracle, price: u128) {
+        contract.report_price(Pair::usd("USDC"), "binance".to_string(), U128(price), 6);
+    }
+
+    #[test]
+    fn test_depeg_after_consecutive_breaches() {
+        let mut contract = setup();
+        report(&mut contract, 1000000);
+        report(&mut contract, 980000);                  // 200 bps, first breach
+        assert!(!contract.get_depeg_status("USDC".to_string()).depegged);
+        assert!(contract.is_depegged("USDC".to_string(), 100));
+        assert!(!contract.is_depegged("USDC".to_string(), 500));
+
+        report(&mut contract, 970000);                  // second breach
+        let status = contract.get_depeg_status("USDC".to_string());
+        assert_eq!(status, DepegStatus { deviation_bps: 300, breaches: 2, depegged: true });
+        assert!(get_logs().iter().any(|l| l.starts_with("Stablecoin depegged: USDC")));
+
+        report(&mut contract, 1002000);                 // back within 100 bps
+        assert!(!contract.get_depeg_status("USDC".to_string()).depegged);
+        assert!(get_logs().iter().any(|l| l.starts_with("Stablecoin repegged: USDC")));
+    }
+
+    #[test]
+    fn test_breach_streak_resets() {
+        let mut contract = setup();
+        report(&mut contract, 980000);
+        report(&mut contract, 1000000);
+        report(&mut contract, 980000);
+        let status = contract.get_depeg_status("USDC".to_string());
+        assert_eq!(status.breaches, 1);
+        assert!(!status.depegged);
+    }
+
+    #[test]
+    fn test_other_quotes_ignored() {
+        let mut contract = setup();
+        contract.report_price(Pair::new("USDC", "EUR"), "binance".to_string(), U128(900000), 6);
+        assert_eq!(contract.get_depeg_status("USDC".to_string()), DepegStatus::default());
+    }
+}
@@ -0,0 +1,328 @@
+use near_sdk::json_types::U128;
+use near_sdk::{env, near, AccountId, NearToken, Promise};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::guard::deviation_bps;
+use crate::stablecoin::BPS_DENOMINATOR;
+use crate::reporter::reporter_account;
+use crate::{now_ms, OracleEvent, Pair, PriceOracle, PriceOracleExt, PriceReport, Role};
+
+/// Time unbonded stake stays slashable before it can be withdrawn
+pub const DEFAULT_UNBONDING_PERIOD_SECONDS: u64 = 7 * 24 * 3600;
+
+/// Penalty for submissions far from the answer their round finalized at
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct SlashingConfig {
+    pub max_deviation_bps: u16, // Submissions further than this from the answer are slashed
+    pub slash_bps: u16,         // Share of the reporter's stake taken per offence
+}
+
+/// Stake on its way out of the bond
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct Unbonding {
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub amount: u128,
+    pub available_at: u64,     // When withdraw_unbonded can pay it out
+}
+
+#[near]
+impl PriceOracle {
+    /// Add the attached deposit to the caller's bonded stake (Reporter or pending registration)
+    #[payable]
+    pub fn bond(&mut self) {
+        let caller = reporter_account();
+        assert!(
+            self.is_reporter(caller.clone()) || self.pending_reporters.contains_key(&caller),
+            "{} is not a reporter",
+            caller
+        );
+        let amount = env::attached_deposit().as_yoctonear();
+        assert!(amount > 0, "Attach the stake to bond");
+        self.add_stake(&caller, amount);
+    }
+
+    /// Start unbonding part of the caller's stake; it stops weighting the caller's
+    /// reports now but stays slashable until the unbonding period ends
+    /// Unbonding more before an earlier amount is withdrawn restarts the period
+    pub fn unbond(&mut self, amount: U128) {
+        let (caller, amount) = (reporter_account(), amount.0);
+        let stake = self.get_reporter_stake(caller.clone()).0;
+        assert!(amount > 0 && amount <= stake, "Cannot unbond {} of a {} stake", amount, stake);
+        self.start_unbonding(&caller, amount);
+    }
+
+    /// Pay out the caller's unbonded stake once its unbonding period has ended
+    pub fn withdraw_unbonded(&mut self) -> Promise {
+        let caller = reporter_account();
+        let unbonding = self
+            .unbonding
+            .get(&caller)
+            .unwrap_or_else(|| panic!("Nothing unbonding for {}", caller));
+        assert!(
+            now_ms() >= unbonding.available_at,
+            "Stake of {} is unbonding until {}",
+            caller,
+            unbonding.available_at
+        );
+        let amount = self.unbonding.remove(&caller).expect("checked above").amount;
+        near_sdk::log!("Withdrawn {} unbonded by {}", amount, caller);
+        Promise::new(caller).transfer(NearToken::from_yoctonear(amount))
+    }
+
+    /// Get the stake an account is unbonding, if any
+    pub fn get_unbonding(&self, account_id: AccountId) -> Option<Unbonding> {
+        self.unbonding.get(&account_id).cloned()
+    }
+
+    /// Get the seconds unbonded stake stays slashable
+    pub fn get_unbonding_period(&self) -> u64 {
+        self.unbonding_period
+    }
+
+    /// Set the seconds unbonded stake stays slashable (ConfigManager)
+    pub fn set_unbonding_period(&mut self, period_seconds: u64) {
+        self.assert_role(Role::ConfigManager);
+        self.unbonding_period = period_seconds;
+    }
+
+    /// Set or clear the slashing penalty (ConfigManager): when a round finalizes, each
+    /// reporter whose submission is more than `max_deviation_bps` from the answer loses
+    /// `slash_bps` of its stake, unbonding stake included
+    pub fn set_slashing_config(&mut self, config: Option<SlashingConfig>) {
+        self.assert_role(Role::ConfigManager);
+        if let Some(config) = &config {
+            assert!(
+                config.slash_bps > 0 && config.slash_bps as u128 <= BPS_DENOMINATOR,
+                "Slash share must be between 1 and {} bps",
+                BPS_DENOMINATOR
+            );
+        }
+        self.slashing = config;
+    }
+
+    /// Get the slashing penalty, if configured
+    pub fn get_slashing_config(&self) -> Option<SlashingConfig> {
+        self.slashing.clone()
+    }
+
+    /// Get the stake slashed so far and not yet paid out, in yoctoNEAR
+    pub fn get_slashed_balance(&self) -> U128 {
+        U128(self.slashed_balance)
+    }
+
+    /// Pay slashed stake out to `receiver_id`, all of it unless `amount` is set (owner only)
+    pub fn withdraw_slashed(&mut self, receiver_id: AccountId, amount: Option<U128>) -> Promise {
+        self.assert_owner();
+        let amount = amount.map_or(self.slashed_balance, |amount| amount.0);
+        assert!(
+            amount > 0 && amount <= self.slashed_balance,
+            "Cannot withdraw {} of {} slashed",
+            amount,
+            self.slashed_balance
+        );
+        self.slashed_balance -= amount;
+        near_sdk::log!("Slashed stake of {} withdrawn to {}", amount, receiver_id);
+        Promise::new(receiver_id).transfer(NearToken::from_yoctonear(amount))
+    }
+}
+
+impl PriceOracle {
+    pub(crate) fn add_stake(&mut self, account_id: &AccountId, amount: u128) {
+        let stake = self.get_reporter_stake(account_id.clone()).0 + amount;
+        self.set_stake(account_id, stake);
+        near_sdk::log!("Bonded {} by {}, stake {}", amount, account_id, stake);
+    }
+
+    /// Moves `amount` of an account's bonded stake into unbonding, restarting the period
+    pub(crate) fn start_unbonding(&mut self, account_id: &AccountId, amount: u128) {
+        let stake = self.get_reporter_stake(account_id.clone()).0;
+        self.set_stake(account_id, stake - amount);
+
+        let available_at = now_ms() + self.unbonding_period * 1000;
+        let unbonding = self.unbonding.entry(account_id.clone()).or_insert(Unbonding { amount: 0, available_at });
+        unbonding.amount += amount;
+        unbonding.available_at = available_at;
+        near_sdk::log!("Unbonding {} from {} until {}", amount, account_id, available_at);
+    }
+
+    /// Takes the whole stake of an account, bonded and unbonding
+    pub(crate) fn take_stake(&mut self, account_id: &AccountId) -> u128 {
+        let bonded = self.stakes.remove(account_id).unwrap_or(0);
+        self.invalidate_aggregates();
+        bonded + self.unbonding.remove(account_id).map(|u| u.amount).unwrap_or(0)
+    }
+
+    /// Slashes the reporters of a finalized round whose submissions strayed too far
+    pub(crate) fn slash_deviations(&mut self, pair: &Pair, round_id: u64, answer: u128, submissions: &[PriceReport]) {
+        let Some(config) = self.slashing.clone() else {
+            return;
+        };
+        for report in submissions {
+            let deviation = deviation_bps(report.price, answer);
+            if deviation <= config.max_deviation_bps as u128 {
+                continue;
+            }
+            let Ok(reporter) = report.reporter.parse::<AccountId>() else {
+                continue;
+            };
+            let slashed = self.slash(&reporter, config.slash_bps);
+            if slashed > 0 {
+                near_sdk::log!(
+                    "Slashed {} from {}: {} bps from round {} answer {} of {}",
+                    slashed,
+                    reporter,
+                    deviation,
+                    round_id,
+                    answer,
+                    pair
+                );
+                OracleEvent::ReporterSlashed {
+                    pair: pair.clone(),
+                    reporter: reporter.to_string(),
+                    round_id,
+                    amount: slashed,
+                    deviation_bps: deviation,
+                }
+                .emit();
+            }
+        }
+    }
+
+    /// Takes `share_bps` of the reporter's bonded stake, then of its unbonding stake
+    fn slash(&mut self, reporter: &AccountId, share_bps: u16) -> u128 {
+        let bonded = self.get_reporter_stake(reporter.clone()).0;
+        let unbonding = self.unbonding.get(reporter).map(|u| u.amount).unwrap_or(0);
+        let penalty = (bonded + unbonding) * share_bps as u128 / BPS_DENOMINATOR;
+
+        let from_bond = penalty.min(bonded);
+        self.set_stake(reporter, bonded - from_bond);
+        if let Some(unbonding) = self.unbonding.get_mut(reporter) {
+            unbonding.amount -= penalty - from_bond;
+        }
+        self.slashed_balance += penalty;
+        penalty
+    }
+
+    fn set_stake(&mut self, account_id: &AccountId, stake: u128) {
+        if stake == 0 {
+            self.stakes.remove(account_id);
+        } else {
+            self.stakes.insert(account_id.clone(), stake);
+        }
+        self.invalidate_aggregates();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::test_utils::{accounts, get_created_receipts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    const NEAR: u128 = 10u128.pow(24);
+
+    fn call(account_id: AccountId, seconds: u64, deposit: u128) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id)
+            .block_timestamp(seconds * 1_000_000_000)
+            .attached_deposit(NearToken::from_yoctonear(deposit))
+            .build());
+    }
+
+    fn setup() -> PriceOracle {
+        call(accounts(0), 1000, 0);
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(3);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        for i in 1..=3 {
+            contract.add_reporter(accounts(i));
+            call(accounts(i), 1000, 10 * NEAR);
+            contract.bond();
+            call(accounts(0), 1000, 0);
+        }
+        contract.set_slashing_config(Some(SlashingConfig { max_deviation_bps: 1000, slash_bps: 2000 }));
+        contract
+    }
+
+    fn report(contract: &mut PriceOracle, reporter: usize, source: &str, price: u128) {
+        call(accounts(reporter), 1000, 0);
+        contract.report_price(Pair::usd("NEAR"), source.to_string(), U128(price), 6);
+    }
+
+    #[test]
+    fn test_deviating_reporter_slashed() {
+        let mut contract = setup();
+        report(&mut contract, 1, "binance", 5000000);
+        report(&mut contract, 2, "coingecko", 5100000);
+        report(&mut contract, 3, "rogue", 9000000);     // 76% from the 5100000 answer
+
+        assert_eq!(contract.get_reporter_stake(accounts(3)).0, 8 * NEAR);
+        assert_eq!(contract.get_reporter_stake(accounts(2)).0, 10 * NEAR);
+        assert_eq!(contract.get_slashed_balance().0, 2 * NEAR);
+        assert!(get_logs().contains(&format!(
+            "Slashed {} from danny: 7647 bps from round 1 answer 5100000 of NEAR/USD",
+            2 * NEAR
+        )));
+        assert!(get_logs().contains(&format!(
+            r#"EVENT_JSON:{{"standard":"price-oracle","version":"2.0.0","event":"reporter_slashed","data":{{"pair":{{"base":"NEAR","quote":"USD"}},"reporter":"danny","round_id":1,"amount":"{}","deviation_bps":"7647"}}}}"#,
+            2 * NEAR
+        )));
+
+        call(accounts(0), 1000, 0);
+        contract.withdraw_slashed(accounts(4), Some(U128(NEAR))).detach();
+        assert_eq!(contract.get_slashed_balance().0, NEAR);
+        assert_eq!(get_created_receipts()[0].receiver_id, accounts(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner")]
+    fn test_withdraw_slashed_owner_only() {
+        let mut contract = setup();
+        call(accounts(1), 1000, 0);
+        contract.withdraw_slashed(accounts(1), None).detach();
+    }
+
+    #[test]
+    fn test_unbonding_stays_slashable() {
+        let mut contract = setup();
+        call(accounts(3), 1000, 0);
+        contract.unbond(U128(10 * NEAR));
+        assert_eq!(contract.get_reporter_stake(accounts(3)).0, 0);
+
+        report(&mut contract, 1, "binance", 5000000);
+        report(&mut contract, 2, "coingecko", 5100000);
+        report(&mut contract, 3, "rogue", 9000000);
+        assert_eq!(contract.get_unbonding(accounts(3)).unwrap().amount, 8 * NEAR);
+
+        call(accounts(3), 1000 + DEFAULT_UNBONDING_PERIOD_SECONDS, 0);
+        contract.withdraw_unbonded().detach();
+        assert_eq!(contract.get_unbonding(accounts(3)), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Stake of danny is unbonding until 605800000")]
+    fn test_withdraw_before_unbonding_ends() {
+        let mut contract = setup();
+        call(accounts(3), 1000, 0);
+        contract.unbond(U128(NEAR));
+        call(accounts(3), 1001, 0);
+        contract.withdraw_unbonded().detach();
+    }
+
+    #[test]
+    #[should_panic(expected = "eugene is not a reporter")]
+    fn test_bond_requires_reporter() {
+        let mut contract = setup();
+        call(accounts(4), 1000, NEAR);
+        contract.bond();
+    }
+}
@@ -0,0 +1,101 @@
+use std::ops::{Deref, DerefMut};
+
+use near_sdk::near;
+
+use crate::{OracleState, PriceOracle, PriceOracleExt};
+
+/// Layout version the contract writes its state in
+pub const STATE_VERSION: u8 = 1;
+
+/// Contract state tagged with its layout
+/// A layout change adds a variant and keeps the older ones decodable; calls then read
+/// state in any of them and upgrade it in place to the newest when they first touch it,
+/// so an upgrade needs no migration pass over the whole state
+#[near(serializers = [borsh])]
+pub enum VersionedState {
+    V1(OracleState),
+}
+
+impl VersionedState {
+    /// Layout version of this state
+    pub fn version(&self) -> u8 {
+        match self {
+            VersionedState::V1(_) => 1,
+        }
+    }
+}
+
+impl From<OracleState> for PriceOracle {
+    fn from(state: OracleState) -> Self {
+        Self { state: VersionedState::V1(state) }
+    }
+}
+
+impl Deref for PriceOracle {
+    type Target = OracleState;
+
+    fn deref(&self) -> &OracleState {
+        match &self.state {
+            VersionedState::V1(state) => state,
+        }
+    }
+}
+
+impl DerefMut for PriceOracle {
+    fn deref_mut(&mut self) -> &mut OracleState {
+        match &mut self.state {
+            VersionedState::V1(state) => state,
+        }
+    }
+}
+
+#[near]
+impl PriceOracle {
+    /// Get the layout version the state was read in
+    pub fn get_state_version(&self) -> u8 {
+        self.state.version()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AssetConfig, Pair};
+    use near_sdk::env;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn test_state_written_with_version() {
+        testing_env!(VMContextBuilder::new().build());
+        let contract = PriceOracle::new("bob.near".parse().unwrap(), 2);
+        assert_eq!(contract.get_state_version(), STATE_VERSION);
+
+        env::state_write(&contract);
+        let bytes = env::storage_read(b"STATE").unwrap();
+        assert_eq!(bytes[0], 0);
+        let read: PriceOracle = env::state_read().unwrap();
+        assert_eq!(read.get_owner().as_str(), "bob.near");
+        assert_eq!(read.get_min_sources(), 2);
+    }
+
+    #[test]
+    fn test_pair_state_kept_outside_state_entry() {
+        testing_env!(VMContextBuilder::new().build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        env::state_write(&contract);
+        let size = env::storage_read(b"STATE").unwrap().len();
+
+        // Rounds, aggregates and EMAs of another pair land in their own entries
+        contract.report_price(Pair::new("NEAR", "USDT"), "binance".to_string(), U128(5010000), 6);
+        env::state_write(&contract);
+        assert_eq!(env::storage_read(b"STATE").unwrap().len(), size);
+        drop(contract); // Store collections write their entries when dropped
+        let read: PriceOracle = env::state_read().unwrap();
+        assert_eq!(read.get_latest_round(Pair::new("NEAR", "USDT")).unwrap().price, 5010000);
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::BTreeMap;
+
+use near_sdk::{near, AccountId};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::guard::{deviation_bps, Verdict};
+use crate::{now_ms, paginate, PriceOracle, PriceOracleExt, PriceReport, RejectReason};
+
+/// What became of a reporter's reports and how close they landed to the answers
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct ReporterStats {
+    pub account_id: String,
+    pub accepted: u64,
+    pub quarantined: u64,
+    pub rejected: BTreeMap<RejectReason, u64>, // Rejected reports by reason
+    pub last_report: u64,                      // When it last submitted a report, whatever became of it
+    pub scored_submissions: u64,               // Accepted reports that made it into a finalized round
+    pub avg_deviation_bps: Option<u64>,        // Mean distance of those from their round's answer
+    #[serde(skip)]
+    #[cfg_attr(feature = "abi", schemars(skip))]
+    pub total_deviation_bps: u128,
+}
+
+impl ReporterStats {
+    fn new(account_id: &AccountId) -> Self {
+        Self {
+            account_id: account_id.to_string(),
+            accepted: 0,
+            quarantined: 0,
+            rejected: BTreeMap::new(),
+            last_report: 0,
+            scored_submissions: 0,
+            avg_deviation_bps: None,
+            total_deviation_bps: 0,
+        }
+    }
+}
+
+#[near]
+impl PriceOracle {
+    /// Get the report outcomes and accuracy of a reporter, if it ever submitted a report
+    pub fn get_reporter_stats(&self, account_id: AccountId) -> Option<ReporterStats> {
+        self.reporter_stats.get(&account_id).cloned()
+    }
+
+    /// Get the report outcomes and accuracy of every reporter, by account id
+    pub fn get_all_reporter_stats(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<ReporterStats> {
+        let mut stats: Vec<ReporterStats> = self.reporter_stats.values().cloned().collect();
+        stats.sort_by(|a, b| a.account_id.cmp(&b.account_id));
+        paginate(stats, from_index, limit)
+    }
+}
+
+impl PriceOracle {
+    /// Counts the outcome of a report's checks against its reporter
+    pub(crate) fn record_verdict(&mut self, reporter: &str, verdict: &Verdict) {
+        let Ok(reporter) = reporter.parse::<AccountId>() else {
+            return;
+        };
+        let stats = self.reporter_stats_mut(&reporter);
+        match verdict {
+            Verdict::Accept => stats.accepted += 1,
+            Verdict::Reject(reason, _) => *stats.rejected.entry(*reason).or_default() += 1,
+            Verdict::Quarantine(_) => stats.quarantined += 1,
+        }
+    }
+
+    /// Counts a report turned away before its checks ran
+    pub(crate) fn record_rejection(&mut self, reporter: &AccountId, reason: RejectReason) {
+        *self.reporter_stats_mut(reporter).rejected.entry(reason).or_default() += 1;
+    }
+
+    /// Scores the submissions of a finalized round by their distance from its answer
+    pub(crate) fn record_deviations(&mut self, answer: u128, submissions: &[PriceReport]) {
+        for report in submissions {
+            let Some(stats) = report.reporter.parse::<AccountId>().ok().and_then(|id| self.reporter_stats.get_mut(&id)) else {
+                continue;
+            };
+            stats.scored_submissions += 1;
+            stats.total_deviation_bps = stats.total_deviation_bps.saturating_add(deviation_bps(report.price, answer));
+            let average = stats.total_deviation_bps / stats.scored_submissions as u128;
+            stats.avg_deviation_bps = Some(average.min(u64::MAX as u128) as u64);
+        }
+    }
+
+    fn reporter_stats_mut(&mut self, reporter: &AccountId) -> &mut ReporterStats {
+        let stats = self
+            .reporter_stats
+            .entry(reporter.clone())
+            .or_insert_with(|| ReporterStats::new(reporter));
+        stats.last_report = now_ms();
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AssetConfig, BatchReport, DeviationGate, Pair, PriceBounds};
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn call(account_id: AccountId, seconds: u64) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id)
+            .block_timestamp(seconds * 1_000_000_000)
+            .build());
+    }
+
+    fn report(contract: &mut PriceOracle, reporter: usize, seconds: u64, source: &str, price: u128) {
+        call(accounts(reporter), seconds);
+        contract.report_price(Pair::usd("NEAR"), source.to_string(), U128(price), 6);
+    }
+
+    fn setup() -> PriceOracle {
+        call(accounts(0), 0);
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(2);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.set_price_bounds("NEAR".to_string(), Some(PriceBounds { min: 1000000, max: 10000000 }));
+        contract.add_reporter(accounts(1));
+        contract.add_reporter(accounts(2));
+        contract
+    }
+
+    #[test]
+    fn test_stats_count_outcomes() {
+        let mut contract = setup();
+        report(&mut contract, 1, 10, "binance", 5000000);
+        report(&mut contract, 1, 20, "binance", 20000000);
+        report(&mut contract, 2, 30, "kraken", 5200000);
+
+        call(accounts(0), 30);
+        contract.set_deviation_gate(Some(DeviationGate { max_deviation_bps: 1000, quarantine: true }));
+        report(&mut contract, 1, 40, "binance", 8000000);
+        call(accounts(1), 50);
+        contract.report_prices(vec![BatchReport {
+            pair: Pair::usd("ETH"),
+            source: "binance".to_string(),
+            price: 3000000000,
+            decimals: 6,
+            volume: None,
+        }]);
+
+        let stats = contract.get_reporter_stats(accounts(1)).unwrap();
+        assert_eq!((stats.accepted, stats.quarantined, stats.last_report), (1, 1, 50000));
+        assert_eq!(stats.rejected, BTreeMap::from([(RejectReason::Invalid, 1), (RejectReason::OutOfBounds, 1)]));
+        assert_eq!(contract.get_reporter_stats(accounts(3)), None);
+    }
+
+    #[test]
+    fn test_stats_average_deviation() {
+        let mut contract = setup();
+        // Answer 5100000: both are 196 bps off
+        report(&mut contract, 1, 10, "binance", 5000000);
+        report(&mut contract, 2, 10, "kraken", 5200000);
+        // Next answer 5150000: both are spot on
+        report(&mut contract, 1, 400, "binance", 5150000);
+        report(&mut contract, 2, 400, "kraken", 5150000);
+
+        let stats = contract.get_all_reporter_stats(None, None);
+        assert_eq!(stats.iter().map(|s| s.account_id.as_str()).collect::<Vec<_>>(), vec!["bob", "charlie"]);
+        assert_eq!((stats[0].scored_submissions, stats[0].avg_deviation_bps), (2, Some(98)));
+        assert_eq!(stats[0].total_deviation_bps, 196);
+    }
+}
@@ -0,0 +1,230 @@
+use near_sdk::{env, near, AccountId, NearToken, Promise};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::{AssetId, Pair, PriceOracle, PriceOracleExt, PriceReport, Role};
+
+/// Bytes a stored report takes besides its encoding and source key (map entry bookkeeping)
+pub const REPORT_OVERHEAD_BYTES: u64 = 40;
+
+/// Storage a reporter's latest reports take and the deposit held to cover it
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct StorageAccount {
+    pub bytes: u64,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub deposit: u128,         // yoctoNEAR, refunded as the reports are replaced or deleted
+}
+
+#[near]
+impl PriceOracle {
+    /// Make reporters pay for the storage their reports take (ConfigManager)
+    /// Reports then have to attach the cost of the bytes they add on top of the report
+    /// deposit; the deposit is refunded as their reports shrink, are replaced by another
+    /// reporter or are deleted. Turning it off refunds held deposits on the next settle
+    pub fn set_storage_charging(&mut self, enabled: bool) {
+        self.assert_role(Role::ConfigManager);
+        self.storage_charging = enabled;
+        near_sdk::log!("Storage charging {}", if enabled { "enabled" } else { "disabled" });
+    }
+
+    /// Check whether reporters pay for the storage their reports take
+    pub fn get_storage_charging(&self) -> bool {
+        self.storage_charging
+    }
+
+    /// Get the storage a reporter's latest reports take and the deposit held for it
+    pub fn get_storage_account(&self, account_id: AccountId) -> StorageAccount {
+        self.storage_accounts.get(&account_id).cloned().unwrap_or_default()
+    }
+
+    /// Get the bytes the latest reports of an asset's pairs take
+    pub fn get_asset_storage_usage(&self, asset_id: AssetId) -> u64 {
+        self.asset_storage.get(&asset_id).copied().unwrap_or(0)
+    }
+}
+
+impl PriceOracle {
+    /// Moves the bytes of a stored report from the reporter of the one it replaced
+    pub(crate) fn track_report_storage(&mut self, pair: &Pair, previous: Option<&PriceReport>, report: &PriceReport) {
+        if let Some(previous) = previous {
+            self.release_report_storage(pair, previous);
+        }
+        let bytes = report_bytes(report);
+        self.storage_accounts.entry(reporter_id(report)).or_default().bytes += bytes;
+        *self.asset_storage.entry(pair.base.clone()).or_default() += bytes;
+        if let Some(previous) = previous.filter(|previous| previous.reporter != report.reporter) {
+            self.refund_storage(&reporter_id(previous));
+        }
+    }
+
+    /// Frees the bytes of deleted reports and refunds their reporters' surplus deposits
+    pub(crate) fn release_reports_storage(&mut self, removed: Vec<(Pair, PriceReport)>) {
+        let mut reporters: Vec<AccountId> = Vec::new();
+        for (pair, report) in &removed {
+            self.release_report_storage(pair, report);
+            let reporter = reporter_id(report);
+            if !reporters.contains(&reporter) {
+                reporters.push(reporter);
+            }
+        }
+        for reporter in reporters {
+            self.refund_storage(&reporter);
+        }
+    }
+
+    /// Brings the deposit an account holds in line with its storage, taking any shortfall
+    /// from `available` (panicking if it's not enough) or adding the surplus to it
+    /// Returns what's left to refund
+    pub(crate) fn settle_storage(&mut self, account_id: &AccountId, available: u128) -> u128 {
+        let cost = self.storage_cost(account_id);
+        let account = self.storage_accounts.entry(account_id.clone()).or_default();
+        let left = if cost > account.deposit {
+            let due = cost - account.deposit;
+            assert!(available >= due, "Attach {} more yoctoNEAR for storage, got {}", due, available);
+            available - due
+        } else {
+            available + (account.deposit - cost)
+        };
+        account.deposit = cost;
+        if account.bytes == 0 {
+            self.storage_accounts.remove(account_id);
+        }
+        left
+    }
+
+    /// settle_storage for an account whose reports someone else relayed: the shortfall is
+    /// taken from `available`, but a surplus stays with the account, not the relayer
+    pub(crate) fn cover_storage(&mut self, account_id: &AccountId, available: u128) -> u128 {
+        let cost = self.storage_cost(account_id);
+        let Some(account) = self.storage_accounts.get_mut(account_id) else {
+            return available;
+        };
+        if cost <= account.deposit {
+            return available;
+        }
+        let due = cost - account.deposit;
+        assert!(available >= due, "Attach {} more yoctoNEAR for storage, got {}", due, available);
+        account.deposit = cost;
+        available - due
+    }
+
+    /// Deposit the storage of an account's reports calls for
+    fn storage_cost(&self, account_id: &AccountId) -> u128 {
+        if !self.storage_charging {
+            return 0;
+        }
+        let bytes = self.storage_accounts.get(account_id).map_or(0, |account| account.bytes);
+        env::storage_byte_cost().as_yoctonear() * bytes as u128
+    }
+
+    fn release_report_storage(&mut self, pair: &Pair, report: &PriceReport) {
+        let bytes = report_bytes(report);
+        if let Some(account) = self.storage_accounts.get_mut(&reporter_id(report)) {
+            account.bytes = account.bytes.saturating_sub(bytes);
+        }
+        if let Some(usage) = self.asset_storage.get_mut(&pair.base) {
+            *usage = usage.saturating_sub(bytes);
+            if *usage == 0 {
+                self.asset_storage.remove(&pair.base);
+            }
+        }
+    }
+
+    fn refund_storage(&mut self, account_id: &AccountId) {
+        let refund = self.settle_storage(account_id, 0);
+        if refund > 0 {
+            near_sdk::log!("Storage deposit of {} refunded to {}", refund, account_id);
+            Promise::new(account_id.clone()).transfer(NearToken::from_yoctonear(refund)).detach();
+        }
+    }
+}
+
+/// Bytes a report takes in the price book, source key included
+fn report_bytes(report: &PriceReport) -> u64 {
+    let encoded = borsh::object_length(report).expect("reports serialize");
+    REPORT_OVERHEAD_BYTES + encoded as u64 + borsh::object_length(&report.source).expect("strings serialize") as u64
+}
+
+fn reporter_id(report: &PriceReport) -> AccountId {
+    report.reporter.parse().expect("reporters are accounts")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{accounts, get_created_receipts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn report_as(contract: &mut PriceOracle, account_id: AccountId, source: &str, deposit: u128) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id)
+            .attached_deposit(NearToken::from_yoctonear(deposit))
+            .build());
+        contract.report_price(Pair::usd("NEAR"), source.to_string(), U128(5250000), 6);
+    }
+
+    fn setup() -> PriceOracle {
+        testing_env!(VMContextBuilder::new().build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.add_reporter(accounts(0));
+        contract.add_reporter(accounts(2));
+        contract.set_storage_charging(true);
+        contract
+    }
+
+    fn cost(contract: &PriceOracle, account_id: AccountId) -> u128 {
+        env::storage_byte_cost().as_yoctonear() * contract.get_storage_account(account_id).bytes as u128
+    }
+
+    #[test]
+    fn test_report_charged_for_its_storage() {
+        let mut contract = setup();
+        let one_near = NearToken::from_near(1).as_yoctonear();
+        report_as(&mut contract, accounts(0), "binance", one_near);
+
+        let account = contract.get_storage_account(accounts(0));
+        assert!(account.bytes > REPORT_OVERHEAD_BYTES);
+        assert_eq!(account.deposit, cost(&contract, accounts(0)));
+        assert_eq!(contract.get_asset_storage_usage("NEAR".to_string()), account.bytes);
+
+        // Replacing its own report costs nothing more
+        report_as(&mut contract, accounts(0), "binance", 0);
+        assert_eq!(contract.get_storage_account(accounts(0)), account);
+    }
+
+    #[test]
+    #[should_panic(expected = "more yoctoNEAR for storage, got 0")]
+    fn test_report_without_storage_deposit_rejected() {
+        let mut contract = setup();
+        report_as(&mut contract, accounts(0), "binance", 0);
+    }
+
+    #[test]
+    fn test_storage_refunded_on_cleanup() {
+        let mut contract = setup();
+        let one_near = NearToken::from_near(1).as_yoctonear();
+        report_as(&mut contract, accounts(0), "binance", one_near);
+        let deposit = contract.get_storage_account(accounts(0)).deposit;
+
+        // Another reporter taking over the source frees the first one's bytes
+        report_as(&mut contract, accounts(2), "binance", one_near);
+        assert_eq!(contract.get_storage_account(accounts(0)), StorageAccount::default());
+        assert!(get_logs().contains(&format!("Storage deposit of {} refunded to alice", deposit)));
+
+        testing_env!(VMContextBuilder::new().build());
+        contract.remove_reporter(accounts(2));
+        assert_eq!(contract.get_storage_account(accounts(2)), StorageAccount::default());
+        assert_eq!(contract.get_asset_storage_usage("NEAR".to_string()), 0);
+        assert_eq!(get_created_receipts()[0].receiver_id, accounts(2));
+    }
+}
@@ -0,0 +1,184 @@
+use near_sdk::json_types::U128;
+use near_sdk::{env, near, AccountId, NearToken, Promise};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::{paginate, Pair, PriceOracle, PriceOracleExt, PriceResponse};
+
+/// Bytes a per-account balance entry takes besides its account key (value and map bookkeeping)
+pub const BALANCE_ENTRY_BYTES: u64 = 56;
+
+/// Prepaid balance of a consumer
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct Subscription {
+    pub account_id: String,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub balance: u128,         // yoctoNEAR left to draw query fees from
+}
+
+#[near]
+impl PriceOracle {
+    /// Top up the prepaid balance of a consumer with the attached deposit. Anyone can
+    /// pay for any account; the deposit opening a balance must cover its storage (see
+    /// get_min_subscription_deposit)
+    #[payable]
+    pub fn deposit_for(&mut self, account_id: AccountId) {
+        let amount = env::attached_deposit().as_yoctonear();
+        assert!(amount > 0, "Attach the deposit to top up {}", account_id);
+        if !self.subscriptions.contains_key(&account_id) {
+            let min = balance_entry_cost(&account_id);
+            assert!(amount >= min, "Attach at least {} yoctoNEAR to open a balance for {}", min, account_id);
+        }
+        let balance = self.subscriptions.entry(account_id.clone()).or_default();
+        *balance += amount;
+        near_sdk::log!("Subscription of {} topped up by {}, balance {}", account_id, amount, balance);
+    }
+
+    /// Get the latest answer of a pair as a fee-backed query paid from the caller's
+    /// prepaid balance; queries are cut off once the balance can't cover the fee
+    pub fn get_price_subscribed(&mut self, pair: Pair) -> PriceResponse {
+        let caller = env::predecessor_account_id();
+        let fee = self.get_query_fee(pair.base.clone()).0;
+        let balance = self.get_subscription_balance(caller.clone()).0;
+        assert!(
+            balance >= fee,
+            "Subscription of {} exhausted: {} left, {} needed",
+            caller,
+            balance,
+            fee
+        );
+        let price = self.get_price(pair.clone());
+
+        if fee > 0 {
+            self.set_subscription_balance(&caller, balance - fee);
+            self.collect_fee(&pair.base, fee);
+        }
+        price
+    }
+
+    /// Refund part of the caller's prepaid balance, all of it unless `amount` is set
+    pub fn withdraw_subscription(&mut self, amount: Option<U128>) -> Promise {
+        let caller = env::predecessor_account_id();
+        let balance = self.get_subscription_balance(caller.clone()).0;
+        let amount = amount.map_or(balance, |amount| amount.0);
+        assert!(amount > 0 && amount <= balance, "Cannot withdraw {} of a {} balance", amount, balance);
+        self.set_subscription_balance(&caller, balance - amount);
+        near_sdk::log!("Subscription of {} withdrawn: {}", caller, amount);
+        Promise::new(caller).transfer(NearToken::from_yoctonear(amount))
+    }
+
+    /// Get the prepaid balance of a consumer, in yoctoNEAR
+    pub fn get_subscription_balance(&self, account_id: AccountId) -> U128 {
+        U128(self.subscriptions.get(&account_id).copied().unwrap_or(0))
+    }
+
+    /// Get the deposit deposit_for requires to open a balance for an account, in yoctoNEAR
+    pub fn get_min_subscription_deposit(&self, account_id: AccountId) -> U128 {
+        U128(balance_entry_cost(&account_id))
+    }
+
+    /// Get every consumer with a prepaid balance, by account id
+    pub fn get_subscriptions(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<Subscription> {
+        let mut subscriptions: Vec<Subscription> = self
+            .subscriptions
+            .iter()
+            .map(|(account_id, balance)| Subscription { account_id: account_id.to_string(), balance: *balance })
+            .collect();
+        subscriptions.sort_by(|a, b| a.account_id.cmp(&b.account_id));
+        paginate(subscriptions, from_index, limit)
+    }
+}
+
+impl PriceOracle {
+    pub(crate) fn set_subscription_balance(&mut self, account_id: &AccountId, balance: u128) {
+        if balance == 0 {
+            self.subscriptions.remove(account_id);
+            near_sdk::log!("Subscription of {} exhausted", account_id);
+        } else {
+            self.subscriptions.insert(account_id.clone(), balance);
+        }
+    }
+}
+
+/// Storage cost of an account's balance entry, so opening one for a stranger isn't free
+pub(crate) fn balance_entry_cost(account_id: &AccountId) -> u128 {
+    let bytes = BALANCE_ENTRY_BYTES + account_id.len() as u64;
+    env::storage_byte_cost().as_yoctonear() * bytes as u128
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::test_utils::{accounts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    const FEE: u128 = 10u128.pow(21);
+
+    fn call(account_id: AccountId, deposit: u128) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id)
+            .attached_deposit(NearToken::from_yoctonear(deposit))
+            .build());
+    }
+
+    fn setup() -> PriceOracle {
+        call(accounts(0), 0);
+        let mut contract = PriceOracle::new(accounts(0), 1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6);
+        contract.set_query_fee("NEAR".to_string(), U128(FEE));
+
+        call(accounts(0), FEE * 2);                     // alice pays for the lending contract
+        contract.deposit_for(accounts(1));
+        contract
+    }
+
+    #[test]
+    fn test_queries_draw_down_balance() {
+        let mut contract = setup();
+        call(accounts(1), 0);
+        contract.get_price_subscribed(Pair::usd("NEAR"));
+        assert_eq!(contract.get_subscription_balance(accounts(1)).0, FEE);
+
+        contract.get_price_subscribed(Pair::usd("NEAR"));
+        assert!(get_logs().contains(&"Subscription of bob exhausted".to_string()));
+        assert!(contract.get_subscriptions(None, None).is_empty());
+        assert_eq!(contract.get_collected_fees("NEAR".to_string()).0, FEE * 2);
+    }
+
+    #[test]
+    fn test_opening_balance_covers_storage() {
+        let mut contract = setup();
+        let min = contract.get_min_subscription_deposit(accounts(2)).0;
+        assert!(min > 1);
+        call(accounts(0), 1);
+        contract.deposit_for(accounts(1)); // Topping up an open balance has no minimum
+        call(accounts(0), min);
+        contract.deposit_for(accounts(2));
+        assert_eq!(contract.get_subscription_balance(accounts(2)).0, min);
+    }
+
+    #[test]
+    #[should_panic(expected = "Attach at least 630000000000000000000 yoctoNEAR to open a balance for charlie")]
+    fn test_dust_cannot_open_balance() {
+        let mut contract = setup();
+        call(accounts(0), 1);
+        contract.deposit_for(accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Subscription of bob exhausted: 0 left, 1000000000000000000000 needed")]
+    fn test_exhausted_subscription_cut_off() {
+        let mut contract = setup();
+        call(accounts(1), 0);
+        contract.withdraw_subscription(None).detach();
+        contract.get_price_subscribed(Pair::usd("NEAR"));
+    }
+}
@@ -0,0 +1,165 @@
+use near_sdk::json_types::U128;
+use near_sdk::near;
+
+use crate::{Attestation, Pair, Price, PriceOracle, PriceOracleExt, PriceReport};
+
+/// Longest measurement or quote hash an attestation takes, in bytes
+pub const MAX_ATTESTATION_BYTES: usize = 64;
+
+#[near]
+impl PriceOracle {
+    /// Trust reports attested by an enclave measurement (owner only)
+    pub fn add_trusted_enclave(&mut self, measurement: String, description: String) {
+        self.assert_owner();
+        let measurement = normalize_hex(&measurement, "Measurement");
+        near_sdk::log!("Trusted enclave added: {} ({})", measurement, description);
+        self.trusted_enclaves.insert(measurement, description);
+    }
+
+    /// Stop trusting an enclave measurement (owner only); its reports stop counting
+    /// toward get_attested_price right away
+    pub fn remove_trusted_enclave(&mut self, measurement: String) {
+        self.assert_owner();
+        let measurement = normalize_hex(&measurement, "Measurement");
+        assert!(self.trusted_enclaves.remove(&measurement).is_some(), "Unknown enclave {}", measurement);
+        near_sdk::log!("Trusted enclave removed: {}", measurement);
+    }
+
+    /// Get the trusted enclave measurements with their descriptions
+    pub fn get_trusted_enclaves(&self) -> Vec<(String, String)> {
+        self.trusted_enclaves.iter().map(|(measurement, description)| (measurement.clone(), description.clone())).collect()
+    }
+
+    /// Submit a price produced inside a TEE with its attestation, otherwise as
+    /// report_price_with_volume. The attestation is recorded as given: the oracle can't
+    /// check a quote, so consumers decide which measurements to trust
+    #[payable]
+    pub fn report_attested_price(
+        &mut self,
+        pair: Pair,
+        source: String,
+        price: U128,
+        decimals: u8,
+        volume: Option<U128>,
+        attestation: Attestation,
+    ) {
+        self.assert_not_paused();
+        self.assert_direct_reports();
+        let attestation = Attestation {
+            measurement: normalize_hex(&attestation.measurement, "Measurement"),
+            quote_hash: normalize_hex(&attestation.quote_hash, "Quote hash"),
+        };
+        self.report_now(pair, source, price.0, decimals, volume.map(|volume| volume.0), Some(attestation));
+    }
+
+    /// Get the price of a pair from the live reports attested by a trusted enclave,
+    /// aggregated with the base asset's method like a round; panics unless there are
+    /// min_sources of them
+    pub fn get_attested_price(&self, pair: Pair) -> Price {
+        self.assert_not_paused();
+        let reports: Vec<&PriceReport> = self
+            .reports(&pair)
+            .into_iter()
+            .filter(|report| self.is_attested(report))
+            .collect();
+        let attested = reports.len();
+        let method = self.asset_config(&pair.base).aggregation;
+        self.aggregate(&pair, reports, method.aggregator().as_ref()).unwrap_or_else(|| {
+            panic!("Not enough attested sources for {}: {} of {} required", pair, attested, self.min_sources_of(&pair.base))
+        })
+    }
+}
+
+impl PriceOracle {
+    /// Whether a report is attested by a trusted enclave
+    fn is_attested(&self, report: &PriceReport) -> bool {
+        report
+            .attestation
+            .as_ref()
+            .is_some_and(|attestation| self.trusted_enclaves.contains_key(&attestation.measurement))
+    }
+}
+
+/// Lowercase form of a hex string, panicking unless it's 1-MAX_ATTESTATION_BYTES bytes
+fn normalize_hex(value: &str, what: &str) -> String {
+    let value = value.trim().trim_start_matches("0x").to_ascii_lowercase();
+    let valid = !value.is_empty()
+        && value.len() & 1 == 0
+        && value.len() <= 2 * MAX_ATTESTATION_BYTES
+        && value.bytes().all(|byte| byte.is_ascii_hexdigit());
+    assert!(valid, "{} must be 1-{} bytes of hex", what, MAX_ATTESTATION_BYTES);
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, AccountId};
+
+    const ENCLAVE: &str = "0xAB12ab12ab12ab12ab12ab12ab12ab12ab12ab12ab12ab12ab12ab12ab12ab12";
+
+    fn call(account_id: AccountId) {
+        testing_env!(VMContextBuilder::new().predecessor_account_id(account_id).build());
+    }
+
+    fn report(contract: &mut PriceOracle, reporter: usize, source: &str, price: u128, measurement: Option<&str>) {
+        call(accounts(reporter));
+        match measurement {
+            Some(measurement) => {
+                let attestation = Attestation { measurement: measurement.to_string(), quote_hash: "ff".repeat(32) };
+                contract.report_attested_price(Pair::usd("NEAR"), source.to_string(), U128(price), 6, None, attestation);
+            }
+            None => contract.report_price(Pair::usd("NEAR"), source.to_string(), U128(price), 6),
+        }
+    }
+
+    fn setup() -> PriceOracle {
+        call(accounts(0));
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(2);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.add_reporter(accounts(1));
+        contract.add_reporter(accounts(2));
+        contract.add_reporter(accounts(3));
+        contract.add_trusted_enclave(ENCLAVE.to_string(), "feeder v1.2 on Nitro".to_string());
+        contract
+    }
+
+    #[test]
+    fn test_attested_price_counts_trusted_reports_only() {
+        let mut contract = setup();
+        report(&mut contract, 1, "binance", 5000000, Some(ENCLAVE));
+        report(&mut contract, 2, "kraken", 5200000, Some(&ENCLAVE.to_lowercase()));
+        report(&mut contract, 3, "coinbase", 9000000, None);
+        report(&mut contract, 3, "okx", 9000000, Some(&"cd".repeat(32)));
+
+        assert_eq!(contract.get_attested_price(Pair::usd("NEAR")).price, 5100000);
+        let details = contract.get_price_details(Pair::usd("NEAR"));
+        let binance = details.iter().find(|report| report.source == "binance").unwrap();
+        assert_eq!(binance.attestation.as_ref().unwrap().measurement, ENCLAVE[2..].to_lowercase());
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough attested sources for NEAR/USD: 1 of 2 required")]
+    fn test_untrusted_enclave_stops_counting() {
+        let mut contract = setup();
+        report(&mut contract, 1, "binance", 5000000, Some(ENCLAVE));
+        report(&mut contract, 2, "kraken", 5200000, Some(ENCLAVE));
+        call(accounts(0));
+        contract.remove_trusted_enclave(ENCLAVE.to_string());
+        contract.add_trusted_enclave("cd".repeat(32), "feeder v1.3".to_string());
+        report(&mut contract, 3, "okx", 5100000, Some(&"cd".repeat(32)));
+        contract.get_attested_price(Pair::usd("NEAR"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Quote hash must be 1-64 bytes of hex")]
+    fn test_attestation_must_be_hex() {
+        let mut contract = setup();
+        call(accounts(1));
+        let attestation = Attestation { measurement: ENCLAVE.to_string(), quote_hash: "not hex".to_string() };
+        contract.report_attested_price(Pair::usd("NEAR"), "binance".to_string(), U128(5000000), 6, None, attestation);
+    }
+}
@@ -0,0 +1,196 @@
+use near_sdk::near;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::{now_ms, AggregationMethod, AssetId, OracleEvent, PriceBounds, PriceOracle, PriceOracleExt, Role};
+
+/// Longest timelock delay, so a change can't be scheduled out of reach
+pub const MAX_TIMELOCK_DELAY_SECONDS: u64 = 30 * 24 * 3600;
+
+/// Parameter change that waits out the timelock delay before it applies
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub enum ConfigChange {
+    MinSources { min_sources: u8 },
+    PriceBounds { asset_id: AssetId, bounds: Option<PriceBounds> },
+    AggregationMethod { asset_id: AssetId, method: AggregationMethod },
+    TimelockDelay { delay_seconds: u64 },
+    AssetMinSources { asset_id: AssetId, min_sources: Option<u8> },
+    SourceWeight { source: String, weight: Option<u32> },
+}
+
+/// Scheduled parameter change
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct PendingChange {
+    pub change_id: u64,
+    pub change: ConfigChange,
+    pub proposed_at: u64,
+    pub eta: u64,              // Earliest time the change can be applied
+}
+
+#[near]
+impl PriceOracle {
+    /// Get the seconds parameter changes wait before they can be applied (0 = immediate)
+    pub fn get_timelock_delay(&self) -> u64 {
+        self.timelock_delay
+    }
+
+    /// Change the timelock delay, at most MAX_TIMELOCK_DELAY_SECONDS (ConfigManager); the
+    /// change itself waits out the current delay, so the lock can't be lifted instantly
+    pub fn set_timelock_delay(&mut self, delay_seconds: u64) {
+        self.assert_role(Role::ConfigManager);
+        assert!(
+            delay_seconds <= MAX_TIMELOCK_DELAY_SECONDS,
+            "Timelock delay must be at most {}s",
+            MAX_TIMELOCK_DELAY_SECONDS
+        );
+        self.request_config_change(ConfigChange::TimelockDelay { delay_seconds });
+    }
+
+    /// Get scheduled parameter changes, by id
+    pub fn get_pending_config_changes(&self) -> Vec<PendingChange> {
+        let mut changes: Vec<PendingChange> = self.pending_changes.values().cloned().collect();
+        changes.sort_by_key(|pending| pending.change_id);
+        changes
+    }
+
+    /// Apply a scheduled change once its eta has passed. Anyone can call this
+    pub fn apply_config_change(&mut self, change_id: u64) {
+        let pending = self
+            .pending_changes
+            .get(&change_id)
+            .unwrap_or_else(|| panic!("Unknown config change {}", change_id));
+        assert!(now_ms() >= pending.eta, "Config change {} is locked until {}", change_id, pending.eta);
+        let pending = self.pending_changes.remove(&change_id).expect("checked above");
+        self.apply_change(pending.change);
+    }
+
+    /// Drop a scheduled change (ConfigManager)
+    pub fn cancel_config_change(&mut self, change_id: u64) {
+        self.assert_role(Role::ConfigManager);
+        assert!(self.pending_changes.remove(&change_id).is_some(), "Unknown config change {}", change_id);
+        near_sdk::log!("Config change {} cancelled", change_id);
+    }
+}
+
+impl PriceOracle {
+    /// Applies a validated change now, or schedules it while a timelock delay is set
+    pub(crate) fn request_config_change(&mut self, change: ConfigChange) {
+        if self.timelock_delay == 0 {
+            self.apply_change(change);
+            return;
+        }
+        let now = now_ms();
+        let eta = self
+            .timelock_delay
+            .checked_mul(1000)
+            .and_then(|delay| now.checked_add(delay))
+            .expect("Timelock delay overflows the eta");
+        self.next_change_id += 1;
+        let pending = PendingChange { change_id: self.next_change_id, change, proposed_at: now, eta };
+        near_sdk::log!("Config change {} scheduled for {}: {:?}", pending.change_id, pending.eta, pending.change);
+        OracleEvent::ConfigChangeScheduled { change_id: pending.change_id, change: pending.change.clone(), eta }.emit();
+        self.pending_changes.insert(pending.change_id, pending);
+    }
+
+    fn apply_change(&mut self, change: ConfigChange) {
+        self.invalidate_aggregates();
+        match change {
+            ConfigChange::MinSources { min_sources } => self.min_sources = min_sources,
+            ConfigChange::PriceBounds { asset_id, bounds } => {
+                self.asset_config(&asset_id);
+                self.assets.get_mut(&asset_id).expect("checked above").bounds = bounds;
+            }
+            ConfigChange::AggregationMethod { asset_id, method } => {
+                self.asset_config(&asset_id);
+                near_sdk::log!("Aggregation method of {} set to {:?}", asset_id, method);
+                self.assets.get_mut(&asset_id).expect("checked above").aggregation = method;
+            }
+            ConfigChange::TimelockDelay { delay_seconds } => self.timelock_delay = delay_seconds,
+            ConfigChange::AssetMinSources { asset_id, min_sources } => {
+                self.asset_config(&asset_id);
+                match min_sources {
+                    Some(min_sources) => self.asset_min_sources.insert(asset_id, min_sources),
+                    None => self.asset_min_sources.remove(&asset_id),
+                };
+            }
+            ConfigChange::SourceWeight { source, weight } => {
+                match weight {
+                    Some(weight) => self.source_weights.insert(source.clone(), weight),
+                    None => self.source_weights.remove(&source),
+                };
+                near_sdk::log!("Weight of source {} set to {}", source, self.source_weight(&source));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::test_utils::{get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn at(seconds: u64) {
+        testing_env!(VMContextBuilder::new().block_timestamp(seconds * 1_000_000_000).build());
+    }
+
+    fn setup() -> PriceOracle {
+        at(1000);
+        let mut contract = PriceOracle::for_tests();
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract.set_timelock_delay(3600);
+        contract
+    }
+
+    #[test]
+    fn test_change_applies_after_delay() {
+        let mut contract = setup();
+        contract.set_min_sources(5);
+        assert!(get_logs().contains(&"Config change 1 scheduled for 4600000: MinSources { min_sources: 5 }".to_string()));
+        assert!(get_logs().contains(&r#"EVENT_JSON:{"standard":"price-oracle","version":"2.0.0","event":"config_change_scheduled","data":{"change_id":1,"change":{"MinSources":{"min_sources":5}},"eta":4600000}}"#.to_string()));
+        assert_eq!(contract.get_min_sources(), 3);
+
+        at(4600);
+        contract.apply_config_change(1);
+        assert_eq!(contract.get_min_sources(), 5);
+        assert!(contract.get_pending_config_changes().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Config change 1 is locked until 4600000")]
+    fn test_change_locked_before_eta() {
+        let mut contract = setup();
+        contract.set_aggregation_method("NEAR".to_string(), AggregationMethod::Mean);
+        at(4599);
+        contract.apply_config_change(1);
+    }
+
+    #[test]
+    fn test_delay_change_waits_out_current_delay() {
+        let mut contract = setup();
+        contract.set_timelock_delay(0);
+        contract.set_price_bounds("NEAR".to_string(), Some(PriceBounds { min: 1, max: 2 }));
+        assert_eq!(contract.get_timelock_delay(), 3600);
+        assert_eq!(contract.get_pending_config_changes().len(), 2);
+
+        contract.cancel_config_change(2);
+        at(4600);
+        contract.apply_config_change(1);
+        contract.set_min_sources(5);
+        assert_eq!(contract.get_min_sources(), 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "Timelock delay must be at most 2592000s")]
+    fn test_delay_capped() {
+        let mut contract = setup();
+        contract.set_timelock_delay(u64::MAX / 1000 + 1);
+    }
+}
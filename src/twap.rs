@@ -0,0 +1,252 @@
+use near_sdk::near;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::{now_ms, AssetId, Pair, Price, PriceOracle, PriceOracleExt};
+
+/// Minimum spacing between stored checkpoints
+pub const TWAP_GRANULARITY_SECONDS: u64 = 60;
+
+/// Checkpoints kept per pair; with the granularity above this covers at least two hours
+pub const TWAP_MAX_CHECKPOINTS: usize = 120;
+
+/// Cumulative price at a point in time
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct TwapCheckpoint {
+    pub timestamp: u64,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub cumulative: u128,
+}
+
+/// Running sum of aggregate price x elapsed milliseconds for one pair
+/// Sums may wrap: only differences between two points are meaningful
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct TwapAccumulator {
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub cumulative: u128,      // Value at `last_update`
+    pub last_update: u64,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub last_price: u128,      // Aggregate in effect since `last_update`
+    pub checkpoints: Vec<TwapCheckpoint>,
+}
+
+impl TwapAccumulator {
+    fn new(timestamp: u64, price: u128) -> Self {
+        Self {
+            cumulative: 0,
+            last_update: timestamp,
+            last_price: price,
+            checkpoints: vec![TwapCheckpoint { timestamp, cumulative: 0 }],
+        }
+    }
+
+    /// Account for the previous price up to `timestamp`, then switch to `price`
+    fn update(&mut self, timestamp: u64, price: u128) {
+        self.cumulative = self.cumulative_at_head(timestamp);
+        self.last_update = timestamp;
+        self.last_price = price;
+
+        let last = self.checkpoints.last().map(|c| c.timestamp).unwrap_or(0);
+        if timestamp >= last + TWAP_GRANULARITY_SECONDS * 1000 {
+            self.checkpoints.push(TwapCheckpoint { timestamp, cumulative: self.cumulative });
+            if self.checkpoints.len() > TWAP_MAX_CHECKPOINTS {
+                self.checkpoints.remove(0);
+            }
+        }
+    }
+
+    fn cumulative_at_head(&self, timestamp: u64) -> u128 {
+        let elapsed = timestamp.saturating_sub(self.last_update) as u128;
+        self.cumulative.wrapping_add(self.last_price.wrapping_mul(elapsed))
+    }
+
+    /// Cumulative price at `timestamp`, interpolated between checkpoints;
+    /// None if it predates the oldest checkpoint
+    pub(crate) fn cumulative_at(&self, timestamp: u64) -> Option<u128> {
+        if timestamp >= self.last_update {
+            return Some(self.cumulative_at_head(timestamp));
+        }
+        if timestamp < self.checkpoints.first()?.timestamp {
+            return None;
+        }
+
+        let head = TwapCheckpoint { timestamp: self.last_update, cumulative: self.cumulative };
+        let points: Vec<&TwapCheckpoint> = self.checkpoints.iter().chain(std::iter::once(&head)).collect();
+        let next = points.iter().position(|p| p.timestamp > timestamp)?;
+        let (a, b) = (points[next - 1], points[next]);
+
+        let span = (b.timestamp - a.timestamp) as u128;
+        let delta = b.cumulative.wrapping_sub(a.cumulative);
+        let offset = (timestamp - a.timestamp) as u128;
+        Some(a.cumulative.wrapping_add(delta / span * offset + delta % span * offset / span))
+    }
+}
+
+#[near]
+impl PriceOracle {
+    /// Get the time-weighted average of a pair's aggregate price over the last
+    /// `window_seconds`, which resists short-lived manipulation of the sources
+    /// The confidence is that of the current aggregate
+    pub fn get_twap(&self, pair: Pair, window_seconds: u64) -> Price {
+        self.assert_not_paused();
+        assert!(window_seconds > 0, "TWAP window must be positive");
+        let now = now_ms();
+        let start = now.checked_sub(window_seconds * 1000);
+        self.twap_between(&pair, start, now)
+    }
+
+    /// Get the time-weighted average of an asset's USD price between two timestamps
+    /// (milliseconds), e.g. the hour before an option expires
+    /// The window must end by now and start within the recorded checkpoints
+    pub fn get_twap_between(&self, asset_id: AssetId, from_ts: u64, to_ts: u64) -> Price {
+        self.assert_not_paused();
+        assert!(from_ts < to_ts, "TWAP window must end after it starts");
+        assert!(to_ts <= now_ms(), "TWAP window ends after {}", now_ms());
+        self.twap_between(&Pair::usd(asset_id), Some(from_ts), to_ts)
+    }
+
+    /// Get the raw accumulator of a pair, for consumers computing their own windows
+    pub fn get_twap_accumulator(&self, pair: Pair) -> Option<TwapAccumulator> {
+        self.twap.get(&pair).cloned()
+    }
+}
+
+impl PriceOracle {
+    fn twap_between(&self, pair: &Pair, start: Option<u64>, end: u64) -> Price {
+        let accumulator = self
+            .twap
+            .get(pair)
+            .unwrap_or_else(|| panic!("No TWAP recorded for {}", pair));
+        let (start, from) = start
+            .and_then(|start| Some((start, accumulator.cumulative_at(start)?)))
+            .unwrap_or_else(|| panic!("TWAP window exceeds recorded history for {}", pair));
+        let to = accumulator
+            .cumulative_at(end)
+            .unwrap_or_else(|| panic!("TWAP window exceeds recorded history for {}", pair));
+
+        Price {
+            price: to.wrapping_sub(from) / (end - start) as u128,
+            decimals: self.asset_config(&pair.base).decimals,
+            confidence: self.try_price(pair).map(|p| p.confidence).unwrap_or(0),
+        }
+    }
+
+    /// Feeds the latest aggregate into the pair's accumulator
+    pub(crate) fn track_twap(&mut self, pair: &Pair) {
+        let Some(price) = self.try_price(pair) else {
+            return;
+        };
+        let now = now_ms();
+        match self.twap.get_mut(pair) {
+            Some(accumulator) => accumulator.update(now, price.price),
+            None => {
+                self.twap.insert(pair.clone(), TwapAccumulator::new(now, price.price));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetConfig;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn at(seconds: u64) {
+        testing_env!(VMContextBuilder::new().block_timestamp(seconds * 1_000_000_000).build());
+    }
+
+    fn setup() -> PriceOracle {
+        at(1000);
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        contract
+    }
+
+    fn report(contract: &mut PriceOracle, price: u128) {
+        contract.report_price(Pair::usd("NEAR"), "binance".to_string(), U128(price), 6);
+    }
+
+    #[test]
+    fn test_twap_weights_by_time() {
+        let mut contract = setup();
+        report(&mut contract, 5000000);       // $5 from t=1000
+        at(1300);
+        report(&mut contract, 6000000);       // $6 from t=1300
+        at(1400);
+
+        // 300s at $5 and 100s at $6
+        assert_eq!(contract.get_twap(Pair::usd("NEAR"), 400).price, 5250000);
+        assert_eq!(contract.get_twap(Pair::usd("NEAR"), 100).price, 6000000);
+        assert_eq!(contract.get_twap(Pair::usd("NEAR"), 200).price, 5500000);
+    }
+
+    #[test]
+    fn test_twap_between_timestamps() {
+        let mut contract = setup();
+        report(&mut contract, 5000000);       // $5 from t=1000
+        at(1300);
+        report(&mut contract, 6000000);       // $6 from t=1300
+        at(1400);
+        report(&mut contract, 7000000);
+        at(1500);
+
+        // 100s at $5 and 50s at $6
+        assert_eq!(contract.get_twap_between("NEAR".to_string(), 1200000, 1350000).price, 5333333);
+        assert_eq!(contract.get_twap_between("NEAR".to_string(), 1400000, 1500000).price, 7000000);
+    }
+
+    #[test]
+    #[should_panic(expected = "TWAP window ends after 1500000")]
+    fn test_twap_between_future_window() {
+        let mut contract = setup();
+        report(&mut contract, 5000000);
+        at(1500);
+        contract.get_twap_between("NEAR".to_string(), 1200000, 1600000);
+    }
+
+    #[test]
+    fn test_twap_resists_spike() {
+        let mut contract = setup();
+        report(&mut contract, 5000000);
+        at(1599);
+        report(&mut contract, 50000000);      // $50 for the last second
+        at(1600);
+
+        let twap = contract.get_twap(Pair::usd("NEAR"), 600).price;
+        assert_eq!(twap, 5075000);
+    }
+
+    #[test]
+    fn test_checkpoints_are_bounded() {
+        let mut contract = setup();
+        for i in 0..(TWAP_MAX_CHECKPOINTS as u64 + 10) {
+            at(1000 + i * TWAP_GRANULARITY_SECONDS);
+            report(&mut contract, 5000000);
+        }
+        let accumulator = contract.get_twap_accumulator(Pair::usd("NEAR")).unwrap();
+        assert_eq!(accumulator.checkpoints.len(), TWAP_MAX_CHECKPOINTS);
+        assert_eq!(contract.get_twap(Pair::usd("NEAR"), 3600).price, 5000000);
+    }
+
+    #[test]
+    #[should_panic(expected = "TWAP window exceeds recorded history for NEAR/USD")]
+    fn test_twap_window_too_long() {
+        let mut contract = setup();
+        report(&mut contract, 5000000);
+        at(1100);
+        contract.get_twap(Pair::usd("NEAR"), 200);
+    }
+}
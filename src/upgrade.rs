@@ -0,0 +1,112 @@
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::{env, near, Gas, NearToken, Promise};
+
+use crate::{PriceOracle, PriceOracleExt};
+
+/// Gas of the migrate call that follows the deploy of an upgrade
+pub const MIGRATE_GAS_TGAS: u64 = 100;
+
+#[near]
+impl PriceOracle {
+    /// Stage the wasm of the next contract version (owner or council member), replacing
+    /// any staged before. It's deployed by upgrade, or by an Upgrade proposal once a
+    /// council is installed, naming its sha256. Returns that hash
+    pub fn stage_code(&mut self, code: Base64VecU8) -> Base64VecU8 {
+        let caller = env::predecessor_account_id();
+        let member = self.council.as_ref().is_some_and(|council| council.members.contains(&caller.to_string()));
+        assert!(caller == self.owner || member, "Only owner or council members stage code");
+        assert!(!code.0.is_empty(), "No code to stage");
+        let code_hash = env::sha256(&code.0);
+        near_sdk::log!("Code staged by {}: {} bytes", caller, code.0.len());
+        self.staged_code.set(Some(code.0));
+        Base64VecU8(code_hash)
+    }
+
+    /// Get the sha256 of the staged code, if any
+    pub fn get_staged_code_hash(&self) -> Option<Base64VecU8> {
+        self.staged_code.get().as_ref().map(|code| Base64VecU8(env::sha256(code)))
+    }
+
+    /// Deploy the staged code and migrate the state into it (owner only, while no council
+    /// is installed; afterwards through an Upgrade proposal). code_hash must be the
+    /// staged code's sha256, so the code reviewed is the code deployed
+    pub fn upgrade(&mut self, code_hash: Base64VecU8) -> Promise {
+        self.assert_owner();
+        self.assert_without_council("upgrade");
+        self.deploy_staged_code(code_hash)
+    }
+}
+
+impl PriceOracle {
+    /// Deploy the staged code, which must hash to `code_hash`, then call its migrate
+    pub(crate) fn deploy_staged_code(&mut self, code_hash: Base64VecU8) -> Promise {
+        let code = self.staged_code.take().expect("No code staged");
+        assert!(env::sha256(&code) == code_hash.0, "Staged code doesn't match the code hash");
+        near_sdk::log!("Upgrading to the staged code: {} bytes", code.len());
+        Promise::new(env::current_account_id()).deploy_contract(code).function_call(
+            "migrate".to_string(),
+            Vec::new(),
+            NearToken::from_yoctonear(0),
+            Gas::from_tgas(MIGRATE_GAS_TGAS),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CouncilAction;
+    use near_sdk::test_utils::{accounts, get_created_receipts, VMContextBuilder};
+    use near_sdk::{testing_env, AccountId};
+
+    fn call(account_id: AccountId) {
+        testing_env!(VMContextBuilder::new().predecessor_account_id(account_id).build());
+    }
+
+    fn setup() -> (PriceOracle, Base64VecU8) {
+        call(accounts(0));
+        let mut contract = PriceOracle::for_tests();
+        let code_hash = contract.stage_code(Base64VecU8(b"\0asm next".to_vec()));
+        (contract, code_hash)
+    }
+
+    #[test]
+    fn test_owner_upgrades_without_council() {
+        let (mut contract, code_hash) = setup();
+        assert_eq!(contract.get_staged_code_hash(), Some(code_hash.clone()));
+        contract.upgrade(code_hash).detach();
+        assert_eq!(contract.get_staged_code_hash(), None);
+
+        let receipts = get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, env::current_account_id());
+        assert_eq!(receipts[0].actions.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Staged code doesn't match the code hash")]
+    fn test_upgrade_needs_staged_hash() {
+        let (mut contract, _) = setup();
+        contract.upgrade(Base64VecU8(vec![0; 32])).detach();
+    }
+
+    #[test]
+    fn test_council_upgrades() {
+        let (mut contract, code_hash) = setup();
+        contract.set_council(vec![accounts(1), accounts(2)], 2, 3600);
+        call(accounts(1));
+        let id = contract.propose(CouncilAction::Upgrade { code_hash });
+        call(accounts(2));
+        contract.approve(id);
+        contract.execute(id);
+        assert_eq!(contract.get_staged_code_hash(), None);
+        assert_eq!(get_created_receipts()[0].actions.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "upgrade requires council approval")]
+    fn test_council_gates_upgrade() {
+        let (mut contract, code_hash) = setup();
+        contract.set_council(vec![accounts(1), accounts(2)], 2, 3600);
+        contract.upgrade(code_hash).detach();
+    }
+}
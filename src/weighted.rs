@@ -0,0 +1,224 @@
+use near_sdk::json_types::U128;
+use near_sdk::{near, AccountId};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "abi")]
+use borsh::BorshSchema;
+#[cfg(feature = "abi")]
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::aggregation::{Aggregator, Sample, StakeWeighted};
+use crate::reporter::normalize_source;
+use crate::{ConfigChange, Pair, PriceOracle, PriceOracleExt, PriceReport, Role};
+
+/// Weight a source contributed to a stake-weighted aggregate
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct SourceWeight {
+    pub source: String,
+    pub reporter: String,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub price: u128,
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub weight: u128,          // Bonded stake of the reporter, or 1 if no reporter is staked
+}
+
+/// Stake-weighted aggregate of a pair with the weights behind it
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "abi", derive(BorshSchema, JsonSchema))]
+pub struct WeightedPrice {
+    #[serde(with = "crate::json::u128_string")]
+    #[cfg_attr(feature = "abi", schemars(with = "String"))]
+    pub price: u128,
+    pub decimals: u8,
+    pub weights: Vec<SourceWeight>,
+}
+
+#[near]
+impl PriceOracle {
+    /// Get the median of a pair's reports weighted by each reporter's bonded stake,
+    /// so sources backed by more stake count for more
+    /// Unstaked reporters get no weight; while nobody is staked all sources count equally
+    /// Outliers are excluded first, as for the other aggregates
+    pub fn get_weighted_price(&self, pair: Pair) -> WeightedPrice {
+        self.assert_not_paused();
+        let decimals = self.asset_config(&pair.base).decimals;
+        let reports = self.accepted_reports(&pair);
+        if self.live_reports(&pair).len() < self.min_sources_of(&pair.base) as usize || reports.is_empty() {
+            self.panic_insufficient_sources(&pair);
+        }
+
+        let mut weights: Vec<SourceWeight> = reports
+            .iter()
+            .zip(self.stake_weights(&reports))
+            .map(|(report, weight)| SourceWeight {
+                source: report.source.clone(),
+                reporter: report.reporter.clone(),
+                price: report.price,
+                weight,
+            })
+            .collect();
+        weights.sort_by(|a, b| a.source.cmp(&b.source));
+
+        let samples: Vec<Sample> =
+            weights.iter().map(|w| Sample { price: w.price, weight: w.weight, source_weight: 1 }).collect();
+        WeightedPrice {
+            price: StakeWeighted.aggregate(&samples),
+            decimals,
+            weights,
+        }
+    }
+
+    /// Set how much a source counts under SourceWeighted aggregation, e.g. 3 for a tier-1
+    /// exchange and 1 for an aggregator; None resets it to 1 (ConfigManager)
+    /// Subject to the timelock delay
+    pub fn set_source_weight(&mut self, source: String, weight: Option<u32>) {
+        self.assert_role(Role::ConfigManager);
+        assert!(weight != Some(0), "Source weight must be positive; disable the source instead");
+        let source = normalize_source(&source);
+        self.request_config_change(ConfigChange::SourceWeight { source, weight });
+    }
+
+    /// Get the weight of every source assigned one, by source; the rest weigh 1
+    pub fn get_source_weights(&self) -> Vec<(String, u32)> {
+        let mut weights: Vec<(String, u32)> =
+            self.source_weights.iter().map(|(source, weight)| (source.clone(), *weight)).collect();
+        weights.sort();
+        weights
+    }
+
+    /// Get the bonded stake of a reporter
+    pub fn get_reporter_stake(&self, account_id: AccountId) -> U128 {
+        U128(self.stakes.get(&account_id).copied().unwrap_or(0))
+    }
+}
+
+impl PriceOracle {
+    /// Aggregation weight of each report: its reporter's stake, or 1 for all when nobody is staked
+    pub(crate) fn stake_weights(&self, reports: &[&PriceReport]) -> Vec<u128> {
+        let stakes: Vec<u128> = reports.iter().map(|r| self.stake_of(&r.reporter)).collect();
+        if stakes.iter().all(|stake| *stake == 0) {
+            return vec![1; stakes.len()];
+        }
+        stakes
+    }
+
+    /// Weight a source counts with under SourceWeighted aggregation
+    pub(crate) fn source_weight(&self, source: &str) -> u32 {
+        self.source_weights.get(source).copied().unwrap_or(1)
+    }
+
+    fn stake_of(&self, reporter: &str) -> u128 {
+        reporter
+            .parse::<AccountId>()
+            .ok()
+            .and_then(|id| self.stakes.get(&id).copied())
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AggregationMethod, AssetConfig};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn report_as(contract: &mut PriceOracle, reporter: &str, source: &str, price: u128) {
+        testing_env!(VMContextBuilder::new().predecessor_account_id(reporter.parse().unwrap()).build());
+        contract.report_price(Pair::usd("NEAR"), source.to_string(), U128(price), 6);
+    }
+
+    fn setup() -> PriceOracle {
+        testing_env!(VMContextBuilder::new().build());
+        let mut contract = PriceOracle::for_tests();
+        contract.set_min_sources(1);
+        contract.add_asset("NEAR".to_string(), AssetConfig::new("NEAR", "NEAR Protocol", 6));
+        for reporter in ["alice.near", "carol.near"] {
+            contract.add_reporter(reporter.parse().unwrap());
+        }
+        report_as(&mut contract, "alice.near", "binance", 5000000);
+        report_as(&mut contract, "bob.near", "coingecko", 5100000);
+        report_as(&mut contract, "carol.near", "kraken", 5200000);
+        contract
+    }
+
+    #[test]
+    fn test_equal_weights_without_stake() {
+        let contract = setup();
+        let weighted = contract.get_weighted_price(Pair::usd("NEAR"));
+        assert_eq!(weighted.price, 5100000);
+        assert!(weighted.weights.iter().all(|w| w.weight == 1));
+        assert_eq!(weighted.weights[0].reporter, "alice.near");
+    }
+
+    #[test]
+    fn test_stake_moves_the_median() {
+        let mut contract = setup();
+        contract.stakes.insert("alice.near".parse().unwrap(), 10);
+        contract.stakes.insert("bob.near".parse().unwrap(), 3);
+        contract.stakes.insert("carol.near".parse().unwrap(), 3);
+
+        let weighted = contract.get_weighted_price(Pair::usd("NEAR"));
+        assert_eq!(weighted.price, 5000000);
+        assert_eq!(weighted.weights[2], SourceWeight {
+            source: "kraken".to_string(),
+            reporter: "carol.near".to_string(),
+            price: 5200000,
+            weight: 3,
+        });
+        assert_eq!(contract.get_reporter_stake("alice.near".parse().unwrap()).0, 10);
+    }
+
+    #[test]
+    fn test_source_weights_steer_the_median() {
+        let mut contract = setup();
+        testing_env!(VMContextBuilder::new().build()); // Back to bob.near, the owner
+        contract.set_aggregation_method("NEAR".to_string(), AggregationMethod::SourceWeighted);
+        assert_eq!(contract.get_aggregate(Pair::usd("NEAR")).price, 5100000);
+        contract.set_source_weight("Kraken".to_string(), Some(3));
+        assert_eq!(contract.get_source_weights(), vec![("kraken".to_string(), 3)]);
+        assert_eq!(contract.get_sources("NEAR".to_string())[2].weight, 3);
+        assert_eq!(contract.get_aggregate(Pair::usd("NEAR")).price, 5200000);
+
+        contract.set_source_weight("kraken".to_string(), None);
+        assert!(contract.get_source_weights().is_empty());
+        assert_eq!(contract.get_aggregate(Pair::usd("NEAR")).price, 5100000);
+    }
+
+    #[test]
+    #[should_panic(expected = "charlie lacks role ConfigManager")]
+    fn test_source_weight_needs_config_manager() {
+        let mut contract = setup();
+        testing_env!(VMContextBuilder::new().predecessor_account_id(accounts(2)).build());
+        contract.set_source_weight("kraken".to_string(), Some(3));
+    }
+
+    #[test]
+    fn test_source_weight_waits_out_timelock() {
+        let mut contract = setup();
+        testing_env!(VMContextBuilder::new().build());
+        contract.set_timelock_delay(3600);
+        contract.set_source_weight("kraken".to_string(), Some(3));
+        assert!(contract.get_source_weights().is_empty());
+        assert_eq!(
+            contract.get_pending_config_changes()[0].change,
+            ConfigChange::SourceWeight { source: "kraken".to_string(), weight: Some(3) }
+        );
+
+        testing_env!(VMContextBuilder::new().block_timestamp(3600 * 1_000_000_000).build());
+        contract.apply_config_change(1);
+        assert_eq!(contract.get_source_weights(), vec![("kraken".to_string(), 3)]);
+    }
+
+    #[test]
+    fn test_unstaked_reporters_are_ignored() {
+        let mut contract = setup();
+        contract.stakes.insert("carol.near".parse().unwrap(), 1);
+        let weighted = contract.get_weighted_price(Pair::usd("NEAR"));
+        assert_eq!(weighted.price, 5200000);
+        assert_eq!(weighted.weights[0].weight, 0);
+    }
+}
@@ -0,0 +1,177 @@
+//! End-to-end tests against a local sandbox node, for what unit tests can't reach:
+//! real accounts, gas, cross-contract promises and redeploys
+//!
+//! They deploy the wasm `cargo near build non-reproducible-wasm --no-abi` writes, so
+//! build it first, then run them with `cargo test --test sandbox -- --ignored`
+
+use near_workspaces::network::Sandbox;
+use near_workspaces::result::ExecutionFinalResult;
+use near_workspaces::types::NearToken;
+use near_workspaces::{Account, Contract, Worker};
+use price_oracle::{AssetConfig, Pair, PriceResponse, PushSubscriber};
+use near_sdk::serde_json::json;
+
+const WASM_PATH: &str = "target/near/price_oracle.wasm";
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+/// A sandbox with the oracle owned by `owner`, NEAR registered and two reporters
+struct Setup {
+    worker: Worker<Sandbox>,
+    oracle: Contract,
+    owner: Account,
+    reporters: [Account; 2],
+    stranger: Account,
+}
+
+async fn account(worker: &Worker<Sandbox>, name: &str) -> Result<Account, Box<dyn std::error::Error>> {
+    let account = worker
+        .root_account()?
+        .create_subaccount(name)
+        .initial_balance(NearToken::from_near(20))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(account)
+}
+
+async fn setup() -> Result<Setup, Box<dyn std::error::Error>> {
+    let wasm = std::fs::read(WASM_PATH)
+        .map_err(|err| format!("{}: {}; build it with cargo near build non-reproducible-wasm --no-abi", WASM_PATH, err))?;
+    let worker = near_workspaces::sandbox().await?;
+    let oracle = worker.dev_deploy(&wasm).await?;
+    let owner = account(&worker, "owner").await?;
+    let reporters = [account(&worker, "alice").await?, account(&worker, "bob").await?];
+    let stranger = account(&worker, "stranger").await?;
+
+    oracle.call("new").args_json(json!({ "owner": owner.id(), "min_sources": 2 })).transact().await?.into_result()?;
+    let config = AssetConfig::new("NEAR", "NEAR Protocol", 6);
+    owner
+        .call(oracle.id(), "add_asset")
+        .args_json(json!({ "asset_id": "NEAR", "config": config }))
+        .transact()
+        .await?
+        .into_result()?;
+    for reporter in &reporters {
+        owner
+            .call(oracle.id(), "add_reporter")
+            .args_json(json!({ "account_id": reporter.id() }))
+            .transact()
+            .await?
+            .into_result()?;
+    }
+    Ok(Setup { worker, oracle, owner, reporters, stranger })
+}
+
+async fn report(oracle: &Contract, reporter: &Account, source: &str, price: u128) -> Result<ExecutionFinalResult, Box<dyn std::error::Error>> {
+    let outcome = reporter
+        .call(oracle.id(), "report_price")
+        .args_json(json!({ "pair": Pair::usd("NEAR"), "source": source, "price": price.to_string(), "decimals": 6 }))
+        .max_gas()
+        .transact()
+        .await?;
+    Ok(outcome)
+}
+
+/// Panic message of a call expected to fail
+fn failure(outcome: ExecutionFinalResult) -> String {
+    format!("{:?}", outcome.into_result().expect_err("call should fail"))
+}
+
+#[tokio::test]
+#[ignore = "needs the NEAR sandbox and a built wasm"]
+async fn test_roles_across_accounts() -> TestResult {
+    let Setup { oracle, reporters, stranger, .. } = setup().await?;
+
+    let outcome = report(&oracle, &stranger, "binance", 5250000).await?;
+    assert!(failure(outcome).contains("is not whitelisted"));
+    let outcome = stranger
+        .call(oracle.id(), "add_reporter")
+        .args_json(json!({ "account_id": stranger.id() }))
+        .transact()
+        .await?;
+    assert!(failure(outcome).contains("lacks role Admin"));
+
+    report(&oracle, &reporters[0], "binance", 5000000).await?.into_result()?;
+    report(&oracle, &reporters[1], "kraken", 5200000).await?.into_result()?;
+    let price: PriceResponse = oracle.view("get_price").args_json(json!({ "pair": Pair::usd("NEAR") })).await?.json()?;
+    assert_eq!((price.price, price.sources_used), (5100000, 2));
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "needs the NEAR sandbox and a built wasm"]
+async fn test_pushes_reach_consumer_contracts() -> TestResult {
+    let Setup { worker, oracle, reporters, stranger, .. } = setup().await?;
+    // Another oracle deployment stands in for a consumer contract: its
+    // get_state_version takes the push like any method ignoring its arguments
+    let consumer = worker.dev_deploy(&std::fs::read(WASM_PATH)?).await?;
+    for (subscriber, method_name) in [(consumer.as_account(), "get_state_version"), (&stranger, "on_price")] {
+        subscriber
+            .call(oracle.id(), "subscribe_push")
+            .args_json(json!({ "pair": Pair::usd("NEAR"), "method_name": method_name, "gas_tgas": 10, "min_change_bps": null }))
+            .deposit(NearToken::from_millinear(10))
+            .transact()
+            .await?
+            .into_result()?;
+    }
+
+    report(&oracle, &reporters[0], "binance", 5000000).await?.into_result()?;
+    let outcome = report(&oracle, &reporters[1], "kraken", 5200000).await?;
+    assert!(outcome.is_success());
+    // The stranger has no contract: its push fails in its own receipt, without
+    // affecting the round or the consumer's push
+    assert!(outcome.logs().iter().any(|log| log.contains(&format!("Push of NEAR/USD to {} failed (1 in a row)", stranger.id()))));
+
+    let subscribers: Vec<PushSubscriber> = oracle
+        .view("get_push_subscribers")
+        .args_json(json!({ "pair": Pair::usd("NEAR") }))
+        .await?
+        .json()?;
+    let failures = |account: &Account| subscribers.iter().find(|s| s.account_id == account.id().as_str()).map(|s| s.failures);
+    assert_eq!((failures(consumer.as_account()), failures(&stranger)), (Some(0), Some(1)));
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "needs the NEAR sandbox and a built wasm"]
+async fn test_failed_oracle_call_refunds_deposit() -> TestResult {
+    let Setup { oracle, reporters, stranger, .. } = setup().await?;
+    report(&oracle, &reporters[0], "binance", 5000000).await?.into_result()?;
+    report(&oracle, &reporters[1], "kraken", 5200000).await?.into_result()?;
+
+    // The stranger has no oracle_on_call, so the forwarded deposit comes back
+    let before = reporters[0].view_account().await?.balance;
+    let outcome = reporters[0]
+        .call(oracle.id(), "oracle_call")
+        .args_json(json!({ "receiver_id": stranger.id(), "asset_ids": ["NEAR"], "msg": "" }))
+        .deposit(NearToken::from_near(5))
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(!outcome.json::<bool>()?);
+    let spent = before.saturating_sub(reporters[0].view_account().await?.balance);
+    assert!(spent < NearToken::from_millinear(100), "spent {}", spent);
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "needs the NEAR sandbox and a built wasm"]
+async fn test_redeploy_and_migrate_keep_state() -> TestResult {
+    let Setup { oracle, owner, reporters, stranger, .. } = setup().await?;
+    report(&oracle, &reporters[0], "binance", 5000000).await?.into_result()?;
+    report(&oracle, &reporters[1], "kraken", 5200000).await?.into_result()?;
+
+    let wasm = std::fs::read(WASM_PATH)?;
+    oracle.as_account().deploy(&wasm).await?.into_result()?;
+    let outcome = stranger.call(oracle.id(), "migrate").transact().await?;
+    assert!(failure(outcome).contains("Method migrate is private"));
+    let outcome = oracle.call("migrate").max_gas().transact().await?.into_result()?;
+    assert!(outcome.logs().iter().any(|log| log.starts_with("State already versioned")));
+
+    let price: PriceResponse = oracle.view("get_price").args_json(json!({ "pair": Pair::usd("NEAR") })).await?.json()?;
+    assert_eq!(price.price, 5100000);
+    let reported_owner: String = oracle.view("get_owner").await?.json()?;
+    assert_eq!(reported_owner, owner.id().as_str());
+    Ok(())
+}